@@ -0,0 +1,67 @@
+//! Embeds build metadata for `bitvault_core::version`. Nothing time-dependent
+//! is recorded, so identical sources still build identical binaries.
+
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let workspace = Path::new(&manifest_dir).join("..");
+    let lockfile = workspace.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+    // HEAD only changes on checkout; commits move the branch ref it points
+    // to, which is either a loose ref file or a line in packed-refs
+    let git_dir = workspace.join(".git");
+    let head = git_dir.join("HEAD");
+    println!("cargo:rerun-if-changed={}", head.display());
+    if let Some(head_ref) = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        watch_if_exists(&git_dir.join(head_ref));
+    }
+    watch_if_exists(&git_dir.join("packed-refs"));
+    println!("cargo:rerun-if-env-changed=BITVAULT_GIT_COMMIT");
+
+    // Source tarballs have no .git, so release builds can pass the commit in
+    let commit = std::env::var("BITVAULT_GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .current_dir(&workspace)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let lockfile_hash = std::fs::read(&lockfile)
+        .map(|bytes| hex(&Sha256::digest(bytes)))
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    println!("cargo:rustc-env=BITVAULT_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BITVAULT_LOCKFILE_SHA256={}", lockfile_hash);
+    println!(
+        "cargo:rustc-env=BITVAULT_BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=BITVAULT_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+}
+
+// Cargo reruns every build for a watched path that doesn't exist
+fn watch_if_exists(path: &Path) {
+    if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::types::Transaction;
+
+/// Bloom filter over wallet addresses (or scripts), sized for a target
+/// false-positive rate. It never gives false negatives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Sizes the filter for `expected_items` at false-positive rate `fp_rate`.
+    pub fn new(expected_items: usize, fp_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = ((bit_count as f64 / n) * ln2).round().clamp(1.0, 30.0) as u32;
+        BloomFilter {
+            bits: vec![0; bit_count.div_ceil(64) as usize],
+            bit_count,
+            hashes,
+        }
+    }
+
+    // Double hashing: the i-th index is h1 + i * h2, from one SHA-256
+    fn indexes(&self, item: &[u8]) -> impl Iterator<Item = u64> {
+        let digest = Sha256::digest(item);
+        let h1 = u64::from_le_bytes(digest[..8].try_into().expect("8 bytes"));
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().expect("8 bytes")) | 1;
+        let bit_count = self.bit_count;
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let indexes: Vec<u64> = self.indexes(item).collect();
+        for index in indexes {
+            self.bits[(index / 64) as usize] |= 1 << (index % 64);
+        }
+    }
+
+    /// `false` means definitely absent; `true` means probably present.
+    pub fn might_contain(&self, item: &[u8]) -> bool {
+        self.indexes(item)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+}
+
+/// Counters for judging how much work the pre-filter saves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterStats {
+    pub checked: u64,
+    /// Passed the bloom filter and needed an exact lookup.
+    pub passed: u64,
+    pub false_positives: u64,
+}
+
+/// Wallet address matcher for block and mempool scanning: a cheap bloom check
+/// first, and the exact set only for the few addresses that pass it.
+#[derive(Clone, Debug)]
+pub struct AddressMatcher {
+    filter: BloomFilter,
+    addresses: HashSet<String>,
+    stats: FilterStats,
+}
+
+impl AddressMatcher {
+    pub fn new<I: IntoIterator<Item = String>>(addresses: I, fp_rate: f64) -> Self {
+        let addresses: HashSet<String> = addresses.into_iter().collect();
+        let mut filter = BloomFilter::new(addresses.len(), fp_rate);
+        for address in &addresses {
+            filter.insert(address.as_bytes());
+        }
+        AddressMatcher {
+            filter,
+            addresses,
+            stats: FilterStats::default(),
+        }
+    }
+
+    /// Adds a newly derived address. The filter keeps its size, so rebuild the
+    /// matcher after adding many more than it was sized for.
+    pub fn insert(&mut self, address: &str) {
+        if self.addresses.insert(address.to_string()) {
+            self.filter.insert(address.as_bytes());
+        }
+    }
+
+    pub fn matches(&mut self, address: &str) -> bool {
+        self.stats.checked += 1;
+        if !self.filter.might_contain(address.as_bytes()) {
+            return false;
+        }
+        self.stats.passed += 1;
+        let found = self.addresses.contains(address);
+        if !found {
+            self.stats.false_positives += 1;
+        }
+        found
+    }
+
+    /// Whether any output of `tx` pays the wallet.
+    pub fn pays_wallet(&mut self, tx: &Transaction) -> bool {
+        tx.outputs
+            .iter()
+            .any(|output| self.matches(&output.address))
+    }
+
+    pub fn stats(&self) -> FilterStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives_and_low_fp_rate() {
+        let ours: Vec<String> = (0..1_000).map(|i| format!("bcrt1qours{}", i)).collect();
+        let mut matcher = AddressMatcher::new(ours.clone(), 0.01);
+
+        assert!(ours.iter().all(|address| matcher.matches(address)));
+        let foreign = (0..10_000)
+            .filter(|i| matcher.matches(&format!("bcrt1qtheirs{}", i)))
+            .count();
+        assert_eq!(foreign, 0);
+
+        let stats = matcher.stats();
+        assert_eq!(stats.checked, 11_000);
+        // Passing 1% of 10k foreign addresses is expected; allow some slack
+        assert!(stats.false_positives < 200, "{:?}", stats);
+    }
+}
@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::clock::SharedClock;
+use crate::crypto::constant_time_eq;
+
+const TOKEN_PREFIX: &str = "bvt";
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// What a token may read. There is deliberately no scope for receive
+/// addresses or signing: those are never available to tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    Balances,
+    History,
+}
+
+/// Endpoints of the wallet RPC/IPC server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiEndpoint {
+    Balance,
+    History,
+    ReceiveAddress,
+    Sign,
+    Send,
+}
+
+impl ApiEndpoint {
+    /// The scope a token needs, or `None` for endpoints tokens can never use.
+    pub fn required_scope(self) -> Option<ApiScope> {
+        match self {
+            ApiEndpoint::Balance => Some(ApiScope::Balances),
+            ApiEndpoint::History => Some(ApiScope::History),
+            ApiEndpoint::ReceiveAddress | ApiEndpoint::Sign | ApiEndpoint::Send => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub id: String,
+    pub label: String,
+    pub scopes: BTreeSet<ApiScope>,
+    /// Requests allowed per minute.
+    pub rate_limit: u32,
+    pub created_at: u64,
+    pub revoked: bool,
+}
+
+/// Why a request with a token was refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApiTokenError {
+    Invalid,
+    Revoked,
+    Forbidden,
+    /// Retry after this many seconds.
+    RateLimited(u64),
+}
+
+impl std::fmt::Display for ApiTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiTokenError::Invalid => write!(f, "Invalid API token"),
+            ApiTokenError::Revoked => write!(f, "API token has been revoked"),
+            ApiTokenError::Forbidden => write!(f, "API token does not allow this request"),
+            ApiTokenError::RateLimited(secs) => {
+                write!(f, "Rate limit exceeded. Try again in {}s", secs)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredToken {
+    info: TokenInfo,
+    // Hex SHA-256 of the secret part; the token itself is only shown once
+    secret_hash: String,
+}
+
+/// Issues and checks scoped read-only tokens for external dashboards.
+pub struct TokenStore {
+    tokens: HashMap<String, StoredToken>,
+    requests: HashMap<String, VecDeque<u64>>,
+    clock: SharedClock,
+}
+
+fn random_hex(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0u8; len];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to get random bytes: {}", e))?;
+    Ok(hex::encode(bytes))
+}
+
+impl TokenStore {
+    pub fn new(clock: SharedClock) -> Self {
+        TokenStore {
+            tokens: HashMap::new(),
+            requests: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// Loads tokens saved with [`TokenStore::save`], or an empty store if
+    /// `path` doesn't exist. Rate limit windows start afresh.
+    pub fn load(path: &Path, clock: SharedClock) -> Result<Self, String> {
+        let tokens = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(TokenStore {
+            tokens,
+            requests: HashMap::new(),
+            clock,
+        })
+    }
+
+    /// Saves every token, revoked ones included, so revocations survive a restart.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.tokens)
+            .map_err(|e| format!("Failed to serialize API tokens: {}", e))?;
+        // Written aside and renamed, so a crash never leaves half the tokens
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Creates a token and returns it with its info. The token string is not
+    /// stored and can't be shown again.
+    pub fn issue(
+        &mut self,
+        label: &str,
+        scopes: BTreeSet<ApiScope>,
+        rate_limit: u32,
+    ) -> Result<(String, TokenInfo), String> {
+        if scopes.is_empty() {
+            return Err("A token needs at least one scope".to_string());
+        }
+        if rate_limit == 0 {
+            return Err("Rate limit must allow at least one request".to_string());
+        }
+
+        let id = random_hex(8)?;
+        let secret = random_hex(32)?;
+        let info = TokenInfo {
+            id: id.clone(),
+            label: label.to_string(),
+            scopes,
+            rate_limit,
+            created_at: self.clock.now(),
+            revoked: false,
+        };
+        self.tokens.insert(
+            id.clone(),
+            StoredToken {
+                info: info.clone(),
+                secret_hash: hex::encode(Sha256::digest(secret.as_bytes())),
+            },
+        );
+        Ok((format!("{}_{}_{}", TOKEN_PREFIX, id, secret), info))
+    }
+
+    /// Revokes a token by id. Returns whether it existed.
+    pub fn revoke(&mut self, id: &str) -> bool {
+        self.requests.remove(id);
+        match self.tokens.get_mut(id) {
+            Some(token) => {
+                token.info.revoked = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<&TokenInfo> {
+        let mut tokens: Vec<&TokenInfo> = self.tokens.values().map(|t| &t.info).collect();
+        tokens.sort_by_key(|info| info.created_at);
+        tokens
+    }
+
+    /// Checks `token` for a request to `endpoint` and counts it against the
+    /// token's rate limit.
+    pub fn authorize(
+        &mut self,
+        token: &str,
+        endpoint: ApiEndpoint,
+    ) -> Result<&TokenInfo, ApiTokenError> {
+        let mut parts = token.splitn(3, '_');
+        let (Some(TOKEN_PREFIX), Some(id), Some(secret)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ApiTokenError::Invalid);
+        };
+        let stored = self.tokens.get(id).ok_or(ApiTokenError::Invalid)?;
+        let secret_hash = hex::encode(Sha256::digest(secret.as_bytes()));
+        if !constant_time_eq(secret_hash.as_bytes(), stored.secret_hash.as_bytes()) {
+            return Err(ApiTokenError::Invalid);
+        }
+        if stored.info.revoked {
+            return Err(ApiTokenError::Revoked);
+        }
+        match endpoint.required_scope() {
+            Some(scope) if stored.info.scopes.contains(&scope) => {}
+            _ => return Err(ApiTokenError::Forbidden),
+        }
+
+        let now = self.clock.now();
+        let window = self.requests.entry(id.to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|at| *at + RATE_WINDOW_SECS <= now)
+        {
+            window.pop_front();
+        }
+        if window.len() >= stored.info.rate_limit as usize {
+            let retry_after = window.front().map_or(0, |at| at + RATE_WINDOW_SECS - now);
+            return Err(ApiTokenError::RateLimited(retry_after));
+        }
+        window.push_back(now);
+        Ok(&stored.info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scopes_rate_limit_and_revocation() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let mut store = TokenStore::new(clock.clone());
+        let (token, info) = store
+            .issue("grafana", BTreeSet::from([ApiScope::Balances]), 2)
+            .unwrap();
+
+        assert!(store.authorize(&token, ApiEndpoint::Balance).is_ok());
+        assert_eq!(
+            store.authorize(&token, ApiEndpoint::ReceiveAddress),
+            Err(ApiTokenError::Forbidden)
+        );
+        assert_eq!(
+            store.authorize(&token, ApiEndpoint::History),
+            Err(ApiTokenError::Forbidden)
+        );
+        assert!(store.authorize(&token, ApiEndpoint::Balance).is_ok());
+        assert_eq!(
+            store.authorize(&token, ApiEndpoint::Balance),
+            Err(ApiTokenError::RateLimited(60))
+        );
+        clock.advance(60);
+        assert!(store.authorize(&token, ApiEndpoint::Balance).is_ok());
+
+        let forged = format!("{}x", token);
+        assert_eq!(
+            store.authorize(&forged, ApiEndpoint::Balance),
+            Err(ApiTokenError::Invalid)
+        );
+        assert!(store.revoke(&info.id));
+        assert_eq!(
+            store.authorize(&token, ApiEndpoint::Balance),
+            Err(ApiTokenError::Revoked)
+        );
+    }
+
+    #[test]
+    fn test_save_load_keeps_revocations() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let path = std::env::temp_dir().join(format!(
+            "bitvault_api_tokens_{}.json",
+            random_hex(8).unwrap()
+        ));
+        assert!(TokenStore::load(&path, clock.clone())
+            .unwrap()
+            .list()
+            .is_empty());
+
+        let mut store = TokenStore::new(clock.clone());
+        let (kept, _) = store
+            .issue("grafana", BTreeSet::from([ApiScope::Balances]), 5)
+            .unwrap();
+        clock.advance(1);
+        let (revoked, info) = store
+            .issue("old", BTreeSet::from([ApiScope::Balances]), 5)
+            .unwrap();
+        assert!(store.revoke(&info.id));
+        store.save(&path).unwrap();
+
+        let mut loaded = TokenStore::load(&path, clock).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.list(), store.list());
+        assert!(loaded.authorize(&kept, ApiEndpoint::Balance).is_ok());
+        assert_eq!(
+            loaded.authorize(&revoked, ApiEndpoint::Balance),
+            Err(ApiTokenError::Revoked)
+        );
+    }
+}
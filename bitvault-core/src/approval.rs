@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::clock::SharedClock;
+use crate::crypto;
+use crate::events::{Event, MessageBus};
+use crate::types::Transaction;
+
+/// When a second credential is needed before signing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    /// Spends above this many satoshis need approval.
+    pub threshold: u64,
+    /// How long a request stays valid, both to be approved and then signed.
+    pub expiry_secs: u64,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        ApprovalPolicy {
+            threshold: 10_000_000,
+            expiry_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved { at: u64 },
+    Rejected { at: u64, reason: String },
+    Expired,
+}
+
+/// A large spend awaiting, or having received, an approver's decision.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    /// Txid of the unsigned transaction; any change to it needs a new request.
+    pub id: String,
+    pub amount: u64,
+    pub requested_at: u64,
+    pub expires_at: u64,
+    pub status: ApprovalStatus,
+}
+
+/// Holds large spends until someone with the separate approver credential
+/// signs off, and refuses to authorize signing until then.
+pub struct ApprovalEngine {
+    policy: ApprovalPolicy,
+    /// Output of [`crypto::hash_credential`] for the approver password.
+    approver_credential: String,
+    requests: HashMap<String, ApprovalRequest>,
+    /// Addresses the wallet owns; outputs paying them are not spent.
+    wallet_addresses: HashSet<String>,
+    clock: SharedClock,
+    bus: Option<Arc<MessageBus>>,
+}
+
+impl ApprovalEngine {
+    pub fn new(policy: ApprovalPolicy, approver_credential: String, clock: SharedClock) -> Self {
+        ApprovalEngine {
+            policy,
+            approver_credential,
+            requests: HashMap::new(),
+            wallet_addresses: HashSet::new(),
+            clock,
+            bus: None,
+        }
+    }
+
+    pub fn with_wallet_addresses(mut self, addresses: HashSet<String>) -> Self {
+        self.wallet_addresses = addresses;
+        self
+    }
+
+    /// Replaces the wallet's addresses, e.g. after deriving new ones.
+    pub fn set_wallet_addresses(&mut self, addresses: HashSet<String>) {
+        self.wallet_addresses = addresses;
+    }
+
+    /// Publishes request, decision and expiry events on `bus` for the audit trail.
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn requires_approval(&self, amount: u64) -> bool {
+        amount > self.policy.threshold
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ApprovalRequest> {
+        self.requests.get(id)
+    }
+
+    /// What `tx` sends out of the wallet: every output except those paying
+    /// the wallet's own addresses, such as change.
+    pub fn outgoing_value(&self, tx: &Transaction) -> u64 {
+        tx.outputs
+            .iter()
+            .filter(|o| !self.wallet_addresses.contains(&o.address))
+            .map(|o| o.value)
+            .sum()
+    }
+
+    /// Requests approval for `tx` and returns the request id.
+    pub fn request(&mut self, tx: &Transaction) -> Result<String, String> {
+        let amount = self.outgoing_value(tx);
+        if !self.requires_approval(amount) {
+            return Err("Spend is below the approval threshold".to_string());
+        }
+        let now = self.clock.now();
+        let id = tx.txid();
+        self.requests.insert(
+            id.clone(),
+            ApprovalRequest {
+                id: id.clone(),
+                amount,
+                requested_at: now,
+                expires_at: now.saturating_add(self.policy.expiry_secs),
+                status: ApprovalStatus::Pending,
+            },
+        );
+        self.publish(Event::ApprovalRequested {
+            request_id: id.clone(),
+            amount,
+            at: now,
+        });
+        Ok(id)
+    }
+
+    fn pending_mut(&mut self, id: &str) -> Result<&mut ApprovalRequest, String> {
+        let now = self.clock.now();
+        let request = self
+            .requests
+            .get_mut(id)
+            .ok_or_else(|| format!("No approval request {}", id))?;
+        if request.status != ApprovalStatus::Pending || now >= request.expires_at {
+            return Err("Approval request is no longer pending".to_string());
+        }
+        Ok(request)
+    }
+
+    /// Approves a pending request after checking the approver credential.
+    pub fn approve(&mut self, id: &str, credential: &str) -> Result<(), String> {
+        self.pending_mut(id)?;
+        if !crypto::verify_credential(&self.approver_credential, credential)? {
+            return Err("Approver credential is incorrect".to_string());
+        }
+        self.decide(
+            id,
+            ApprovalStatus::Approved {
+                at: self.clock.now(),
+            },
+        )
+    }
+
+    /// Rejects a pending request. Anyone may reject, so no credential is needed.
+    pub fn reject(&mut self, id: &str, reason: &str) -> Result<(), String> {
+        self.pending_mut(id)?;
+        self.decide(
+            id,
+            ApprovalStatus::Rejected {
+                at: self.clock.now(),
+                reason: reason.to_string(),
+            },
+        )
+    }
+
+    fn decide(&mut self, id: &str, status: ApprovalStatus) -> Result<(), String> {
+        let approved = matches!(status, ApprovalStatus::Approved { .. });
+        self.pending_mut(id)?.status = status;
+        self.publish(Event::ApprovalDecided {
+            request_id: id.to_string(),
+            approved,
+            at: self.clock.now(),
+        });
+        Ok(())
+    }
+
+    /// Marks pending and approved requests past their expiry as expired and
+    /// returns how many were.
+    pub fn expire_stale(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        for request in self.requests.values_mut() {
+            let open = matches!(
+                request.status,
+                ApprovalStatus::Pending | ApprovalStatus::Approved { .. }
+            );
+            if open && now >= request.expires_at {
+                request.status = ApprovalStatus::Expired;
+                expired.push(request.id.clone());
+            }
+        }
+        for request_id in &expired {
+            self.publish(Event::ApprovalExpired {
+                request_id: request_id.clone(),
+                at: now,
+            });
+        }
+        expired.len()
+    }
+
+    /// Must pass before `tx` is handed to a signer.
+    pub fn authorize_signing(&self, tx: &Transaction) -> Result<(), String> {
+        let amount = self.outgoing_value(tx);
+        if !self.requires_approval(amount) {
+            return Ok(());
+        }
+        let request = self
+            .requests
+            .get(&tx.txid())
+            .ok_or_else(|| "This spend needs approval before signing".to_string())?;
+        match &request.status {
+            ApprovalStatus::Approved { .. } if self.clock.now() < request.expires_at => {
+                if amount > request.amount {
+                    Err("Amount exceeds the approved amount".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            ApprovalStatus::Pending => Err("Spend is still awaiting approval".to_string()),
+            ApprovalStatus::Rejected { reason, .. } => {
+                Err(format!("Spend was rejected: {}", reason))
+            }
+            _ => Err("Approval has expired".to_string()),
+        }
+    }
+
+    fn publish(&self, event: Event) {
+        if let Some(bus) = &self.bus {
+            bus.publish(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::types::{OutPoint, TxIn, TxOut, SEQUENCE_FINAL};
+
+    fn transaction(value: u64) -> Transaction {
+        Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new("aa", 0),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![
+                TxOut {
+                    address: "bcrt1qpayee".to_string(),
+                    value,
+                },
+                TxOut {
+                    address: "bcrt1qchange".to_string(),
+                    value: 90_000_000,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_approval_lifecycle() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let credential = crypto::hash_credential("approver pass").unwrap();
+        let mut engine = ApprovalEngine::new(ApprovalPolicy::default(), credential, clock.clone())
+            .with_wallet_addresses(HashSet::from(["bcrt1qchange".to_string()]))
+            .with_event_bus(bus);
+
+        // Change back to the wallet doesn't count towards the threshold
+        let small = transaction(1_000);
+        assert_eq!(engine.outgoing_value(&small), 1_000);
+        assert!(engine.authorize_signing(&small).is_ok());
+        assert!(engine.request(&small).is_err());
+
+        let large = transaction(50_000_000);
+        assert!(engine.authorize_signing(&large).is_err());
+        let id = engine.request(&large).unwrap();
+        assert_eq!(engine.get(&id).unwrap().amount, 50_000_000);
+        assert!(engine.approve(&id, "wrong").is_err());
+        engine.approve(&id, "approver pass").unwrap();
+        assert!(engine.authorize_signing(&large).is_ok());
+
+        // Without the change address known, the whole output total is outgoing
+        engine.set_wallet_addresses(HashSet::new());
+        assert!(engine.authorize_signing(&large).is_err());
+        engine.set_wallet_addresses(HashSet::from(["bcrt1qchange".to_string()]));
+
+        clock.advance(ApprovalPolicy::default().expiry_secs);
+        assert_eq!(engine.expire_stale(), 1);
+        assert!(engine.authorize_signing(&large).is_err());
+
+        let audit: Vec<Event> = events.try_iter().collect();
+        assert!(matches!(audit[0], Event::ApprovalRequested { .. }));
+        assert!(matches!(
+            audit[1],
+            Event::ApprovalDecided { approved: true, .. }
+        ));
+        assert!(matches!(audit[2], Event::ApprovalExpired { .. }));
+    }
+}
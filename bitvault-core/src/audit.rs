@@ -0,0 +1,268 @@
+//! Self-audit of the wallet's local state against the chain.
+//!
+//! Cross-checks the coins, history, balance and labels the wallet holds
+//! and lists every disagreement, so corruption or a missed reorg shows up
+//! as a report rather than a wrong balance.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::backend::{BlockchainBackend, TxStatus};
+use crate::metadata::WalletMetadata;
+use crate::tx_history::{HistoryEntry, TxHistory};
+use crate::types::{OutPoint, Utxo};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discrepancy {
+    /// A wallet coin the backend doesn't list as unspent.
+    MissingUtxo { outpoint: OutPoint, value: u64 },
+    /// A history transaction the backend doesn't know.
+    MissingTransaction { txid: String },
+    /// A transaction confirmed at a different height than recorded, or not
+    /// at all (`actual` is `None`), e.g. after a reorg.
+    HeightMismatch {
+        txid: String,
+        recorded: Option<u32>,
+        actual: Option<u32>,
+    },
+    /// The balance summed from history differs from the coins held.
+    BalanceMismatch { history: i64, utxos: u64 },
+    /// A label on an address, transaction or coin the wallet doesn't have.
+    DanglingLabel { reference: String, label: String },
+}
+
+/// Result of [`audit`], as JSON for support tickets and scripts.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Unix timestamp (seconds) the audit ran.
+    pub checked_at: u64,
+    pub utxos_checked: usize,
+    pub transactions_checked: usize,
+    pub labels_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize audit report: {}", e))
+    }
+}
+
+fn check_transaction(
+    backend: &dyn BlockchainBackend,
+    entry: &HistoryEntry,
+) -> Result<Option<Discrepancy>, String> {
+    let actual = match backend.transaction_status(&entry.txid)? {
+        TxStatus::NotFound => {
+            return Ok(Some(Discrepancy::MissingTransaction {
+                txid: entry.txid.clone(),
+            }))
+        }
+        TxStatus::Unconfirmed => None,
+        TxStatus::Confirmed { height, .. } => Some(height),
+    };
+    // Pending rows are confirmed on the next sync, so only a confirmed row
+    // can be out of date
+    Ok(match entry.height {
+        Some(recorded) if actual != Some(recorded) => Some(Discrepancy::HeightMismatch {
+            txid: entry.txid.clone(),
+            recorded: Some(recorded),
+            actual,
+        }),
+        _ => None,
+    })
+}
+
+/// Audits `utxos`, `history` and the labels in `metadata` against `backend`.
+/// `addresses` are all addresses the wallet has derived.
+pub fn audit(
+    backend: &dyn BlockchainBackend,
+    history: &mut TxHistory,
+    utxos: &[Utxo],
+    metadata: &WalletMetadata,
+    addresses: &[String],
+    now: u64,
+) -> Result<AuditReport, String> {
+    let mut report = AuditReport {
+        checked_at: now,
+        ..Default::default()
+    };
+
+    // One backend query per address rather than per coin
+    let mut by_address: BTreeMap<&str, Vec<&Utxo>> = BTreeMap::new();
+    for utxo in utxos {
+        by_address.entry(&utxo.address).or_default().push(utxo);
+    }
+    for (address, coins) in by_address {
+        let unspent: HashSet<OutPoint> = backend
+            .address_utxos(address)?
+            .into_iter()
+            .map(|u| u.outpoint)
+            .collect();
+        for coin in coins {
+            report.utxos_checked += 1;
+            if !unspent.contains(&coin.outpoint) {
+                report.discrepancies.push(Discrepancy::MissingUtxo {
+                    outpoint: coin.outpoint.clone(),
+                    value: coin.value,
+                });
+            }
+        }
+    }
+
+    // Conflicted transactions are expected to be gone, so they're skipped
+    let mut txids = HashSet::new();
+    let mut history_addresses = HashSet::new();
+    let mut check = |entry: &HistoryEntry, report: &mut AuditReport| -> Result<(), String> {
+        report.transactions_checked += 1;
+        txids.insert(entry.txid.clone());
+        history_addresses.extend(entry.addresses.iter().cloned());
+        if let Some(discrepancy) = check_transaction(backend, entry)? {
+            report.discrepancies.push(discrepancy);
+        }
+        Ok(())
+    };
+    for entry in history.pending() {
+        check(entry, &mut report)?;
+    }
+    for page in 0..history.page_count() {
+        for entry in history.page(page)? {
+            check(&entry, &mut report)?;
+        }
+    }
+
+    let held: u64 = utxos.iter().map(|u| u.value).sum();
+    if history.balance() != held as i64 {
+        report.discrepancies.push(Discrepancy::BalanceMismatch {
+            history: history.balance(),
+            utxos: held,
+        });
+    }
+
+    let outpoints: HashSet<String> = utxos.iter().map(|u| u.outpoint.to_string()).collect();
+    for (reference, label) in &metadata.labels {
+        report.labels_checked += 1;
+        // Outputs of wallet transactions stay labeled after they're spent
+        let exists = match reference.split_once(':') {
+            Some((txid, _)) => outpoints.contains(reference) || txids.contains(txid),
+            None => {
+                txids.contains(reference)
+                    || addresses.contains(reference)
+                    || history_addresses.contains(reference)
+            }
+        };
+        if !exists {
+            report.discrepancies.push(Discrepancy::DanglingLabel {
+                reference: reference.clone(),
+                label: label.clone(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    #[test]
+    fn test_audit_reports_discrepancies() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_audit_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut history = TxHistory::open(&path, 10, 1).unwrap();
+        let backend = MockBackend::new();
+        let address = "bcrt1qaudit".to_string();
+        let coin = backend.fund_address(&address, 50_000);
+        backend.mine_blocks(1);
+
+        let entry = |txid: &str, height: Option<u32>, net_amount: i64| HistoryEntry {
+            txid: txid.to_string(),
+            height,
+            timestamp: 0,
+            net_amount,
+            fee: None,
+            spent: Vec::new(),
+            replaced_by: None,
+            addresses: vec![address.clone()],
+            memo: None,
+        };
+        history
+            .append_confirmed(&entry(&coin.txid, Some(1), 50_000))
+            .unwrap();
+        let utxos = vec![Utxo {
+            outpoint: coin.clone(),
+            address: address.clone(),
+            value: 50_000,
+            confirmations: 1,
+            is_coinbase: false,
+        }];
+        let mut metadata = WalletMetadata::default();
+        metadata.set_label(&address, "Income").unwrap();
+        metadata.set_label(&coin.to_string(), "Income").unwrap();
+
+        let report = audit(&backend, &mut history, &utxos, &metadata, &[], 1).unwrap();
+        assert!(report.is_clean(), "{:?}", report.discrepancies);
+        assert_eq!(
+            (
+                report.utxos_checked,
+                report.transactions_checked,
+                report.labels_checked
+            ),
+            (1, 1, 2)
+        );
+
+        // A reorg moves the confirmation and a phantom payment shows up
+        backend.reorg(1).unwrap();
+        backend.mine_blocks(2);
+        let phantom = format!("{:064x}", 7);
+        history.add_pending(entry(&phantom, None, 1_000));
+        metadata.set_label("bcrt1qgone", "Old").unwrap();
+        let mut missing = utxos[0].clone();
+        missing.outpoint = OutPoint::new(&phantom, 0);
+        let report = audit(
+            &backend,
+            &mut history,
+            &[utxos[0].clone(), missing],
+            &metadata,
+            &[],
+            2,
+        )
+        .unwrap();
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::MissingUtxo {
+                    outpoint: OutPoint::new(&phantom, 0),
+                    value: 50_000,
+                },
+                Discrepancy::MissingTransaction { txid: phantom },
+                Discrepancy::HeightMismatch {
+                    txid: coin.txid.clone(),
+                    recorded: Some(1),
+                    actual: Some(2),
+                },
+                Discrepancy::BalanceMismatch {
+                    history: 51_000,
+                    utxos: 100_000,
+                },
+                Discrepancy::DanglingLabel {
+                    reference: "bcrt1qgone".to_string(),
+                    label: "Old".to_string(),
+                },
+            ]
+        );
+        assert!(report
+            .to_json()
+            .unwrap()
+            .contains("\"kind\": \"missing_utxo\""));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::events::{Event, MessageBus};
+
+/// Limits applied to failed unlock attempts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThrottlePolicy {
+    /// Failures allowed before any delay is imposed.
+    pub free_attempts: u32,
+    /// Delay after the first throttled failure; doubles with each further failure.
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+    /// Lock out permanently after this many consecutive failures, if set.
+    pub lockout_after: Option<u32>,
+    /// Publish a `SecurityAlert` every time the failure count reaches a multiple of this.
+    pub alert_every: u32,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        ThrottlePolicy {
+            free_attempts: 3,
+            base_delay_secs: 5,
+            max_delay_secs: 60 * 60,
+            lockout_after: None,
+            alert_every: 3,
+        }
+    }
+}
+
+/// Why an attempt is currently refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThrottleError {
+    /// Another attempt is allowed after this many seconds.
+    Wait(u64),
+    /// The failure limit has been reached; only a wallet restore can proceed.
+    LockedOut,
+}
+
+impl std::fmt::Display for ThrottleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottleError::Wait(secs) => write!(f, "Too many attempts. Try again in {}s", secs),
+            ThrottleError::LockedOut => write!(f, "Too many failed attempts. Wallet is locked out"),
+        }
+    }
+}
+
+// Persisted attempt counters
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct AttemptState {
+    failed_attempts: u32,
+    last_failure_at: Option<u64>,
+}
+
+/// Tracks failed unlock attempts with exponential backoff, persisted across restarts.
+#[derive(Clone, Debug, Default)]
+pub struct AttemptThrottle {
+    policy: ThrottlePolicy,
+    state: AttemptState,
+    path: Option<PathBuf>,
+}
+
+impl AttemptThrottle {
+    /// Creates an in-memory throttle that is not persisted.
+    pub fn new(policy: ThrottlePolicy) -> Self {
+        AttemptThrottle {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the attempt state from `path`, as of unix time `now`.
+    ///
+    /// A corrupt state file counts as a throttled failure at `now` rather than a
+    /// reset, so mangling it doesn't bypass the backoff. So does a missing file
+    /// when `existing_wallet` is set, since one is written on the first run and
+    /// the wallet can't predate it. A fresh start writes that file.
+    pub fn load(path: &Path, policy: ThrottlePolicy, now: u64, existing_wallet: bool) -> Self {
+        let tampered = AttemptState {
+            failed_attempts: policy.free_attempts + 1,
+            last_failure_at: Some(now),
+        };
+        let (state, fresh) = match fs::read_to_string(path) {
+            Ok(json) => (
+                serde_json::from_str(&json).unwrap_or_else(|e| {
+                    log::warn!("Failed to parse attempt state: {}", e);
+                    tampered
+                }),
+                false,
+            ),
+            Err(_) if existing_wallet => {
+                log::warn!("Attempt state is missing for an existing wallet");
+                (tampered, false)
+            }
+            Err(_) => (AttemptState::default(), true),
+        };
+
+        let throttle = AttemptThrottle {
+            policy,
+            state,
+            path: Some(path.to_path_buf()),
+        };
+        if fresh {
+            if let Err(e) = throttle.save() {
+                log::warn!("{}", e);
+            }
+        }
+        throttle
+    }
+
+    pub fn failed_attempts(&self) -> u32 {
+        self.state.failed_attempts
+    }
+
+    /// Returns the delay imposed after the current number of failures.
+    pub fn current_delay(&self) -> u64 {
+        let throttled = self
+            .state
+            .failed_attempts
+            .saturating_sub(self.policy.free_attempts);
+        if throttled == 0 {
+            return 0;
+        }
+        let factor = 1u64.checked_shl(throttled - 1).unwrap_or(u64::MAX);
+        self.policy
+            .base_delay_secs
+            .saturating_mul(factor)
+            .min(self.policy.max_delay_secs)
+    }
+
+    /// Checks whether an attempt may be made at unix time `now`.
+    pub fn check(&self, now: u64) -> Result<(), ThrottleError> {
+        if let Some(limit) = self.policy.lockout_after {
+            if self.state.failed_attempts >= limit {
+                return Err(ThrottleError::LockedOut);
+            }
+        }
+
+        let delay = self.current_delay();
+        match self.state.last_failure_at {
+            Some(last) if now < last.saturating_add(delay) => {
+                Err(ThrottleError::Wait(last + delay - now))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a failed attempt, persisting it and raising alerts on repeated failures.
+    pub fn record_failure(&mut self, now: u64, bus: Option<&MessageBus>) -> Result<(), String> {
+        self.state.failed_attempts = self.state.failed_attempts.saturating_add(1);
+        self.state.last_failure_at = Some(now);
+
+        let failures = self.state.failed_attempts;
+        if let Some(bus) = bus {
+            if self.policy.alert_every > 0 && failures.is_multiple_of(self.policy.alert_every) {
+                bus.publish(Event::SecurityAlert {
+                    message: format!("{} consecutive failed unlock attempts", failures),
+                });
+            }
+            if self.policy.lockout_after == Some(failures) {
+                bus.publish(Event::SecurityAlert {
+                    message: "Unlock attempts locked out after repeated failures".to_string(),
+                });
+            }
+        }
+
+        self.save()
+    }
+
+    /// Resets the counters after a successful unlock.
+    pub fn record_success(&mut self) -> Result<(), String> {
+        self.state = AttemptState::default();
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(&self.state)
+            .map_err(|e| format!("Failed to serialize attempt state: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to save attempt state: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_lockout() {
+        let policy = ThrottlePolicy {
+            free_attempts: 2,
+            base_delay_secs: 10,
+            max_delay_secs: 25,
+            lockout_after: Some(5),
+            alert_every: 2,
+        };
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut throttle = AttemptThrottle::new(policy);
+
+        throttle.record_failure(100, Some(&bus)).unwrap();
+        throttle.record_failure(100, Some(&bus)).unwrap();
+        assert_eq!(throttle.check(100), Ok(()));
+        assert!(matches!(events.try_recv(), Ok(Event::SecurityAlert { .. })));
+
+        throttle.record_failure(100, Some(&bus)).unwrap();
+        assert_eq!(throttle.check(105), Err(ThrottleError::Wait(5)));
+        assert_eq!(throttle.check(110), Ok(()));
+
+        throttle.record_failure(110, Some(&bus)).unwrap();
+        assert_eq!(throttle.current_delay(), 20);
+        throttle.record_failure(200, Some(&bus)).unwrap();
+        assert_eq!(throttle.check(10_000), Err(ThrottleError::LockedOut));
+
+        throttle.record_success().unwrap();
+        assert_eq!(throttle.check(10_000), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_state_keeps_backoff() {
+        let path = std::env::temp_dir().join(format!("bitvault_attempts_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let policy = ThrottlePolicy::default();
+
+        let fresh = AttemptThrottle::load(&path, policy.clone(), 100, false);
+        assert_eq!(fresh.check(100), Ok(()));
+        assert!(path.exists());
+
+        fs::write(&path, "not json").unwrap();
+        let corrupt = AttemptThrottle::load(&path, policy.clone(), 100, true);
+        assert_eq!(corrupt.check(100), Err(ThrottleError::Wait(5)));
+
+        fs::remove_file(&path).unwrap();
+        let deleted = AttemptThrottle::load(&path, policy, 100, true);
+        assert_eq!(deleted.failed_attempts(), 4);
+        assert_eq!(deleted.check(104), Err(ThrottleError::Wait(1)));
+        assert_eq!(deleted.check(105), Ok(()));
+    }
+}
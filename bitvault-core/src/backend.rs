@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Transaction, Utxo};
+
+pub mod aggregate;
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub height: u32,
+    pub hash: String,
+    pub prev_hash: String,
+    pub time: u64, // unix timestamp
+}
+
+/// Where a transaction currently is, as seen by a backend.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxStatus {
+    NotFound,
+    Unconfirmed,
+    Confirmed { height: u32, block_hash: String },
+}
+
+/// Fee rates in sat/vB paid by a block's transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockFeeStats {
+    pub min_fee_rate: f64,
+    pub median_fee_rate: f64,
+    pub max_fee_rate: f64,
+}
+
+/// Node fee policy floors in sat/vB; transactions paying less are not relayed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RelayFees {
+    /// Static `minrelaytxfee` of the node.
+    pub min_relay_fee: f64,
+    /// Dynamic `mempoolminfee`, which rises above the relay fee when the mempool is full.
+    pub mempool_min_fee: f64,
+}
+
+impl RelayFees {
+    /// The rate a transaction must pay to be accepted right now.
+    pub fn floor(&self) -> f64 {
+        self.min_relay_fee.max(self.mempool_min_fee)
+    }
+}
+
+impl Default for RelayFees {
+    fn default() -> Self {
+        RelayFees {
+            min_relay_fee: 1.0,
+            mempool_min_fee: 1.0,
+        }
+    }
+}
+
+/// Why a node would refuse a transaction, parsed from its reject reason.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// An input doesn't exist or is already spent on chain.
+    InputsMissing,
+    /// An input is spent by another mempool transaction.
+    MempoolConflict,
+    /// Outputs are worth more than inputs.
+    OutputsExceedInputs,
+    /// Below the relay or mempool minimum fee.
+    FeeTooLow,
+    Dust,
+    /// Locktime or sequence not yet satisfied.
+    NonFinal,
+    AlreadyKnown,
+    Other(String),
+}
+
+impl RejectReason {
+    /// Maps Bitcoin Core's reject reasons, which Electrum and Esplora
+    /// servers relay as-is.
+    pub fn from_node(reason: &str) -> Self {
+        let lower = reason.to_ascii_lowercase();
+        let has = |pattern: &str| lower.contains(pattern);
+        if has("inputs-missing") || has("missingorspent") {
+            RejectReason::InputsMissing
+        } else if has("mempool-conflict") {
+            RejectReason::MempoolConflict
+        } else if has("in-belowout") {
+            RejectReason::OutputsExceedInputs
+        } else if has("fee not met") || has("insufficient fee") || has("min relay fee") {
+            RejectReason::FeeTooLow
+        } else if has("dust") {
+            RejectReason::Dust
+        } else if has("non-final") || has("non-bip68-final") {
+            RejectReason::NonFinal
+        } else if has("already-in-mempool") || has("already-known") {
+            RejectReason::AlreadyKnown
+        } else {
+            RejectReason::Other(reason.to_string())
+        }
+    }
+}
+
+/// Outcome of a `testmempoolaccept`-style dry run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DryRun {
+    Accepted {
+        txid: String,
+        fee: u64,
+        vsize: u64,
+        /// sat/vB
+        fee_rate: f64,
+    },
+    Rejected {
+        reason: RejectReason,
+        /// The node's message, for display.
+        message: String,
+    },
+    /// The backend can't test acceptance without broadcasting.
+    Unsupported,
+}
+
+/// Source of chain data and transaction relay (Electrum, Esplora, Core RPC, mock).
+pub trait BlockchainBackend: Send + Sync {
+    /// Header of the current best block.
+    fn tip(&self) -> Result<BlockHeader, String>;
+
+    /// Header at `height` on the best chain, if the chain is that long.
+    fn header(&self, height: u32) -> Result<Option<BlockHeader>, String>;
+
+    fn transaction(&self, txid: &str) -> Result<Option<Transaction>, String>;
+
+    fn transaction_status(&self, txid: &str) -> Result<TxStatus, String>;
+
+    /// Txids of confirmed and unconfirmed transactions paying to or spending from `address`.
+    fn address_history(&self, address: &str) -> Result<Vec<String>, String>;
+
+    /// `address_history` for many addresses at once, in the same order.
+    /// Backends with batch support (Electrum batched requests, concurrent
+    /// Esplora calls) should override this.
+    fn batch_address_history(&self, addresses: &[String]) -> Result<Vec<Vec<String>>, String> {
+        addresses
+            .iter()
+            .map(|address| self.address_history(address))
+            .collect()
+    }
+
+    fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, String>;
+
+    /// Submits a transaction to the network and returns its txid.
+    fn broadcast(&self, tx: &Transaction) -> Result<String, String>;
+
+    /// Checks whether `tx` would be accepted without broadcasting it, like
+    /// Core's `testmempoolaccept`.
+    fn test_accept(&self, _tx: &Transaction) -> Result<DryRun, String> {
+        Ok(DryRun::Unsupported)
+    }
+
+    /// Current relay fee floors. Backends that can't report them assume the
+    /// default relay policy.
+    fn relay_fees(&self) -> Result<RelayFees, String> {
+        Ok(RelayFees::default())
+    }
+
+    /// Mempool fee histogram as `(fee_rate, vsize)` buckets, highest rate first,
+    /// like Electrum's `mempool.get_fee_histogram`: `vsize` vbytes of
+    /// transactions pay between this bucket's rate and the previous one's.
+    fn mempool_histogram(&self) -> Result<Vec<(f64, u64)>, String> {
+        Err("Mempool histogram is not supported by this backend".to_string())
+    }
+
+    /// Fee rates paid in the block with `hash`, if the backend can tell.
+    fn block_fee_stats(&self, _hash: &str) -> Result<Option<BlockFeeStats>, String> {
+        Ok(None)
+    }
+}
+
+/// Broadcasts `tx` after a dry run, refusing it when the backend says it
+/// would be rejected. Backends without dry runs broadcast directly.
+pub fn broadcast_checked(
+    backend: &dyn BlockchainBackend,
+    tx: &Transaction,
+) -> Result<String, String> {
+    match backend.test_accept(tx)? {
+        DryRun::Rejected { message, .. } => {
+            Err(format!("Transaction would be rejected: {}", message))
+        }
+        DryRun::Accepted { .. } | DryRun::Unsupported => backend.broadcast(tx),
+    }
+}
@@ -0,0 +1,231 @@
+use std::sync::{Arc, Mutex};
+
+use super::{BlockFeeStats, BlockHeader, BlockchainBackend, DryRun, RelayFees, TxStatus};
+use crate::events::{Event, MessageBus};
+use crate::types::{Transaction, Utxo};
+
+/// Tips further apart than this many blocks are treated as a divergence.
+pub const DEFAULT_DIVERGENCE_BLOCKS: u32 = 2;
+
+/// One provider's answer when tips were last compared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderTip {
+    pub provider: String,
+    pub tip: Result<BlockHeader, String>,
+}
+
+/// Outcome of comparing every provider's tip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TipConsensus {
+    pub chosen: BlockHeader,
+    /// Provider whose view the wallet follows.
+    pub preferred: String,
+    pub tips: Vec<ProviderTip>,
+    /// Some provider's tip is more than the threshold away from the chosen one.
+    pub diverged: bool,
+}
+
+/// Several backends queried as one.
+///
+/// Each tip lookup asks every provider. The wallet follows the tip most
+/// providers agree on, and the highest one when there is no majority, since
+/// headers carry no chainwork and height is the closest stand-in. When a
+/// provider drifts more than the threshold from that view — a lagging server,
+/// or one being fed a fake chain — a `SecurityAlert` lists every provider's tip.
+pub struct AggregateBackend {
+    providers: Vec<(String, Arc<dyn BlockchainBackend>)>,
+    threshold: u32,
+    bus: Option<Arc<MessageBus>>,
+    preferred: Mutex<usize>,
+    diverged: Mutex<bool>,
+}
+
+impl AggregateBackend {
+    pub fn new(providers: Vec<(String, Arc<dyn BlockchainBackend>)>) -> Result<Self, String> {
+        if providers.is_empty() {
+            return Err("At least one backend is required".to_string());
+        }
+        Ok(AggregateBackend {
+            providers,
+            threshold: DEFAULT_DIVERGENCE_BLOCKS,
+            bus: None,
+            preferred: Mutex::new(0),
+            diverged: Mutex::new(false),
+        })
+    }
+
+    pub fn with_divergence_threshold(mut self, blocks: u32) -> Self {
+        self.threshold = blocks;
+        self
+    }
+
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    fn preferred(&self) -> &dyn BlockchainBackend {
+        let index = *self.preferred.lock().unwrap_or_else(|e| e.into_inner());
+        self.providers[index].1.as_ref()
+    }
+
+    /// Asks every provider for its tip and picks the view to follow.
+    pub fn compare_tips(&self) -> Result<TipConsensus, String> {
+        let tips: Vec<ProviderTip> = self
+            .providers
+            .iter()
+            .map(|(name, backend)| ProviderTip {
+                provider: name.clone(),
+                tip: backend.tip(),
+            })
+            .collect();
+
+        // Providers grouped by the tip they report
+        let mut groups: Vec<(&BlockHeader, Vec<usize>)> = Vec::new();
+        for (i, tip) in tips.iter().enumerate() {
+            let Ok(header) = &tip.tip else { continue };
+            match groups.iter_mut().find(|(h, _)| h.hash == header.hash) {
+                Some((_, members)) => members.push(i),
+                None => groups.push((header, vec![i])),
+            }
+        }
+        let (chosen, members) = groups
+            .iter()
+            .max_by_key(|(header, members)| (members.len(), header.height))
+            .ok_or_else(|| "No backend reported a chain tip".to_string())?;
+        let chosen = (*chosen).clone();
+        let preferred = members[0];
+
+        let diverged = tips.iter().any(|t| {
+            t.tip
+                .as_ref()
+                .is_ok_and(|h| h.height.abs_diff(chosen.height) > self.threshold)
+        });
+        *self.preferred.lock().unwrap_or_else(|e| e.into_inner()) = preferred;
+
+        let was_diverged = std::mem::replace(
+            &mut *self.diverged.lock().unwrap_or_else(|e| e.into_inner()),
+            diverged,
+        );
+        if diverged && !was_diverged {
+            let details: Vec<String> = tips
+                .iter()
+                .map(|t| match &t.tip {
+                    Ok(h) => format!("{} at {} ({})", t.provider, h.height, h.hash),
+                    Err(e) => format!("{} unavailable ({})", t.provider, e),
+                })
+                .collect();
+            let message = format!(
+                "Backends disagree on the chain tip by more than {} blocks: {}. Following {}.",
+                self.threshold,
+                details.join(", "),
+                tips[preferred].provider
+            );
+            log::warn!("{}", message);
+            if let Some(bus) = &self.bus {
+                bus.publish(Event::SecurityAlert { message });
+            }
+        }
+
+        Ok(TipConsensus {
+            chosen,
+            preferred: tips[preferred].provider.clone(),
+            tips,
+            diverged,
+        })
+    }
+}
+
+impl BlockchainBackend for AggregateBackend {
+    fn tip(&self) -> Result<BlockHeader, String> {
+        self.compare_tips().map(|consensus| consensus.chosen)
+    }
+
+    fn header(&self, height: u32) -> Result<Option<BlockHeader>, String> {
+        self.preferred().header(height)
+    }
+
+    fn transaction(&self, txid: &str) -> Result<Option<Transaction>, String> {
+        self.preferred().transaction(txid)
+    }
+
+    fn transaction_status(&self, txid: &str) -> Result<TxStatus, String> {
+        self.preferred().transaction_status(txid)
+    }
+
+    fn address_history(&self, address: &str) -> Result<Vec<String>, String> {
+        self.preferred().address_history(address)
+    }
+
+    fn batch_address_history(&self, addresses: &[String]) -> Result<Vec<Vec<String>>, String> {
+        self.preferred().batch_address_history(addresses)
+    }
+
+    fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        self.preferred().address_utxos(address)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<String, String> {
+        self.preferred().broadcast(tx)
+    }
+
+    fn test_accept(&self, tx: &Transaction) -> Result<DryRun, String> {
+        self.preferred().test_accept(tx)
+    }
+
+    fn relay_fees(&self) -> Result<RelayFees, String> {
+        self.preferred().relay_fees()
+    }
+
+    fn mempool_histogram(&self) -> Result<Vec<(f64, u64)>, String> {
+        self.preferred().mempool_histogram()
+    }
+
+    fn block_fee_stats(&self, hash: &str) -> Result<Option<BlockFeeStats>, String> {
+        self.preferred().block_fee_stats(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    #[test]
+    fn test_majority_view_and_divergence_alert() {
+        let honest_a = Arc::new(MockBackend::new());
+        let honest_b = Arc::new(MockBackend::new());
+        let outlier = Arc::new(MockBackend::new());
+        honest_a.mine_blocks(10);
+        honest_b.mine_blocks(10);
+        outlier.mine_blocks(20);
+
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let aggregate = AggregateBackend::new(vec![
+            ("outlier".to_string(), outlier as Arc<dyn BlockchainBackend>),
+            (
+                "a".to_string(),
+                honest_a.clone() as Arc<dyn BlockchainBackend>,
+            ),
+            ("b".to_string(), honest_b as Arc<dyn BlockchainBackend>),
+        ])
+        .unwrap()
+        .with_event_bus(bus);
+
+        // The higher but lone tip loses to the two agreeing providers
+        let consensus = aggregate.compare_tips().unwrap();
+        assert_eq!(consensus.chosen.height, 10);
+        assert_eq!(consensus.preferred, "a");
+        assert!(consensus.diverged);
+        match events.try_recv().unwrap() {
+            Event::SecurityAlert { message } => assert!(message.contains("outlier at 20")),
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        // No repeat alert while the divergence persists
+        assert_eq!(aggregate.tip().unwrap().height, 10);
+        assert!(events.try_recv().is_err());
+        assert_eq!(aggregate.header(10).unwrap(), honest_a.header(10).unwrap());
+    }
+}
@@ -0,0 +1,513 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+use super::{BlockHeader, BlockchainBackend, DryRun, RejectReason, RelayFees, TxStatus};
+use crate::types::{OutPoint, Transaction, TxIn, TxOut, Utxo, SEQUENCE_FINAL};
+use crate::utxo_selection::estimate_vsize;
+
+const GENESIS_TIME: u64 = 1_600_000_000;
+const BLOCK_INTERVAL_SECS: u64 = 600;
+
+struct Block {
+    header: BlockHeader,
+    txids: Vec<String>,
+}
+
+struct ChainState {
+    blocks: Vec<Block>,
+    mempool: Vec<String>,
+    transactions: HashMap<String, Transaction>,
+    funding_counter: u64,
+    time: u64,
+    relay_fees: RelayFees,
+    mempool_histogram: Vec<(f64, u64)>,
+}
+
+impl ChainState {
+    fn tip(&self) -> &BlockHeader {
+        &self.blocks[self.blocks.len() - 1].header
+    }
+
+    fn confirmed_height(&self, txid: &str) -> Option<&BlockHeader> {
+        self.blocks
+            .iter()
+            .find(|block| block.txids.iter().any(|t| t == txid))
+            .map(|block| &block.header)
+    }
+
+    // Transactions on the best chain followed by the mempool, in order
+    fn live_txids(&self) -> impl Iterator<Item = &String> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.txids.iter())
+            .chain(self.mempool.iter())
+    }
+
+    fn spent_outpoints(&self) -> HashSet<OutPoint> {
+        self.live_txids()
+            .filter_map(|txid| self.transactions.get(txid))
+            .flat_map(|tx| tx.inputs.iter().map(|i| i.previous_output.clone()))
+            .collect()
+    }
+
+    fn output(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        let is_live = self.live_txids().any(|t| *t == outpoint.txid);
+        if !is_live {
+            return None;
+        }
+        self.transactions
+            .get(&outpoint.txid)
+            .and_then(|tx| tx.outputs.get(outpoint.vout as usize))
+    }
+
+    // Mempool policy: returns the fee and vsize, or the node's reject reason
+    fn check_acceptance(&self, tx: &Transaction) -> Result<(u64, u64), String> {
+        if tx.inputs.is_empty() || tx.outputs.is_empty() {
+            return Err("bad-txns-vin-or-vout-empty".to_string());
+        }
+
+        let spent = self.spent_outpoints();
+        let mut input_value = 0u64;
+        for input in &tx.inputs {
+            if spent.contains(&input.previous_output) {
+                return Err(format!(
+                    "txn-mempool-conflict: {} already spent",
+                    input.previous_output
+                ));
+            }
+            let output = self
+                .output(&input.previous_output)
+                .ok_or_else(|| format!("bad-txns-inputs-missing: {}", input.previous_output))?;
+            input_value += output.value;
+        }
+
+        if input_value < tx.output_value() {
+            return Err("bad-txns-in-belowout".to_string());
+        }
+
+        let fee = input_value - tx.output_value();
+        let vsize = estimate_vsize(tx.inputs.len(), tx.outputs.len());
+        let required = (self.relay_fees.floor() * vsize as f64).ceil() as u64;
+        if fee < required {
+            return Err(format!("mempool min fee not met, {} < {}", fee, required));
+        }
+        Ok((fee, vsize))
+    }
+
+    fn push_block(&mut self, txids: Vec<String>) -> BlockHeader {
+        let prev = self.tip().clone();
+        self.time += BLOCK_INTERVAL_SECS;
+
+        let mut hasher = Sha256::new();
+        hasher.update(prev.hash.as_bytes());
+        hasher.update((prev.height + 1).to_le_bytes());
+        hasher.update(self.time.to_le_bytes());
+        for txid in &txids {
+            hasher.update(txid.as_bytes());
+        }
+
+        let header = BlockHeader {
+            height: prev.height + 1,
+            hash: hex::encode(hasher.finalize()),
+            prev_hash: prev.hash,
+            time: self.time,
+        };
+        self.blocks.push(Block {
+            header: header.clone(),
+            txids,
+        });
+        header
+    }
+}
+
+/// In-memory chain for hermetic tests of sync, reorg handling and broadcast.
+///
+/// Nothing happens on its own: tests fund addresses, mine blocks and trigger
+/// reorgs explicitly, so every run sees exactly the same chain.
+pub struct MockBackend {
+    state: Mutex<ChainState>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    /// Creates a chain containing only an empty genesis block at height 0.
+    pub fn new() -> Self {
+        let genesis = Block {
+            header: BlockHeader {
+                height: 0,
+                hash: hex::encode(Sha256::digest(b"bitvault mock genesis")),
+                prev_hash: String::new(),
+                time: GENESIS_TIME,
+            },
+            txids: Vec::new(),
+        };
+
+        MockBackend {
+            state: Mutex::new(ChainState {
+                blocks: vec![genesis],
+                mempool: Vec::new(),
+                transactions: HashMap::new(),
+                funding_counter: 0,
+                time: GENESIS_TIME,
+                relay_fees: RelayFees::default(),
+                mempool_histogram: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ChainState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds an unconfirmed transaction paying `value` sats to `address` from an
+    /// external source and returns the funded outpoint.
+    pub fn fund_address(&self, address: &str, value: u64) -> OutPoint {
+        let mut state = self.lock();
+        state.funding_counter += 1;
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"bitvault mock faucet");
+        hasher.update(state.funding_counter.to_le_bytes());
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new(&hex::encode(hasher.finalize()), 0),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: address.to_string(),
+                value,
+            }],
+        };
+
+        let txid = tx.txid();
+        state.transactions.insert(txid.clone(), tx);
+        state.mempool.push(txid.clone());
+        OutPoint::new(&txid, 0)
+    }
+
+    /// Mines `count` blocks; the first one confirms everything in the mempool.
+    pub fn mine_blocks(&self, count: u32) -> Vec<BlockHeader> {
+        let mut state = self.lock();
+        (0..count)
+            .map(|_| {
+                let txids = std::mem::take(&mut state.mempool);
+                state.push_block(txids)
+            })
+            .collect()
+    }
+
+    /// Replaces the last `depth` blocks with the same number of empty blocks.
+    ///
+    /// Transactions from the disconnected blocks return to the mempool, as they
+    /// would on a real node, and can be confirmed again with `mine_blocks`.
+    pub fn reorg(&self, depth: u32) -> Result<Vec<BlockHeader>, String> {
+        let mut state = self.lock();
+        if depth as usize >= state.blocks.len() {
+            return Err(format!("Cannot reorg {} blocks past genesis", depth));
+        }
+
+        let mut returned = Vec::new();
+        for _ in 0..depth {
+            if let Some(block) = state.blocks.pop() {
+                let mut txids = block.txids;
+                txids.append(&mut returned);
+                returned = txids;
+            }
+        }
+        returned.append(&mut state.mempool);
+        state.mempool = returned;
+
+        Ok((0..depth).map(|_| state.push_block(Vec::new())).collect())
+    }
+
+    /// Simulates a mempool spike (or its end) by changing the fee floors
+    /// reported and enforced on broadcast.
+    pub fn set_relay_fees(&self, relay_fees: RelayFees) {
+        self.lock().relay_fees = relay_fees;
+    }
+
+    /// Sets the histogram reported for the rest of the mempool, which the mock
+    /// doesn't otherwise model.
+    pub fn set_mempool_histogram(&self, histogram: Vec<(f64, u64)>) {
+        self.lock().mempool_histogram = histogram;
+    }
+
+    pub fn mempool_txids(&self) -> Vec<String> {
+        self.lock().mempool.clone()
+    }
+
+    /// Drops a transaction (and anything spending it) from the mempool, as if it expired.
+    pub fn evict_from_mempool(&self, txid: &str) -> bool {
+        let mut state = self.lock();
+        if !state.mempool.iter().any(|t| t == txid) {
+            return false;
+        }
+
+        let mut evicted = HashSet::from([txid.to_string()]);
+        let mempool = std::mem::take(&mut state.mempool);
+        for candidate in mempool {
+            let spends_evicted = state.transactions.get(&candidate).is_some_and(|tx| {
+                tx.inputs
+                    .iter()
+                    .any(|i| evicted.contains(&i.previous_output.txid))
+            });
+            if candidate == txid || spends_evicted {
+                evicted.insert(candidate);
+            } else {
+                state.mempool.push(candidate);
+            }
+        }
+        true
+    }
+}
+
+impl BlockchainBackend for MockBackend {
+    fn tip(&self) -> Result<BlockHeader, String> {
+        Ok(self.lock().tip().clone())
+    }
+
+    fn header(&self, height: u32) -> Result<Option<BlockHeader>, String> {
+        Ok(self
+            .lock()
+            .blocks
+            .get(height as usize)
+            .map(|block| block.header.clone()))
+    }
+
+    fn transaction(&self, txid: &str) -> Result<Option<Transaction>, String> {
+        Ok(self.lock().transactions.get(txid).cloned())
+    }
+
+    fn transaction_status(&self, txid: &str) -> Result<TxStatus, String> {
+        let state = self.lock();
+        if let Some(header) = state.confirmed_height(txid) {
+            return Ok(TxStatus::Confirmed {
+                height: header.height,
+                block_hash: header.hash.clone(),
+            });
+        }
+        if state.mempool.iter().any(|t| t == txid) {
+            return Ok(TxStatus::Unconfirmed);
+        }
+        Ok(TxStatus::NotFound)
+    }
+
+    fn address_history(&self, address: &str) -> Result<Vec<String>, String> {
+        let state = self.lock();
+        let history = state
+            .live_txids()
+            .filter(|txid| {
+                let Some(tx) = state.transactions.get(*txid) else {
+                    return false;
+                };
+                let receives = tx.outputs.iter().any(|o| o.address == address);
+                let spends = tx.inputs.iter().any(|i| {
+                    state
+                        .output(&i.previous_output)
+                        .is_some_and(|o| o.address == address)
+                });
+                receives || spends
+            })
+            .cloned()
+            .collect();
+        Ok(history)
+    }
+
+    fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        let state = self.lock();
+        let spent = state.spent_outpoints();
+        let tip_height = state.tip().height;
+
+        let mut utxos = Vec::new();
+        for txid in state.live_txids() {
+            let Some(tx) = state.transactions.get(txid) else {
+                continue;
+            };
+            let confirmations = state
+                .confirmed_height(txid)
+                .map(|header| tip_height - header.height + 1)
+                .unwrap_or(0);
+
+            for (vout, output) in tx.outputs.iter().enumerate() {
+                let outpoint = OutPoint::new(txid, vout as u32);
+                if output.address == address && !spent.contains(&outpoint) {
+                    utxos.push(Utxo {
+                        outpoint,
+                        address: output.address.clone(),
+                        value: output.value,
+                        confirmations,
+                        is_coinbase: tx.is_coinbase(),
+                    });
+                }
+            }
+        }
+        Ok(utxos)
+    }
+
+    fn relay_fees(&self) -> Result<RelayFees, String> {
+        Ok(self.lock().relay_fees)
+    }
+
+    fn mempool_histogram(&self) -> Result<Vec<(f64, u64)>, String> {
+        Ok(self.lock().mempool_histogram.clone())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<String, String> {
+        let mut state = self.lock();
+        let txid = tx.txid();
+        if state.live_txids().any(|t| *t == txid) {
+            return Ok(txid);
+        }
+        state.check_acceptance(tx)?;
+        state.transactions.insert(txid.clone(), tx.clone());
+        state.mempool.push(txid.clone());
+        Ok(txid)
+    }
+
+    fn test_accept(&self, tx: &Transaction) -> Result<DryRun, String> {
+        let state = self.lock();
+        let txid = tx.txid();
+        if state.live_txids().any(|t| *t == txid) {
+            return Ok(DryRun::Rejected {
+                reason: RejectReason::AlreadyKnown,
+                message: "txn-already-known".to_string(),
+            });
+        }
+        Ok(match state.check_acceptance(tx) {
+            Ok((fee, vsize)) => DryRun::Accepted {
+                txid,
+                fee,
+                vsize,
+                fee_rate: fee as f64 / vsize as f64,
+            },
+            Err(message) => DryRun::Rejected {
+                reason: RejectReason::from_node(&message),
+                message,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::broadcast_checked;
+
+    fn spend(outpoint: &OutPoint, to: &str, value: u64) -> Transaction {
+        Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: outpoint.clone(),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: to.to_string(),
+                value,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_fund_mine_and_broadcast() {
+        let backend = MockBackend::new();
+        let funded = backend.fund_address("alice", 50_000);
+        assert_eq!(backend.address_utxos("alice").unwrap()[0].confirmations, 0);
+
+        backend.mine_blocks(3);
+        assert_eq!(backend.tip().unwrap().height, 3);
+        assert_eq!(backend.address_utxos("alice").unwrap()[0].confirmations, 3);
+
+        let txid = backend.broadcast(&spend(&funded, "bob", 49_000)).unwrap();
+        assert_eq!(
+            backend.transaction_status(&txid).unwrap(),
+            TxStatus::Unconfirmed
+        );
+        assert!(backend.address_utxos("alice").unwrap().is_empty());
+        assert_eq!(backend.address_history("alice").unwrap().len(), 2);
+
+        // Double spends and overspends are rejected
+        assert!(backend.broadcast(&spend(&funded, "carol", 1_000)).is_err());
+        let other = backend.fund_address("dave", 1_000);
+        assert!(backend.broadcast(&spend(&other, "erin", 2_000)).is_err());
+
+        // So are transactions below the mempool floor during a spike
+        backend.set_relay_fees(RelayFees {
+            min_relay_fee: 1.0,
+            mempool_min_fee: 20.0,
+        });
+        assert!(backend.broadcast(&spend(&other, "erin", 500)).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_matches_broadcast() {
+        let backend = MockBackend::new();
+        let funded = backend.fund_address("alice", 50_000);
+        let tx = spend(&funded, "bob", 49_000);
+        let vsize = estimate_vsize(1, 1);
+        assert_eq!(
+            backend.test_accept(&tx).unwrap(),
+            DryRun::Accepted {
+                txid: tx.txid(),
+                fee: 1_000,
+                vsize,
+                fee_rate: 1_000.0 / vsize as f64,
+            }
+        );
+        // Nothing was broadcast
+        assert_eq!(backend.mempool_txids().len(), 1);
+
+        let overspend = spend(&funded, "bob", 60_000);
+        assert!(matches!(
+            backend.test_accept(&overspend).unwrap(),
+            DryRun::Rejected {
+                reason: RejectReason::OutputsExceedInputs,
+                ..
+            }
+        ));
+        assert_eq!(
+            broadcast_checked(&backend, &overspend).unwrap_err(),
+            "Transaction would be rejected: bad-txns-in-belowout"
+        );
+        broadcast_checked(&backend, &tx).unwrap();
+        assert!(matches!(
+            backend
+                .test_accept(&spend(&funded, "carol", 1_000))
+                .unwrap(),
+            DryRun::Rejected {
+                reason: RejectReason::MempoolConflict,
+                ..
+            }
+        ));
+        assert_eq!(
+            RejectReason::from_node("min relay fee not met, 100 < 141"),
+            RejectReason::FeeTooLow
+        );
+    }
+
+    #[test]
+    fn test_reorg_returns_transactions_to_mempool() {
+        let backend = MockBackend::new();
+        let funded = backend.fund_address("alice", 10_000);
+        backend.mine_blocks(2);
+        let old_tip = backend.tip().unwrap();
+
+        let new_headers = backend.reorg(2).unwrap();
+        assert_eq!(new_headers.len(), 2);
+        assert_eq!(backend.tip().unwrap().height, old_tip.height);
+        assert_ne!(backend.tip().unwrap().hash, old_tip.hash);
+        assert_eq!(
+            backend.transaction_status(&funded.txid).unwrap(),
+            TxStatus::Unconfirmed
+        );
+
+        assert!(backend.evict_from_mempool(&funded.txid));
+        assert!(backend.address_utxos("alice").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,373 @@
+//! Pushes encrypted backup archives to storage the user configures, keeping
+//! a number of past versions there and restoring from any of them.
+//!
+//! Archives are encrypted on this machine before upload, so the storage
+//! (and anyone with access to it) only ever sees ciphertext. Next to the
+//! versions the store holds an index, [`INDEX_NAME`], listing each version's
+//! time, size and hash; it says nothing about the wallet.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+use crate::clock::SharedClock;
+use crate::crypto;
+use crate::progress::{self, Progress, ProgressSink};
+use crate::scheduler::{CancellationToken, Scheduler};
+
+#[cfg(feature = "cloud-backup")]
+pub mod s3;
+#[cfg(feature = "cloud-backup")]
+pub mod webdav;
+
+pub const INDEX_NAME: &str = "bitvault-backups.json";
+pub const DEFAULT_RETENTION: usize = 10;
+
+/// Somewhere backups can be written to and read back from, by object name.
+pub trait BackupStore: Send {
+    /// Where the store points, for messages.
+    fn describe(&self) -> String;
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String>;
+    /// The object's contents, or `None` if there is no such object.
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, String>;
+    /// Removes the object; removing a missing object is not an error.
+    fn delete(&self, name: &str) -> Result<(), String>;
+}
+
+/// A local folder, such as a mounted drive or a folder a sync client watches.
+pub struct FolderStore {
+    dir: PathBuf,
+}
+
+impl FolderStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FolderStore { dir: dir.into() }
+    }
+}
+
+impl BackupStore for FolderStore {
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        // Written aside and renamed, so a crash never leaves half an object
+        let path = self.dir.join(check_name(name)?);
+        let partial = self.dir.join(format!("{}.partial", name));
+        fs::write(&partial, data)
+            .and_then(|_| fs::rename(&partial, &path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let path = self.dir.join(check_name(name)?);
+        match fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let path = self.dir.join(check_name(name)?);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to delete {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where backups go, as saved in settings. Passwords and secret keys are not
+/// part of it and are given to [`BackupTarget::open`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackupTarget {
+    Folder {
+        path: PathBuf,
+    },
+    /// A WebDAV collection, e.g. on Nextcloud.
+    WebDav {
+        url: String,
+        username: String,
+    },
+    /// An S3-compatible bucket, addressed path-style.
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        access_key_id: String,
+    },
+}
+
+impl BackupTarget {
+    /// Opens the store; `secret` is the WebDAV password or S3 secret key.
+    pub fn open(&self, secret: Option<&str>) -> Result<Box<dyn BackupStore>, String> {
+        match self {
+            BackupTarget::Folder { path } => Ok(Box::new(FolderStore::new(path))),
+            #[cfg(feature = "cloud-backup")]
+            BackupTarget::WebDav { url, username } => Ok(Box::new(webdav::WebDavStore::new(
+                url,
+                username,
+                secret.ok_or("WebDAV backups need a password")?,
+            ))),
+            #[cfg(feature = "cloud-backup")]
+            BackupTarget::S3 {
+                endpoint,
+                region,
+                bucket,
+                access_key_id,
+            } => Ok(Box::new(s3::S3Store::new(
+                endpoint,
+                region,
+                bucket,
+                access_key_id,
+                secret.ok_or("S3 backups need a secret key")?,
+            ))),
+            #[cfg(not(feature = "cloud-backup"))]
+            BackupTarget::WebDav { .. } | BackupTarget::S3 { .. } => {
+                let _ = secret;
+                Err("Built without cloud backup support".to_string())
+            }
+        }
+    }
+}
+
+/// One stored backup.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupVersion {
+    pub name: String,
+    /// Unix timestamp (seconds) it was pushed.
+    pub created_at: u64,
+    /// Encrypted size in bytes.
+    pub size: usize,
+    /// Hex SHA-256 of the encrypted object, checked before decrypting.
+    pub sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackupIndex {
+    versions: Vec<BackupVersion>,
+}
+
+/// Encrypts, uploads, prunes and restores backups on one store.
+pub struct BackupSync {
+    store: Box<dyn BackupStore>,
+    retention: usize,
+    progress: Option<Arc<dyn ProgressSink>>,
+}
+
+impl BackupSync {
+    pub fn new(store: Box<dyn BackupStore>) -> Self {
+        BackupSync {
+            store,
+            retention: DEFAULT_RETENTION,
+            progress: None,
+        }
+    }
+
+    /// Reports each push and restore step to `sink`.
+    pub fn with_progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    fn report(&self, phase: &str, step: u32, steps: u32) {
+        progress::report(self.progress.as_deref(), || {
+            Progress::step("backup", phase, step, steps)
+        });
+    }
+
+    /// Keeps the `versions` most recent backups, at least one.
+    pub fn with_retention(mut self, versions: usize) -> Self {
+        self.retention = versions.max(1);
+        self
+    }
+
+    fn index(&self) -> Result<BackupIndex, String> {
+        match self.store.get(INDEX_NAME)? {
+            Some(data) => serde_json::from_slice(&data)
+                .map_err(|e| format!("Failed to parse backup index: {}", e)),
+            None => Ok(BackupIndex::default()),
+        }
+    }
+
+    fn save_index(&self, index: &BackupIndex) -> Result<(), String> {
+        let data = serde_json::to_vec(index)
+            .map_err(|e| format!("Failed to serialize backup index: {}", e))?;
+        self.store.put(INDEX_NAME, &data)
+    }
+
+    /// Stored backups, oldest first.
+    pub fn versions(&self) -> Result<Vec<BackupVersion>, String> {
+        Ok(self.index()?.versions)
+    }
+
+    /// Encrypts `archive` with `key`, uploads it as a new version and deletes
+    /// versions beyond the retention count.
+    pub fn push(&self, archive: &[u8], key: &[u8; 32], now: u64) -> Result<BackupVersion, String> {
+        let mut index = self.index()?;
+        let name = format!("bitvault-backup-{}.enc", now);
+        if index.versions.iter().any(|v| v.name == name) {
+            return Err(format!("A backup was already pushed at {}", now));
+        }
+        self.report("Encrypting", 0, 3);
+        let encrypted = crypto::encrypt_with_key(key, archive)?.into_bytes();
+        let version = BackupVersion {
+            name,
+            created_at: now,
+            size: encrypted.len(),
+            sha256: hex::encode(Sha256::digest(&encrypted)),
+        };
+        self.report("Uploading", 1, 3);
+        self.store.put(&version.name, &encrypted).map_err(|e| {
+            format!(
+                "Failed to upload backup to {}: {}",
+                self.store.describe(),
+                e
+            )
+        })?;
+        index.versions.push(version.clone());
+
+        let excess = index.versions.len().saturating_sub(self.retention);
+        let expired: Vec<BackupVersion> = index.versions.drain(..excess).collect();
+        // The index goes first, so it never lists a version already deleted
+        self.report("Pruning old versions", 2, 3);
+        self.save_index(&index)?;
+        for old in expired {
+            if let Err(e) = self.store.delete(&old.name) {
+                log::warn!("Failed to delete old backup {}: {}", old.name, e);
+            }
+        }
+        self.report("Done", 3, 3);
+        Ok(version)
+    }
+
+    /// Downloads, checks and decrypts the version named `name`.
+    pub fn restore(&self, name: &str, key: &[u8; 32]) -> Result<Zeroizing<Vec<u8>>, String> {
+        let version = self
+            .index()?
+            .versions
+            .into_iter()
+            .find(|v| v.name == name)
+            .ok_or_else(|| format!("No backup named {}", name))?;
+        self.report("Downloading", 0, 2);
+        let encrypted = self
+            .store
+            .get(&version.name)?
+            .ok_or_else(|| format!("Backup {} is missing from the store", name))?;
+        if hex::encode(Sha256::digest(&encrypted)) != version.sha256 {
+            return Err(format!("Backup {} is corrupted", name));
+        }
+        let encrypted =
+            String::from_utf8(encrypted).map_err(|_| format!("Backup {} is corrupted", name))?;
+        self.report("Decrypting", 1, 2);
+        let archive = crypto::decrypt_with_key(key, &encrypted)
+            .map(Zeroizing::new)
+            .map_err(|_| format!("Backup {} could not be decrypted with this key", name))?;
+        self.report("Done", 2, 2);
+        Ok(archive)
+    }
+
+    /// Restores the most recent version.
+    pub fn restore_latest(&self, key: &[u8; 32]) -> Result<Zeroizing<Vec<u8>>, String> {
+        let latest = self
+            .versions()?
+            .pop()
+            .ok_or_else(|| format!("No backups in {}", self.store.describe()))?;
+        self.restore(&latest.name, key)
+    }
+
+    /// Pushes the archive `archive` returns every `interval_secs` as the
+    /// scheduler task `backup-sync`.
+    pub fn schedule<F>(
+        self,
+        scheduler: &mut Scheduler,
+        interval_secs: u64,
+        key: [u8; 32],
+        clock: SharedClock,
+        mut archive: F,
+    ) -> Result<CancellationToken, String>
+    where
+        F: FnMut() -> Result<Vec<u8>, String> + Send + 'static,
+    {
+        let key = Zeroizing::new(key);
+        scheduler.schedule(
+            "backup-sync",
+            interval_secs,
+            interval_secs / 10,
+            move |_| {
+                let archive = Zeroizing::new(archive()?);
+                let version = self.push(&archive, &key, clock.now())?;
+                log::info!("Backed up {} to {}", version.name, self.store.describe());
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Object names are generated here, but settings could point a store at
+/// anything; refuse names that would escape it.
+pub(crate) fn check_name(name: &str) -> Result<&str, String> {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && Path::new(name).file_name().is_some_and(|f| f == name);
+    if plain {
+        Ok(name)
+    } else {
+        Err(format!("Invalid backup object name {:?}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_retention_and_restore() {
+        let dir = std::env::temp_dir().join(format!("bitvault_backup_sync_{}", std::process::id()));
+        let sync = BackupSync::new(Box::new(FolderStore::new(&dir))).with_retention(2);
+        let key = [7u8; 32];
+
+        for (i, now) in [1_700_000_000, 1_700_000_100, 1_700_000_200]
+            .iter()
+            .enumerate()
+        {
+            sync.push(format!("archive {}", i).as_bytes(), &key, *now)
+                .unwrap();
+        }
+        assert!(sync.push(b"again", &key, 1_700_000_200).is_err());
+        let versions = sync.versions().unwrap();
+        assert_eq!(
+            versions.iter().map(|v| v.created_at).collect::<Vec<_>>(),
+            vec![1_700_000_100, 1_700_000_200]
+        );
+        assert!(!dir.join("bitvault-backup-1700000000.enc").exists());
+        // Only ciphertext reaches the store
+        let stored = fs::read(dir.join(&versions[1].name)).unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("archive"));
+
+        assert_eq!(sync.restore_latest(&key).unwrap().as_slice(), b"archive 2");
+        assert_eq!(
+            sync.restore(&versions[0].name, &key).unwrap().as_slice(),
+            b"archive 1"
+        );
+        assert!(sync.restore_latest(&[8u8; 32]).is_err());
+
+        fs::write(dir.join(&versions[1].name), b"00").unwrap();
+        assert_eq!(
+            sync.restore_latest(&key).unwrap_err(),
+            format!("Backup {} is corrupted", versions[1].name)
+        );
+        assert!(check_name("../wallet.dat").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
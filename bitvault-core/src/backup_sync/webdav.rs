@@ -0,0 +1,71 @@
+//! WebDAV collections, as served by Nextcloud, ownCloud and most NAS boxes.
+
+use base64::Engine;
+use std::io::Read;
+
+use super::{check_name, BackupStore};
+
+pub struct WebDavStore {
+    /// Collection URL, always ending in `/`.
+    url: String,
+    authorization: String,
+}
+
+impl WebDavStore {
+    pub fn new(url: &str, username: &str, password: &str) -> Self {
+        WebDavStore {
+            url: format!("{}/", url.trim_end_matches('/')),
+            authorization: format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password))
+            ),
+        }
+    }
+
+    fn object_url(&self, name: &str) -> Result<String, String> {
+        Ok(format!("{}{}", self.url, check_name(name)?))
+    }
+}
+
+impl BackupStore for WebDavStore {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        ureq::put(&self.object_url(name)?)
+            .set("Authorization", &self.authorization)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(data)
+            .map(|_| ())
+            .map_err(|e| format!("WebDAV upload failed: {}", e))
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let response = match ureq::get(&self.object_url(name)?)
+            .set("Authorization", &self.authorization)
+            .call()
+        {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(format!("WebDAV download failed: {}", e)),
+        };
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|e| format!("WebDAV download failed: {}", e))?;
+        Ok(Some(data))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        match ureq::delete(&self.object_url(name)?)
+            .set("Authorization", &self.authorization)
+            .call()
+        {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(e) => Err(format!("WebDAV delete failed: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use zeroize::Zeroize;
+
+use crate::clock::Clock;
+use crate::events::{Event, MessageBus};
+use crate::key_management;
+use crate::metadata::WalletMetadata;
+
+/// A single quiz question: pick the word at `position` (1-based) from `choices`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    pub position: usize,
+    pub choices: Vec<String>,
+}
+
+/// Backup verification quiz generated from a freshly created mnemonic.
+///
+/// Holds the mnemonic words until dropped so answers can be checked; they are
+/// zeroized on drop.
+pub struct BackupQuiz {
+    words: Vec<String>,
+    challenges: Vec<Challenge>,
+}
+
+impl BackupQuiz {
+    /// Creates `num_challenges` questions about distinct word positions, each offering
+    /// the correct word mixed with `num_choices - 1` distractors from the wordlist.
+    pub fn new(mnemonic: &str, num_challenges: usize, num_choices: usize) -> Result<Self, String> {
+        let mnemonic = key_management::validate_mnemonic(mnemonic)?;
+        let words: Vec<String> = mnemonic.words().map(String::from).collect();
+
+        if num_challenges == 0 || num_challenges > words.len() {
+            return Err(format!(
+                "Number of challenges must be between 1 and {}",
+                words.len()
+            ));
+        }
+        // Distractors come from the wordlist minus the phrase's own words, and
+        // asking for more than there are would never finish
+        let wordlist = mnemonic.language().word_list();
+        let max_choices = wordlist
+            .iter()
+            .filter(|w| !words.iter().any(|word| word == *w))
+            .count()
+            + 1;
+        if num_choices < 2 || num_choices > max_choices {
+            return Err(format!(
+                "Number of choices must be between 2 and {}",
+                max_choices
+            ));
+        }
+
+        let mut rng = rand::rng();
+        let mut positions: Vec<usize> = (0..words.len()).collect();
+        positions.shuffle(&mut rng);
+        positions.truncate(num_challenges);
+        positions.sort_unstable();
+
+        let challenges = positions
+            .into_iter()
+            .map(|index| {
+                let mut choices = vec![words[index].clone()];
+                while choices.len() < num_choices {
+                    let candidate = wordlist[rng.random_range(0..wordlist.len())];
+                    // Distractors must not reveal other words from the phrase
+                    if !words.iter().any(|w| w == candidate)
+                        && !choices.iter().any(|c| c == candidate)
+                    {
+                        choices.push(candidate.to_string());
+                    }
+                }
+                choices.shuffle(&mut rng);
+                Challenge {
+                    position: index + 1,
+                    choices,
+                }
+            })
+            .collect();
+
+        Ok(BackupQuiz { words, challenges })
+    }
+
+    pub fn challenges(&self) -> &[Challenge] {
+        &self.challenges
+    }
+
+    /// Checks the answer for a single 1-based word position.
+    pub fn check_answer(&self, position: usize, answer: &str) -> bool {
+        position >= 1
+            && position <= self.words.len()
+            && self.words[position - 1] == key_management::normalize_phrase(answer)
+    }
+
+    /// Returns true when every challenge has been answered correctly.
+    pub fn verify(&self, answers: &[(usize, String)]) -> bool {
+        self.challenges.iter().all(|challenge| {
+            answers
+                .iter()
+                .find(|(position, _)| *position == challenge.position)
+                .is_some_and(|(position, answer)| self.check_answer(*position, answer))
+        })
+    }
+}
+
+impl Drop for BackupQuiz {
+    fn drop(&mut self) {
+        self.words.zeroize();
+    }
+}
+
+/// Checks the quiz answers and, on success, records the verification time in the
+/// wallet metadata and publishes `BackupVerified`.
+pub fn complete_verification(
+    quiz: &BackupQuiz,
+    answers: &[(usize, String)],
+    metadata: &mut WalletMetadata,
+    bus: &MessageBus,
+    clock: &dyn Clock,
+) -> Result<(), String> {
+    if !quiz.verify(answers) {
+        return Err("One or more answers are incorrect".to_string());
+    }
+
+    let now = clock.now();
+    metadata.backup_verified_at = Some(now);
+    bus.publish(Event::BackupVerified { verified_at: now });
+    Ok(())
+}
+
+/// Publishes `BackupRequired` while the recovery phrase backup is
+/// unverified or the wallet changed since its last backup, saying why.
+pub fn check_backup_status(metadata: &WalletMetadata, bus: &MessageBus) {
+    let mut reasons = Vec::new();
+    if !metadata.is_backup_verified() {
+        reasons.push("Recovery phrase backup has not been verified".to_string());
+    }
+    if !metadata.backup_changes.is_empty() {
+        reasons.push(format!(
+            "Changed since the last backup: {}",
+            metadata.backup_changes.summary()
+        ));
+    }
+    if !reasons.is_empty() {
+        bus.publish(Event::BackupRequired {
+            reason: reasons.join("; "),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    const PHRASE: &str =
+        "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    #[test]
+    fn test_backup_quiz() {
+        let quiz = BackupQuiz::new(PHRASE, 3, 4).unwrap();
+        assert_eq!(quiz.challenges().len(), 3);
+        for challenge in quiz.challenges() {
+            assert_eq!(challenge.choices.len(), 4);
+        }
+        assert!(BackupQuiz::new(PHRASE, 3, 1).is_err());
+        // Nine distinct words leave 2039 distractors
+        assert!(BackupQuiz::new(PHRASE, 1, 2041).is_err());
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut metadata = WalletMetadata::default();
+        let clock = MockClock::new(1_700_000_000);
+
+        check_backup_status(&metadata, &bus);
+        assert!(matches!(
+            events.recv().unwrap(),
+            Event::BackupRequired { .. }
+        ));
+
+        let words: Vec<&str> = PHRASE.split(' ').collect();
+        let wrong: Vec<(usize, String)> = quiz
+            .challenges()
+            .iter()
+            .map(|c| (c.position, "zoo".to_string()))
+            .collect();
+        assert!(complete_verification(&quiz, &wrong, &mut metadata, &bus, &clock).is_err());
+        assert!(!metadata.is_backup_verified());
+
+        let answers: Vec<(usize, String)> = quiz
+            .challenges()
+            .iter()
+            .map(|c| (c.position, words[c.position - 1].to_string()))
+            .collect();
+        complete_verification(&quiz, &answers, &mut metadata, &bus, &clock).unwrap();
+        assert_eq!(metadata.backup_verified_at, Some(1_700_000_000));
+        assert!(matches!(
+            events.recv().unwrap(),
+            Event::BackupVerified { .. }
+        ));
+    }
+
+    #[test]
+    fn test_backup_required_after_changes() {
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut metadata = WalletMetadata {
+            backup_verified_at: Some(1_700_000_000),
+            ..Default::default()
+        };
+        check_backup_status(&metadata, &bus);
+        assert!(events.try_recv().is_err());
+
+        metadata.set_label("bc1qa", "Income/Salary").unwrap();
+        metadata.set_label("bc1qa", "Income/Salary").unwrap();
+        metadata.set_label("bc1qb", "Savings").unwrap();
+        // Within the gap a restore finds it anyway
+        metadata.note_address(0, 25, Some(10), 20);
+        metadata.note_address(0, 45, Some(10), 20);
+        metadata.note_xpub("73c5da0a");
+        check_backup_status(&metadata, &bus);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            Event::BackupRequired {
+                reason: "Changed since the last backup: 2 new labels, \
+                         addresses beyond the gap limit (up to #45), 1 new key (73c5da0a)"
+                    .to_string()
+            }
+        );
+
+        metadata.mark_backed_up(1_700_000_100);
+        check_backup_status(&metadata, &bus);
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, injected into services so tests can control it.
+pub trait Clock: Send + Sync {
+    /// Current unix timestamp in seconds.
+    fn now(&self) -> u64;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Wall clock time from the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Manually driven clock for simulating time passage in tests.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(start: u64) -> Self {
+        MockClock {
+            now: AtomicU64::new(start),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1_000);
+        clock.advance(60);
+        assert_eq!(clock.now(), 1_060);
+        clock.set(5);
+        assert_eq!(clock.now(), 5);
+
+        // Anything after 2020 means the system clock is wired up
+        assert!(SystemClock.now() > 1_577_836_800);
+    }
+}
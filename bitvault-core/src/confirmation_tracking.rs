@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::backend::{BlockchainBackend, TxStatus};
+use crate::events::{Event, MessageBus};
+use crate::fee_estimation::{FeeEstimationService, FeePriority};
+use crate::utxo_selection::estimate_vsize;
+
+pub mod advisor;
+
+/// BIP-125 incremental relay fee a replacement must add on top of the original fee.
+const INCREMENTAL_RELAY_FEE: f64 = 1.0;
+
+/// Where a sent transaction is relative to its confirmation target.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionConfirmationStatus {
+    /// In the mempool and still within its target window.
+    Pending,
+    /// In the mempool past its target window.
+    TargetMissed {
+        blocks_waiting: u32,
+    },
+    Confirmed {
+        height: u32,
+        confirmations: u32,
+    },
+    /// No longer known to the backend (evicted or replaced).
+    Dropped,
+}
+
+/// A sent transaction and the fee choice made for it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackedTransaction {
+    pub txid: String,
+    pub priority: FeePriority,
+    pub target_blocks: u32,
+    /// Fee rate paid, in sat/vB.
+    pub fee_rate: f64,
+    pub vsize: u64,
+    /// Tip height when the transaction was broadcast.
+    pub sent_at_height: u32,
+    pub status: TransactionConfirmationStatus,
+    /// Whether a fee bump has already been suggested, so it is only raised once.
+    pub bump_suggested: bool,
+}
+
+impl TrackedTransaction {
+    pub fn fee(&self) -> u64 {
+        (self.fee_rate * self.vsize as f64).ceil() as u64
+    }
+
+    /// Extra fee a replacement paying `new_rate` needs over the original fee.
+    pub fn rbf_additional_fee(&self, new_rate: f64) -> u64 {
+        let rate = new_rate.max(self.fee_rate + INCREMENTAL_RELAY_FEE);
+        ((rate * self.vsize as f64).ceil() as u64).saturating_sub(self.fee())
+    }
+
+    /// Fee a one-input, one-output child must pay so the package averages `new_rate`.
+    pub fn cpfp_additional_fee(&self, new_rate: f64) -> u64 {
+        let child_vsize = estimate_vsize(1, 1);
+        let package = (new_rate * (self.vsize + child_vsize) as f64).ceil() as u64;
+        package.saturating_sub(self.fee())
+    }
+}
+
+/// Follows sent transactions until they confirm and suggests fee bumps for those
+/// that miss their confirmation target.
+#[derive(Clone, Debug, Default)]
+pub struct ConfirmationTracker {
+    transactions: HashMap<String, TrackedTransaction>,
+}
+
+impl ConfirmationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking a transaction broadcast at tip height `sent_at_height`.
+    pub fn track(
+        &mut self,
+        txid: &str,
+        priority: FeePriority,
+        fee_rate: f64,
+        vsize: u64,
+        sent_at_height: u32,
+    ) {
+        self.transactions.insert(
+            txid.to_string(),
+            TrackedTransaction {
+                txid: txid.to_string(),
+                priority,
+                target_blocks: priority.target_blocks(),
+                fee_rate,
+                vsize,
+                sent_at_height,
+                status: TransactionConfirmationStatus::Pending,
+                bump_suggested: false,
+            },
+        );
+    }
+
+    pub fn get(&self, txid: &str) -> Option<&TrackedTransaction> {
+        self.transactions.get(txid)
+    }
+
+    /// Tracked transactions still waiting in the mempool.
+    pub fn unconfirmed(&self) -> impl Iterator<Item = &TrackedTransaction> {
+        self.transactions.values().filter(|tx| {
+            matches!(
+                tx.status,
+                TransactionConfirmationStatus::Pending
+                    | TransactionConfirmationStatus::TargetMissed { .. }
+            )
+        })
+    }
+
+    /// Refreshes every tracked transaction from `backend`, publishing a
+    /// `FeeBumpSuggested` event the first time one misses its target.
+    pub fn update(
+        &mut self,
+        backend: &dyn BlockchainBackend,
+        fees: &FeeEstimationService,
+        bus: Option<&MessageBus>,
+    ) -> Result<(), String> {
+        let tip = backend.tip()?.height;
+
+        for tx in self.transactions.values_mut() {
+            let blocks_waiting = tip.saturating_sub(tx.sent_at_height);
+            let was_confirmed =
+                matches!(tx.status, TransactionConfirmationStatus::Confirmed { .. });
+            tx.status = match backend.transaction_status(&tx.txid)? {
+                TxStatus::Confirmed { height, .. } => TransactionConfirmationStatus::Confirmed {
+                    height,
+                    confirmations: tip.saturating_sub(height) + 1,
+                },
+                TxStatus::NotFound => TransactionConfirmationStatus::Dropped,
+                TxStatus::Unconfirmed if blocks_waiting > tx.target_blocks => {
+                    TransactionConfirmationStatus::TargetMissed { blocks_waiting }
+                }
+                TxStatus::Unconfirmed => TransactionConfirmationStatus::Pending,
+            };
+
+            if let (TransactionConfirmationStatus::Confirmed { height, .. }, Some(bus)) =
+                (&tx.status, bus)
+            {
+                if !was_confirmed {
+                    bus.publish(Event::TransactionConfirmed {
+                        txid: tx.txid.clone(),
+                        height: *height,
+                    });
+                }
+            }
+
+            if !matches!(
+                tx.status,
+                TransactionConfirmationStatus::TargetMissed { .. }
+            ) || tx.bump_suggested
+            {
+                continue;
+            }
+            tx.bump_suggested = true;
+
+            let suggested = fees.get_fee_rate(tx.priority);
+            if suggested <= tx.fee_rate {
+                log::info!(
+                    "{} missed its target but already pays the current rate",
+                    tx.txid
+                );
+                continue;
+            }
+            if let Some(bus) = bus {
+                bus.publish(Event::FeeBumpSuggested {
+                    txid: tx.txid.clone(),
+                    target_blocks: tx.target_blocks,
+                    blocks_waiting,
+                    suggested_fee_rate: suggested,
+                    rbf_additional_fee: tx.rbf_additional_fee(suggested),
+                    cpfp_additional_fee: tx.cpfp_additional_fee(suggested),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops tracking transactions with at least `confirmations` confirmations.
+    pub fn prune_confirmed(&mut self, confirmations: u32) {
+        self.transactions.retain(|_, tx| match tx.status {
+            TransactionConfirmationStatus::Confirmed {
+                confirmations: c, ..
+            } => c < confirmations,
+            _ => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::clock::MockClock;
+    use crate::fee_estimation::FeeProvider;
+    use crate::types::{Transaction, TxIn, TxOut, SEQUENCE_FINAL};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    struct FixedProvider;
+
+    impl FeeProvider for FixedProvider {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn fetch(&self) -> Result<BTreeMap<u32, f64>, String> {
+            Ok(BTreeMap::from([(1, 20.0), (6, 10.0)]))
+        }
+    }
+
+    #[test]
+    fn test_target_miss_suggests_bump() {
+        let backend = MockBackend::new();
+        let funded = backend.fund_address("alice", 10_000);
+        backend.mine_blocks(3);
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: funded,
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: "bob".to_string(),
+                value: 9_780,
+            }],
+        };
+        let txid = backend.broadcast(&tx).unwrap();
+
+        let fees =
+            FeeEstimationService::new(vec![Box::new(FixedProvider)], Arc::new(MockClock::new(0)));
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut tracker = ConfirmationTracker::new();
+        // Sent at height 1 paying 2 sat/vB over 110 vB with a next-block target
+        tracker.track(&txid, FeePriority::High, 2.0, 110, 1);
+
+        tracker.update(&backend, &fees, Some(&bus)).unwrap();
+        assert_eq!(
+            tracker.get(&txid).unwrap().status,
+            TransactionConfirmationStatus::TargetMissed { blocks_waiting: 2 }
+        );
+        match events.try_recv().unwrap() {
+            Event::FeeBumpSuggested {
+                suggested_fee_rate,
+                rbf_additional_fee,
+                cpfp_additional_fee,
+                ..
+            } => {
+                assert_eq!(suggested_fee_rate, 20.0);
+                assert_eq!(rbf_additional_fee, 18 * 110);
+                assert_eq!(cpfp_additional_fee, 20 * (110 + 110) - 220);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // The suggestion is made once; confirmation is picked up on the next update
+        tracker.update(&backend, &fees, Some(&bus)).unwrap();
+        assert!(events.try_recv().is_err());
+        backend.mine_blocks(1);
+        tracker.update(&backend, &fees, Some(&bus)).unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::TransactionConfirmed { height: 4, .. })
+        ));
+        assert!(matches!(
+            tracker.get(&txid).unwrap().status,
+            TransactionConfirmationStatus::Confirmed {
+                confirmations: 1,
+                ..
+            }
+        ));
+        tracker.prune_confirmed(1);
+        assert!(tracker.get(&txid).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ConfirmationTracker, TrackedTransaction};
+use crate::backend::BlockchainBackend;
+
+/// Virtual size of a block's worth of transactions.
+pub const BLOCK_VSIZE: u64 = 1_000_000;
+
+/// Where an unconfirmed transaction sits in the mempool and what it would
+/// take to get it into the next block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BumpAdvice {
+    pub txid: String,
+    pub fee_rate: f64,
+    /// Vbytes of mempool transactions paying a higher rate.
+    pub vsize_ahead: u64,
+    /// Blocks until it confirms if nothing else arrives.
+    pub estimated_blocks: u32,
+    /// Lowest rate that moves it into the next block, if it isn't there already.
+    pub next_block_rate: Option<f64>,
+    pub rbf_additional_fee: Option<u64>,
+    pub cpfp_additional_fee: Option<u64>,
+}
+
+/// Vbytes paying more than `fee_rate` in a histogram sorted highest rate first.
+pub fn vsize_ahead(histogram: &[(f64, u64)], fee_rate: f64) -> u64 {
+    histogram
+        .iter()
+        .take_while(|(rate, _)| *rate > fee_rate)
+        .map(|(_, vsize)| vsize)
+        .sum()
+}
+
+/// Rate that outbids everything beyond the first `BLOCK_VSIZE - vsize` vbytes
+/// of the mempool, so a transaction of `vsize` fits into the next block.
+pub fn next_block_rate(histogram: &[(f64, u64)], vsize: u64) -> f64 {
+    let room = BLOCK_VSIZE.saturating_sub(vsize);
+    let mut filled = 0;
+    for (rate, bucket) in histogram {
+        filled += bucket;
+        if filled > room {
+            // Whole sat/vB above the bucket that overflows the block
+            return rate.floor() + 1.0;
+        }
+    }
+    // The whole mempool fits; the relay minimum is enough
+    1.0
+}
+
+fn advise_one(tx: &TrackedTransaction, histogram: &[(f64, u64)]) -> BumpAdvice {
+    let ahead = vsize_ahead(histogram, tx.fee_rate);
+    let estimated_blocks = ((ahead + tx.vsize) / BLOCK_VSIZE) as u32 + 1;
+    let next_block_rate =
+        Some(next_block_rate(histogram, tx.vsize)).filter(|rate| *rate > tx.fee_rate);
+
+    BumpAdvice {
+        txid: tx.txid.clone(),
+        fee_rate: tx.fee_rate,
+        vsize_ahead: ahead,
+        estimated_blocks,
+        next_block_rate,
+        rbf_additional_fee: next_block_rate.map(|rate| tx.rbf_additional_fee(rate)),
+        cpfp_additional_fee: next_block_rate.map(|rate| tx.cpfp_additional_fee(rate)),
+    }
+}
+
+/// Advice for every unconfirmed transaction in `tracker`, from the backend's
+/// current mempool histogram.
+pub fn advise(
+    tracker: &ConfirmationTracker,
+    backend: &dyn BlockchainBackend,
+) -> Result<Vec<BumpAdvice>, String> {
+    let mut histogram = backend.mempool_histogram()?;
+    histogram.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut advice: Vec<BumpAdvice> = tracker
+        .unconfirmed()
+        .map(|tx| advise_one(tx, &histogram))
+        .collect();
+    advice.sort_by(|a, b| a.txid.cmp(&b.txid));
+    Ok(advice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::fee_estimation::FeePriority;
+
+    #[test]
+    fn test_mempool_position_and_minimal_bump() {
+        let backend = MockBackend::new();
+        backend.set_mempool_histogram(vec![
+            (50.0, 300_000),
+            (20.0, 500_000),
+            (8.0, 400_000),
+            (3.0, 900_000),
+        ]);
+        let mut tracker = ConfirmationTracker::new();
+        tracker.track("slow", FeePriority::Low, 5.0, 200, 1);
+        tracker.track("fast", FeePriority::High, 30.0, 200, 1);
+
+        let advice = advise(&tracker, &backend).unwrap();
+        let fast = &advice[0];
+        assert_eq!(fast.vsize_ahead, 300_000);
+        assert_eq!(fast.estimated_blocks, 1);
+        assert_eq!(fast.next_block_rate, None);
+
+        let slow = &advice[1];
+        assert_eq!(slow.vsize_ahead, 1_200_000);
+        assert_eq!(slow.estimated_blocks, 2);
+        // 800k vB pay 20+ sat/vB; the 8 sat/vB bucket overflows the block
+        assert_eq!(slow.next_block_rate, Some(9.0));
+        assert_eq!(slow.rbf_additional_fee, Some(4 * 200));
+    }
+}
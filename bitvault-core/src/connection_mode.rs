@@ -0,0 +1,128 @@
+//! Metered connections (mobile data, capped hotspots).
+//!
+//! While metered, the scheduler stretches polling intervals and holds back
+//! deferrable downloads such as rate backfills, fee estimates are kept for
+//! longer, and scans query the backend in fewer, larger batches.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::config_manager::ConfigManager;
+#[cfg(feature = "native")]
+use crate::platform::PlatformProvider;
+
+/// Config key holding a [`ConnectionModeSetting`].
+pub const CONFIG_KEY: &str = "connection_mode";
+/// How many times longer polling intervals and cache lifetimes get while metered.
+pub const METERED_INTERVAL_FACTOR: u64 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionMode {
+    #[default]
+    Normal,
+    Metered,
+}
+
+impl ConnectionMode {
+    pub fn is_metered(self) -> bool {
+        self == ConnectionMode::Metered
+    }
+
+    /// `interval_secs` stretched by [`METERED_INTERVAL_FACTOR`] while metered.
+    pub fn interval(self, interval_secs: u64) -> u64 {
+        match self {
+            ConnectionMode::Normal => interval_secs,
+            ConnectionMode::Metered => interval_secs.saturating_mul(METERED_INTERVAL_FACTOR),
+        }
+    }
+}
+
+/// The user's choice; `Auto` follows what the platform reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionModeSetting {
+    #[default]
+    Auto,
+    Normal,
+    Metered,
+}
+
+impl ConnectionModeSetting {
+    /// The setting stored under [`CONFIG_KEY`], `Auto` when unset.
+    pub fn from_config(config: &ConfigManager) -> Result<Self, String> {
+        match config.get(CONFIG_KEY) {
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid {} setting: {}", CONFIG_KEY, e)),
+            None => Ok(ConnectionModeSetting::Auto),
+        }
+    }
+
+    /// The mode to use: the user's choice, else what `platform` detects,
+    /// else normal.
+    #[cfg(feature = "native")]
+    pub fn resolve(self, platform: &dyn PlatformProvider) -> ConnectionMode {
+        match self {
+            ConnectionModeSetting::Normal => ConnectionMode::Normal,
+            ConnectionModeSetting::Metered => ConnectionMode::Metered,
+            ConnectionModeSetting::Auto => match platform.is_metered_connection() {
+                Some(true) => ConnectionMode::Metered,
+                Some(false) | None => ConnectionMode::Normal,
+            },
+        }
+    }
+}
+
+/// The current mode, shared by the components that adapt to it.
+#[derive(Clone, Debug, Default)]
+pub struct SharedConnectionMode(Arc<AtomicBool>);
+
+impl SharedConnectionMode {
+    pub fn new(mode: ConnectionMode) -> Self {
+        SharedConnectionMode(Arc::new(AtomicBool::new(mode.is_metered())))
+    }
+
+    pub fn get(&self) -> ConnectionMode {
+        if self.0.load(Ordering::SeqCst) {
+            ConnectionMode::Metered
+        } else {
+            ConnectionMode::Normal
+        }
+    }
+
+    /// Switches to `mode`. Returns whether it changed.
+    pub fn set(&self, mode: ConnectionMode) -> bool {
+        self.0.swap(mode.is_metered(), Ordering::SeqCst) != mode.is_metered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_setting_and_shared_mode() {
+        let mut config = ConfigManager::new().unwrap();
+        assert_eq!(
+            ConnectionModeSetting::from_config(&config),
+            Ok(ConnectionModeSetting::Auto)
+        );
+        config.set(CONFIG_KEY, json!("metered")).unwrap();
+        assert_eq!(
+            ConnectionModeSetting::from_config(&config),
+            Ok(ConnectionModeSetting::Metered)
+        );
+        config.set(CONFIG_KEY, json!("sometimes")).unwrap();
+        assert!(ConnectionModeSetting::from_config(&config).is_err());
+
+        let shared = SharedConnectionMode::default();
+        let seen = shared.clone();
+        assert!(shared.set(ConnectionMode::Metered));
+        assert!(!shared.set(ConnectionMode::Metered));
+        assert_eq!(seen.get(), ConnectionMode::Metered);
+        assert_eq!(seen.get().interval(60), 240);
+        assert_eq!(ConnectionMode::Normal.interval(60), 60);
+    }
+}
@@ -7,23 +7,22 @@ use argon2::{
     Argon2, ParamsBuilder, Version,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::logging;
 
 #[derive(Serialize, Deserialize)]
 pub struct EncryptedData {
     ciphertext: String, // hex encoded
     nonce: String,      // hex encoded
     salt: String,       // password-hash encoded
+    // Hex encoded SHA-256 of the derived key, absent in wallets created before it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_check: Option<String>,
 }
 
-#[allow(dead_code)]
-pub fn encrypt_seed(seed: &str, pin: &str) -> Result<String, String> {
-    // Generate a random salt using getrandom
-    let mut salt_bytes = [0u8; 16];
-    getrandom::fill(&mut salt_bytes)
-        .map_err(|e| format!("Failed to generate random salt: {}", e))?;
-    let salt =
-        SaltString::encode_b64(&salt_bytes).map_err(|e| format!("Failed to encode salt: {}", e))?;
-
+// Derive the 32-byte AES key from a PIN with Argon2id
+fn derive_key(pin: &str, salt: &SaltString) -> Result<Vec<u8>, String> {
     // Configure Argon2id with strong parameters
     let params = ParamsBuilder::new()
         .m_cost(64 * 1024) // 64MB memory cost
@@ -35,15 +34,111 @@ pub fn encrypt_seed(seed: &str, pin: &str) -> Result<String, String> {
 
     // Create Argon2id instance
     let argon2 = Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params);
+    logging::trace("crypto", || {
+        format!("Deriving key with salt {}", salt.as_str())
+    });
 
-    // Derive key from PIN
-    let key = argon2
-        .hash_password(pin.as_bytes(), &salt)
+    Ok(argon2
+        .hash_password(pin.as_bytes(), salt)
         .map_err(|e| format!("Failed to derive key: {}", e))?
         .hash
         .ok_or("No hash value generated")?
         .as_bytes()
-        .to_vec();
+        .to_vec())
+}
+
+// Domain-separated fingerprint of the derived key, used to check a PIN without decrypting
+fn key_check_value(key: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"bitvault/key-check/v1");
+    hasher.update(key);
+    hasher.finalize().to_vec()
+}
+
+// Compare two byte slices without early exit on the first mismatch
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+// Salt and key check of a credential that protects nothing but itself
+#[derive(Serialize, Deserialize)]
+struct CredentialHash {
+    salt: String,
+    key_check: String,
+}
+
+/// Hashes a standalone credential, such as an approver password, for storage.
+pub fn hash_credential(password: &str) -> Result<String, String> {
+    let mut salt_bytes = [0u8; 16];
+    getrandom::fill(&mut salt_bytes)
+        .map_err(|e| format!("Failed to generate random salt: {}", e))?;
+    let salt =
+        SaltString::encode_b64(&salt_bytes).map_err(|e| format!("Failed to encode salt: {}", e))?;
+    let key = derive_key(password, &salt)?;
+
+    let hash = CredentialHash {
+        salt: salt.to_string(),
+        key_check: hex::encode(key_check_value(&key)),
+    };
+    serde_json::to_string(&hash).map_err(|e| format!("Serialization failed: {}", e))
+}
+
+/// Checks `password` against a hash from [`hash_credential`] in constant time.
+pub fn verify_credential(stored: &str, password: &str) -> Result<bool, String> {
+    let hash: CredentialHash =
+        serde_json::from_str(stored).map_err(|e| format!("Failed to parse credential: {}", e))?;
+    let expected =
+        hex::decode(&hash.key_check).map_err(|e| format!("Failed to decode key check: {}", e))?;
+    let salt = SaltString::from_b64(&hash.salt).map_err(|e| format!("Invalid salt: {}", e))?;
+    let key = derive_key(password, &salt)?;
+
+    Ok(constant_time_eq(&key_check_value(&key), &expected))
+}
+
+/// Encrypts `plaintext` with a random 32-byte data key (not a PIN) and returns
+/// the hex encoded nonce followed by the ciphertext.
+pub fn encrypt_with_key(key: &[u8; 32], plaintext: &[u8]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let mut nonce_bytes = [0u8; 12];
+    getrandom::fill(&mut nonce_bytes)
+        .map_err(|e| format!("Failed to generate random nonce: {}", e))?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+    Ok(hex::encode([nonce_bytes.as_slice(), &ciphertext].concat()))
+}
+
+/// Reverses [`encrypt_with_key`].
+pub fn decrypt_with_key(key: &[u8; 32], encrypted: &str) -> Result<Vec<u8>, String> {
+    let bytes =
+        hex::decode(encrypted).map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
+    if bytes.len() < 12 {
+        return Err("Ciphertext is too short".to_string());
+    }
+    let (nonce, ciphertext) = bytes.split_at(12);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))
+}
+
+#[allow(dead_code)]
+pub fn encrypt_seed(seed: &str, pin: &str) -> Result<String, String> {
+    // Generate a random salt using getrandom
+    let mut salt_bytes = [0u8; 16];
+    getrandom::fill(&mut salt_bytes)
+        .map_err(|e| format!("Failed to generate random salt: {}", e))?;
+    let salt =
+        SaltString::encode_b64(&salt_bytes).map_err(|e| format!("Failed to encode salt: {}", e))?;
+
+    // Derive key from PIN
+    let key = derive_key(pin, &salt)?;
+    let key_check = hex::encode(key_check_value(&key));
 
     // Create AES-GCM cipher
     let key = Key::<Aes256Gcm>::from_slice(&key);
@@ -65,6 +160,7 @@ pub fn encrypt_seed(seed: &str, pin: &str) -> Result<String, String> {
         ciphertext: hex::encode(ciphertext),
         nonce: hex::encode(nonce_bytes),
         salt: salt.to_string(),
+        key_check: Some(key_check),
     };
 
     // Serialize to JSON string
@@ -87,26 +183,8 @@ pub fn decrypt_seed(encrypted_data_str: &str, pin: &str) -> Result<String, Strin
     let salt =
         SaltString::from_b64(&encrypted_data.salt).map_err(|e| format!("Invalid salt: {}", e))?;
 
-    // Configure Argon2id with same parameters
-    let params = ParamsBuilder::new()
-        .m_cost(64 * 1024) // 64MB memory cost
-        .t_cost(3) // 3 iterations
-        .p_cost(4) // 4 parallel threads
-        .output_len(32) // 32 bytes output for AES-256
-        .build()
-        .map_err(|e| format!("Failed to build Argon2 params: {}", e))?;
-
-    // Create Argon2id instance
-    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params);
-
     // Derive key from PIN using stored salt
-    let key = argon2
-        .hash_password(pin.as_bytes(), &salt)
-        .map_err(|e| format!("Failed to derive key: {}", e))?
-        .hash
-        .ok_or("No hash value generated")?
-        .as_bytes()
-        .to_vec();
+    let key = derive_key(pin, &salt)?;
 
     // Create AES-GCM cipher
     let key = Key::<Aes256Gcm>::from_slice(&key);
@@ -124,6 +202,27 @@ pub fn decrypt_seed(encrypted_data_str: &str, pin: &str) -> Result<String, Strin
     String::from_utf8(plaintext).map_err(|e| format!("Failed to decode seed: {}", e))
 }
 
+/// Checks whether `pin` unlocks the encrypted data.
+///
+/// Compares the stored key check value in constant time rather than decrypting the
+/// seed. Wallets saved before key checks existed fall back to trial decryption.
+pub fn verify_pin(encrypted_data_str: &str, pin: &str) -> Result<bool, String> {
+    let encrypted_data: EncryptedData = serde_json::from_str(encrypted_data_str)
+        .map_err(|e| format!("Failed to parse encrypted data: {}", e))?;
+
+    let Some(key_check) = &encrypted_data.key_check else {
+        return Ok(decrypt_seed(encrypted_data_str, pin).is_ok());
+    };
+
+    let expected =
+        hex::decode(key_check).map_err(|e| format!("Failed to decode key check: {}", e))?;
+    let salt =
+        SaltString::from_b64(&encrypted_data.salt).map_err(|e| format!("Invalid salt: {}", e))?;
+    let key = derive_key(pin, &salt)?;
+
+    Ok(constant_time_eq(&key_check_value(&key), &expected))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,4 +235,12 @@ mod tests {
         let decrypted_data = decrypt_seed(&encrypted_data, pin).unwrap();
         assert_eq!(seed, decrypted_data);
     }
+
+    #[test]
+    fn test_verify_pin() {
+        let seed = "math tomorrow must labor noodle cost cattle place intact enforce method layer";
+        let encrypted_data = encrypt_seed(seed, "123456").unwrap();
+        assert!(verify_pin(&encrypted_data, "123456").unwrap());
+        assert!(!verify_pin(&encrypted_data, "654321").unwrap());
+    }
 }
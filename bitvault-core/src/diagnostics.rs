@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::startup::{Startup, StartupReport};
+use crate::tx_history::TxHistory;
+use crate::utxo_index::UtxoIndex;
+
+pub mod bundle;
+
+/// How much of the wallet is held in memory, for the diagnostics screen.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub history_rows: usize,
+    pub cached_history_rows: usize,
+    pub pending_transactions: usize,
+    pub utxos: usize,
+    /// Rough total in bytes; good for spotting trends, not exact accounting.
+    pub approx_bytes: usize,
+}
+
+pub fn memory_stats(history: &TxHistory, utxos: &UtxoIndex) -> MemoryStats {
+    MemoryStats {
+        history_rows: history.confirmed_len(),
+        cached_history_rows: history.cached_rows(),
+        pending_transactions: history.pending().len(),
+        utxos: utxos.len(),
+        approx_bytes: history.approx_bytes() + utxos.approx_bytes(),
+    }
+}
+
+/// Per-stage startup timings, including lazy services built since.
+pub fn startup_report(startup: &Startup) -> StartupReport {
+    startup.report()
+}
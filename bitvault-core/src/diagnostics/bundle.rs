@@ -0,0 +1,181 @@
+//! Diagnostic bundles users attach to bug reports.
+//!
+//! A bundle is one JSON file with everything support usually asks for.
+//! Everything that could identify keys or funds is redacted before it goes
+//! in: sensitive config values, and anything in log lines or config strings
+//! that looks like a key, ciphertext or mnemonic.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::MemoryStats;
+use crate::config_manager::ConfigManager;
+use crate::events::{BusStats, MessageBus};
+use crate::logging::redact;
+use crate::platform::capabilities::PlatformCapabilities;
+use crate::platform::PlatformProvider;
+use crate::startup::StartupReport;
+use crate::version::BuildInfo;
+
+/// Log lines kept from the end of the log.
+pub const LOG_TAIL_LINES: usize = 200;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticBundle {
+    pub created_at: u64,
+    pub app_version: String,
+    pub build: BuildInfo,
+    pub os: String,
+    pub arch: String,
+    pub platform: Option<String>,
+    pub capabilities: Option<PlatformCapabilities>,
+    /// Config values, sensitive ones replaced.
+    pub config: BTreeMap<String, Value>,
+    pub log_tail: Vec<String>,
+    pub bus: Option<BusStats>,
+    pub wallet: Option<MemoryStats>,
+    pub startup: Option<StartupReport>,
+}
+
+// Redacts every string inside a config value, not just flagged keys
+fn redact_value(value: Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(redact(&s)),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact_value).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, redact_value(value)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+impl DiagnosticBundle {
+    pub fn new(app_version: &str, now: u64) -> Self {
+        DiagnosticBundle {
+            created_at: now,
+            app_version: app_version.to_string(),
+            build: BuildInfo::current(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Probes what `provider` supports.
+    pub fn with_platform(mut self, provider: &dyn PlatformProvider) -> Self {
+        self.platform = Some(provider.name().to_string());
+        self.capabilities = Some(PlatformCapabilities::detect(provider));
+        self
+    }
+
+    pub fn with_config(mut self, config: &ConfigManager) -> Self {
+        self.config = config
+            .redacted_values()
+            .into_iter()
+            .map(|(key, value)| (key, redact_value(value)))
+            .collect();
+        self
+    }
+
+    /// The last `lines` lines of the log at `path`, redacted. A missing log
+    /// leaves the tail empty.
+    pub fn with_log_tail(mut self, path: &Path, lines: usize) -> Result<Self, String> {
+        let log = match fs::read_to_string(path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let all: Vec<&str> = log.lines().collect();
+        self.log_tail = all[all.len().saturating_sub(lines)..]
+            .iter()
+            .map(|line| redact(line))
+            .collect();
+        Ok(self)
+    }
+
+    pub fn with_bus(mut self, bus: &MessageBus) -> Self {
+        self.bus = Some(bus.stats());
+        self
+    }
+
+    /// UTXO and transaction counts, from [`memory_stats`](super::memory_stats).
+    pub fn with_wallet_stats(mut self, stats: MemoryStats) -> Self {
+        self.wallet = Some(stats);
+        self
+    }
+
+    pub fn with_startup(mut self, report: StartupReport) -> Self {
+        self.startup = Some(report);
+        self
+    }
+
+    /// Writes the bundle to `path` as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize diagnostic bundle: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::HeadlessPlatform;
+    use serde_json::json;
+
+    #[test]
+    fn test_bundle_redacts_secrets() {
+        let dir = std::env::temp_dir().join(format!("bitvault_bundle_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("bitvault.log");
+        let lines: Vec<String> = (0..300).map(|i| format!("line {}", i)).collect();
+        fs::write(
+            &log,
+            format!(
+                "{}\nrestored from abandon abandon abandon ability\nkey 00112233445566778899aabbccddeeff\n",
+                lines.join("\n")
+            ),
+        )
+        .unwrap();
+
+        let mut config = ConfigManager::new().unwrap();
+        config.set("network", json!("signet")).unwrap();
+        config.set("electrum.password", json!("hunter2")).unwrap();
+        config
+            .set("notes", json!(["xpub 0123456789abcdef0123456789abcdef"]))
+            .unwrap();
+        let bus = MessageBus::new();
+
+        let bundle = DiagnosticBundle::new("1.2.3", 1_700_000_000)
+            .with_platform(&HeadlessPlatform)
+            .with_config(&config)
+            .with_log_tail(&log, LOG_TAIL_LINES)
+            .unwrap()
+            .with_bus(&bus);
+        assert_eq!(bundle.log_tail.len(), LOG_TAIL_LINES);
+        assert_eq!(bundle.log_tail[0], "line 102");
+        let json = serde_json::to_string(&bundle).unwrap();
+        for secret in ["hunter2", "abandon", "00112233", "0123456789abcdef"] {
+            assert!(!json.contains(secret), "{} leaked", secret);
+        }
+        assert_eq!(bundle.config["network"], json!("signet"));
+        assert_eq!(bundle.platform.as_deref(), Some("headless"));
+
+        let path = dir.join("bundle.json");
+        bundle.write(&path).unwrap();
+        let read: DiagnosticBundle =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read, bundle);
+        assert!(DiagnosticBundle::new("1.2.3", 0)
+            .with_log_tail(&dir.join("missing.log"), 10)
+            .unwrap()
+            .log_tail
+            .is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
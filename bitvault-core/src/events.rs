@@ -0,0 +1,601 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Events published by core components for frontends and other subscribers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// The wallet has no verified backup of its recovery phrase.
+    BackupRequired { reason: String },
+    /// The user passed the recovery phrase verification quiz.
+    BackupVerified { verified_at: u64 },
+    /// Something security relevant happened, such as repeated failed unlock attempts.
+    SecurityAlert { message: String },
+    /// An incoming payment was seen.
+    PaymentReceived { txid: String, amount: u64 },
+    /// Initial scan progress for one keychain (0 receive, 1 change).
+    SyncProgress {
+        keychain: u32,
+        scanned: u32,
+        used: u32,
+        addresses_per_sec: f64,
+    },
+    /// A tracked transaction got its first confirmation.
+    TransactionConfirmed { txid: String, height: u32 },
+    /// A transaction missed its confirmation target; paying this much more via
+    /// RBF or CPFP should get it confirmed at the current rate for that target.
+    FeeBumpSuggested {
+        txid: String,
+        target_blocks: u32,
+        blocks_waiting: u32,
+        suggested_fee_rate: f64,
+        rbf_additional_fee: u64,
+        cpfp_additional_fee: u64,
+    },
+    /// The inheritance recovery kit must be re-created, because its locktime is
+    /// approaching or its coins changed.
+    InheritanceRefreshDue {
+        blocks_remaining: u32,
+        reason: String,
+    },
+    /// Vault funds moved to the unvault output and become spendable by the hot
+    /// key after `blocks_until_spendable`. Unexpected unvaults should be cancelled.
+    UnvaultDetected {
+        outpoint: String,
+        value: u64,
+        blocks_until_spendable: u32,
+        expected: bool,
+    },
+    /// A spend above the approval threshold is waiting for an approver.
+    ApprovalRequested {
+        request_id: String,
+        amount: u64,
+        at: u64,
+    },
+    /// An approver approved or rejected a pending spend.
+    ApprovalDecided {
+        request_id: String,
+        approved: bool,
+        at: u64,
+    },
+    /// A pending or approved spend was not signed in time.
+    ApprovalExpired { request_id: String, at: u64 },
+    /// Wallet data was destroyed by an emergency wipe.
+    WalletWiped {
+        trigger: String,
+        files_erased: usize,
+        at: u64,
+    },
+    /// A block was added to the tip of the best chain.
+    BlockConnected {
+        height: u32,
+        hash: String,
+        time: u64,
+    },
+    /// The best chain switched branches: `depth` blocks from `fork_height`
+    /// up were replaced.
+    ChainReorg { fork_height: u32, depth: u32 },
+    /// A hardware signer was plugged in.
+    DeviceConnected { vendor: String, product: String },
+    /// A hardware signer was unplugged.
+    DeviceDisconnected { vendor: String, product: String },
+    /// A hardware signer can't proceed until the user acts on it, e.g.
+    /// unlocks it or opens an app; retrying afterwards should work.
+    DeviceActionRequired { vendor: String, action: String },
+    /// A signing flow moved on, such as a PSBT written for an air-gapped signer.
+    SigningProgress { signer: String, stage: String },
+    /// Progress of a long operation such as a scan or backup.
+    OperationProgress {
+        operation: String,
+        phase: String,
+        percent: f64,
+        eta_secs: Option<u64>,
+    },
+    /// A restored wallet with no history has funds under another layout,
+    /// such as a different derivation standard or no passphrase.
+    RestoreHint {
+        description: String,
+        used_addresses: u32,
+    },
+    /// Something is about to reveal wallet activity to a third party, such
+    /// as the first link to an external block explorer.
+    PrivacyWarning { message: String },
+    /// The network connection switched between normal and metered, so
+    /// background traffic is now reduced or back to normal.
+    ConnectionModeChanged { metered: bool },
+    /// A log level was changed at runtime; `module` is `None` for the
+    /// default level.
+    LogLevelChanged {
+        module: Option<String>,
+        level: String,
+    },
+    /// Operational status from a core service, such as health statistics.
+    System { component: String, message: String },
+}
+
+/// Delivery order on the bus: queued higher-priority events reach each
+/// subscriber before lower ones, whatever order they were published in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MessagePriority {
+    Low,
+    Normal,
+    High,
+    /// Security alerts and wipes, delivered ahead of any backlog.
+    Critical,
+}
+
+/// Higher-priority events delivered in a row while a lower one waits before
+/// the oldest waiting lower-priority event gets a turn.
+pub const STARVATION_LIMIT: u32 = 16;
+
+impl Event {
+    pub fn priority(&self) -> MessagePriority {
+        match self {
+            Event::SecurityAlert { .. } | Event::WalletWiped { .. } => MessagePriority::Critical,
+            Event::BackupRequired { .. }
+            | Event::InheritanceRefreshDue { .. }
+            | Event::UnvaultDetected { .. }
+            | Event::ApprovalRequested { .. }
+            | Event::ApprovalDecided { .. }
+            | Event::ApprovalExpired { .. }
+            | Event::ChainReorg { .. } => MessagePriority::High,
+            Event::BackupVerified { .. }
+            | Event::PaymentReceived { .. }
+            | Event::TransactionConfirmed { .. }
+            | Event::FeeBumpSuggested { .. }
+            | Event::DeviceConnected { .. }
+            | Event::DeviceDisconnected { .. }
+            | Event::DeviceActionRequired { .. }
+            | Event::RestoreHint { .. }
+            | Event::PrivacyWarning { .. }
+            | Event::ConnectionModeChanged { .. } => MessagePriority::Normal,
+            Event::SyncProgress { .. }
+            | Event::BlockConnected { .. }
+            | Event::LogLevelChanged { .. }
+            | Event::SigningProgress { .. }
+            | Event::OperationProgress { .. }
+            | Event::System { .. } => MessagePriority::Low,
+        }
+    }
+
+    /// Variant name, used to key bus statistics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::BackupRequired { .. } => "BackupRequired",
+            Event::BackupVerified { .. } => "BackupVerified",
+            Event::SecurityAlert { .. } => "SecurityAlert",
+            Event::PaymentReceived { .. } => "PaymentReceived",
+            Event::SyncProgress { .. } => "SyncProgress",
+            Event::TransactionConfirmed { .. } => "TransactionConfirmed",
+            Event::FeeBumpSuggested { .. } => "FeeBumpSuggested",
+            Event::InheritanceRefreshDue { .. } => "InheritanceRefreshDue",
+            Event::UnvaultDetected { .. } => "UnvaultDetected",
+            Event::ApprovalRequested { .. } => "ApprovalRequested",
+            Event::ApprovalDecided { .. } => "ApprovalDecided",
+            Event::ApprovalExpired { .. } => "ApprovalExpired",
+            Event::WalletWiped { .. } => "WalletWiped",
+            Event::BlockConnected { .. } => "BlockConnected",
+            Event::ChainReorg { .. } => "ChainReorg",
+            Event::DeviceConnected { .. } => "DeviceConnected",
+            Event::DeviceDisconnected { .. } => "DeviceDisconnected",
+            Event::DeviceActionRequired { .. } => "DeviceActionRequired",
+            Event::SigningProgress { .. } => "SigningProgress",
+            Event::OperationProgress { .. } => "OperationProgress",
+            Event::RestoreHint { .. } => "RestoreHint",
+            Event::PrivacyWarning { .. } => "PrivacyWarning",
+            Event::ConnectionModeChanged { .. } => "ConnectionModeChanged",
+            Event::LogLevelChanged { .. } => "LogLevelChanged",
+            Event::System { .. } => "System",
+        }
+    }
+}
+
+/// Delivery counters for one event type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventTypeStats {
+    pub published: u64,
+    /// Copies handed to subscriber queues.
+    pub delivered: u64,
+    /// Copies lost because the subscriber had gone away.
+    pub dropped: u64,
+}
+
+/// Snapshot of a bus's counters, from [`MessageBus::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BusStats {
+    pub by_event: BTreeMap<String, EventTypeStats>,
+    pub subscribers: usize,
+    /// Events queued but not yet received, across all subscribers.
+    pub queue_depth: u64,
+    /// Queue depth of the slowest subscriber.
+    pub max_subscriber_lag: u64,
+}
+
+// One queue per priority, indexed by `MessagePriority as usize`
+#[derive(Default)]
+struct Lanes {
+    queues: [VecDeque<Event>; 4],
+    // Higher-priority deliveries since a lower-priority event last got a turn
+    streak: u32,
+    // The bus was dropped or disconnected this subscriber; nothing more will arrive
+    closed: bool,
+}
+
+impl Lanes {
+    fn pop(&mut self) -> Option<Event> {
+        let highest = self.queues.iter().rposition(|q| !q.is_empty())?;
+        let lowest = self.queues.iter().position(|q| !q.is_empty())?;
+        if highest == lowest {
+            self.streak = 0;
+            return self.queues[highest].pop_front();
+        }
+        if self.streak >= STARVATION_LIMIT {
+            self.streak = 0;
+            return self.queues[lowest].pop_front();
+        }
+        self.streak += 1;
+        self.queues[highest].pop_front()
+    }
+}
+
+// Shared between the bus and a subscription
+#[derive(Default)]
+struct Channel {
+    lanes: Mutex<Lanes>,
+    ready: Condvar,
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl Channel {
+    fn lag(&self) -> u64 {
+        let received = self.received.load(Ordering::Relaxed);
+        self.sent.load(Ordering::Relaxed).saturating_sub(received)
+    }
+
+    fn push(&self, event: Event) {
+        if let Ok(mut lanes) = self.lanes.lock() {
+            lanes.queues[event.priority() as usize].push_back(event);
+            self.sent.fetch_add(1, Ordering::Relaxed);
+        }
+        self.ready.notify_one();
+    }
+
+    fn close(&self) {
+        if let Ok(mut lanes) = self.lanes.lock() {
+            lanes.closed = true;
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// Receiving end of a bus subscription, with the same methods as an mpsc
+/// `Receiver`. Events come out highest priority first.
+pub struct Subscription {
+    channel: Arc<Channel>,
+}
+
+impl Subscription {
+    fn take(&self, event: Option<Event>) -> Option<Event> {
+        if event.is_some() {
+            self.channel.received.fetch_add(1, Ordering::Relaxed);
+        }
+        event
+    }
+
+    pub fn recv(&self) -> Result<Event, RecvError> {
+        let mut lanes = self.channel.lanes.lock().map_err(|_| RecvError)?;
+        loop {
+            if let Some(event) = self.take(lanes.pop()) {
+                return Ok(event);
+            }
+            if lanes.closed {
+                return Err(RecvError);
+            }
+            lanes = self.channel.ready.wait(lanes).map_err(|_| RecvError)?;
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<Event, TryRecvError> {
+        let mut lanes = self
+            .channel
+            .lanes
+            .lock()
+            .map_err(|_| TryRecvError::Disconnected)?;
+        match self.take(lanes.pop()) {
+            Some(event) => Ok(event),
+            None if lanes.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Event, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut lanes = self
+            .channel
+            .lanes
+            .lock()
+            .map_err(|_| RecvTimeoutError::Disconnected)?;
+        loop {
+            if let Some(event) = self.take(lanes.pop()) {
+                return Ok(event);
+            }
+            if lanes.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            lanes = self
+                .channel
+                .ready
+                .wait_timeout(lanes, deadline - now)
+                .map_err(|_| RecvTimeoutError::Disconnected)?
+                .0;
+        }
+    }
+
+    /// Whether the bus can still deliver to this subscription: false once the
+    /// bus is dropped or has disconnected it for falling too far behind.
+    pub fn is_connected(&self) -> bool {
+        self.channel
+            .lanes
+            .lock()
+            .map(|l| !l.closed)
+            .unwrap_or(false)
+    }
+
+    /// Stops receiving events and frees the queue. Equivalent to dropping the
+    /// subscription, but explicit at call sites that outlive their interest.
+    pub fn unsubscribe(self) {}
+
+    /// Iterates over the events already queued without blocking.
+    pub fn try_iter(&self) -> impl Iterator<Item = Event> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+
+    /// Blocks for each event until the bus is dropped.
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+}
+
+/// Fan-out bus delivering every published event to all live subscribers,
+/// ordered by [`MessagePriority`] within each subscriber's queue.
+///
+/// The bus only holds weak references: a subscription's queue is freed as soon
+/// as it is dropped or unsubscribed, and pruned from the list on the next publish.
+#[derive(Default)]
+pub struct MessageBus {
+    subscribers: Mutex<Vec<Weak<Channel>>>,
+    stats: Mutex<BTreeMap<&'static str, EventTypeStats>>,
+    max_backlog: Option<u64>,
+}
+
+impl MessageBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disconnects subscribers with more than `limit` unreceived events, treating
+    /// them as dead rather than letting their queues grow without bound.
+    pub fn with_max_backlog(mut self, limit: u64) -> Self {
+        self.max_backlog = Some(limit);
+        self
+    }
+
+    /// Registers a new subscriber and returns the receiving end of its channel.
+    pub fn subscribe(&self) -> Subscription {
+        let channel = Arc::new(Channel::default());
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|s| s.strong_count() > 0);
+            subscribers.push(Arc::downgrade(&channel));
+        }
+        Subscription { channel }
+    }
+
+    /// Publishes an event, dropping subscribers that have gone away or stopped receiving.
+    pub fn publish(&self, event: Event) {
+        let mut counts = EventTypeStats {
+            published: 1,
+            ..Default::default()
+        };
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| {
+                let Some(channel) = subscriber.upgrade() else {
+                    counts.dropped += 1;
+                    return false;
+                };
+                if self.max_backlog.is_some_and(|limit| channel.lag() >= limit) {
+                    log::warn!(
+                        "Disconnecting event subscriber with {} unreceived events",
+                        channel.lag()
+                    );
+                    channel.close();
+                    counts.dropped += 1;
+                    return false;
+                }
+                channel.push(event.clone());
+                counts.delivered += 1;
+                true
+            });
+        }
+        if let Ok(mut stats) = self.stats.lock() {
+            let entry = stats.entry(event.name()).or_default();
+            entry.published += counts.published;
+            entry.delivered += counts.delivered;
+            entry.dropped += counts.dropped;
+        }
+    }
+
+    /// Live subscribers; dropped subscriptions are not counted even before pruning.
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .lock()
+            .map(|s| s.iter().filter(|s| s.strong_count() > 0).count())
+            .unwrap_or(0)
+    }
+
+    pub fn stats(&self) -> BusStats {
+        let by_event = self
+            .stats
+            .lock()
+            .map(|stats| {
+                stats
+                    .iter()
+                    .map(|(name, counts)| (name.to_string(), *counts))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let lags: Vec<u64> = self
+            .subscribers
+            .lock()
+            .map(|s| {
+                s.iter()
+                    .filter_map(Weak::upgrade)
+                    .map(|channel| channel.lag())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        BusStats {
+            by_event,
+            subscribers: lags.len(),
+            queue_depth: lags.iter().sum(),
+            max_subscriber_lag: lags.iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// Publishes the current [`stats`](Self::stats) as a `System` event. Schedule
+    /// it periodically to spot slow subscribers in production.
+    pub fn publish_stats(&self) {
+        let stats = self.stats();
+        let published: u64 = stats.by_event.values().map(|s| s.published).sum();
+        let dropped: u64 = stats.by_event.values().map(|s| s.dropped).sum();
+        self.publish(Event::System {
+            component: "event_bus".to_string(),
+            message: format!(
+                "{} published, {} dropped, {} subscribers, queue depth {}, max lag {}",
+                published, dropped, stats.subscribers, stats.queue_depth, stats.max_subscriber_lag
+            ),
+        });
+    }
+}
+
+impl Drop for MessageBus {
+    // Wakes blocked subscribers so `recv` and `iter` return once the queue drains
+    fn drop(&mut self) {
+        if let Ok(subscribers) = self.subscribers.lock() {
+            subscribers
+                .iter()
+                .filter_map(Weak::upgrade)
+                .for_each(|channel| channel.close());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_subscribe() {
+        let bus = MessageBus::new();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        bus.publish(Event::BackupVerified { verified_at: 1 });
+        assert_eq!(
+            first.recv().unwrap(),
+            Event::BackupVerified { verified_at: 1 }
+        );
+        assert_eq!(
+            second.recv().unwrap(),
+            Event::BackupVerified { verified_at: 1 }
+        );
+
+        // Dropped receivers are pruned on the next publish
+        drop(second);
+        bus.publish(Event::BackupVerified { verified_at: 2 });
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn test_stats_track_lag_and_drops() {
+        let bus = MessageBus::new();
+        let fast = bus.subscribe();
+        let slow = bus.subscribe();
+        for verified_at in 0..3 {
+            bus.publish(Event::BackupVerified { verified_at });
+        }
+        assert_eq!(fast.try_iter().count(), 3);
+        slow.recv().unwrap();
+
+        let stats = bus.stats();
+        assert_eq!(stats.queue_depth, 2);
+        assert_eq!(stats.max_subscriber_lag, 2);
+
+        drop(slow);
+        bus.publish_stats();
+        let stats = bus.stats();
+        assert_eq!(
+            stats.by_event["BackupVerified"],
+            EventTypeStats {
+                published: 3,
+                delivered: 6,
+                dropped: 0,
+            }
+        );
+        assert_eq!(stats.by_event["System"].dropped, 1);
+        assert!(matches!(fast.try_recv(), Ok(Event::System { .. })));
+    }
+
+    #[test]
+    fn test_priority_order_without_starvation() {
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        bus.publish(Event::BackupVerified { verified_at: 0 });
+        for _ in 0..=STARVATION_LIMIT {
+            bus.publish(Event::SecurityAlert {
+                message: "alert".to_string(),
+            });
+        }
+
+        let order: Vec<MessagePriority> = events.try_iter().map(|e| e.priority()).collect();
+        let normal = order
+            .iter()
+            .position(|p| *p == MessagePriority::Normal)
+            .unwrap();
+        assert_eq!(normal, STARVATION_LIMIT as usize);
+        assert_eq!(order.len(), STARVATION_LIMIT as usize + 2);
+
+        drop(bus);
+        assert_eq!(events.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_unsubscribe_and_backlog_limit() {
+        let bus = MessageBus::new().with_max_backlog(2);
+        let gone = bus.subscribe();
+        let stalled = bus.subscribe();
+        let active = bus.subscribe();
+        gone.unsubscribe();
+        assert_eq!(bus.subscriber_count(), 2);
+
+        for verified_at in 0..3 {
+            bus.publish(Event::BackupVerified { verified_at });
+            active.recv().unwrap();
+        }
+        assert!(!stalled.is_connected());
+        assert!(active.is_connected());
+        assert_eq!(bus.subscriber_count(), 1);
+
+        // A disconnected subscriber still drains what it had queued
+        assert_eq!(stalled.try_iter().count(), 2);
+        assert_eq!(stalled.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
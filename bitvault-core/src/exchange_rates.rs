@@ -0,0 +1,283 @@
+//! Historical daily BTC exchange rates for fiat values in history, exports
+//! and cost basis.
+//!
+//! Rates for the days the wallet transacted on are fetched from a
+//! configurable [`RateSource`] and cached on disk, so each day is only
+//! fetched once. Days the source has no rate for are interpolated between
+//! the nearest known days, and flagged as such, when those are close
+//! enough; otherwise they are reported as unavailable rather than guessed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clock::SharedClock;
+use crate::export::activity::FiatPrices;
+use crate::scheduler::{CancellationToken, Scheduler};
+
+#[cfg(feature = "fiat-rates")]
+pub mod http;
+
+pub const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// Widest gap, in days, a rate is interpolated across.
+pub const MAX_INTERPOLATION_DAYS: u64 = 7;
+
+/// Start (00:00 UTC) of the day containing `timestamp`.
+pub fn day_start(timestamp: u64) -> u64 {
+    timestamp - timestamp % SECS_PER_DAY
+}
+
+/// Where historical rates come from (a price API, a self-hosted service...).
+pub trait RateSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Daily prices of one BTC in `currency` keyed by day start, for the
+    /// days from `from` to `to` inclusive. Days it has no price for are left
+    /// out.
+    fn fetch_daily(&self, currency: &str, from: u64, to: u64)
+        -> Result<BTreeMap<u64, f64>, String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DailyRate {
+    pub per_btc: f64,
+    /// Estimated from the surrounding days rather than reported by the source.
+    pub interpolated: bool,
+}
+
+/// Cached daily rates in one currency.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateCache {
+    pub currency: String,
+    pub days: BTreeMap<u64, DailyRate>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl RateCache {
+    /// An in-memory cache that is not persisted.
+    pub fn new(currency: &str) -> Self {
+        RateCache {
+            currency: currency.to_uppercase(),
+            ..Default::default()
+        }
+    }
+
+    /// Loads the cache at `path`, starting empty if there is none yet. A
+    /// cache for another currency is replaced.
+    pub fn open(path: &Path, currency: &str) -> Result<Self, String> {
+        let mut cache = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str::<RateCache>(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RateCache::default(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        if cache.currency != currency.to_uppercase() {
+            cache = RateCache::new(currency);
+        }
+        cache.path = Some(path.to_path_buf());
+        Ok(cache)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize rate cache: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The rate for the day containing `timestamp`.
+    pub fn rate_at(&self, timestamp: u64) -> Option<DailyRate> {
+        self.days.get(&day_start(timestamp)).copied()
+    }
+
+    /// The cached rates for the activity export, interpolated days included.
+    pub fn to_fiat_prices(&self) -> FiatPrices {
+        FiatPrices {
+            currency: self.currency.clone(),
+            per_btc: self.days.iter().map(|(day, r)| (*day, r.per_btc)).collect(),
+        }
+    }
+
+    // Between the nearest reported days on either side, if both are close
+    fn interpolate(&self, day: u64) -> Option<f64> {
+        let reported = |(d, r): (&u64, &DailyRate)| (!r.interpolated).then_some((*d, r.per_btc));
+        let (before, low) = self.days.range(..day).rev().find_map(reported)?;
+        let (after, high) = self.days.range(day + 1..).find_map(reported)?;
+        if after - before > MAX_INTERPOLATION_DAYS * SECS_PER_DAY {
+            return None;
+        }
+        Some(low + (high - low) * (day - before) as f64 / (after - before) as f64)
+    }
+}
+
+/// What a backfill did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillReport {
+    /// Days now holding a rate from the source.
+    pub fetched: usize,
+    pub interpolated: Vec<u64>,
+    /// Days left without a rate.
+    pub unavailable: Vec<u64>,
+}
+
+/// Fills `cache` with rates for the days of `timestamps` from `source`.
+///
+/// Days already reported by the source are skipped; interpolated ones are
+/// asked for again in case the source has them now. Rates for the current
+/// day are left until it's over.
+pub fn backfill(
+    source: &dyn RateSource,
+    cache: &mut RateCache,
+    timestamps: impl IntoIterator<Item = u64>,
+    now: u64,
+) -> Result<BackfillReport, String> {
+    let today = day_start(now);
+    let missing: BTreeSet<u64> = timestamps
+        .into_iter()
+        .map(day_start)
+        .filter(|day| *day < today)
+        .filter(|day| cache.days.get(day).is_none_or(|r| r.interpolated))
+        .collect();
+    let mut report = BackfillReport::default();
+    let (Some(first), Some(last)) = (missing.first(), missing.last()) else {
+        return Ok(report);
+    };
+
+    // Neighbours give interpolation something to work with at the edges
+    let from = first.saturating_sub(MAX_INTERPOLATION_DAYS * SECS_PER_DAY);
+    let to = (last + MAX_INTERPOLATION_DAYS * SECS_PER_DAY).min(today - SECS_PER_DAY);
+    let fetched = source.fetch_daily(&cache.currency, from, to)?;
+    for (day, per_btc) in fetched {
+        if !per_btc.is_finite() || per_btc <= 0.0 {
+            log::warn!(
+                "Ignoring rate {} for day {} from {}",
+                per_btc,
+                day,
+                source.name()
+            );
+            continue;
+        }
+        let rate = DailyRate {
+            per_btc,
+            interpolated: false,
+        };
+        cache.days.insert(day_start(day), rate);
+    }
+
+    for day in missing {
+        if cache.days.get(&day).is_some_and(|r| !r.interpolated) {
+            report.fetched += 1;
+            continue;
+        }
+        match cache.interpolate(day) {
+            Some(per_btc) => {
+                let rate = DailyRate {
+                    per_btc,
+                    interpolated: true,
+                };
+                cache.days.insert(day, rate);
+                report.interpolated.push(day);
+            }
+            None => {
+                cache.days.remove(&day);
+                report.unavailable.push(day);
+            }
+        }
+    }
+    cache.save()?;
+    Ok(report)
+}
+
+/// Runs [`backfill`] every `interval_secs` as the scheduler task
+/// `rate-backfill`, over the transaction times `timestamps` returns. It is
+/// deferred while the connection is metered.
+pub fn schedule<F>(
+    source: Box<dyn RateSource>,
+    mut cache: RateCache,
+    scheduler: &mut Scheduler,
+    interval_secs: u64,
+    clock: SharedClock,
+    mut timestamps: F,
+) -> Result<CancellationToken, String>
+where
+    F: FnMut() -> Result<Vec<u64>, String> + Send + 'static,
+{
+    scheduler.schedule_deferrable(
+        "rate-backfill",
+        interval_secs,
+        interval_secs / 10,
+        move |_| {
+            let report = backfill(source.as_ref(), &mut cache, timestamps()?, clock.now())?;
+            log::info!(
+                "Rate backfill from {}: {} fetched, {} interpolated, {} unavailable",
+                source.name(),
+                report.fetched,
+                report.interpolated.len(),
+                report.unavailable.len()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct FixedSource {
+        rates: BTreeMap<u64, f64>,
+        requests: Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl RateSource for FixedSource {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn fetch_daily(&self, _: &str, from: u64, to: u64) -> Result<BTreeMap<u64, f64>, String> {
+            self.requests.lock().unwrap().push((from, to));
+            Ok(self.rates.range(from..=to).map(|(d, r)| (*d, *r)).collect())
+        }
+    }
+
+    #[test]
+    fn test_backfill_interpolates_and_flags_gaps() {
+        let day = |n: u64| 1_700_006_400 + n * SECS_PER_DAY;
+        // Nothing on days 2 and 3, then nothing for weeks after day 4
+        let source = FixedSource {
+            rates: BTreeMap::from([(day(1), 30_000.0), (day(4), 36_000.0)]),
+            requests: Mutex::new(Vec::new()),
+        };
+        let path = std::env::temp_dir().join(format!("bitvault_rates_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut cache = RateCache::open(&path, "usd").unwrap();
+
+        let report = backfill(
+            &source,
+            &mut cache,
+            [day(1) + 3_600, day(2) + 60, day(4), day(30), day(40)],
+            day(40) + 60,
+        )
+        .unwrap();
+        assert_eq!(report.fetched, 2);
+        assert_eq!(report.interpolated, vec![day(2)]);
+        assert_eq!(report.unavailable, vec![day(30)]);
+        let rate = cache.rate_at(day(2) + 100).unwrap();
+        assert!(rate.interpolated);
+        assert_eq!(rate.per_btc, 32_000.0);
+        assert_eq!(cache.to_fiat_prices().price_at(day(4) + 1), Some(36_000.0));
+
+        // Cached days aren't fetched again
+        let mut reopened = RateCache::open(&path, "USD").unwrap();
+        assert_eq!(reopened.days, cache.days);
+        let report = backfill(&source, &mut reopened, [day(1), day(4)], day(41)).unwrap();
+        assert_eq!(report, BackfillReport::default());
+        assert_eq!(source.requests.lock().unwrap().len(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
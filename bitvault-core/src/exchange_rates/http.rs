@@ -0,0 +1,90 @@
+//! Rates from an HTTP API answering in the CoinGecko `market_chart/range`
+//! format, `{"prices": [[unix_millis, price], ...]}`.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use super::{day_start, RateSource, SECS_PER_DAY};
+
+pub const COINGECKO_URL: &str = "https://api.coingecko.com/api/v3/coins/bitcoin/market_chart/range\
+     ?vs_currency={currency}&from={from}&to={to}";
+
+#[derive(Deserialize)]
+struct PriceChart {
+    prices: Vec<(f64, f64)>,
+}
+
+pub struct HttpRateSource {
+    name: String,
+    /// URL with `{currency}`, `{from}` and `{to}` (unix seconds) placeholders.
+    url_template: String,
+}
+
+impl HttpRateSource {
+    pub fn new(name: &str, url_template: &str) -> Self {
+        HttpRateSource {
+            name: name.to_string(),
+            url_template: url_template.to_string(),
+        }
+    }
+
+    pub fn coingecko() -> Self {
+        HttpRateSource::new("CoinGecko", COINGECKO_URL)
+    }
+}
+
+/// The first price of each day in a response.
+fn daily_prices(response: &str) -> Result<BTreeMap<u64, f64>, String> {
+    let chart: PriceChart = serde_json::from_str(response).map_err(|e| e.to_string())?;
+    let mut days = BTreeMap::new();
+    for (millis, price) in chart.prices {
+        if millis >= 0.0 {
+            days.entry(day_start(millis as u64 / 1_000))
+                .or_insert(price);
+        }
+    }
+    Ok(days)
+}
+
+impl RateSource for HttpRateSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_daily(
+        &self,
+        currency: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<BTreeMap<u64, f64>, String> {
+        let url = self
+            .url_template
+            .replace("{currency}", &currency.to_lowercase())
+            .replace("{from}", &from.to_string())
+            .replace("{to}", &(to + SECS_PER_DAY).to_string());
+        let response = ureq::get(&url)
+            .call()
+            .map_err(|e| format!("{} request failed: {}", self.name, e))?
+            .into_string()
+            .map_err(|e| format!("Failed to read {} response: {}", self.name, e))?;
+        let mut days = daily_prices(&response)
+            .map_err(|e| format!("Unexpected response from {}: {}", self.name, e))?;
+        days.retain(|day, _| (from..=to).contains(day));
+        Ok(days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_prices_keeps_first_of_day() {
+        let response = r#"{"prices": [[1700006400000, 37000.5], [1700010000000, 37100.0], [1700092800000, 36500.0]]}"#;
+        assert_eq!(
+            daily_prices(response).unwrap(),
+            BTreeMap::from([(1_700_006_400, 37_000.5), (1_700_092_800, 36_500.0)])
+        );
+        assert!(daily_prices("{}").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! Links to addresses and transactions on a block explorer.
+//!
+//! Opening a link tells the explorer which addresses and transactions the
+//! user cares about, so the first link to a third-party explorer raises a
+//! `PrivacyWarning` event; self-hosted explorers don't.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::events::{Event, MessageBus};
+use crate::types::Network;
+
+/// URL templates for one network. `{address}` and `{txid}` are replaced.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExplorerTemplate {
+    pub address: String,
+    pub tx: String,
+}
+
+impl ExplorerTemplate {
+    /// The `/address/…` and `/tx/…` layout of mempool and Esplora instances.
+    pub fn from_base(base_url: &str) -> Self {
+        let base = base_url.trim_end_matches('/');
+        ExplorerTemplate {
+            address: format!("{}/address/{{address}}", base),
+            tx: format!("{}/tx/{{txid}}", base),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explorer {
+    pub name: String,
+    pub templates: BTreeMap<Network, ExplorerTemplate>,
+    /// Run by the user, so links reveal nothing to anyone else.
+    pub self_hosted: bool,
+}
+
+impl Default for Explorer {
+    fn default() -> Self {
+        Explorer::mempool_space()
+    }
+}
+
+impl Explorer {
+    pub fn mempool_space() -> Self {
+        Explorer {
+            name: "mempool.space".to_string(),
+            templates: [
+                (Network::Bitcoin, "https://mempool.space"),
+                (Network::Testnet, "https://mempool.space/testnet"),
+                (Network::Signet, "https://mempool.space/signet"),
+            ]
+            .into_iter()
+            .map(|(network, base)| (network, ExplorerTemplate::from_base(base)))
+            .collect(),
+            self_hosted: false,
+        }
+    }
+
+    pub fn blockstream() -> Self {
+        Explorer {
+            name: "blockstream.info".to_string(),
+            templates: [
+                (Network::Bitcoin, "https://blockstream.info"),
+                (Network::Testnet, "https://blockstream.info/testnet"),
+            ]
+            .into_iter()
+            .map(|(network, base)| (network, ExplorerTemplate::from_base(base)))
+            .collect(),
+            self_hosted: false,
+        }
+    }
+
+    /// A mempool or Esplora instance of the user's, serving `network`.
+    pub fn self_hosted(network: Network, base_url: &str) -> Self {
+        Explorer {
+            name: base_url.trim_end_matches('/').to_string(),
+            templates: BTreeMap::from([(network, ExplorerTemplate::from_base(base_url))]),
+            self_hosted: true,
+        }
+    }
+
+    /// Checks every template is an http(s) URL with its placeholder.
+    pub fn validate(&self) -> Result<(), String> {
+        for (network, template) in &self.templates {
+            for (url, placeholder) in [(&template.address, "{address}"), (&template.tx, "{txid}")] {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(format!("{:?} explorer URL {} is not http(s)", network, url));
+                }
+                if !url.contains(placeholder) {
+                    return Err(format!(
+                        "{:?} explorer URL {} has no {} placeholder",
+                        network, url, placeholder
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds explorer links, warning once about third-party explorers.
+#[derive(Clone, Debug)]
+pub struct ExplorerLinks {
+    explorer: Explorer,
+    warned: bool,
+}
+
+impl ExplorerLinks {
+    pub fn new(explorer: Explorer) -> Result<Self, String> {
+        explorer.validate()?;
+        Ok(ExplorerLinks {
+            explorer,
+            warned: false,
+        })
+    }
+
+    /// Skips the warning, for users who already saw it in an earlier session.
+    pub fn with_warned(mut self, warned: bool) -> Self {
+        self.warned = warned;
+        self
+    }
+
+    /// Whether the privacy warning has been raised, to persist across sessions.
+    pub fn warned(&self) -> bool {
+        self.warned
+    }
+
+    pub fn address_url(
+        &mut self,
+        network: Network,
+        address: &str,
+        bus: Option<&MessageBus>,
+    ) -> Result<String, String> {
+        if address.is_empty() || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Invalid address: {}", address));
+        }
+        let template = self.template(network)?.address.clone();
+        self.warn(bus);
+        Ok(template.replace("{address}", address))
+    }
+
+    pub fn tx_url(
+        &mut self,
+        network: Network,
+        txid: &str,
+        bus: Option<&MessageBus>,
+    ) -> Result<String, String> {
+        if txid.len() != 64 || !txid.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid transaction id: {}", txid));
+        }
+        let template = self.template(network)?.tx.clone();
+        self.warn(bus);
+        Ok(template.replace("{txid}", &txid.to_lowercase()))
+    }
+
+    fn template(&self, network: Network) -> Result<&ExplorerTemplate, String> {
+        self.explorer
+            .templates
+            .get(&network)
+            .ok_or_else(|| format!("{} has no {:?} explorer", self.explorer.name, network))
+    }
+
+    fn warn(&mut self, bus: Option<&MessageBus>) {
+        if self.warned || self.explorer.self_hosted {
+            return;
+        }
+        self.warned = true;
+        if let Some(bus) = bus {
+            bus.publish(Event::PrivacyWarning {
+                message: format!(
+                    "Opening this link shows {} which addresses and transactions you look up, \
+                     along with your IP address. Use a self-hosted explorer to avoid this.",
+                    self.explorer.name
+                ),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_and_privacy_warning() {
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut links = ExplorerLinks::new(Explorer::mempool_space()).unwrap();
+        let txid = "AB".repeat(32);
+
+        assert_eq!(
+            links.tx_url(Network::Signet, &txid, Some(&bus)).unwrap(),
+            format!("https://mempool.space/signet/tx/{}", "ab".repeat(32))
+        );
+        assert_eq!(
+            links
+                .address_url(Network::Bitcoin, "bc1qexample", Some(&bus))
+                .unwrap(),
+            "https://mempool.space/address/bc1qexample"
+        );
+        assert!(links.warned());
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::PrivacyWarning { .. })
+        ));
+        assert!(events.try_recv().is_err());
+
+        assert!(links.address_url(Network::Regtest, "bcrt1q", None).is_err());
+        assert!(links
+            .address_url(Network::Bitcoin, "bc1q/../x", None)
+            .is_err());
+
+        let mut own = ExplorerLinks::new(Explorer::self_hosted(
+            Network::Regtest,
+            "http://localhost:8080/",
+        ))
+        .unwrap();
+        assert_eq!(
+            own.address_url(Network::Regtest, "bcrt1qlocal", Some(&bus))
+                .unwrap(),
+            "http://localhost:8080/address/bcrt1qlocal"
+        );
+        assert!(events.try_recv().is_err());
+
+        let mut broken = Explorer::blockstream();
+        broken.templates.insert(
+            Network::Signet,
+            ExplorerTemplate {
+                address: "https://example.com/a".to_string(),
+                tx: "https://example.com/tx/{txid}".to_string(),
+            },
+        );
+        assert!(ExplorerLinks::new(broken).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Versioned JSON export of a wallet's public data, and its import into a
+//! wallet restored from seed.
+//!
+//! Schema `bitvault-wallet-export`, version 1:
+//!
+//! ```text
+//! {
+//!   "schema": "bitvault-wallet-export",
+//!   "version": 1,
+//!   "network": "Bitcoin" | "Testnet" | "Signet" | "Regtest",
+//!   "exported_at": <unix seconds>,
+//!   "addresses": [{ "address", "index", "is_change", "label"? }],
+//!   "transactions": [{ "txid", "height"?, "label"?, "transaction" }],
+//!   "utxos": [{ "outpoint": { "txid", "vout" }, "address", "value",
+//!               "confirmations", "frozen", "label"? }]
+//! }
+//! ```
+//!
+//! Amounts are in satoshis. The document never contains keys, seeds or
+//! descriptors with private material.
+
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::WalletMetadata;
+use crate::types::{Network, OutPoint, Transaction, Utxo};
+
+#[cfg(feature = "native")]
+pub mod activity;
+
+pub const EXPORT_SCHEMA: &str = "bitvault-wallet-export";
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// A derived wallet address.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedAddress {
+    pub address: String,
+    pub index: u32,
+    pub is_change: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedTransaction {
+    pub txid: String,
+    /// Confirmation height, absent while unconfirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub transaction: Transaction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedUtxo {
+    pub outpoint: OutPoint,
+    pub address: String,
+    pub value: u64,
+    pub confirmations: u32,
+    pub frozen: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// The export document; see the module docs for the schema.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletExport {
+    pub schema: String,
+    pub version: u32,
+    pub network: Network,
+    pub exported_at: u64,
+    pub addresses: Vec<ExportedAddress>,
+    pub transactions: Vec<ExportedTransaction>,
+    pub utxos: Vec<ExportedUtxo>,
+}
+
+/// What an import restored and what it had to skip.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub labels_restored: usize,
+    pub coins_frozen: usize,
+    /// Entries referring to addresses the restored wallet doesn't derive.
+    pub skipped: usize,
+}
+
+impl WalletExport {
+    /// Builds an export, attaching labels and freeze state from `metadata`.
+    pub fn build(
+        network: Network,
+        exported_at: u64,
+        metadata: &WalletMetadata,
+        addresses: &[ExportedAddress],
+        transactions: &[(Transaction, Option<u32>)],
+        utxos: &[Utxo],
+    ) -> Self {
+        let label = |reference: &str| metadata.label(reference).map(str::to_string);
+        WalletExport {
+            schema: EXPORT_SCHEMA.to_string(),
+            version: EXPORT_SCHEMA_VERSION,
+            network,
+            exported_at,
+            addresses: addresses
+                .iter()
+                .map(|a| ExportedAddress {
+                    label: label(&a.address),
+                    ..a.clone()
+                })
+                .collect(),
+            transactions: transactions
+                .iter()
+                .map(|(tx, height)| {
+                    let txid = tx.txid();
+                    ExportedTransaction {
+                        label: label(&txid),
+                        txid,
+                        height: *height,
+                        transaction: tx.clone(),
+                    }
+                })
+                .collect(),
+            utxos: utxos
+                .iter()
+                .map(|u| ExportedUtxo {
+                    outpoint: u.outpoint.clone(),
+                    address: u.address.clone(),
+                    value: u.value,
+                    confirmations: u.confirmations,
+                    frozen: metadata.frozen.contains(&u.outpoint),
+                    label: label(&u.outpoint.to_string()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize export: {}", e))
+    }
+
+    /// Parses an export, rejecting other schemas and newer versions.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let export: WalletExport =
+            serde_json::from_str(json).map_err(|e| format!("Invalid wallet export: {}", e))?;
+        if export.schema != EXPORT_SCHEMA {
+            return Err(format!("Unknown export schema: {}", export.schema));
+        }
+        if export.version > EXPORT_SCHEMA_VERSION {
+            return Err(format!(
+                "Export version {} is newer than supported ({})",
+                export.version, EXPORT_SCHEMA_VERSION
+            ));
+        }
+        Ok(export)
+    }
+
+    /// Restores labels and frozen coins into the metadata of a wallet freshly
+    /// restored from seed. Only entries for `wallet_addresses`, the addresses
+    /// the restored wallet derives, are taken over, so an export from another
+    /// wallet can't plant labels.
+    pub fn import_into(
+        &self,
+        network: Network,
+        wallet_addresses: &[String],
+        metadata: &mut WalletMetadata,
+    ) -> Result<ImportSummary, String> {
+        if self.network != network {
+            return Err(format!(
+                "Export is for {:?}, but the wallet is on {:?}",
+                self.network, network
+            ));
+        }
+
+        let ours = |address: &str| wallet_addresses.iter().any(|a| a == address);
+        let mut summary = ImportSummary::default();
+        let mut restore =
+            |reference: String, label: &Option<String>, summary: &mut ImportSummary| {
+                if let Some(label) = label {
+                    metadata.labels.insert(reference, label.clone());
+                    summary.labels_restored += 1;
+                }
+            };
+
+        for address in &self.addresses {
+            if ours(&address.address) {
+                restore(address.address.clone(), &address.label, &mut summary);
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        for tx in &self.transactions {
+            // Sends without change pay nothing back to us and are skipped
+            let touches_wallet = tx.transaction.outputs.iter().any(|o| ours(&o.address));
+            if touches_wallet {
+                restore(tx.txid.clone(), &tx.label, &mut summary);
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        let mut frozen = Vec::new();
+        for utxo in &self.utxos {
+            if !ours(&utxo.address) {
+                summary.skipped += 1;
+                continue;
+            }
+            restore(utxo.outpoint.to_string(), &utxo.label, &mut summary);
+            if utxo.frozen {
+                frozen.push(utxo.outpoint.clone());
+            }
+        }
+        summary.coins_frozen = frozen.len();
+        metadata.frozen.extend(frozen);
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TxIn, TxOut, SEQUENCE_FINAL};
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new("aa", 0),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: "bcrt1qours".to_string(),
+                value: 20_000,
+            }],
+        };
+        let utxo = Utxo {
+            outpoint: OutPoint::new(&tx.txid(), 0),
+            address: "bcrt1qours".to_string(),
+            value: 20_000,
+            confirmations: 3,
+            is_coinbase: false,
+        };
+        let mut metadata = WalletMetadata::default();
+        metadata
+            .labels
+            .insert("bcrt1qours".to_string(), "Donations".to_string());
+        metadata.labels.insert(tx.txid(), "From Alice".to_string());
+        metadata.frozen.insert(utxo.outpoint.clone());
+        let addresses = vec![
+            ExportedAddress {
+                address: "bcrt1qours".to_string(),
+                index: 0,
+                is_change: false,
+                label: None,
+            },
+            ExportedAddress {
+                address: "bcrt1qforeign".to_string(),
+                index: 1,
+                is_change: false,
+                label: Some("planted".to_string()),
+            },
+        ];
+
+        let export = WalletExport::build(
+            Network::Regtest,
+            1_000,
+            &metadata,
+            &addresses,
+            &[(tx.clone(), Some(100))],
+            &[utxo],
+        );
+        let json = export.to_json().unwrap();
+        assert!(!json.contains("seed"));
+
+        let parsed = WalletExport::from_json(&json).unwrap();
+        let mut restored = WalletMetadata::default();
+        let summary = parsed
+            .import_into(Network::Regtest, &["bcrt1qours".to_string()], &mut restored)
+            .unwrap();
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(restored.labels, metadata.labels);
+        assert_eq!(restored.frozen, metadata.frozen);
+        assert!(parsed
+            .import_into(Network::Bitcoin, &[], &mut restored)
+            .is_err());
+    }
+}
@@ -0,0 +1,528 @@
+//! Transaction history export for spreadsheets and accounting software.
+//!
+//! Rows are written one at a time, so histories larger than memory can be
+//! exported straight from [`TxHistory`] pages. CSV uses the configured
+//! [`NumberFormat`]; OFX and QIF always use `.` decimals as their readers expect.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::localization::{CivilDateTime, DateTimeFormat};
+use crate::metadata::WalletMetadata;
+use crate::tx_history::{HistoryEntry, TxHistory};
+
+const SATS_PER_BTC: i64 = 100_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivityFormat {
+    Csv,
+    /// OFX 1.02 bank statement, as imported by most accounting software.
+    Ofx,
+    /// Quicken interchange format, bank account type.
+    Qif,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivityColumn {
+    Date,
+    Txid,
+    Label,
+    /// Net amount in the configured [`AmountUnit`].
+    Amount,
+    /// Net amount at the price of the day, when prices are given.
+    FiatAmount,
+    Fee,
+    BalanceAfter,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmountUnit {
+    #[default]
+    Sats,
+    Btc,
+}
+
+/// Decimal and digit grouping separators for CSV amounts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    pub group_separator: Option<char>,
+}
+
+impl NumberFormat {
+    /// `1234567.89`, what spreadsheets parse regardless of locale.
+    pub const PLAIN: NumberFormat = NumberFormat {
+        decimal_separator: '.',
+        group_separator: None,
+    };
+    /// `1,234,567.89`
+    pub const ENGLISH: NumberFormat = NumberFormat {
+        decimal_separator: '.',
+        group_separator: Some(','),
+    };
+    /// `1.234.567,89`
+    pub const CONTINENTAL: NumberFormat = NumberFormat {
+        decimal_separator: ',',
+        group_separator: Some('.'),
+    };
+
+    /// Formats `units / 10^decimals` with the separators of this format.
+    fn format(&self, units: i64, decimals: u32) -> String {
+        let scale = 10u64.pow(decimals);
+        let whole = (units.unsigned_abs() / scale).to_string();
+        let mut out = String::new();
+        if units < 0 {
+            out.push('-');
+        }
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                if let Some(group) = self.group_separator {
+                    out.push(group);
+                }
+            }
+            out.push(digit);
+        }
+        if decimals > 0 {
+            out.push(self.decimal_separator);
+            out.push_str(&format!(
+                "{:0width$}",
+                units.unsigned_abs() % scale,
+                width = decimals as usize
+            ));
+        }
+        out
+    }
+}
+
+/// Daily BTC prices in one fiat currency, keyed by unix time of the day's start.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FiatPrices {
+    pub currency: String,
+    pub per_btc: BTreeMap<u64, f64>,
+}
+
+impl FiatPrices {
+    /// The latest price at or before `timestamp`.
+    pub fn price_at(&self, timestamp: u64) -> Option<f64> {
+        self.per_btc
+            .range(..=timestamp)
+            .next_back()
+            .map(|(_, p)| *p)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActivityExportOptions {
+    /// CSV columns, in order. OFX and QIF have fixed fields.
+    pub columns: Vec<ActivityColumn>,
+    pub unit: AmountUnit,
+    pub number_format: NumberFormat,
+    pub fiat: Option<FiatPrices>,
+    /// Balance before the first exported row, in satoshis.
+    pub opening_balance: i64,
+}
+
+impl Default for ActivityExportOptions {
+    fn default() -> Self {
+        ActivityExportOptions {
+            columns: vec![
+                ActivityColumn::Date,
+                ActivityColumn::Txid,
+                ActivityColumn::Label,
+                ActivityColumn::Amount,
+                ActivityColumn::Fee,
+                ActivityColumn::BalanceAfter,
+            ],
+            unit: AmountUnit::default(),
+            number_format: NumberFormat::PLAIN,
+            fiat: None,
+            opening_balance: 0,
+        }
+    }
+}
+
+/// Streams history rows, oldest first, in one of the [`ActivityFormat`]s.
+pub struct ActivityWriter<W: Write> {
+    out: W,
+    format: ActivityFormat,
+    options: ActivityExportOptions,
+    balance: i64,
+    /// Running OFX and QIF balance in hundredths of the fiat currency, from
+    /// the first row on.
+    fiat_balance: Option<i64>,
+    rows: usize,
+}
+
+impl<W: Write> ActivityWriter<W> {
+    /// Writes the header for `format` and returns a writer ready for rows.
+    pub fn new(
+        mut out: W,
+        format: ActivityFormat,
+        options: ActivityExportOptions,
+    ) -> Result<Self, String> {
+        let header = match format {
+            ActivityFormat::Csv => {
+                let names: Vec<String> = options
+                    .columns
+                    .iter()
+                    .map(|column| column_name(*column, &options))
+                    .collect();
+                format!("{}\n", names.join(&csv_delimiter(&options).to_string()))
+            }
+            ActivityFormat::Ofx => format!(
+                "OFXHEADER:100\nDATA:OFXSGML\nVERSION:102\nSECURITY:NONE\nENCODING:UTF-8\n\
+                 CHARSET:NONE\nCOMPRESSION:NONE\nOLDFILEUID:NONE\nNEWFILEUID:NONE\n\n\
+                 <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>{}\n<BANKTRANLIST>\n",
+                ofx_currency(&options)
+            ),
+            ActivityFormat::Qif => "!Type:Bank\n".to_string(),
+        };
+        write(&mut out, &header)?;
+
+        Ok(ActivityWriter {
+            out,
+            format,
+            balance: options.opening_balance,
+            fiat_balance: None,
+            options,
+            rows: 0,
+        })
+    }
+
+    /// Fails for OFX and QIF when fiat prices are set but have none for the
+    /// entry's date.
+    pub fn write_entry(&mut self, entry: &HistoryEntry, label: Option<&str>) -> Result<(), String> {
+        let machine_amount = match self.format {
+            ActivityFormat::Csv => String::new(),
+            ActivityFormat::Ofx | ActivityFormat::Qif => self.machine_amount(entry)?,
+        };
+        self.balance += entry.net_amount;
+        let row = match self.format {
+            ActivityFormat::Csv => {
+                let delimiter = csv_delimiter(&self.options);
+                let fields: Vec<String> = self
+                    .options
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        let value = self.field(*column, entry, label, self.options.number_format);
+                        csv_escape(&value, delimiter)
+                    })
+                    .collect();
+                format!("{}\n", fields.join(&delimiter.to_string()))
+            }
+            ActivityFormat::Ofx => format!(
+                concat!(
+                    "<STMTTRN><TRNTYPE>{}<DTPOSTED>{}<TRNAMT>{}",
+                    "<FITID>{}<NAME>{}<MEMO>{}</STMTTRN>\n"
+                ),
+                if entry.net_amount < 0 {
+                    "DEBIT"
+                } else {
+                    "CREDIT"
+                },
+                format_datetime_compact(entry.timestamp),
+                machine_amount,
+                entry.txid,
+                sgml_escape(label.unwrap_or(&entry.txid)),
+                entry.txid
+            ),
+            ActivityFormat::Qif => format!(
+                "D{}\nT{}\nP{}\nM{}\n^\n",
+                format_date_us(entry.timestamp),
+                machine_amount,
+                label.unwrap_or(&entry.txid).replace('\n', " "),
+                entry.txid
+            ),
+        };
+        write(&mut self.out, &row)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Writes the trailer and flushes. Returns the number of rows written.
+    pub fn finish(mut self) -> Result<usize, String> {
+        if self.format == ActivityFormat::Ofx {
+            // In the statement currency, like the transaction amounts
+            let balance = match (&self.options.fiat, self.fiat_balance) {
+                (None, _) => NumberFormat::PLAIN.format(self.balance, 8),
+                (Some(_), Some(cents)) => NumberFormat::PLAIN.format(cents, 2),
+                (Some(_), None) if self.balance == 0 => NumberFormat::PLAIN.format(0, 2),
+                (Some(fiat), None) => {
+                    return Err(format!(
+                        "No date to value the opening balance in {} at",
+                        fiat.currency
+                    ))
+                }
+            };
+            let footer = format!(
+                concat!(
+                    "</BANKTRANLIST><LEDGERBAL><BALAMT>{}</LEDGERBAL>",
+                    "</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n"
+                ),
+                balance
+            );
+            write(&mut self.out, &footer)?;
+        }
+        self.out
+            .flush()
+            .map_err(|e| format!("Failed to flush export: {}", e))?;
+        Ok(self.rows)
+    }
+
+    fn field(
+        &self,
+        column: ActivityColumn,
+        entry: &HistoryEntry,
+        label: Option<&str>,
+        format: NumberFormat,
+    ) -> String {
+        match column {
+            ActivityColumn::Date => format_date_iso(entry.timestamp),
+            ActivityColumn::Txid => entry.txid.clone(),
+            ActivityColumn::Label => label.unwrap_or_default().to_string(),
+            ActivityColumn::Amount => self.amount(entry.net_amount, format),
+            ActivityColumn::FiatAmount => self
+                .options
+                .fiat
+                .as_ref()
+                .and_then(|fiat| fiat_cents(fiat, entry.net_amount, entry.timestamp).ok())
+                .map(|cents| format.format(cents, 2))
+                .unwrap_or_default(),
+            ActivityColumn::Fee => entry
+                .fee
+                .map(|fee| self.amount(fee as i64, format))
+                .unwrap_or_default(),
+            ActivityColumn::BalanceAfter => self.amount(self.balance, format),
+        }
+    }
+
+    // OFX and QIF amounts: fiat when prices are configured, otherwise BTC,
+    // since the formats have no notion of satoshis
+    fn machine_amount(&mut self, entry: &HistoryEntry) -> Result<String, String> {
+        let Some(fiat) = &self.options.fiat else {
+            return Ok(NumberFormat::PLAIN.format(entry.net_amount, 8));
+        };
+        let cents = fiat_cents(fiat, entry.net_amount, entry.timestamp)?;
+        // The opening balance is valued at the first row's price
+        let opening = match self.fiat_balance {
+            Some(balance) => balance,
+            None => fiat_cents(fiat, self.options.opening_balance, entry.timestamp)?,
+        };
+        self.fiat_balance = Some(opening + cents);
+        Ok(NumberFormat::PLAIN.format(cents, 2))
+    }
+
+    fn amount(&self, sats: i64, format: NumberFormat) -> String {
+        match self.options.unit {
+            AmountUnit::Sats => format.format(sats, 0),
+            AmountUnit::Btc => format.format(sats, 8),
+        }
+    }
+}
+
+/// Exports every confirmed row of `history` oldest first, then pending ones,
+/// skipping transactions that were replaced. Labels come from `metadata`.
+pub fn export_history<W: Write>(
+    history: &mut TxHistory,
+    metadata: &WalletMetadata,
+    format: ActivityFormat,
+    options: ActivityExportOptions,
+    out: W,
+) -> Result<usize, String> {
+    let mut writer = ActivityWriter::new(out, format, options)?;
+    for page in (0..history.page_count()).rev() {
+        for entry in history.page(page)?.iter().rev() {
+            writer.write_entry(entry, metadata.label(&entry.txid))?;
+        }
+    }
+    for entry in history.pending() {
+        if entry.replaced_by.is_none() {
+            writer.write_entry(entry, metadata.label(&entry.txid))?;
+        }
+    }
+    writer.finish()
+}
+
+// `sats` in hundredths of the fiat currency, at the price on `timestamp`'s day
+fn fiat_cents(fiat: &FiatPrices, sats: i64, timestamp: u64) -> Result<i64, String> {
+    let price = fiat.price_at(timestamp).ok_or_else(|| {
+        format!(
+            "No {} price for {}",
+            fiat.currency,
+            format_date_iso(timestamp)
+        )
+    })?;
+    Ok((sats as f64 * price / SATS_PER_BTC as f64 * 100.0).round() as i64)
+}
+
+fn write(out: &mut impl Write, text: &str) -> Result<(), String> {
+    out.write_all(text.as_bytes())
+        .map_err(|e| format!("Failed to write export: {}", e))
+}
+
+fn column_name(column: ActivityColumn, options: &ActivityExportOptions) -> String {
+    let unit = match options.unit {
+        AmountUnit::Sats => "sats",
+        AmountUnit::Btc => "BTC",
+    };
+    match column {
+        ActivityColumn::Date => "Date".to_string(),
+        ActivityColumn::Txid => "Txid".to_string(),
+        ActivityColumn::Label => "Label".to_string(),
+        ActivityColumn::Amount => format!("Amount ({})", unit),
+        ActivityColumn::FiatAmount => format!(
+            "Amount ({})",
+            options
+                .fiat
+                .as_ref()
+                .map(|f| f.currency.as_str())
+                .unwrap_or("fiat")
+        ),
+        ActivityColumn::Fee => format!("Fee ({})", unit),
+        ActivityColumn::BalanceAfter => format!("Balance ({})", unit),
+    }
+}
+
+// Comma decimals need another delimiter, as spreadsheets in those locales expect
+fn csv_delimiter(options: &ActivityExportOptions) -> char {
+    if options.number_format.decimal_separator == ',' {
+        ';'
+    } else {
+        ','
+    }
+}
+
+fn csv_escape(value: &str, delimiter: char) -> String {
+    // A leading formula character would be evaluated by spreadsheets
+    let value = if value.starts_with(['=', '+', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn sgml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn ofx_currency(options: &ActivityExportOptions) -> String {
+    options
+        .fiat
+        .as_ref()
+        .map(|f| f.currency.clone())
+        .unwrap_or_else(|| "XBT".to_string())
+}
+
+fn format_date_iso(timestamp: u64) -> String {
+    DateTimeFormat::ISO.format_date(&CivilDateTime::from_timestamp(timestamp, 0))
+}
+
+fn format_date_us(timestamp: u64) -> String {
+    DateTimeFormat::for_locale("en-US").format_date(&CivilDateTime::from_timestamp(timestamp, 0))
+}
+
+fn format_datetime_compact(timestamp: u64) -> String {
+    let t = CivilDateTime::from_timestamp(timestamp, 0);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(txid: &str, timestamp: u64, net_amount: i64, fee: Option<u64>) -> HistoryEntry {
+        HistoryEntry {
+            txid: txid.to_string(),
+            height: Some(1),
+            timestamp,
+            net_amount,
+            fee,
+            spent: Vec::new(),
+            replaced_by: None,
+            addresses: Vec::new(),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_localized_and_ofx() {
+        let rows = [
+            entry("aa", 1_700_000_000, 150_000_000, None),
+            entry("bb", 1_700_086_400, -20_000_000, Some(1_000)),
+        ];
+        let options = ActivityExportOptions {
+            unit: AmountUnit::Btc,
+            number_format: NumberFormat::CONTINENTAL,
+            ..Default::default()
+        };
+
+        let mut csv = Vec::new();
+        let mut writer = ActivityWriter::new(&mut csv, ActivityFormat::Csv, options).unwrap();
+        writer
+            .write_entry(&rows[0], Some("=salary; March"))
+            .unwrap();
+        writer.write_entry(&rows[1], None).unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Date;Txid;Label;Amount (BTC);Fee (BTC);Balance (BTC)"
+        );
+        assert_eq!(
+            lines[1],
+            "2023-11-14;aa;\"'=salary; March\";1,50000000;;1,50000000"
+        );
+        assert_eq!(lines[2], "2023-11-15;bb;;-0,20000000;0,00001000;1,30000000");
+
+        let mut ofx = Vec::new();
+        let mut writer =
+            ActivityWriter::new(&mut ofx, ActivityFormat::Ofx, Default::default()).unwrap();
+        writer.write_entry(&rows[1], Some("Rent & bills")).unwrap();
+        writer.finish().unwrap();
+        let ofx = String::from_utf8(ofx).unwrap();
+        assert!(ofx.contains("<TRNTYPE>DEBIT<DTPOSTED>20231115221320<TRNAMT>-0.20000000"));
+        assert!(ofx.contains("<NAME>Rent &amp; bills"));
+        assert!(ofx.ends_with(
+            "<BALAMT>-0.20000000</LEDGERBAL></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n"
+        ));
+
+        let fiat = ActivityExportOptions {
+            fiat: Some(FiatPrices {
+                currency: "EUR".to_string(),
+                per_btc: BTreeMap::from([(1_699_920_000, 30_000.0), (1_700_006_400, 32_000.0)]),
+            }),
+            opening_balance: 100_000_000,
+            ..Default::default()
+        };
+        let mut ofx = Vec::new();
+        let mut writer = ActivityWriter::new(&mut ofx, ActivityFormat::Ofx, fiat.clone()).unwrap();
+        writer.write_entry(&rows[0], None).unwrap();
+        writer.write_entry(&rows[1], None).unwrap();
+        writer.finish().unwrap();
+        let ofx = String::from_utf8(ofx).unwrap();
+        assert!(ofx.contains("<CURDEF>EUR"));
+        assert!(ofx.contains("<TRNAMT>-6400.00"));
+        // 30,000 opening, +45,000, -6,400
+        assert!(ofx.contains("<BALAMT>68600.00</LEDGERBAL>"));
+
+        let mut qif = Vec::new();
+        let mut writer = ActivityWriter::new(&mut qif, ActivityFormat::Qif, fiat).unwrap();
+        let err = writer
+            .write_entry(&entry("cc", 1_600_000_000, 1_000, None), None)
+            .unwrap_err();
+        assert_eq!(err, "No EUR price for 2020-09-13");
+    }
+}
@@ -0,0 +1,226 @@
+//! Requests test coins from signet and testnet faucets so a development
+//! wallet can be funded without leaving the app.
+//!
+//! Funding transactions go through the same [`IncomingWatcher`] as any other
+//! payment, so what contributors see while testing is the real receive path.
+
+use serde::{Deserialize, Serialize};
+
+use crate::address_filter::AddressMatcher;
+use crate::backend::BlockchainBackend;
+use crate::events::MessageBus;
+use crate::incoming::IncomingWatcher;
+use crate::payment_uri;
+use crate::types::Network;
+
+/// A faucet accepting `{"address": ..., "amount": ...}` POSTed as JSON and
+/// answering with the funding txid, either bare or as `{"txid": ...}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Faucet {
+    pub name: String,
+    pub network: Network,
+    pub url: String,
+    /// Most the faucet hands out per request, in satoshis.
+    pub max_amount: u64,
+}
+
+/// How requests reach the faucet, swappable so tests stay offline.
+pub trait FaucetTransport: Send + Sync {
+    /// POSTs `body` as JSON and returns the response body.
+    fn post_json(&self, url: &str, body: &str) -> Result<String, String>;
+}
+
+/// Sends requests over HTTP.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpFaucetTransport;
+
+impl FaucetTransport for HttpFaucetTransport {
+    fn post_json(&self, url: &str, body: &str) -> Result<String, String> {
+        ureq::post(url)
+            .set("Content-Type", "application/json")
+            .send_string(body)
+            .map_err(|e| format!("Faucet request failed: {}", e))?
+            .into_string()
+            .map_err(|e| format!("Failed to read faucet response: {}", e))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaucetRequest {
+    pub faucet: String,
+    pub address: String,
+    pub amount: u64,
+    pub txid: String,
+    /// The funding transaction was handed to the incoming payment watcher.
+    pub observed: bool,
+}
+
+// Accepts a bare txid or a JSON object with a `txid` field
+fn parse_txid(response: &str) -> Result<String, String> {
+    let response = response.trim();
+    let txid = match serde_json::from_str::<serde_json::Value>(response) {
+        Ok(json) => json
+            .get("txid")
+            .and_then(|t| t.as_str())
+            .map(str::to_string)
+            .unwrap_or_default(),
+        Err(_) => response.to_string(),
+    };
+    if txid.len() == 64 && txid.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(txid.to_ascii_lowercase())
+    } else {
+        Err(format!("Faucet response has no txid: {}", response))
+    }
+}
+
+/// Requests coins from configured faucets and tracks the funding transactions.
+pub struct FaucetClient {
+    faucets: Vec<Faucet>,
+    transport: Box<dyn FaucetTransport>,
+    requests: Vec<FaucetRequest>,
+}
+
+impl FaucetClient {
+    pub fn new(faucets: Vec<Faucet>) -> Result<Self, String> {
+        Self::with_transport(faucets, Box::new(HttpFaucetTransport))
+    }
+
+    /// Refuses mainnet faucets, which can only be scams.
+    pub fn with_transport(
+        faucets: Vec<Faucet>,
+        transport: Box<dyn FaucetTransport>,
+    ) -> Result<Self, String> {
+        if let Some(faucet) = faucets.iter().find(|f| f.network == Network::Bitcoin) {
+            return Err(format!("Faucet {} is configured for mainnet", faucet.name));
+        }
+        Ok(FaucetClient {
+            faucets,
+            transport,
+            requests: Vec::new(),
+        })
+    }
+
+    pub fn faucets(&self, network: Network) -> impl Iterator<Item = &Faucet> {
+        self.faucets.iter().filter(move |f| f.network == network)
+    }
+
+    pub fn requests(&self) -> &[FaucetRequest] {
+        &self.requests
+    }
+
+    /// Asks the first faucet for `network` that will give `amount` to send it
+    /// to `address`, trying the next one if a faucet fails. Returns the txid.
+    pub fn request(
+        &mut self,
+        network: Network,
+        address: &str,
+        amount: u64,
+    ) -> Result<String, String> {
+        payment_uri::check_address(address, network)?;
+        let body = serde_json::json!({ "address": address, "amount": amount }).to_string();
+
+        let mut last_error = format!("No faucet for {:?} gives {} sats", network, amount);
+        let candidates: Vec<Faucet> = self
+            .faucets(network)
+            .filter(|f| amount <= f.max_amount)
+            .cloned()
+            .collect();
+        for faucet in candidates {
+            match self
+                .transport
+                .post_json(&faucet.url, &body)
+                .and_then(|response| parse_txid(&response))
+            {
+                Ok(txid) => {
+                    log::info!("Faucet {} sent {} sats in {}", faucet.name, amount, txid);
+                    self.requests.push(FaucetRequest {
+                        faucet: faucet.name,
+                        address: address.to_string(),
+                        amount,
+                        txid: txid.clone(),
+                        observed: false,
+                    });
+                    return Ok(txid);
+                }
+                Err(e) => {
+                    log::warn!("Faucet {} failed: {}", faucet.name, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Feeds funding transactions the backend has seen to `watcher`, which
+    /// then follows them to confirmation like any incoming payment. Returns
+    /// how many were newly handed over.
+    pub fn track(
+        &mut self,
+        backend: &dyn BlockchainBackend,
+        watcher: &mut IncomingWatcher,
+        wallet: &mut AddressMatcher,
+        bus: Option<&MessageBus>,
+    ) -> Result<usize, String> {
+        let mut observed = 0;
+        for request in self.requests.iter_mut().filter(|r| !r.observed) {
+            if let Some(tx) = backend.transaction(&request.txid)? {
+                watcher.observe(&tx, wallet, bus);
+                request.observed = true;
+                observed += 1;
+            }
+        }
+        Ok(observed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct CannedTransport(Mutex<Vec<Result<String, String>>>);
+
+    impl FaucetTransport for CannedTransport {
+        fn post_json(&self, _url: &str, body: &str) -> Result<String, String> {
+            assert!(body.contains("\"amount\":10000"));
+            self.0.lock().unwrap().remove(0)
+        }
+    }
+
+    fn faucet(name: &str, network: Network) -> Faucet {
+        Faucet {
+            name: name.to_string(),
+            network,
+            url: format!("https://{}.example/api", name),
+            max_amount: 100_000,
+        }
+    }
+
+    #[test]
+    fn test_request_falls_back_between_faucets() {
+        assert!(FaucetClient::new(vec![faucet("scam", Network::Bitcoin)]).is_err());
+
+        let txid = "ab".repeat(32);
+        let transport = CannedTransport(Mutex::new(vec![
+            Err("rate limited".to_string()),
+            Ok(format!("{{\"txid\": \"{}\"}}", txid)),
+        ]));
+        let mut client = FaucetClient::with_transport(
+            vec![
+                faucet("one", Network::Signet),
+                faucet("two", Network::Signet),
+            ],
+            Box::new(transport),
+        )
+        .unwrap();
+
+        let address = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+        assert!(client.request(Network::Signet, address, 1_000_000).is_err());
+        assert_eq!(
+            client.request(Network::Signet, address, 10_000).unwrap(),
+            txid
+        );
+        assert_eq!(client.requests()[0].faucet, "two");
+        assert!(!client.requests()[0].observed);
+    }
+}
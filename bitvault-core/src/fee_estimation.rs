@@ -0,0 +1,825 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::backend::{BlockchainBackend, RelayFees};
+use crate::clock::SharedClock;
+use crate::connection_mode::SharedConnectionMode;
+use crate::events::{Event, MessageBus};
+
+pub mod defaults;
+pub mod history;
+
+use history::FeeHistory;
+
+/// How quickly a transaction should confirm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FeePriority {
+    High,
+    Medium,
+    Low,
+    /// "Whenever": no urgency, priced from the cheap end of recent history.
+    Economy,
+}
+
+impl FeePriority {
+    /// Confirmation target in blocks.
+    pub fn target_blocks(self) -> u32 {
+        match self {
+            FeePriority::High => 1,
+            FeePriority::Medium => 6,
+            FeePriority::Low => 24,
+            FeePriority::Economy => 144,
+        }
+    }
+}
+
+/// Fee rates in sat/vB keyed by confirmation target in blocks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimates {
+    pub rates: BTreeMap<u32, f64>,
+    pub fetched_at: u64,
+    pub source: String,
+    /// Set on estimates restored from disk; they may predate current mempool conditions.
+    #[serde(skip)]
+    pub possibly_stale: bool,
+}
+
+impl FeeEstimates {
+    pub fn fallback(now: u64) -> Self {
+        FeeEstimates {
+            rates: defaults::FALLBACK_RATES.iter().copied().collect(),
+            fetched_at: now,
+            source: "defaults".to_string(),
+            possibly_stale: true,
+        }
+    }
+
+    /// Raises every rate to at least `floor`.
+    pub fn apply_floor(&mut self, floor: f64) {
+        for rate in self.rates.values_mut() {
+            *rate = rate.max(floor);
+        }
+    }
+
+    /// Rate for `target` blocks, using the nearest faster target when there is no
+    /// exact entry (paying slightly more rather than missing the target).
+    pub fn rate_for_target(&self, target: u32) -> Option<f64> {
+        self.rates
+            .range(..=target)
+            .next_back()
+            .or_else(|| self.rates.iter().next())
+            .map(|(_, rate)| *rate)
+    }
+}
+
+/// How answers from several providers are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeeEstimationMode {
+    /// Use the first provider that answers, in configured order.
+    #[default]
+    FirstSuccess,
+    /// Query every available provider, drop outliers and take a weighted median
+    /// per confirmation target.
+    Consensus,
+}
+
+/// Rates further than this factor from the median are ignored in consensus mode.
+const OUTLIER_FACTOR: f64 = 2.0;
+
+/// Economy sends pay what the cheapest fifth of low-priority rates over the
+/// history window paid; those rates cleared eventually, so waiting for them
+/// again is a good bet when there is no deadline.
+const ECONOMY_PERCENTILE: f64 = 0.2;
+/// How much history the economy tier looks at.
+const ECONOMY_WINDOW_SECS: u64 = 7 * 24 * 60 * 60;
+/// Samples needed before history is trusted over the provider's own long target.
+const ECONOMY_MIN_SAMPLES: usize = 12;
+
+/// External source of fee estimates (mempool.space, Esplora, Core RPC...).
+pub trait FeeProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Fetches fee rates in sat/vB keyed by confirmation target.
+    fn fetch(&self) -> Result<BTreeMap<u32, f64>, String>;
+
+    /// Relative trust in this provider when forming a consensus.
+    fn weight(&self) -> f64 {
+        1.0
+    }
+}
+
+/// Holds the last successful estimates and decides when they are too old to use.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeEstimationCache {
+    estimates: Option<FeeEstimates>,
+    max_age_secs: u64,
+    path: Option<PathBuf>,
+}
+
+impl FeeEstimationCache {
+    /// Creates an in-memory cache that is not persisted.
+    pub fn new(max_age_secs: u64) -> Self {
+        FeeEstimationCache {
+            estimates: None,
+            max_age_secs,
+            path: None,
+        }
+    }
+
+    /// Loads the cache from `path` so recent estimates are available at startup,
+    /// before the first fetch completes. Restored estimates are marked possibly
+    /// stale; a missing or unreadable file just starts empty.
+    pub fn load(path: &Path, max_age_secs: u64) -> Self {
+        let estimates = fs::read_to_string(path).ok().and_then(|json| {
+            serde_json::from_str::<FeeEstimates>(&json)
+                .map_err(|e| log::warn!("Failed to parse fee cache: {}", e))
+                .ok()
+        });
+
+        FeeEstimationCache {
+            estimates: estimates.map(|e| FeeEstimates {
+                possibly_stale: true,
+                ..e
+            }),
+            max_age_secs,
+            path: Some(path.to_path_buf()),
+        }
+    }
+
+    /// Estimates younger than the maximum age, if any.
+    pub fn get_fresh(&self, now: u64) -> Option<&FeeEstimates> {
+        self.get_within(now, self.max_age_secs)
+    }
+
+    /// Estimates no older than `max_age_secs`, if any.
+    pub fn get_within(&self, now: u64, max_age_secs: u64) -> Option<&FeeEstimates> {
+        self.estimates
+            .as_ref()
+            .filter(|e| now.saturating_sub(e.fetched_at) <= max_age_secs)
+    }
+
+    /// The last estimates regardless of age.
+    pub fn get_any(&self) -> Option<&FeeEstimates> {
+        self.estimates.as_ref()
+    }
+
+    /// Replaces the cached estimates, writing them through to disk when persisted.
+    pub fn store(&mut self, estimates: FeeEstimates) {
+        if let Some(path) = &self.path {
+            let saved = serde_json::to_string(&estimates)
+                .map_err(|e| e.to_string())
+                .and_then(|json| fs::write(path, json).map_err(|e| e.to_string()));
+            if let Err(e) = saved {
+                log::warn!("Failed to save fee cache: {}", e);
+            }
+        }
+        self.estimates = Some(estimates);
+    }
+}
+
+/// When a failing provider is taken out of rotation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit skips the provider.
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            cooldown_secs: 5 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ProviderHealth {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    total_latency_ms: u64,
+    last_error: Option<String>,
+    open_until: Option<u64>,
+}
+
+/// Health snapshot of one provider, as returned by [`FeeEstimationService::stats`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProviderStats {
+    pub name: String,
+    pub requests: u64,
+    pub success_rate: f64,
+    pub avg_latency_ms: u64,
+    pub last_error: Option<String>,
+    pub circuit_open: bool,
+}
+
+/// Queries fee providers in order, tracking their health and short-circuiting
+/// providers that keep failing.
+pub struct FeeEstimationService {
+    providers: Vec<Box<dyn FeeProvider>>,
+    health: Mutex<HashMap<String, ProviderHealth>>,
+    cache: Mutex<FeeEstimationCache>,
+    history: Mutex<FeeHistory>,
+    breaker: CircuitBreakerConfig,
+    mode: FeeEstimationMode,
+    relay_source: Option<Arc<dyn BlockchainBackend>>,
+    relay_fees: Mutex<Option<RelayFees>>,
+    connection: SharedConnectionMode,
+    clock: SharedClock,
+    bus: Option<Arc<MessageBus>>,
+    stats_interval_secs: u64,
+    last_stats_at: Mutex<u64>,
+}
+
+impl FeeEstimationService {
+    pub fn new(providers: Vec<Box<dyn FeeProvider>>, clock: SharedClock) -> Self {
+        FeeEstimationService {
+            providers,
+            health: Mutex::new(HashMap::new()),
+            cache: Mutex::new(FeeEstimationCache::new(10 * 60)),
+            history: Mutex::new(FeeHistory::new(ECONOMY_WINDOW_SECS)),
+            breaker: CircuitBreakerConfig::default(),
+            mode: FeeEstimationMode::default(),
+            relay_source: None,
+            relay_fees: Mutex::new(None),
+            connection: SharedConnectionMode::default(),
+            clock,
+            bus: None,
+            stats_interval_secs: 15 * 60,
+            last_stats_at: Mutex::new(0),
+        }
+    }
+
+    /// Replaces the default in-memory cache, e.g. with one from [`FeeEstimationCache::load`].
+    pub fn with_cache(mut self, cache: FeeEstimationCache) -> Self {
+        self.cache = Mutex::new(cache);
+        self
+    }
+
+    /// Queries `backend` for relay and mempool minimum fees on every fetch and
+    /// never recommends less than them.
+    pub fn with_relay_fee_source(mut self, backend: Arc<dyn BlockchainBackend>) -> Self {
+        self.relay_source = Some(backend);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: FeeEstimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Keeps cached estimates for longer while the connection is metered,
+    /// deferring refreshes.
+    pub fn with_connection_mode(mut self, connection: SharedConnectionMode) -> Self {
+        self.connection = connection;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreakerConfig) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    /// Publishes provider statistics as `System` events at most every `interval_secs`.
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>, interval_secs: u64) -> Self {
+        self.bus = Some(bus);
+        self.stats_interval_secs = interval_secs;
+        self
+    }
+
+    /// Returns fresh cached estimates or fetches from the first healthy provider
+    /// that answers, falling back to defaults when none do. Rates are never below
+    /// the current fee floor.
+    pub fn get_estimates(&self) -> FeeEstimates {
+        let mut estimates = self.get_estimates_unfloored();
+        estimates.apply_floor(self.fee_floor());
+        estimates
+    }
+
+    fn get_estimates_unfloored(&self) -> FeeEstimates {
+        let now = self.clock.now();
+        let mode = self.connection.get();
+        if let Some(estimates) = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|c| c.get_within(now, mode.interval(c.max_age_secs)).cloned())
+        {
+            return estimates;
+        }
+
+        match self.fetch_estimates() {
+            Ok(estimates) => estimates,
+            Err(e) => {
+                log::warn!("Fee estimation failed, using fallback: {}", e);
+                self.cache
+                    .lock()
+                    .ok()
+                    .and_then(|c| c.get_any().cloned())
+                    .unwrap_or_else(|| FeeEstimates::fallback(now))
+            }
+        }
+    }
+
+    /// Whatever estimates are cached, without touching the network. Lets the UI
+    /// show recommendations immediately while a fetch is in flight.
+    pub fn cached_estimates(&self) -> FeeEstimates {
+        self.cache
+            .lock()
+            .ok()
+            .and_then(|c| c.get_any().cloned())
+            .unwrap_or_else(|| FeeEstimates::fallback(self.clock.now()))
+    }
+
+    /// Fee rate in sat/vB for a priority.
+    pub fn get_fee_rate(&self, priority: FeePriority) -> f64 {
+        let estimates = self.get_estimates();
+        let rate_for = |priority: FeePriority| {
+            estimates
+                .rate_for_target(priority.target_blocks())
+                .unwrap_or(defaults::min_reasonable_fee_rate())
+        };
+
+        let rate = match priority {
+            FeePriority::Economy => {
+                let historical = self
+                    .history
+                    .lock()
+                    .ok()
+                    .filter(|h| h.len() >= ECONOMY_MIN_SAMPLES)
+                    .and_then(|h| h.percentile(ECONOMY_PERCENTILE));
+                // Never pay more than a Low priority send would
+                historical
+                    .unwrap_or_else(|| rate_for(FeePriority::Economy))
+                    .min(rate_for(FeePriority::Low))
+            }
+            _ => rate_for(priority),
+        };
+        rate.max(self.fee_floor())
+    }
+
+    /// Lowest rate that will currently be relayed, from the last relay fee query.
+    pub fn fee_floor(&self) -> f64 {
+        let relay_fees = self.relay_fees.lock().ok().and_then(|fees| *fees);
+        defaults::min_fee_rate_with_floor(relay_fees.as_ref())
+    }
+
+    /// Queries the relay fee source, keeping the previous floor if it fails.
+    pub fn refresh_relay_fees(&self) {
+        let Some(backend) = &self.relay_source else {
+            return;
+        };
+        match backend.relay_fees() {
+            Ok(fees) => {
+                if let Ok(mut relay_fees) = self.relay_fees.lock() {
+                    *relay_fees = Some(fees);
+                }
+            }
+            Err(e) => log::warn!("Failed to query relay fees: {}", e),
+        }
+    }
+
+    /// Queries providers according to the configured mode, skipping open circuits,
+    /// and caches the result.
+    pub fn fetch_estimates(&self) -> Result<FeeEstimates, String> {
+        self.refresh_relay_fees();
+        let mut errors = Vec::new();
+        let mut answers = Vec::new();
+
+        for provider in &self.providers {
+            match self.query(provider.as_ref()) {
+                Ok(rates) => {
+                    answers.push((provider.name().to_string(), provider.weight(), rates));
+                    if self.mode == FeeEstimationMode::FirstSuccess {
+                        break;
+                    }
+                }
+                Err(e) => errors.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+        self.publish_stats_if_due();
+
+        let (rates, source) = match answers.len() {
+            0 => return Err(format!("No fee provider succeeded ({})", errors.join("; "))),
+            1 => {
+                let (name, _, rates) = answers.remove(0);
+                (rates, name)
+            }
+            _ => {
+                let names: Vec<&str> = answers.iter().map(|(name, _, _)| name.as_str()).collect();
+                let source = format!("consensus({})", names.join(", "));
+                (consensus_rates(&answers), source)
+            }
+        };
+
+        let mut estimates = FeeEstimates {
+            rates,
+            fetched_at: self.clock.now(),
+            source,
+            possibly_stale: false,
+        };
+        estimates.apply_floor(self.fee_floor());
+        if let (Some(rate), Ok(mut history)) = (
+            estimates.rate_for_target(FeePriority::Low.target_blocks()),
+            self.history.lock(),
+        ) {
+            history.record(estimates.fetched_at, rate);
+        }
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.store(estimates.clone());
+        }
+        Ok(estimates)
+    }
+
+    /// Per-provider health statistics.
+    pub fn stats(&self) -> Vec<ProviderStats> {
+        let now = self.clock.now();
+        let health = self.health.lock().map(|h| h.clone()).unwrap_or_default();
+
+        self.providers
+            .iter()
+            .map(|provider| {
+                let h = health.get(provider.name()).cloned().unwrap_or_default();
+                let requests = h.successes + h.failures;
+                ProviderStats {
+                    name: provider.name().to_string(),
+                    requests,
+                    success_rate: if requests == 0 {
+                        1.0
+                    } else {
+                        h.successes as f64 / requests as f64
+                    },
+                    avg_latency_ms: h.total_latency_ms.checked_div(requests).unwrap_or(0),
+                    last_error: h.last_error,
+                    circuit_open: h.open_until.is_some_and(|until| now < until),
+                }
+            })
+            .collect()
+    }
+
+    // Fetches from one provider, recording the outcome in its health stats
+    fn query(&self, provider: &dyn FeeProvider) -> Result<BTreeMap<u32, f64>, String> {
+        let now = self.clock.now();
+        if self.is_circuit_open(provider.name(), now) {
+            return Err("circuit open".to_string());
+        }
+
+        let started = Instant::now();
+        let result = provider.fetch().and_then(validate_rates);
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match &result {
+            Ok(_) => self.record_success(provider.name(), latency_ms),
+            Err(e) => self.record_failure(provider.name(), latency_ms, e, now),
+        }
+        result
+    }
+
+    fn is_circuit_open(&self, name: &str, now: u64) -> bool {
+        self.health
+            .lock()
+            .ok()
+            .and_then(|h| h.get(name).and_then(|h| h.open_until))
+            .is_some_and(|until| now < until)
+    }
+
+    fn record_success(&self, name: &str, latency_ms: u64) {
+        if let Ok(mut health) = self.health.lock() {
+            let h = health.entry(name.to_string()).or_default();
+            h.successes += 1;
+            h.total_latency_ms += latency_ms;
+            h.consecutive_failures = 0;
+            h.open_until = None;
+        }
+    }
+
+    fn record_failure(&self, name: &str, latency_ms: u64, error: &str, now: u64) {
+        let opened = if let Ok(mut health) = self.health.lock() {
+            let h = health.entry(name.to_string()).or_default();
+            h.failures += 1;
+            h.total_latency_ms += latency_ms;
+            h.consecutive_failures += 1;
+            h.last_error = Some(error.to_string());
+            if h.consecutive_failures >= self.breaker.failure_threshold {
+                h.open_until = Some(now + self.breaker.cooldown_secs);
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+
+        if opened {
+            log::warn!(
+                "Fee provider {} disabled for {}s",
+                name,
+                self.breaker.cooldown_secs
+            );
+            if let Some(bus) = &self.bus {
+                bus.publish(Event::System {
+                    component: "fee_estimation".to_string(),
+                    message: format!(
+                        "Provider {} disabled for {}s after repeated failures: {}",
+                        name, self.breaker.cooldown_secs, error
+                    ),
+                });
+            }
+        }
+    }
+
+    fn publish_stats_if_due(&self) {
+        let Some(bus) = &self.bus else {
+            return;
+        };
+        let now = self.clock.now();
+        let Ok(mut last) = self.last_stats_at.lock() else {
+            return;
+        };
+        if *last != 0 && now < *last + self.stats_interval_secs {
+            return;
+        }
+        *last = now;
+
+        let summary: Vec<String> = self
+            .stats()
+            .iter()
+            .map(|s| {
+                format!(
+                    "{}: {:.0}% ok, {}ms avg{}",
+                    s.name,
+                    s.success_rate * 100.0,
+                    s.avg_latency_ms,
+                    if s.circuit_open { ", circuit open" } else { "" }
+                )
+            })
+            .collect();
+        bus.publish(Event::System {
+            component: "fee_estimation".to_string(),
+            message: summary.join("; "),
+        });
+    }
+}
+
+// Per target: drop rates far from the plain median, then take the weighted median
+fn consensus_rates(answers: &[(String, f64, BTreeMap<u32, f64>)]) -> BTreeMap<u32, f64> {
+    let targets: BTreeSet<u32> = answers
+        .iter()
+        .flat_map(|(_, _, rates)| rates.keys().copied())
+        .collect();
+
+    targets
+        .into_iter()
+        .filter_map(|target| {
+            let mut samples: Vec<(f64, f64)> = answers
+                .iter()
+                .filter_map(|(_, weight, rates)| rates.get(&target).map(|r| (*r, *weight)))
+                .filter(|(_, weight)| *weight > 0.0)
+                .collect();
+            samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            // With only two samples there is no way to tell which one is the outlier
+            if samples.len() >= 3 {
+                let median = samples[samples.len() / 2].0;
+                samples.retain(|(rate, _)| {
+                    *rate <= median * OUTLIER_FACTOR && *rate >= median / OUTLIER_FACTOR
+                });
+            }
+
+            let half = samples.iter().map(|(_, w)| w).sum::<f64>() / 2.0;
+            let mut cumulative = 0.0;
+            samples
+                .iter()
+                .find(|(_, weight)| {
+                    cumulative += weight;
+                    cumulative >= half
+                })
+                .map(|(rate, _)| (target, *rate))
+        })
+        .collect()
+}
+
+// Reject empty answers and rates outside the sane range
+fn validate_rates(rates: BTreeMap<u32, f64>) -> Result<BTreeMap<u32, f64>, String> {
+    if rates.is_empty() {
+        return Err("Provider returned no estimates".to_string());
+    }
+    let min = defaults::min_reasonable_fee_rate();
+    let max = defaults::max_reasonable_fee_rate();
+    for (target, rate) in &rates {
+        if !rate.is_finite() || *rate > max || *rate < 0.0 {
+            return Err(format!(
+                "Unreasonable fee rate {} for target {}",
+                rate, target
+            ));
+        }
+    }
+    Ok(rates
+        .into_iter()
+        .map(|(target, rate)| (target, rate.max(min)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct TestProvider {
+        name: &'static str,
+        failing: Arc<AtomicBool>,
+        rate: f64,
+        weight: f64,
+    }
+
+    impl FeeProvider for TestProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn fetch(&self) -> Result<BTreeMap<u32, f64>, String> {
+            if self.failing.load(Ordering::SeqCst) {
+                Err("connection refused".to_string())
+            } else {
+                Ok(BTreeMap::from([(1, self.rate), (6, self.rate / 2.0)]))
+            }
+        }
+
+        fn weight(&self) -> f64 {
+            self.weight
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_and_stats() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let failing = Arc::new(AtomicBool::new(true));
+        let providers: Vec<Box<dyn FeeProvider>> = vec![
+            Box::new(TestProvider {
+                name: "primary",
+                failing: failing.clone(),
+                rate: 30.0,
+                weight: 1.0,
+            }),
+            Box::new(TestProvider {
+                name: "backup",
+                failing: Arc::new(AtomicBool::new(false)),
+                rate: 10.0,
+                weight: 1.0,
+            }),
+        ];
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let service = FeeEstimationService::new(providers, clock.clone())
+            .with_circuit_breaker(CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown_secs: 60,
+            })
+            .with_event_bus(bus, 3600);
+
+        for _ in 0..2 {
+            assert_eq!(service.fetch_estimates().unwrap().source, "backup");
+        }
+        let stats = service.stats();
+        assert!(stats[0].circuit_open);
+        assert_eq!(stats[0].success_rate, 0.0);
+        assert_eq!(stats[0].last_error.as_deref(), Some("connection refused"));
+        assert!(events.try_iter().any(|e| matches!(e, Event::System { .. })));
+
+        // The open circuit skips the primary even after it recovers, until the cooldown ends
+        failing.store(false, Ordering::SeqCst);
+        assert_eq!(service.fetch_estimates().unwrap().source, "backup");
+        clock.advance(61);
+        assert_eq!(service.fetch_estimates().unwrap().source, "primary");
+        assert!(!service.stats()[0].circuit_open);
+    }
+
+    #[test]
+    fn test_consensus_mode() {
+        let provider = |name, rate, weight| -> Box<dyn FeeProvider> {
+            Box::new(TestProvider {
+                name,
+                failing: Arc::new(AtomicBool::new(false)),
+                rate,
+                weight,
+            })
+        };
+        let providers = vec![
+            provider("a", 10.0, 1.0),
+            provider("b", 12.0, 3.0),
+            provider("outlier", 90.0, 1.0),
+        ];
+        let service = FeeEstimationService::new(providers, Arc::new(MockClock::new(0)))
+            .with_mode(FeeEstimationMode::Consensus);
+
+        let estimates = service.fetch_estimates().unwrap();
+        assert_eq!(estimates.source, "consensus(a, b, outlier)");
+        assert_eq!(estimates.rates.get(&1), Some(&12.0));
+        assert_eq!(estimates.rates.get(&6), Some(&6.0));
+        assert_eq!(
+            service.stats().iter().filter(|s| s.requests == 1).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_relay_fee_floor() {
+        let backend = Arc::new(crate::backend::mock::MockBackend::new());
+        let providers: Vec<Box<dyn FeeProvider>> = vec![Box::new(TestProvider {
+            name: "primary",
+            failing: Arc::new(AtomicBool::new(false)),
+            rate: 8.0,
+            weight: 1.0,
+        })];
+        let service = FeeEstimationService::new(providers, Arc::new(MockClock::new(0)))
+            .with_relay_fee_source(backend.clone());
+        assert_eq!(service.get_fee_rate(FeePriority::Medium), 4.0);
+
+        backend.set_relay_fees(RelayFees {
+            min_relay_fee: 1.0,
+            mempool_min_fee: 6.5,
+        });
+        let estimates = service.fetch_estimates().unwrap();
+        assert_eq!(estimates.rates.get(&6), Some(&6.5));
+        assert_eq!(service.get_fee_rate(FeePriority::Low), 6.5);
+    }
+
+    #[test]
+    fn test_economy_uses_history_above_floor() {
+        let clock = Arc::new(MockClock::new(0));
+        let rate = Arc::new(Mutex::new(40.0));
+
+        struct VaryingProvider(Arc<Mutex<f64>>);
+        impl FeeProvider for VaryingProvider {
+            fn name(&self) -> &str {
+                "varying"
+            }
+            fn fetch(&self) -> Result<BTreeMap<u32, f64>, String> {
+                Ok(BTreeMap::from([(24, *self.0.lock().unwrap())]))
+            }
+        }
+
+        let backend = Arc::new(crate::backend::mock::MockBackend::new());
+        let service =
+            FeeEstimationService::new(vec![Box::new(VaryingProvider(rate.clone()))], clock.clone())
+                .with_relay_fee_source(backend.clone());
+
+        // Without enough history the economy rate is the provider's slowest rate
+        assert_eq!(service.get_fee_rate(FeePriority::Economy), 40.0);
+
+        for i in 0..ECONOMY_MIN_SAMPLES {
+            *rate.lock().unwrap() = 3.0 + i as f64 * 5.0;
+            service.fetch_estimates().unwrap();
+            clock.advance(3600);
+        }
+        *rate.lock().unwrap() = 40.0;
+        service.fetch_estimates().unwrap();
+        let economy = service.get_fee_rate(FeePriority::Economy);
+        assert!(economy < service.get_fee_rate(FeePriority::Low));
+        assert_eq!(economy, 18.0);
+
+        backend.set_relay_fees(RelayFees {
+            min_relay_fee: 1.0,
+            mempool_min_fee: 25.0,
+        });
+        service.fetch_estimates().unwrap();
+        assert_eq!(service.get_fee_rate(FeePriority::Economy), 25.0);
+    }
+
+    #[test]
+    fn test_rate_for_target() {
+        let estimates = FeeEstimates::fallback(0);
+        assert_eq!(estimates.rate_for_target(1), Some(20.0));
+        assert_eq!(estimates.rate_for_target(12), Some(10.0));
+        assert_eq!(estimates.rate_for_target(1_000), Some(2.0));
+    }
+
+    #[test]
+    fn test_cache_persistence() {
+        let path = std::env::temp_dir().join(format!("fee_cache_{}.json", std::process::id()));
+        let mut cache = FeeEstimationCache::load(&path, 600);
+        assert!(cache.get_any().is_none());
+
+        cache.store(FeeEstimates {
+            rates: BTreeMap::from([(1, 12.0)]),
+            fetched_at: 1_000,
+            source: "primary".to_string(),
+            possibly_stale: false,
+        });
+
+        let service = FeeEstimationService::new(Vec::new(), Arc::new(MockClock::new(5_000)))
+            .with_cache(FeeEstimationCache::load(&path, 600));
+        let restored = service.cached_estimates();
+        assert_eq!(restored.source, "primary");
+        assert!(restored.possibly_stale);
+        let _ = fs::remove_file(&path);
+    }
+}
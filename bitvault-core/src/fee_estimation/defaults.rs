@@ -0,0 +1,22 @@
+/// Fee rates (sat/vB per confirmation target) used before any provider has answered.
+pub const FALLBACK_RATES: [(u32, f64); 4] = [(1, 20.0), (6, 10.0), (24, 5.0), (144, 2.0)];
+
+use crate::backend::RelayFees;
+
+/// Lowest fee rate ever recommended; lower provider rates are raised to it.
+pub fn min_reasonable_fee_rate() -> f64 {
+    1.0
+}
+
+/// Lowest fee rate that can be broadcast right now: [`min_reasonable_fee_rate`]
+/// raised to the node's relay and mempool floors when they are known.
+pub fn min_fee_rate_with_floor(relay_fees: Option<&RelayFees>) -> f64 {
+    relay_fees.map_or(min_reasonable_fee_rate(), |fees| {
+        fees.floor().max(min_reasonable_fee_rate())
+    })
+}
+
+/// Highest fee rate a provider may report before its answer is treated as bogus.
+pub fn max_reasonable_fee_rate() -> f64 {
+    2_000.0
+}
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+/// Rolling record of observed fee rates, used to find how cheap a transaction
+/// can be while still confirming eventually.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeHistory {
+    samples: VecDeque<(u64, f64)>,
+    window_secs: u64,
+}
+
+impl FeeHistory {
+    pub fn new(window_secs: u64) -> Self {
+        FeeHistory {
+            samples: VecDeque::new(),
+            window_secs,
+        }
+    }
+
+    /// Records a rate observed at unix time `now`, dropping samples that have
+    /// fallen out of the window.
+    pub fn record(&mut self, now: u64, rate: f64) {
+        self.samples.push_back((now, rate));
+        while let Some((at, _)) = self.samples.front() {
+            if now.saturating_sub(*at) <= self.window_secs {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The rate below which `percentile` (0.0 to 1.0) of the samples fall.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        let mut rates: Vec<f64> = self.samples.iter().map(|(_, rate)| *rate).collect();
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_by(|a, b| a.total_cmp(b));
+        let index = ((rates.len() - 1) as f64 * percentile.clamp(0.0, 1.0)).round() as usize;
+        Some(rates[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_and_percentile() {
+        let mut history = FeeHistory::new(100);
+        for (i, rate) in [50.0, 4.0, 8.0, 2.0, 6.0].iter().enumerate() {
+            history.record(i as u64 * 50, *rate);
+        }
+
+        // The first two samples are older than the window
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.percentile(0.0), Some(2.0));
+        assert_eq!(history.percentile(0.5), Some(6.0));
+        assert_eq!(FeeHistory::new(100).percentile(0.5), None);
+    }
+}
@@ -0,0 +1,333 @@
+//! Versions of every file kept in the data directory, and their migrations.
+//!
+//! Each persisted artifact is registered once with its current format
+//! version and the functions that upgrade older files. The versions found on
+//! disk are tracked in a manifest, [`MANIFEST_NAME`], next to the files rather
+//! than in per-file headers, so any file type (JSON, TOML, binary) can be
+//! versioned the same way. Startup runs [`FormatRegistry::upgrade`], which
+//! migrates old files and refuses files written by a newer release.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Manifest of on-disk format versions, in the data directory.
+pub const MANIFEST_NAME: &str = "formats.json";
+
+/// Upgrades the file at the path from one version to the next, in place.
+pub type Migration = fn(&Path) -> Result<(), String>;
+
+/// A kind of file in the data directory.
+#[derive(Clone, Debug)]
+pub struct Artifact {
+    pub name: String,
+    /// Relative to the data directory.
+    pub file_name: String,
+    pub version: u32,
+    /// Migration from each older version to the one after it.
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl Artifact {
+    /// An artifact at format `version`, starting from 1.
+    pub fn new(name: &str, file_name: &str, version: u32) -> Self {
+        Artifact {
+            name: name.to_string(),
+            file_name: file_name.to_string(),
+            version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Upgrades files at version `from` to `from + 1`.
+    pub fn with_migration(mut self, from: u32, migration: Migration) -> Self {
+        self.migrations.insert(from, migration);
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.version == 0 {
+            return Err(format!("{} format versions start at 1", self.name));
+        }
+        if let Some(missing) = (1..self.version).find(|v| !self.migrations.contains_key(v)) {
+            return Err(format!(
+                "{} has no migration from version {}",
+                self.name, missing
+            ));
+        }
+        if let Some(extra) = self.migrations.keys().find(|v| **v >= self.version) {
+            return Err(format!(
+                "{} has a migration from version {}, which is not older than {}",
+                self.name, extra, self.version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How an artifact on disk compares to the registered format.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FormatState {
+    Current,
+    /// Not written yet; it will be created in the current format.
+    Missing,
+    Outdated {
+        found: u32,
+    },
+    /// Written by a newer release; this one can't read it safely.
+    TooNew {
+        found: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactStatus {
+    pub name: String,
+    pub version: u32,
+    pub state: FormatState,
+}
+
+impl fmt::Display for ArtifactStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state {
+            FormatState::Current => write!(f, "{} is current (v{})", self.name, self.version),
+            FormatState::Missing => write!(f, "{} does not exist yet", self.name),
+            FormatState::Outdated { found } => {
+                write!(
+                    f,
+                    "{} needs upgrading from v{} to v{}",
+                    self.name, found, self.version
+                )
+            }
+            FormatState::TooNew { found } => write!(
+                f,
+                "{} was written by a newer BitVault (v{}, this release reads up to v{})",
+                self.name, found, self.version
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    versions: BTreeMap<String, u32>,
+}
+
+/// The artifacts an application persists.
+#[derive(Clone, Debug, Default)]
+pub struct FormatRegistry {
+    artifacts: BTreeMap<String, Artifact>,
+}
+
+impl FormatRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `artifact`, checking its migrations cover every older version.
+    pub fn register(&mut self, artifact: Artifact) -> Result<(), String> {
+        artifact.validate()?;
+        if self.artifacts.contains_key(&artifact.name) {
+            return Err(format!("{} is already registered", artifact.name));
+        }
+        if let Some(other) = self
+            .artifacts
+            .values()
+            .find(|a| a.file_name == artifact.file_name)
+        {
+            return Err(format!(
+                "{} and {} both use {}",
+                other.name, artifact.name, artifact.file_name
+            ));
+        }
+        self.artifacts.insert(artifact.name.clone(), artifact);
+        Ok(())
+    }
+
+    pub fn with_artifact(mut self, artifact: Artifact) -> Result<Self, String> {
+        self.register(artifact)?;
+        Ok(self)
+    }
+
+    fn load_manifest(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST_NAME);
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn save_manifest(dir: &Path, manifest: &Manifest) -> Result<(), String> {
+        let path = dir.join(MANIFEST_NAME);
+        let json = serde_json::to_string_pretty(manifest)
+            .map_err(|e| format!("Failed to serialize format manifest: {}", e))?;
+        // Written aside and renamed, so a crash never leaves half a manifest
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    // Files from before the manifest existed are taken to be version 1
+    fn status(&self, dir: &Path, manifest: &Manifest, artifact: &Artifact) -> ArtifactStatus {
+        let state = if !dir.join(&artifact.file_name).exists() {
+            FormatState::Missing
+        } else {
+            match manifest.versions.get(&artifact.name).copied().unwrap_or(1) {
+                found if found == artifact.version => FormatState::Current,
+                found if found < artifact.version => FormatState::Outdated { found },
+                found => FormatState::TooNew { found },
+            }
+        };
+        ArtifactStatus {
+            name: artifact.name.clone(),
+            version: artifact.version,
+            state,
+        }
+    }
+
+    /// The state of every registered artifact in `dir`, changing nothing.
+    pub fn check(&self, dir: &Path) -> Result<Vec<ArtifactStatus>, String> {
+        let manifest = Self::load_manifest(dir)?;
+        Ok(self
+            .artifacts
+            .values()
+            .map(|artifact| self.status(dir, &manifest, artifact))
+            .collect())
+    }
+
+    /// Migrates outdated artifacts in `dir` and records every artifact's
+    /// version. Fails before touching anything if a file is too new.
+    ///
+    /// Each file is copied to `<file>.v<version>.bak` before its first
+    /// migration and restored from the copy if a migration fails. The
+    /// manifest is updated after every artifact, so an interrupted upgrade
+    /// resumes where it stopped.
+    pub fn upgrade(&self, dir: &Path) -> Result<Vec<ArtifactStatus>, String> {
+        let mut manifest = Self::load_manifest(dir)?;
+        let statuses = self.check(dir)?;
+        let too_new: Vec<String> = statuses
+            .iter()
+            .filter(|s| matches!(s.state, FormatState::TooNew { .. }))
+            .map(|s| s.to_string())
+            .collect();
+        if !too_new.is_empty() {
+            return Err(too_new.join("; "));
+        }
+
+        for status in &statuses {
+            let artifact = &self.artifacts[&status.name];
+            if let FormatState::Outdated { found } = status.state {
+                let path = dir.join(&artifact.file_name);
+                let backup = dir.join(format!("{}.v{}.bak", artifact.file_name, found));
+                fs::copy(&path, &backup)
+                    .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+                for from in found..artifact.version {
+                    log::info!(
+                        "Upgrading {} from v{} to v{}",
+                        artifact.name,
+                        from,
+                        from + 1
+                    );
+                    if let Err(e) = (artifact.migrations[&from])(&path) {
+                        fs::copy(&backup, &path).map_err(|restore| {
+                            format!(
+                                "Failed to upgrade {} ({}) and to restore it from {}: {}",
+                                artifact.name,
+                                e,
+                                backup.display(),
+                                restore
+                            )
+                        })?;
+                        return Err(format!(
+                            "Failed to upgrade {} from v{}: {}",
+                            artifact.name, from, e
+                        ));
+                    }
+                }
+            }
+            if manifest.versions.get(&artifact.name) != Some(&artifact.version) {
+                manifest
+                    .versions
+                    .insert(artifact.name.clone(), artifact.version);
+                Self::save_manifest(dir, &manifest)?;
+            }
+        }
+        self.check(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(path: &Path, text: &str) -> Result<(), String> {
+        let mut contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        contents.push_str(text);
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_upgrade_migrates_and_refuses_newer_files() {
+        let dir = std::env::temp_dir().join(format!("bitvault_formats_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("utxos.json"), "v1").unwrap();
+
+        let registry = FormatRegistry::new()
+            .with_artifact(
+                Artifact::new("utxo-store", "utxos.json", 3)
+                    .with_migration(1, |path| append(path, "+v2"))
+                    .with_migration(2, |path| append(path, "+v3")),
+            )
+            .unwrap()
+            .with_artifact(Artifact::new("config", "config.json", 1))
+            .unwrap();
+        assert!(FormatRegistry::new()
+            .with_artifact(Artifact::new("keys", "wallet.dat", 2))
+            .is_err());
+
+        let statuses = registry.upgrade(&dir).unwrap();
+        assert!(statuses
+            .iter()
+            .all(|s| matches!(s.state, FormatState::Current | FormatState::Missing)));
+        assert_eq!(
+            fs::read_to_string(dir.join("utxos.json")).unwrap(),
+            "v1+v2+v3"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("utxos.json.v1.bak")).unwrap(),
+            "v1"
+        );
+        // Already current: nothing runs again
+        registry.upgrade(&dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("utxos.json")).unwrap(),
+            "v1+v2+v3"
+        );
+
+        // An older release sees a newer file
+        let older = FormatRegistry::new()
+            .with_artifact(Artifact::new("utxo-store", "utxos.json", 1))
+            .unwrap();
+        let err = older.upgrade(&dir).unwrap_err();
+        assert!(err.contains("newer BitVault"), "{}", err);
+
+        // A failed migration leaves the file as it was
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        let failing = FormatRegistry::new()
+            .with_artifact(
+                Artifact::new("config", "config.json", 2).with_migration(1, |path| {
+                    fs::write(path, "garbage").map_err(|e| e.to_string())?;
+                    Err("unexpected layout".to_string())
+                }),
+            )
+            .unwrap();
+        assert!(failing.upgrade(&dir).is_err());
+        assert_eq!(fs::read_to_string(dir.join("config.json")).unwrap(), "{}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::address_filter::AddressMatcher;
+use crate::backend::{BlockchainBackend, TxStatus};
+use crate::events::{Event, MessageBus};
+use crate::types::{OutPoint, Transaction, SEQUENCE_ENABLE_LOCKTIME};
+
+/// Whether `tx` opts in to BIP-125 replacement.
+pub fn signals_rbf(tx: &Transaction) -> bool {
+    tx.inputs
+        .iter()
+        .any(|input| input.sequence < SEQUENCE_ENABLE_LOCKTIME)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncomingStatus {
+    Pending,
+    Confirmed {
+        height: u32,
+    },
+    /// A conflicting transaction spending the same inputs replaced it.
+    Replaced {
+        by: String,
+        new_amount: u64,
+    },
+    /// Disappeared from the backend without a replacement being seen.
+    Dropped,
+}
+
+/// An unconfirmed payment to the wallet that is being watched.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncomingPayment {
+    pub txid: String,
+    /// Satoshis paid to wallet addresses.
+    pub amount: u64,
+    /// The sender can replace it until it confirms.
+    pub replaceable: bool,
+    pub inputs: Vec<OutPoint>,
+    pub status: IncomingStatus,
+}
+
+/// Watches incoming unconfirmed payments and raises a `SecurityAlert` when
+/// one is replaced by a transaction paying the wallet less, a typical
+/// double-spend attempt against merchants who act on unconfirmed payments.
+#[derive(Clone, Debug, Default)]
+pub struct IncomingWatcher {
+    payments: HashMap<String, IncomingPayment>,
+}
+
+impl IncomingWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, txid: &str) -> Option<&IncomingPayment> {
+        self.payments.get(txid)
+    }
+
+    /// Feeds an unconfirmed transaction from the mempool watcher. Returns the
+    /// payment it starts tracking, if it pays the wallet.
+    pub fn observe(
+        &mut self,
+        tx: &Transaction,
+        wallet: &mut AddressMatcher,
+        bus: Option<&MessageBus>,
+    ) -> Option<&IncomingPayment> {
+        let txid = tx.txid();
+        let amount: u64 = tx
+            .outputs
+            .iter()
+            .filter(|output| wallet.matches(&output.address))
+            .map(|output| output.value)
+            .sum();
+
+        for payment in self.payments.values_mut() {
+            let conflicts = payment.txid != txid
+                && payment.status == IncomingStatus::Pending
+                && tx
+                    .inputs
+                    .iter()
+                    .any(|input| payment.inputs.contains(&input.previous_output));
+            if !conflicts {
+                continue;
+            }
+            payment.status = IncomingStatus::Replaced {
+                by: txid.clone(),
+                new_amount: amount,
+            };
+            if amount < payment.amount {
+                log::warn!(
+                    "Incoming {} replaced by {} paying {} instead of {} sats",
+                    payment.txid,
+                    txid,
+                    amount,
+                    payment.amount
+                );
+                if let Some(bus) = bus {
+                    bus.publish(Event::SecurityAlert {
+                        message: format!(
+                            "An incoming payment of {} sats was replaced by one paying {} sats. \
+                             This may be a double-spend attempt; wait for confirmation.",
+                            payment.amount, amount
+                        ),
+                    });
+                }
+            }
+        }
+
+        if amount == 0 || self.payments.contains_key(&txid) {
+            return None;
+        }
+        let payment = IncomingPayment {
+            txid: txid.clone(),
+            amount,
+            replaceable: signals_rbf(tx),
+            inputs: tx
+                .inputs
+                .iter()
+                .map(|input| input.previous_output.clone())
+                .collect(),
+            status: IncomingStatus::Pending,
+        };
+        Some(self.payments.entry(txid).or_insert(payment))
+    }
+
+    /// Refreshes pending payments from `backend`.
+    pub fn update(&mut self, backend: &dyn BlockchainBackend) -> Result<(), String> {
+        for payment in self.payments.values_mut() {
+            if payment.status != IncomingStatus::Pending {
+                continue;
+            }
+            match backend.transaction_status(&payment.txid)? {
+                TxStatus::Confirmed { height, .. } => {
+                    payment.status = IncomingStatus::Confirmed { height }
+                }
+                TxStatus::NotFound => payment.status = IncomingStatus::Dropped,
+                TxStatus::Unconfirmed => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets payments that reached a final state.
+    pub fn prune(&mut self) {
+        self.payments
+            .retain(|_, payment| payment.status == IncomingStatus::Pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TxIn, TxOut};
+
+    fn payment(sequence: u32, to_wallet: u64) -> Transaction {
+        Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new("aa", 0),
+                sequence,
+            }],
+            outputs: vec![
+                TxOut {
+                    address: "bcrt1qours".to_string(),
+                    value: to_wallet,
+                },
+                TxOut {
+                    address: "bcrt1qsender".to_string(),
+                    value: 100_000 - to_wallet,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_replacement_paying_less_alerts() {
+        let mut wallet = AddressMatcher::new(["bcrt1qours".to_string()], 0.01);
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut watcher = IncomingWatcher::new();
+
+        let original = payment(0xffff_fffd, 50_000);
+        assert!(
+            watcher
+                .observe(&original, &mut wallet, Some(&bus))
+                .unwrap()
+                .replaceable
+        );
+
+        let replacement = payment(0xffff_fffd, 0);
+        assert!(watcher
+            .observe(&replacement, &mut wallet, Some(&bus))
+            .is_none());
+        assert_eq!(
+            watcher.get(&original.txid()).unwrap().status,
+            IncomingStatus::Replaced {
+                by: replacement.txid(),
+                new_amount: 0
+            }
+        );
+        assert!(matches!(events.try_recv(), Ok(Event::SecurityAlert { .. })));
+    }
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::events::{Event, MessageBus};
+use crate::payment_uri;
+use crate::signer::{SignedTransaction, TransactionSigner};
+use crate::types::{
+    Network, Transaction, TxIn, TxOut, Utxo, LOCKTIME_THRESHOLD, SEQUENCE_ENABLE_LOCKTIME,
+};
+use crate::utxo_selection::dust::DustPolicy;
+use crate::utxo_selection::estimate_vsize;
+
+// Shares are expressed in basis points of the recoverable amount
+const TOTAL_SHARES_BPS: u32 = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heir {
+    pub name: String,
+    pub address: String,
+    /// Share of the funds in basis points (1/100 of a percent).
+    pub share_bps: u32,
+}
+
+/// Who inherits and how long after the kit is made they can claim.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InheritancePlan {
+    pub heirs: Vec<Heir>,
+    /// Blocks from kit creation until the recovery transaction becomes valid.
+    pub delay_blocks: u32,
+    /// Start reminding the owner to refresh this many blocks before unlock.
+    pub reminder_blocks: u32,
+    /// Fee rate in sat/vB; pick generously, it is fixed when the kit is signed.
+    pub fee_rate: u64,
+}
+
+impl InheritancePlan {
+    pub fn validate(&self, network: Network) -> Result<(), String> {
+        if self.heirs.is_empty() {
+            return Err("Inheritance plan has no heirs".to_string());
+        }
+        let total: u32 = self.heirs.iter().map(|h| h.share_bps).sum();
+        if total != TOTAL_SHARES_BPS {
+            return Err(format!(
+                "Heir shares add up to {}%, not 100%",
+                total as f64 / 100.0
+            ));
+        }
+        for heir in &self.heirs {
+            payment_uri::check_address(&heir.address, network)
+                .map_err(|e| format!("Invalid address for {}: {}", heir.name, e))?;
+        }
+        if self.delay_blocks == 0 || self.reminder_blocks >= self.delay_blocks {
+            return Err("Reminder window must be shorter than the delay".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A signed transaction paying the heirs that only becomes valid at `unlock_height`.
+///
+/// As long as the owner is around, they refresh the kit before it unlocks by
+/// spending the coins it uses (invalidating it) and preparing a new one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryKit {
+    pub signed: SignedTransaction,
+    pub created_at_height: u32,
+    pub unlock_height: u32,
+    pub heirs: Vec<Heir>,
+    pub fee: u64,
+}
+
+/// Builds and signs a recovery transaction sweeping `utxos` to the heirs with
+/// a height locktime `plan.delay_blocks` after `tip_height`.
+pub fn prepare_recovery_kit(
+    plan: &InheritancePlan,
+    utxos: &[Utxo],
+    tip_height: u32,
+    network: Network,
+    signer: &dyn TransactionSigner,
+) -> Result<RecoveryKit, String> {
+    plan.validate(network)?;
+    if utxos.is_empty() {
+        return Err("No coins to include in the recovery kit".to_string());
+    }
+
+    let unlock_height = tip_height
+        .checked_add(plan.delay_blocks)
+        .filter(|h| *h < LOCKTIME_THRESHOLD)
+        .ok_or_else(|| "Delay is too long for a height locktime".to_string())?;
+
+    let mut prevouts = utxos.to_vec();
+    prevouts.sort_by(|a, b| a.outpoint.cmp(&b.outpoint));
+    let total: u64 = prevouts.iter().map(|u| u.value).sum();
+    let fee = estimate_vsize(prevouts.len(), plan.heirs.len()) * plan.fee_rate;
+    let distributable = total
+        .checked_sub(fee)
+        .ok_or_else(|| format!("Coins ({} sats) don't cover the fee ({} sats)", total, fee))?;
+
+    // In u128, as sats times basis points can overflow u64
+    let mut outputs: Vec<TxOut> = plan
+        .heirs
+        .iter()
+        .map(|heir| {
+            let value = distributable as u128 * heir.share_bps as u128 / TOTAL_SHARES_BPS as u128;
+            Ok(TxOut {
+                address: heir.address.clone(),
+                value: u64::try_from(value)
+                    .map_err(|_| format!("Share for {} is too large", heir.address))?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    // Rounding leftovers go to the first heir
+    let assigned: u64 = outputs.iter().map(|o| o.value).sum();
+    outputs[0].value += distributable - assigned;
+    let dust = DustPolicy::for_network(network);
+    if let Some(small) = outputs
+        .iter()
+        .find(|o| dust.is_dust(o.value, plan.fee_rate))
+    {
+        return Err(format!("Share for {} would be dust", small.address));
+    }
+
+    let transaction = Transaction {
+        lock_time: unlock_height,
+        inputs: prevouts
+            .iter()
+            .map(|u| TxIn {
+                previous_output: u.outpoint.clone(),
+                sequence: SEQUENCE_ENABLE_LOCKTIME,
+            })
+            .collect(),
+        outputs,
+    };
+    let signed = signer.sign(&transaction, &prevouts)?;
+    if !signed.is_complete() {
+        return Err("Signer did not sign every input".to_string());
+    }
+
+    Ok(RecoveryKit {
+        signed,
+        created_at_height: tip_height,
+        unlock_height,
+        heirs: plan.heirs.clone(),
+        fee,
+    })
+}
+
+impl RecoveryKit {
+    pub fn blocks_remaining(&self, tip_height: u32) -> u32 {
+        self.unlock_height.saturating_sub(tip_height)
+    }
+
+    /// Checks whether the kit needs refreshing and publishes an
+    /// `InheritanceRefreshDue` event if so: the unlock is within
+    /// `reminder_blocks`, or the wallet's coins no longer match the kit.
+    pub fn check_refresh(
+        &self,
+        tip_height: u32,
+        reminder_blocks: u32,
+        wallet_utxos: &[Utxo],
+        bus: Option<&MessageBus>,
+    ) -> bool {
+        let blocks_remaining = self.blocks_remaining(tip_height);
+        let spent = self.signed.transaction.inputs.iter().any(|input| {
+            !wallet_utxos
+                .iter()
+                .any(|u| u.outpoint == input.previous_output)
+        });
+        let uncovered = wallet_utxos.iter().any(|u| {
+            !self
+                .signed
+                .transaction
+                .inputs
+                .iter()
+                .any(|input| input.previous_output == u.outpoint)
+        });
+
+        let reason = if blocks_remaining <= reminder_blocks {
+            format!("Recovery kit unlocks in {} blocks", blocks_remaining)
+        } else if spent {
+            "Coins in the recovery kit were spent".to_string()
+        } else if uncovered {
+            "The wallet has coins not covered by the recovery kit".to_string()
+        } else {
+            return false;
+        };
+
+        if let Some(bus) = bus {
+            bus.publish(Event::InheritanceRefreshDue {
+                blocks_remaining,
+                reason,
+            });
+        }
+        true
+    }
+
+    /// Writes the kit and human-readable instructions for the heirs into `dir`.
+    pub fn export_bundle(&self, dir: &Path) -> Result<Vec<PathBuf>, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let kit_path = dir.join("recovery-kit.json");
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize recovery kit: {}", e))?;
+        fs::write(&kit_path, json).map_err(|e| format!("Failed to write recovery kit: {}", e))?;
+
+        let shares: Vec<String> = self
+            .heirs
+            .iter()
+            .zip(&self.signed.transaction.outputs)
+            .map(|(heir, output)| {
+                format!(
+                    "  - {}: {} sats to {}",
+                    heir.name, output.value, output.address
+                )
+            })
+            .collect();
+        let instructions = format!(
+            "BitVault inheritance recovery kit\n\n\
+             This kit contains a signed Bitcoin transaction that pays:\n{}\n\n\
+             It cannot be broadcast before block height {}. After that, load\n\
+             recovery-kit.json into BitVault (or give the transaction to any Bitcoin\n\
+             node or block explorer) and broadcast it.\n\n\
+             If broadcasting fails with \"missing inputs\", the owner has replaced\n\
+             this kit with a newer one.\n",
+            shares.join("\n"),
+            self.unlock_height
+        );
+        let instructions_path = dir.join("INSTRUCTIONS.txt");
+        fs::write(&instructions_path, instructions)
+            .map_err(|e| format!("Failed to write instructions: {}", e))?;
+
+        Ok(vec![kit_path, instructions_path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+
+    struct DummySigner;
+
+    impl TransactionSigner for DummySigner {
+        fn id(&self) -> &str {
+            "dummy"
+        }
+
+        fn sign(&self, tx: &Transaction, _prevouts: &[Utxo]) -> Result<SignedTransaction, String> {
+            Ok(SignedTransaction {
+                transaction: tx.clone(),
+                witnesses: vec![vec!["00".to_string()]; tx.inputs.len()],
+            })
+        }
+    }
+
+    fn utxo(vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new("aa", vout),
+            address: "bcrt1qowner".to_string(),
+            value,
+            confirmations: 10,
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_prepare_and_refresh() {
+        let plan = InheritancePlan {
+            heirs: vec![
+                Heir {
+                    name: "Alex".to_string(),
+                    address: "bcrt1qheiralex".to_string(),
+                    share_bps: 7_000,
+                },
+                Heir {
+                    name: "Sam".to_string(),
+                    address: "bcrt1qheirsam".to_string(),
+                    share_bps: 3_000,
+                },
+            ],
+            delay_blocks: 52_560,
+            reminder_blocks: 4_320,
+            fee_rate: 10,
+        };
+        let utxos = vec![utxo(0, 600_000), utxo(1, 400_000)];
+        let kit =
+            prepare_recovery_kit(&plan, &utxos, 800_000, Network::Regtest, &DummySigner).unwrap();
+
+        let tx = &kit.signed.transaction;
+        assert_eq!(tx.lock_time, 852_560);
+        assert!(tx
+            .inputs
+            .iter()
+            .all(|i| i.sequence == SEQUENCE_ENABLE_LOCKTIME));
+        assert_eq!(tx.output_value() + kit.fee, 1_000_000);
+        assert_eq!(tx.outputs[1].value, (1_000_000 - kit.fee) * 3 / 10);
+
+        // Sats times basis points past u64 still split exactly
+        let whale = [utxo(0, 3_000_000_000_000_000)];
+        let large =
+            prepare_recovery_kit(&plan, &whale, 800_000, Network::Regtest, &DummySigner).unwrap();
+        let outputs = &large.signed.transaction.outputs;
+        assert_eq!(
+            outputs[1].value,
+            (3_000_000_000_000_000 - large.fee) / 10 * 3
+        );
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        assert!(!kit.check_refresh(800_100, plan.reminder_blocks, &utxos, Some(&bus)));
+        assert!(kit.check_refresh(849_000, plan.reminder_blocks, &utxos, Some(&bus)));
+        assert!(kit.check_refresh(800_100, plan.reminder_blocks, &utxos[..1], None));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::InheritanceRefreshDue {
+                blocks_remaining: 3_560,
+                ..
+            })
+        ));
+    }
+}
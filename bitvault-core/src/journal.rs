@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::metadata::WalletMetadata;
+use crate::types::OutPoint;
+
+/// A wallet state change recorded before it is applied.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalOp {
+    FreezeUtxo {
+        outpoint: OutPoint,
+        frozen: bool,
+    },
+    /// Sets or, with `None`, removes a label.
+    SetLabel {
+        reference: String,
+        label: Option<String>,
+    },
+    /// Saves or, with `None`, deletes a draft.
+    SaveDraft {
+        id: String,
+        draft: Option<String>,
+    },
+}
+
+impl JournalOp {
+    /// Applies the change. Every op sets a final value, so replaying one that
+    /// was already applied is harmless.
+    pub fn apply(&self, metadata: &mut WalletMetadata) {
+        match self {
+            JournalOp::FreezeUtxo { outpoint, frozen } => {
+                if *frozen {
+                    metadata.frozen.insert(outpoint.clone());
+                } else {
+                    metadata.frozen.remove(outpoint);
+                }
+            }
+            JournalOp::SetLabel { reference, label } => match label {
+                Some(label) => {
+                    metadata.labels.insert(reference.clone(), label.clone());
+                }
+                None => {
+                    metadata.labels.remove(reference);
+                }
+            },
+            JournalOp::SaveDraft { id, draft } => match draft {
+                Some(draft) => {
+                    metadata.drafts.insert(id.clone(), draft.clone());
+                }
+                None => {
+                    metadata.drafts.remove(id);
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Record {
+    Begin { seq: u64, op: JournalOp },
+    Commit { seq: u64 },
+    Abort { seq: u64 },
+}
+
+// Each line is "<checksum> <json>", the checksum being the first 8 bytes of
+// the SHA-256 of the JSON, hex encoded
+fn checksum(json: &str) -> String {
+    hex::encode(&Sha256::digest(json.as_bytes())[..8])
+}
+
+/// What was found in the journal on startup.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecovery {
+    /// Operations begun but neither committed nor aborted, in order. Replay
+    /// them with [`Journal::commit`] or roll back with [`Journal::abort`].
+    pub pending: Vec<(u64, JournalOp)>,
+    /// The last line was cut short by a crash and was dropped.
+    pub torn_tail: bool,
+    /// Descriptions of lines that failed their checksum; nothing after the
+    /// first corrupt line is trusted.
+    pub corrupted: Vec<String>,
+}
+
+/// Write-ahead journal for wallet state changes.
+///
+/// Callers `begin` an op (durably written), apply and persist it, then
+/// `commit`. After a crash, [`Journal::open`] reports the ops that may or may
+/// not have reached the wallet state.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    next_seq: u64,
+    open_seqs: Vec<u64>,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> Result<(Journal, JournalRecovery), String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read journal: {}", e)),
+        };
+
+        let mut recovery = JournalRecovery::default();
+        let mut next_seq = 0;
+        let lines: Vec<&str> = contents.split_terminator('\n').collect();
+        for (i, line) in lines.iter().enumerate() {
+            let record = line
+                .split_once(' ')
+                .filter(|(sum, json)| *sum == checksum(json))
+                .and_then(|(_, json)| serde_json::from_str::<Record>(json).ok());
+            let Some(record) = record else {
+                if i + 1 == lines.len() && !contents.ends_with('\n') {
+                    recovery.torn_tail = true;
+                } else {
+                    log::error!("Journal {} is corrupt at line {}", path.display(), i + 1);
+                    recovery
+                        .corrupted
+                        .push(format!("Line {} failed its checksum", i + 1));
+                }
+                break;
+            };
+            match record {
+                Record::Begin { seq, op } => {
+                    next_seq = next_seq.max(seq + 1);
+                    recovery.pending.push((seq, op));
+                }
+                Record::Commit { seq } | Record::Abort { seq } => {
+                    recovery.pending.retain(|(pending, _)| *pending != seq);
+                }
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open journal: {}", e))?;
+        let mut journal = Journal {
+            path: path.to_path_buf(),
+            file,
+            next_seq,
+            open_seqs: recovery.pending.iter().map(|(seq, _)| *seq).collect(),
+        };
+        if recovery.torn_tail || !recovery.corrupted.is_empty() {
+            // Rewrite only the trusted prefix so new records follow valid ones
+            journal.rewrite(&recovery.pending)?;
+        }
+        Ok((journal, recovery))
+    }
+
+    fn append(&mut self, record: &Record) -> Result<(), String> {
+        let json = serde_json::to_string(record)
+            .map_err(|e| format!("Failed to serialize journal record: {}", e))?;
+        writeln!(self.file, "{} {}", checksum(&json), json)
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("Failed to write journal: {}", e))
+    }
+
+    fn rewrite(&mut self, pending: &[(u64, JournalOp)]) -> Result<(), String> {
+        self.file =
+            File::create(&self.path).map_err(|e| format!("Failed to rewrite journal: {}", e))?;
+        for (seq, op) in pending {
+            self.append(&Record::Begin {
+                seq: *seq,
+                op: op.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Durably records `op` before it is applied and returns its sequence number.
+    pub fn begin(&mut self, op: JournalOp) -> Result<u64, String> {
+        let seq = self.next_seq;
+        self.append(&Record::Begin { seq, op })?;
+        self.next_seq += 1;
+        self.open_seqs.push(seq);
+        Ok(seq)
+    }
+
+    /// Marks `seq` as applied and persisted.
+    pub fn commit(&mut self, seq: u64) -> Result<(), String> {
+        self.close(seq, Record::Commit { seq })
+    }
+
+    /// Marks `seq` as rolled back: it must not be, or no longer is, applied.
+    pub fn abort(&mut self, seq: u64) -> Result<(), String> {
+        self.close(seq, Record::Abort { seq })
+    }
+
+    fn close(&mut self, seq: u64, record: Record) -> Result<(), String> {
+        if !self.open_seqs.contains(&seq) {
+            return Err(format!("Journal entry {} is not open", seq));
+        }
+        self.append(&record)?;
+        self.open_seqs.retain(|open| *open != seq);
+        Ok(())
+    }
+
+    /// Empties the journal once nothing is open, so it doesn't grow forever.
+    pub fn checkpoint(&mut self) -> Result<(), String> {
+        if !self.open_seqs.is_empty() {
+            return Err("Cannot checkpoint with open journal entries".to_string());
+        }
+        self.rewrite(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_after_crash() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_journal_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (mut journal, recovery) = Journal::open(&path).unwrap();
+        assert_eq!(recovery, JournalRecovery::default());
+        let label = JournalOp::SetLabel {
+            reference: "bcrt1qours".to_string(),
+            label: Some("Savings".to_string()),
+        };
+        let done = journal.begin(label).unwrap();
+        journal.commit(done).unwrap();
+        let freeze = JournalOp::FreezeUtxo {
+            outpoint: OutPoint::new("aa", 0),
+            frozen: true,
+        };
+        journal.begin(freeze.clone()).unwrap();
+        drop(journal);
+
+        // Simulate a crash in the middle of writing the next record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"0123 {\"Beg").unwrap();
+
+        let (mut journal, recovery) = Journal::open(&path).unwrap();
+        assert!(recovery.torn_tail);
+        assert!(recovery.corrupted.is_empty());
+        assert_eq!(recovery.pending, vec![(1, freeze.clone())]);
+
+        let mut metadata = WalletMetadata::default();
+        freeze.apply(&mut metadata);
+        journal.commit(1).unwrap();
+        journal.checkpoint().unwrap();
+        assert!(metadata.frozen.contains(&OutPoint::new("aa", 0)));
+
+        fs::write(&path, "deadbeef {\"Commit\":{\"seq\":0}}\n").unwrap();
+        let (_, recovery) = Journal::open(&path).unwrap();
+        assert_eq!(recovery.corrupted.len(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
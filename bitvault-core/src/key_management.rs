@@ -0,0 +1,464 @@
+use bip39::Mnemonic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+use zxcvbn::time_estimates::CrackTimeSeconds;
+
+use crate::logging;
+
+pub mod ceremony;
+pub mod rng_health;
+pub mod wordlist;
+
+use wordlist::{detect_language, nfkd, WordlistLanguage};
+
+/// Word counts accepted by BIP-39 (128 to 256 bits of entropy in 32-bit steps).
+pub const SUPPORTED_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// Amount of entropy used when generating a new mnemonic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EntropySize {
+    #[default]
+    Bits128,
+    Bits160,
+    Bits192,
+    Bits224,
+    Bits256,
+}
+
+impl EntropySize {
+    pub fn from_word_count(word_count: usize) -> Option<Self> {
+        match word_count {
+            12 => Some(EntropySize::Bits128),
+            15 => Some(EntropySize::Bits160),
+            18 => Some(EntropySize::Bits192),
+            21 => Some(EntropySize::Bits224),
+            24 => Some(EntropySize::Bits256),
+            _ => None,
+        }
+    }
+
+    pub fn byte_len(self) -> usize {
+        match self {
+            EntropySize::Bits128 => 16,
+            EntropySize::Bits160 => 20,
+            EntropySize::Bits192 => 24,
+            EntropySize::Bits224 => 28,
+            EntropySize::Bits256 => 32,
+        }
+    }
+
+    pub fn word_count(self) -> usize {
+        self.byte_len() * 8 * 33 / 32 / 11
+    }
+}
+
+/// Maximum edit distance for a wordlist entry to be offered as a typo suggestion.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Result of checking a single word typed during an interactive restore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WordCheck {
+    /// The word is in the wordlist.
+    Valid,
+    /// The word is a prefix of one or more wordlist entries (still being typed).
+    Incomplete(Vec<&'static str>),
+    /// The word is not in the wordlist; closest matches are included.
+    Unknown(Vec<&'static str>),
+}
+
+/// Generates a new English mnemonic with the requested amount of entropy.
+pub fn generate_mnemonic(
+    size: EntropySize,
+    extra_entropy: Option<&[u8]>,
+) -> Result<Mnemonic, String> {
+    generate_mnemonic_in(WordlistLanguage::English, size, extra_entropy)
+}
+
+/// Generates a new mnemonic in `language` with the requested amount of entropy.
+///
+/// When `extra_entropy` is given (for example dice rolls parsed with
+/// [`parse_dice_rolls`]) it is hashed together with OS randomness, so weak or
+/// biased user input can add entropy but never reduce it below the OS source.
+pub fn generate_mnemonic_in(
+    language: WordlistLanguage,
+    size: EntropySize,
+    extra_entropy: Option<&[u8]>,
+) -> Result<Mnemonic, String> {
+    rng_health::check_os_rng().map_err(|e| {
+        rng_health::report_failure(&e, None);
+        format!("Refusing to generate keys: {}", e)
+    })?;
+
+    let mut entropy = [0u8; 32];
+    getrandom::fill(&mut entropy)
+        .map_err(|e| format!("Failed to generate random entropy: {}", e))?;
+
+    if let Some(extra) = extra_entropy {
+        let mut hasher = Sha256::new();
+        hasher.update(b"bitvault/mnemonic-entropy/v1");
+        hasher.update(entropy);
+        hasher.update((extra.len() as u64).to_be_bytes());
+        hasher.update(extra);
+        let mut digest: [u8; 32] = hasher.finalize().into();
+        entropy.copy_from_slice(&digest);
+        digest.zeroize();
+    }
+
+    let result = Mnemonic::from_entropy_in(language.bip39(), &entropy[..size.byte_len()])
+        .map_err(|e| format!("Failed to create mnemonic: {}", e));
+    entropy.zeroize();
+
+    logging::trace("key_management", || {
+        format!(
+            "Generated {}-word {} mnemonic (extra entropy: {})",
+            size.word_count(),
+            language,
+            extra_entropy.is_some()
+        )
+    });
+    result
+}
+
+/// Parses a sequence of six-sided dice rolls (digits 1-6, whitespace ignored).
+///
+/// Each roll carries about 2.58 bits; 50 rolls cover 128 bits and 100 rolls cover 256 bits.
+pub fn parse_dice_rolls(rolls: &str) -> Result<Vec<u8>, String> {
+    rolls
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .enumerate()
+        .map(|(i, c)| match c {
+            '1'..='6' => Ok(c as u8 - b'0'),
+            _ => Err(format!(
+                "Invalid dice roll #{}: '{}' (expected 1-6)",
+                i + 1,
+                c
+            )),
+        })
+        .collect()
+}
+
+/// Normalizes user input into lowercase NFKD words separated by single spaces.
+pub fn normalize_phrase(phrase: &str) -> String {
+    nfkd(phrase)
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the number of entropy bits encoded by a mnemonic of `word_count` words.
+pub fn entropy_bits_for_word_count(word_count: usize) -> Option<usize> {
+    if SUPPORTED_WORD_COUNTS.contains(&word_count) {
+        Some(word_count * 11 * 32 / 33)
+    } else {
+        None
+    }
+}
+
+pub fn is_valid_word(word: &str, language: WordlistLanguage) -> bool {
+    language
+        .bip39()
+        .find_word(&normalize_phrase(word))
+        .is_some()
+}
+
+/// Checks a single word against the wordlist, for word-by-word validation in
+/// restore forms. [`detect_language`] on the words so far gives `language`.
+pub fn check_word(word: &str, language: WordlistLanguage) -> WordCheck {
+    let word = normalize_phrase(word);
+    if language.bip39().find_word(&word).is_some() {
+        return WordCheck::Valid;
+    }
+
+    let prefixed = language.bip39().words_by_prefix(&word);
+    if !word.is_empty() && !prefixed.is_empty() {
+        return WordCheck::Incomplete(prefixed.iter().take(5).copied().collect());
+    }
+
+    WordCheck::Unknown(suggest_words(&word, language, 3))
+}
+
+/// Suggests up to `max` wordlist entries closest to `word`, for correcting typos.
+pub fn suggest_words(word: &str, language: WordlistLanguage, max: usize) -> Vec<&'static str> {
+    let word = normalize_phrase(word);
+    if word.is_empty() {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<(usize, &'static str)> = language
+        .bip39()
+        .word_list()
+        .iter()
+        .map(|candidate| (edit_distance(&word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .collect();
+
+    // Sort by distance, then alphabetically so suggestions are stable
+    candidates.sort();
+    candidates.into_iter().take(max).map(|(_, w)| w).collect()
+}
+
+/// Validates a full mnemonic phrase in whichever language it is written.
+pub fn validate_mnemonic(phrase: &str) -> Result<Mnemonic, String> {
+    validate_mnemonic_in(phrase, detect_language(phrase)?)
+}
+
+/// Validates a full mnemonic phrase: word count, wordlist membership and checksum.
+///
+/// The error message names the first offending word and its position so restore
+/// flows can point the user at it.
+pub fn validate_mnemonic_in(phrase: &str, language: WordlistLanguage) -> Result<Mnemonic, String> {
+    let normalized = normalize_phrase(phrase);
+    let words: Vec<&str> = normalized.split(' ').filter(|w| !w.is_empty()).collect();
+
+    if !SUPPORTED_WORD_COUNTS.contains(&words.len()) {
+        return Err(format!(
+            "Invalid word count: {} (expected 12, 15, 18, 21 or 24)",
+            words.len()
+        ));
+    }
+
+    for (i, word) in words.iter().enumerate() {
+        if !is_valid_word(word, language) {
+            let suggestions = suggest_words(word, language, 3);
+            return if suggestions.is_empty() {
+                Err(format!(
+                    "Word #{} ('{}') is not in the {} wordlist",
+                    i + 1,
+                    word,
+                    language
+                ))
+            } else {
+                Err(format!(
+                    "Word #{} ('{}') is not in the {} wordlist. Did you mean: {}?",
+                    i + 1,
+                    word,
+                    language,
+                    suggestions.join(", ")
+                ))
+            };
+        }
+    }
+
+    logging::trace("key_management", || {
+        format!("Verifying checksum of {}-word mnemonic", words.len())
+    });
+    Mnemonic::parse_in_normalized(language.bip39(), &normalized).map_err(|e| match e {
+        bip39::Error::InvalidChecksum => {
+            "Invalid checksum: one or more words are wrong or out of order".to_string()
+        }
+        e => format!("Invalid mnemonic: {}", e),
+    })
+}
+
+/// Derives the 64-byte BIP-39 seed, refusing phrases that fail checksum verification.
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], String> {
+    let mnemonic = validate_mnemonic(phrase)?;
+    // BIP-39 hashes the passphrase in NFKD too, or an accented passphrase
+    // would give a different wallet depending on the keyboard it was typed on
+    Ok(mnemonic.to_seed_normalized(&nfkd(passphrase)))
+}
+
+/// Rough time an offline attacker needs to guess a passphrase, assuming a slow
+/// password hash (10k guesses per second).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CrackTimeClass {
+    Instant,
+    Minutes,
+    Hours,
+    Days,
+    Years,
+    Centuries,
+}
+
+impl CrackTimeClass {
+    fn from_seconds(seconds: f64) -> Self {
+        const HOUR: f64 = 3_600.0;
+        const DAY: f64 = 24.0 * HOUR;
+        const YEAR: f64 = 365.0 * DAY;
+        match seconds {
+            s if s < 60.0 => CrackTimeClass::Instant,
+            s if s < HOUR => CrackTimeClass::Minutes,
+            s if s < DAY => CrackTimeClass::Hours,
+            s if s < YEAR => CrackTimeClass::Days,
+            s if s < 100.0 * YEAR => CrackTimeClass::Years,
+            _ => CrackTimeClass::Centuries,
+        }
+    }
+}
+
+/// Strength estimate for a wallet encryption passphrase.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PassphraseStrength {
+    /// 0 (trivially guessable) to 4 (very strong).
+    pub score: u8,
+    /// Estimated entropy in bits, from the number of guesses needed.
+    pub entropy_bits: f64,
+    pub crack_time: CrackTimeClass,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Estimates passphrase strength with zxcvbn's pattern matching (dictionary
+/// words, keyboard walks, dates, repeats). `user_inputs` are strings the
+/// passphrase shouldn't be based on, such as the wallet name.
+pub fn estimate_passphrase_strength(passphrase: &str, user_inputs: &[&str]) -> PassphraseStrength {
+    match zxcvbn::zxcvbn(passphrase, user_inputs) {
+        Ok(estimate) => {
+            let feedback = estimate.feedback().as_ref();
+            PassphraseStrength {
+                score: estimate.score(),
+                entropy_bits: estimate.guesses_log10() * std::f64::consts::LOG2_10,
+                crack_time: CrackTimeClass::from_seconds(
+                    match estimate.crack_times().offline_slow_hashing_1e4_per_second() {
+                        CrackTimeSeconds::Integer(seconds) => seconds as f64,
+                        CrackTimeSeconds::Float(seconds) => seconds,
+                    },
+                ),
+                warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+                suggestions: feedback
+                    .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+                    .unwrap_or_default(),
+            }
+        }
+        // zxcvbn refuses empty input
+        Err(_) => PassphraseStrength {
+            score: 0,
+            entropy_bits: 0.0,
+            crack_time: CrackTimeClass::Instant,
+            warning: Some("Passphrase is empty".to_string()),
+            suggestions: Vec::new(),
+        },
+    }
+}
+
+/// Rejects passphrases scoring below `min_score`, with zxcvbn's feedback as the error.
+pub fn check_passphrase_strength(
+    passphrase: &str,
+    user_inputs: &[&str],
+    min_score: u8,
+) -> Result<PassphraseStrength, String> {
+    let strength = estimate_passphrase_strength(passphrase, user_inputs);
+    if strength.score >= min_score {
+        return Ok(strength);
+    }
+
+    let mut message = format!(
+        "Passphrase is too weak (score {} of 4, at least {} required)",
+        strength.score, min_score
+    );
+    if let Some(warning) = &strength.warning {
+        message.push_str(&format!(". {}", warning));
+    }
+    if let Some(suggestion) = strength.suggestions.first() {
+        message.push_str(&format!(". {}", suggestion));
+    }
+    Err(message)
+}
+
+// Levenshtein distance over the characters of the NFKD forms, as the
+// wordlists are stored
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = nfkd(a).chars().collect();
+    let b: Vec<char> = nfkd(b).chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_mnemonic() {
+        let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        assert!(validate_mnemonic(phrase).is_ok());
+        assert!(validate_mnemonic(&phrase.to_uppercase()).is_ok());
+
+        let bad_checksum = ["abandon"; 12].join(" ");
+        assert!(validate_mnemonic(&bad_checksum)
+            .unwrap_err()
+            .contains("checksum"));
+
+        let typo = "legal winer thank year wave sausage worth useful legal winner thank yellow";
+        let err = validate_mnemonic(typo).unwrap_err();
+        assert!(err.contains("Word #2"));
+        assert!(suggest_words("winer", WordlistLanguage::English, 5).contains(&"winner"));
+
+        assert!(validate_mnemonic("legal winner thank").is_err());
+    }
+
+    #[test]
+    fn test_generate_mnemonic() {
+        for words in SUPPORTED_WORD_COUNTS {
+            let size = EntropySize::from_word_count(words).unwrap();
+            assert_eq!(size.word_count(), words);
+            let mnemonic = generate_mnemonic(size, None).unwrap();
+            assert_eq!(mnemonic.word_count(), words);
+        }
+
+        let dice = parse_dice_rolls("1 2 3 4 5 6 6 5 4 3 2 1").unwrap();
+        let mnemonic = generate_mnemonic(EntropySize::Bits256, Some(&dice)).unwrap();
+        assert!(validate_mnemonic(&mnemonic.to_string()).is_ok());
+        assert!(parse_dice_rolls("1237").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_strength() {
+        let weak = estimate_passphrase_strength("password1", &[]);
+        assert_eq!(weak.crack_time, CrackTimeClass::Instant);
+        assert!(weak.score <= 1);
+        assert!(check_passphrase_strength("password1", &[], 3).is_err());
+        assert!(check_passphrase_strength("bitvault2024", &["bitvault"], 3).is_err());
+
+        let strong = check_passphrase_strength("correct-quartz-lantern-mosaic-97", &[], 3).unwrap();
+        assert!(strong.entropy_bits > weak.entropy_bits);
+        assert!(strong.crack_time >= CrackTimeClass::Years);
+        assert_eq!(estimate_passphrase_strength("", &[]).score, 0);
+    }
+
+    #[test]
+    fn test_check_word() {
+        let english = WordlistLanguage::English;
+        assert_eq!(check_word("abandon", english), WordCheck::Valid);
+        assert!(matches!(
+            check_word("aban", english),
+            WordCheck::Incomplete(_)
+        ));
+        match check_word("abandn", english) {
+            WordCheck::Unknown(suggestions) => assert_eq!(suggestions[0], "abandon"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Typed precomposed, stored decomposed
+        let spanish = WordlistLanguage::Spanish;
+        assert_eq!(check_word("\u{e1}baco", spanish), WordCheck::Valid);
+        assert_eq!(
+            check_word("abandon", spanish),
+            WordCheck::Unknown(Vec::new())
+        );
+        match check_word("abeha", spanish) {
+            WordCheck::Unknown(suggestions) => assert_eq!(suggestions[0], "abeja"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // One mistyped kana is one edit
+        assert_eq!(edit_distance("あいこくしん", "あいこくしな"), 1);
+        assert_eq!(
+            suggest_words("あいこくしな", WordlistLanguage::Japanese, 1),
+            ["あいこくしん"]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Key ceremonies: generating a phrase from entropy made by hand, such as
+//! dice rolls or coin flips, on an offline machine.
+//!
+//! The hand-made entropy is checked for obvious mistakes (too few rolls, a
+//! loaded die, a lazily repeated pattern) and then hashed together with OS
+//! entropy, so it can add to the OS source but never weaken it. The report
+//! records what was done and which checks passed, never the inputs or the
+//! phrase, so it can be kept for audit.
+
+use bip39::Mnemonic;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use zeroize::Zeroize;
+
+use super::{generate_mnemonic, parse_dice_rolls, EntropySize};
+
+pub const REPORT_VERSION: u32 = 1;
+
+// Chi-squared critical values at p = 0.001, so an honest source fails about
+// once in a thousand ceremonies, by degrees of freedom (faces - 1)
+const CHI_SQUARED_DICE: f64 = 20.515;
+const CHI_SQUARED_COIN: f64 = 10.828;
+
+// A same-face run this long in a few hundred honest inputs is less likely
+// than one in a thousand
+const MAX_DICE_RUN: usize = 8;
+const MAX_COIN_RUN: usize = 20;
+
+/// Hand-made entropy, as typed in.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntropyInput {
+    /// Six-sided dice, digits 1-6.
+    DiceRolls(String),
+    /// `H`/`T` or `1`/`0`.
+    CoinFlips(String),
+}
+
+impl EntropyInput {
+    fn kind(&self) -> &'static str {
+        match self {
+            EntropyInput::DiceRolls(_) => "dice rolls",
+            EntropyInput::CoinFlips(_) => "coin flips",
+        }
+    }
+
+    fn faces(&self) -> u8 {
+        match self {
+            EntropyInput::DiceRolls(_) => 6,
+            EntropyInput::CoinFlips(_) => 2,
+        }
+    }
+
+    /// Each input as a face index from 0.
+    fn parse(&self) -> Result<Vec<u8>, String> {
+        match self {
+            EntropyInput::DiceRolls(rolls) => Ok(parse_dice_rolls(rolls)?
+                .into_iter()
+                .map(|r| r - 1)
+                .collect()),
+            EntropyInput::CoinFlips(flips) => flips
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .enumerate()
+                .map(|(i, c)| match c.to_ascii_uppercase() {
+                    'H' | '1' => Ok(1),
+                    'T' | '0' => Ok(0),
+                    _ => Err(format!(
+                        "Invalid coin flip #{}: '{}' (expected H or T)",
+                        i + 1,
+                        c
+                    )),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CeremonyCheck {
+    pub name: String,
+    pub passed: bool,
+    /// What was required, without any statistic that would hint at the
+    /// inputs.
+    pub requirement: String,
+}
+
+/// What a ceremony did, free of secrets.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CeremonyReport {
+    pub version: u32,
+    /// Unix time the ceremony ran.
+    pub timestamp: u64,
+    pub word_count: usize,
+    pub input_kind: String,
+    pub input_count: usize,
+    /// Entropy the inputs carry if fair, in bits.
+    pub input_bits: u32,
+    pub checks: Vec<CeremonyCheck>,
+    /// Whether OS entropy passed its health check and was mixed in.
+    pub os_entropy: bool,
+}
+
+impl CeremonyReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// Plain-text report; the same ceremony always renders the same text.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "BitVault key ceremony report v{}", self.version);
+        let _ = writeln!(text, "Time: {}", self.timestamp);
+        let _ = writeln!(text, "Phrase: {} words", self.word_count);
+        let _ = writeln!(
+            text,
+            "Input: {} {} ({} bits if fair)",
+            self.input_count, self.input_kind, self.input_bits
+        );
+        for check in &self.checks {
+            let _ = writeln!(
+                text,
+                "[{}] {}: {}",
+                if check.passed { "PASS" } else { "FAIL" },
+                check.name,
+                check.requirement
+            );
+        }
+        let _ = writeln!(
+            text,
+            "OS entropy: {}",
+            if self.os_entropy {
+                "healthy, mixed in"
+            } else {
+                "not used"
+            }
+        );
+        text
+    }
+}
+
+fn chi_squared(counts: &[usize], total: usize) -> f64 {
+    let expected = total as f64 / counts.len() as f64;
+    counts
+        .iter()
+        .map(|c| (*c as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+/// Checks `input` is fit to seed a phrase of `size`, without generating
+/// anything.
+pub fn check_input(size: EntropySize, input: &EntropyInput) -> Result<CeremonyReport, String> {
+    let mut values = input.parse()?;
+    let faces = input.faces() as usize;
+    let bits_per_input = (faces as f64).log2();
+    let needed_bits = size.byte_len() * 8;
+    let needed = (needed_bits as f64 / bits_per_input).ceil() as usize;
+    let (chi_limit, max_run) = match input {
+        EntropyInput::DiceRolls(_) => (CHI_SQUARED_DICE, MAX_DICE_RUN),
+        EntropyInput::CoinFlips(_) => (CHI_SQUARED_COIN, MAX_COIN_RUN),
+    };
+
+    let mut counts = vec![0; faces];
+    for v in &values {
+        counts[*v as usize] += 1;
+    }
+    // Steps between consecutive inputs catch cycles like 123456123456,
+    // which have perfectly even face counts
+    let mut steps = vec![0; faces];
+    for pair in values.windows(2) {
+        steps[(pair[1] as usize + faces - pair[0] as usize) % faces] += 1;
+    }
+    let mut longest = 0;
+    let mut run = 0;
+    for (i, v) in values.iter().enumerate() {
+        run = if i > 0 && values[i - 1] == *v {
+            run + 1
+        } else {
+            1
+        };
+        longest = longest.max(run);
+    }
+
+    let enough = values.len() >= needed;
+    let checks = vec![
+        CeremonyCheck {
+            name: "Count".to_string(),
+            passed: enough,
+            requirement: format!(
+                "at least {} {} for {} bits",
+                needed,
+                input.kind(),
+                needed_bits
+            ),
+        },
+        CeremonyCheck {
+            name: "Face frequency".to_string(),
+            passed: enough && chi_squared(&counts, values.len()) < chi_limit,
+            requirement: format!("chi-squared below {} (p = 0.001)", chi_limit),
+        },
+        CeremonyCheck {
+            name: "Patterns".to_string(),
+            passed: enough && chi_squared(&steps, values.len() - 1) < chi_limit,
+            requirement: format!("chi-squared of successive steps below {}", chi_limit),
+        },
+        CeremonyCheck {
+            name: "Runs".to_string(),
+            passed: longest < max_run,
+            requirement: format!("no face {} or more times in a row", max_run),
+        },
+    ];
+    let report = CeremonyReport {
+        version: REPORT_VERSION,
+        timestamp: 0,
+        word_count: size.word_count(),
+        input_kind: input.kind().to_string(),
+        input_count: values.len(),
+        input_bits: (values.len() as f64 * bits_per_input) as u32,
+        checks,
+        os_entropy: false,
+    };
+    values.zeroize();
+    counts.zeroize();
+    steps.zeroize();
+    Ok(report)
+}
+
+/// Runs a ceremony at unix time `now`: checks `input`, then generates a
+/// phrase from it and OS entropy. Refuses if any check fails.
+pub fn run_ceremony(
+    size: EntropySize,
+    input: &EntropyInput,
+    now: u64,
+) -> Result<(Mnemonic, CeremonyReport), String> {
+    let mut report = check_input(size, input)?;
+    report.timestamp = now;
+    if let Some(failed) = report.checks.iter().find(|c| !c.passed) {
+        return Err(format!(
+            "The {} failed the {} check ({}); redo them",
+            input.kind(),
+            failed.name.to_lowercase(),
+            failed.requirement
+        ));
+    }
+    // generate_mnemonic health-checks the OS source before mixing it in.
+    // Tagged with the kind, so dice "1" and a coin "1" never hash alike
+    let mut extra = input.parse()?;
+    extra.insert(0, input.faces());
+    let mnemonic = generate_mnemonic(size, Some(&extra));
+    extra.zeroize();
+    report.os_entropy = true;
+    Ok((mnemonic?, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DICE: &str = "5315263415656113444131535425223346232531352641512445425223534513634214431125135465533561553456151153";
+    const COINS: &str = "THTTTHHTHHHHTHTHTTHTTTHHTTHTTTHTHTHTHTHTTHHHHTTHTHTTHHHHTTHHHTTHHTTTHHTHHHTHHTHHTHHTHHHHTHTHHHTTHHHTTHHHHTTHTHTTHTHTTTHHHHHHHHTH";
+
+    #[test]
+    fn test_ceremony_checks_and_report() {
+        let dice = EntropyInput::DiceRolls(DICE.to_string());
+        let (mnemonic, report) = run_ceremony(EntropySize::Bits256, &dice, 1_700_000_000).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+        assert!(report.passed() && report.os_entropy);
+        assert_eq!(report.input_bits, 258);
+        // Same ceremony, same report, and nothing from the rolls in it
+        let (other, again) = run_ceremony(EntropySize::Bits256, &dice, 1_700_000_000).unwrap();
+        assert_ne!(other, mnemonic);
+        assert_eq!(again.to_text(), report.to_text());
+        assert!(!report.to_text().contains(&DICE[..8]));
+        assert!(report.to_text().contains("[PASS] Patterns"));
+
+        let coins = EntropyInput::CoinFlips(COINS.to_string());
+        assert!(check_input(EntropySize::Bits128, &coins).unwrap().passed());
+        assert!(!check_input(EntropySize::Bits256, &coins).unwrap().passed());
+    }
+
+    #[test]
+    fn test_rejects_bad_inputs() {
+        let failed = |input: EntropyInput| -> Vec<String> {
+            check_input(EntropySize::Bits128, &input)
+                .unwrap()
+                .checks
+                .into_iter()
+                .filter(|c| !c.passed)
+                .map(|c| c.name)
+                .collect()
+        };
+        assert_eq!(
+            failed(EntropyInput::DiceRolls("123456".repeat(9))),
+            vec!["Patterns"]
+        );
+        assert_eq!(
+            failed(EntropyInput::DiceRolls("6".repeat(60))),
+            vec!["Face frequency", "Patterns", "Runs"]
+        );
+        assert_eq!(
+            failed(EntropyInput::CoinFlips("HT".repeat(70))),
+            vec!["Patterns"]
+        );
+        assert_eq!(
+            failed(EntropyInput::DiceRolls(DICE[..40].to_string())),
+            vec!["Count", "Face frequency", "Patterns"]
+        );
+
+        let err = run_ceremony(
+            EntropySize::Bits128,
+            &EntropyInput::DiceRolls("123456".repeat(9)),
+            0,
+        )
+        .unwrap_err();
+        assert!(err.contains("failed the patterns check"), "{}", err);
+        assert!(check_input(
+            EntropySize::Bits128,
+            &EntropyInput::CoinFlips("HTX".to_string())
+        )
+        .is_err());
+    }
+}
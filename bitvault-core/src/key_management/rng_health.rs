@@ -0,0 +1,202 @@
+//! Sanity checks on the random number generator before it makes keys.
+//!
+//! No test can prove output random, but these catch the failures seen in
+//! practice on unusual platforms: a missing source, one stuck on a single
+//! value, or one replaying the same output (e.g. a badly seeded emulator).
+
+use std::fmt;
+use zeroize::Zeroize;
+
+use crate::events::{Event, MessageBus};
+#[cfg(feature = "native")]
+use crate::platform::{
+    capabilities::{Capability, Support},
+    PlatformProvider,
+};
+
+const SAMPLES: usize = 8;
+const SAMPLE_LEN: usize = 32;
+
+/// 256 random bytes take about 162 distinct values; far fewer means a
+/// heavily biased source.
+const MIN_DISTINCT_BYTES: usize = 96;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RngHealthError {
+    /// No usable entropy source.
+    Unavailable(String),
+    /// A sample was one byte value repeated.
+    Stuck,
+    /// Two samples were identical.
+    Repeated,
+    LowDiversity {
+        distinct: usize,
+    },
+}
+
+impl fmt::Display for RngHealthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RngHealthError::Unavailable(reason) => {
+                write!(f, "no secure random number source: {}", reason)
+            }
+            RngHealthError::Stuck => {
+                write!(f, "the random number generator returned constant output")
+            }
+            RngHealthError::Repeated => {
+                write!(f, "the random number generator repeated its output")
+            }
+            RngHealthError::LowDiversity { distinct } => write!(
+                f,
+                "the random number generator looks biased ({} distinct byte values in {} bytes)",
+                distinct,
+                SAMPLES * SAMPLE_LEN
+            ),
+        }
+    }
+}
+
+/// Draws a few samples from `fill` and checks they look random.
+pub fn check_rng(
+    mut fill: impl FnMut(&mut [u8]) -> Result<(), String>,
+) -> Result<(), RngHealthError> {
+    let mut samples = [[0u8; SAMPLE_LEN]; SAMPLES];
+    let result = check_samples(&mut fill, &mut samples);
+    samples.zeroize();
+    result
+}
+
+fn check_samples(
+    fill: &mut impl FnMut(&mut [u8]) -> Result<(), String>,
+    samples: &mut [[u8; SAMPLE_LEN]; SAMPLES],
+) -> Result<(), RngHealthError> {
+    for sample in samples.iter_mut() {
+        fill(sample).map_err(RngHealthError::Unavailable)?;
+        if sample.iter().all(|b| *b == sample[0]) {
+            return Err(RngHealthError::Stuck);
+        }
+    }
+    for (i, sample) in samples.iter().enumerate() {
+        if samples[i + 1..].contains(sample) {
+            return Err(RngHealthError::Repeated);
+        }
+    }
+    let mut seen = [false; 256];
+    for byte in samples.iter().flatten() {
+        seen[*byte as usize] = true;
+    }
+    let distinct = seen.iter().filter(|s| **s).count();
+    if distinct < MIN_DISTINCT_BYTES {
+        return Err(RngHealthError::LowDiversity { distinct });
+    }
+    Ok(())
+}
+
+/// [`check_rng`] on the OS generator keys are made from.
+pub fn check_os_rng() -> Result<(), RngHealthError> {
+    check_rng(|buf| getrandom::fill(buf).map_err(|e| e.to_string()))
+}
+
+/// Logs a failed check as a security event, also published on `bus`.
+pub fn report_failure(error: &RngHealthError, bus: Option<&MessageBus>) {
+    let message = format!("Key generation refused: {}", error);
+    log::error!(target: "security", "{}", message);
+    if let Some(bus) = bus {
+        bus.publish(Event::SecurityAlert { message });
+    }
+}
+
+/// Checks that `provider` reports an OS entropy source and that it passes
+/// [`check_rng`], reporting any failure.
+#[cfg(feature = "native")]
+pub fn check_platform(
+    provider: &dyn PlatformProvider,
+    bus: Option<&MessageBus>,
+) -> Result<(), RngHealthError> {
+    let result = match provider.capability(Capability::OsEntropy) {
+        Support::Unavailable { reason } => Err(RngHealthError::Unavailable(reason)),
+        Support::Available => check_os_rng(),
+    };
+    if let Err(e) = &result {
+        report_failure(e, bus);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_broken_generators() {
+        assert_eq!(check_os_rng(), Ok(()));
+        assert_eq!(
+            check_rng(|buf| {
+                buf.fill(0);
+                Ok(())
+            }),
+            Err(RngHealthError::Stuck)
+        );
+        assert_eq!(
+            check_rng(|buf| {
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = i as u8;
+                }
+                Ok(())
+            }),
+            Err(RngHealthError::Repeated)
+        );
+        // A counter never repeats but only covers a few byte values
+        let mut counter = 0u8;
+        let low = check_rng(|buf| {
+            counter += 1;
+            for (i, b) in buf.iter_mut().enumerate() {
+                *b = counter.wrapping_add(i as u8 % 4);
+            }
+            Ok(())
+        });
+        assert!(matches!(low, Err(RngHealthError::LowDiversity { .. })));
+        assert_eq!(
+            check_rng(|_| Err("ENOSYS".to_string())),
+            Err(RngHealthError::Unavailable("ENOSYS".to_string()))
+        );
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_platform_without_entropy_refuses() {
+        use crate::platform::Urgency;
+
+        struct NoEntropy;
+
+        impl PlatformProvider for NoEntropy {
+            fn name(&self) -> &str {
+                "emulator"
+            }
+
+            fn notify(&self, _title: &str, _body: &str, _urgency: Urgency) -> Result<(), String> {
+                Ok(())
+            }
+
+            fn capability(&self, _capability: Capability) -> Support {
+                Support::unavailable("no /dev/urandom")
+            }
+        }
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let err = check_platform(&NoEntropy, Some(&bus)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no secure random number source: no /dev/urandom"
+        );
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::SecurityAlert { message }) if message.contains("Key generation refused")
+        ));
+        assert_eq!(
+            check_platform(&crate::platform::HeadlessPlatform, None),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! The BIP-39 wordlists beyond English.
+//!
+//! Phrases are compared in NFKD form, as BIP-39 requires, so a word typed
+//! with precomposed accents ("ñ") matches the list's decomposed one ("n"
+//! plus a combining tilde), and Japanese phrases written with ideographic
+//! spaces read like any other.
+
+use bip39::{Language, Mnemonic};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+use super::normalize_phrase;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WordlistLanguage {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl WordlistLanguage {
+    /// Every language, in the order detection prefers them.
+    pub const ALL: [WordlistLanguage; 10] = [
+        WordlistLanguage::English,
+        WordlistLanguage::ChineseSimplified,
+        WordlistLanguage::ChineseTraditional,
+        WordlistLanguage::Czech,
+        WordlistLanguage::French,
+        WordlistLanguage::Italian,
+        WordlistLanguage::Japanese,
+        WordlistLanguage::Korean,
+        WordlistLanguage::Portuguese,
+        WordlistLanguage::Spanish,
+    ];
+
+    pub fn bip39(self) -> Language {
+        match self {
+            WordlistLanguage::English => Language::English,
+            WordlistLanguage::ChineseSimplified => Language::SimplifiedChinese,
+            WordlistLanguage::ChineseTraditional => Language::TraditionalChinese,
+            WordlistLanguage::Czech => Language::Czech,
+            WordlistLanguage::French => Language::French,
+            WordlistLanguage::Italian => Language::Italian,
+            WordlistLanguage::Japanese => Language::Japanese,
+            WordlistLanguage::Korean => Language::Korean,
+            WordlistLanguage::Portuguese => Language::Portuguese,
+            WordlistLanguage::Spanish => Language::Spanish,
+        }
+    }
+
+    pub fn from_bip39(language: Language) -> Self {
+        WordlistLanguage::ALL
+            .into_iter()
+            .find(|l| l.bip39() == language)
+            .unwrap_or_default()
+    }
+
+    /// The language's name for itself, for pickers.
+    pub fn native_name(self) -> &'static str {
+        match self {
+            WordlistLanguage::English => "English",
+            WordlistLanguage::ChineseSimplified => "简体中文",
+            WordlistLanguage::ChineseTraditional => "繁體中文",
+            WordlistLanguage::Czech => "Čeština",
+            WordlistLanguage::French => "Français",
+            WordlistLanguage::Italian => "Italiano",
+            WordlistLanguage::Japanese => "日本語",
+            WordlistLanguage::Korean => "한국어",
+            WordlistLanguage::Portuguese => "Português",
+            WordlistLanguage::Spanish => "Español",
+        }
+    }
+
+    /// Joins words for display: Japanese phrases use ideographic spaces.
+    pub fn join(self, words: &[&str]) -> String {
+        let separator = match self {
+            WordlistLanguage::Japanese => "\u{3000}",
+            _ => " ",
+        };
+        words.join(separator)
+    }
+}
+
+impl fmt::Display for WordlistLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WordlistLanguage::ChineseSimplified => "Chinese (Simplified)",
+            WordlistLanguage::ChineseTraditional => "Chinese (Traditional)",
+            other => return write!(f, "{:?}", other),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// `text` in NFKD form, the form wordlists, phrases and passphrases are
+/// compared and hashed in.
+pub fn nfkd(text: &str) -> String {
+    text.nfkd().collect()
+}
+
+/// The wordlist `phrase` is most likely written in, for restoring without
+/// asking. Works on partial or mistyped phrases too, picking the list with
+/// the most of its words.
+///
+/// Some lists share words (English and French, and most of the two Chinese
+/// lists). When a whole phrase fits several, the first whose checksum
+/// verifies wins; the seed only depends on the text, so the choice doesn't
+/// change the wallet.
+pub fn detect_language(phrase: &str) -> Result<WordlistLanguage, String> {
+    let normalized = normalize_phrase(phrase);
+    let words: Vec<&str> = normalized.split(' ').filter(|w| !w.is_empty()).collect();
+    let matches = |language: WordlistLanguage| {
+        words
+            .iter()
+            .filter(|w| language.bip39().find_word(w).is_some())
+            .count()
+    };
+    let best = WordlistLanguage::ALL
+        .into_iter()
+        .map(matches)
+        .max()
+        .unwrap_or(0);
+    if best == 0 {
+        return Err("None of the words are in a BIP-39 wordlist".to_string());
+    }
+    let candidates: Vec<WordlistLanguage> = WordlistLanguage::ALL
+        .into_iter()
+        .filter(|l| matches(*l) == best)
+        .collect();
+    Ok(candidates
+        .iter()
+        .copied()
+        .find(|l| Mnemonic::parse_in_normalized(l.bip39(), &normalized).is_ok())
+        .unwrap_or(candidates[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_management::validate_mnemonic;
+
+    #[test]
+    fn test_detects_language_across_normalization_forms() {
+        for language in [
+            WordlistLanguage::Japanese,
+            WordlistLanguage::Spanish,
+            WordlistLanguage::French,
+            WordlistLanguage::Korean,
+        ] {
+            let mnemonic = Mnemonic::from_entropy_in(language.bip39(), &[7u8; 16]).unwrap();
+            let words: Vec<&str> = mnemonic.words().collect();
+            // As typed on most keyboards: precomposed, not as stored
+            let typed: String = language.join(&words).nfc().collect();
+            assert_eq!(detect_language(&typed), Ok(language));
+            let restored = validate_mnemonic(&typed).unwrap();
+            assert_eq!(restored.to_entropy(), vec![7u8; 16]);
+            assert_eq!(restored.to_string(), mnemonic.to_string());
+        }
+
+        let english = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        assert_eq!(detect_language(english), Ok(WordlistLanguage::English));
+        assert_eq!(
+            detect_language("legal winer thank"),
+            Ok(WordlistLanguage::English)
+        );
+        assert!(detect_language("qqqq zzzz xxxx").is_err());
+        assert_eq!(nfkd("ñ"), "n\u{303}");
+    }
+}
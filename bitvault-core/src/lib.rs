@@ -1,4 +1,74 @@
+pub mod address_filter;
+pub mod api_tokens;
+pub mod approval;
+#[cfg(feature = "native")]
+pub mod audit;
+pub mod auth_throttle;
+pub mod backend;
+#[cfg(feature = "native")]
+pub mod backup_sync;
+pub mod backup_verification;
+pub mod clock;
+pub mod config_manager;
+pub mod confirmation_tracking;
+pub mod connection_mode;
 pub mod crypto;
+#[cfg(feature = "native")]
+pub mod diagnostics;
+pub mod events;
+#[cfg(feature = "native")]
+pub mod exchange_rates;
+pub mod explorer;
+pub mod export;
+#[cfg(feature = "faucet")]
+pub mod faucet;
+pub mod fee_estimation;
+#[cfg(feature = "native")]
+pub mod formats;
+pub mod incoming;
+pub mod inheritance;
+#[cfg(feature = "native")]
+pub mod journal;
+pub mod key_management;
+pub mod localization;
+pub mod logging;
+pub mod metadata;
+pub mod network_status;
+pub mod notifier;
+#[cfg(feature = "native")]
+pub mod observer;
+pub mod ownership_proof;
+pub mod payment_uri;
+pub mod permissions;
+#[cfg(feature = "native")]
+pub mod platform;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+pub mod progress;
+pub mod qr;
+#[cfg(feature = "native")]
+pub mod scheduler;
+pub mod setup;
+#[cfg(feature = "native")]
+pub mod shutdown;
+pub mod signer;
+#[cfg(feature = "native")]
+pub mod startup;
+#[cfg(feature = "native")]
+pub mod sync;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod tx_builder;
+#[cfg(feature = "native")]
+pub mod tx_history;
+pub mod types;
+pub mod utxo_index;
+pub mod utxo_selection;
+pub mod vault;
+pub mod version;
+pub mod wallet_manager;
+#[cfg(feature = "native")]
+pub mod wipe;
 
 pub fn placeholder() -> &'static str {
     "BitVault Core Library"
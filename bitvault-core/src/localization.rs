@@ -0,0 +1,247 @@
+//! Date and time formatting shared by every frontend, so transaction
+//! timestamps read the same in each of them.
+
+#[cfg(feature = "native")]
+use crate::platform::PlatformProvider;
+
+/// A unix timestamp broken into calendar fields at some UTC offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CivilDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl CivilDateTime {
+    /// Converts `timestamp` to local time at `utc_offset_secs` east of UTC.
+    pub fn from_timestamp(timestamp: u64, utc_offset_secs: i32) -> Self {
+        let local = timestamp as i64 + i64::from(utc_offset_secs);
+        let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+        let secs = local.rem_euclid(86_400) as u32;
+        CivilDateTime {
+            year,
+            month,
+            day,
+            hour: secs / 3_600,
+            minute: secs % 3_600 / 60,
+            second: secs % 60,
+        }
+    }
+}
+
+// Proleptic Gregorian date from days since the unix epoch
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateOrder {
+    YearMonthDay,
+    MonthDayYear,
+    DayMonthYear,
+}
+
+/// How absolute dates and times are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTimeFormat {
+    pub order: DateOrder,
+    pub separator: char,
+    pub hour12: bool,
+}
+
+impl Default for DateTimeFormat {
+    fn default() -> Self {
+        DateTimeFormat::ISO
+    }
+}
+
+impl DateTimeFormat {
+    /// `2024-03-09 14:05`
+    pub const ISO: DateTimeFormat = DateTimeFormat {
+        order: DateOrder::YearMonthDay,
+        separator: '-',
+        hour12: false,
+    };
+
+    /// Format conventionally used for a BCP 47 or POSIX locale tag such as
+    /// `en-US` or `de_DE.UTF-8`. Unknown locales get [`ISO`](Self::ISO).
+    pub fn for_locale(tag: &str) -> Self {
+        let tag = tag.split('.').next().unwrap_or_default().replace('_', "-");
+        let mut parts = tag.split('-');
+        let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let region = parts.next().unwrap_or_default().to_ascii_uppercase();
+
+        let (order, separator, hour12) = match (language.as_str(), region.as_str()) {
+            ("en", "US") => (DateOrder::MonthDayYear, '/', true),
+            ("en", "GB" | "IE" | "AU" | "NZ") => (DateOrder::DayMonthYear, '/', false),
+            ("en", "IN") => (DateOrder::DayMonthYear, '/', true),
+            ("de" | "ru" | "pl" | "cs" | "fi" | "nb" | "tr", _) => {
+                (DateOrder::DayMonthYear, '.', false)
+            }
+            ("fr" | "es" | "it" | "pt", _) => (DateOrder::DayMonthYear, '/', false),
+            ("nl", _) => (DateOrder::DayMonthYear, '-', false),
+            ("ja" | "zh" | "ko", _) => (DateOrder::YearMonthDay, '/', false),
+            _ => return DateTimeFormat::ISO,
+        };
+        DateTimeFormat {
+            order,
+            separator,
+            hour12,
+        }
+    }
+
+    pub fn format_date(&self, time: &CivilDateTime) -> String {
+        let s = self.separator;
+        match self.order {
+            DateOrder::YearMonthDay => {
+                format!("{:04}{s}{:02}{s}{:02}", time.year, time.month, time.day)
+            }
+            DateOrder::MonthDayYear => {
+                format!("{:02}{s}{:02}{s}{:04}", time.month, time.day, time.year)
+            }
+            DateOrder::DayMonthYear => {
+                format!("{:02}{s}{:02}{s}{:04}", time.day, time.month, time.year)
+            }
+        }
+    }
+
+    pub fn format_time(&self, time: &CivilDateTime) -> String {
+        if self.hour12 {
+            let hour = match time.hour % 12 {
+                0 => 12,
+                h => h,
+            };
+            let suffix = if time.hour < 12 { "AM" } else { "PM" };
+            format!("{}:{:02} {}", hour, time.minute, suffix)
+        } else {
+            format!("{:02}:{:02}", time.hour, time.minute)
+        }
+    }
+
+    pub fn format_date_time(&self, time: &CivilDateTime) -> String {
+        format!("{} {}", self.format_date(time), self.format_time(time))
+    }
+}
+
+/// "just now", "5 minutes ago", "yesterday", "in 2 hours"...
+pub fn format_relative(timestamp: u64, now: u64) -> String {
+    let (delta, future) = if timestamp > now {
+        (timestamp - now, true)
+    } else {
+        (now - timestamp, false)
+    };
+    let (count, unit) = match delta {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (delta / 60, "minute"),
+        3_600..=86_399 => (delta / 3_600, "hour"),
+        86_400..=172_799 => return (if future { "tomorrow" } else { "yesterday" }).to_string(),
+        172_800..=2_591_999 => (delta / 86_400, "day"),
+        2_592_000..=31_535_999 => (delta / 2_592_000, "month"),
+        _ => (delta / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    if future {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+/// Formats timestamps for display: relative when recent, absolute otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeDisplay {
+    pub format: DateTimeFormat,
+    pub utc_offset_secs: i32,
+    /// Timestamps at most this old are shown relative to now.
+    pub relative_within_secs: u64,
+}
+
+impl Default for TimeDisplay {
+    fn default() -> Self {
+        TimeDisplay {
+            format: DateTimeFormat::ISO,
+            utc_offset_secs: 0,
+            relative_within_secs: 7 * 86_400,
+        }
+    }
+}
+
+impl TimeDisplay {
+    /// Uses the locale and timezone the operating system reports, falling
+    /// back to ISO dates and UTC.
+    #[cfg(feature = "native")]
+    pub fn from_platform(provider: &dyn PlatformProvider) -> Self {
+        TimeDisplay {
+            format: provider
+                .locale()
+                .map(|tag| DateTimeFormat::for_locale(&tag))
+                .unwrap_or_default(),
+            utc_offset_secs: provider.utc_offset_secs().unwrap_or(0),
+            ..Default::default()
+        }
+    }
+
+    pub fn local(&self, timestamp: u64) -> CivilDateTime {
+        CivilDateTime::from_timestamp(timestamp, self.utc_offset_secs)
+    }
+
+    pub fn absolute(&self, timestamp: u64) -> String {
+        self.format.format_date_time(&self.local(timestamp))
+    }
+
+    pub fn format(&self, timestamp: u64, now: u64) -> String {
+        if timestamp.abs_diff(now) <= self.relative_within_secs {
+            format_relative(timestamp, now)
+        } else {
+            self.absolute(timestamp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_formats_and_offsets() {
+        // 2023-11-14 22:13:20 UTC
+        let timestamp = 1_700_000_000;
+        let berlin = CivilDateTime::from_timestamp(timestamp, 3_600);
+        assert_eq!(
+            DateTimeFormat::for_locale("de_DE.UTF-8").format_date_time(&berlin),
+            "14.11.2023 23:13"
+        );
+        let tokyo = CivilDateTime::from_timestamp(timestamp, 9 * 3_600);
+        assert_eq!(DateTimeFormat::ISO.format_date(&tokyo), "2023-11-15");
+        let new_york = CivilDateTime::from_timestamp(timestamp, -5 * 3_600);
+        assert_eq!(
+            DateTimeFormat::for_locale("en-US").format_date_time(&new_york),
+            "11/14/2023 5:13 PM"
+        );
+    }
+
+    #[test]
+    fn test_relative_and_cutoff() {
+        let now = 1_700_000_000;
+        assert_eq!(format_relative(now - 30, now), "just now");
+        assert_eq!(format_relative(now - 2 * 3_600, now), "2 hours ago");
+        assert_eq!(format_relative(now - 90_000, now), "yesterday");
+        assert_eq!(format_relative(now + 300, now), "in 5 minutes");
+
+        let display = TimeDisplay::default();
+        assert_eq!(display.format(now - 3 * 86_400, now), "3 days ago");
+        assert_eq!(display.format(now - 30 * 86_400, now), "2023-10-15 22:13");
+    }
+}
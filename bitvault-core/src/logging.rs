@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::{OnceLock, RwLock};
+
+use crate::config_manager::ConfigManager;
+use crate::events::{Event, MessageBus};
+use crate::key_management::wordlist::{nfkd, WordlistLanguage};
+
+/// Replacement text for redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+// Hex or base64 strings at least this long are treated as keys, salts or ciphertext
+const MIN_SECRET_LEN: usize = 16;
+
+// This many consecutive wordlist entries are treated as (part of) a mnemonic
+const MIN_MNEMONIC_RUN: usize = 3;
+
+/// Masks values that look like secrets: long hex or base64 strings and runs of
+/// BIP-39 words. Used for every diagnostic that may include user or key data.
+pub fn redact(message: &str) -> String {
+    // Japanese phrases are separated by ideographic spaces
+    let tokens: Vec<&str> = message.split([' ', '\u{3000}']).collect();
+    let mut output: Vec<&str> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+
+    while i < tokens.len() {
+        // Collapse runs of mnemonic words into a single marker
+        let run = tokens[i..]
+            .iter()
+            .take_while(|t| is_wordlist_token(t))
+            .count();
+        if run >= MIN_MNEMONIC_RUN {
+            output.push(REDACTED);
+            i += run;
+            continue;
+        }
+
+        if looks_like_secret(tokens[i]) {
+            output.push(REDACTED);
+        } else {
+            output.push(tokens[i]);
+        }
+        i += 1;
+    }
+
+    output.join(" ")
+}
+
+/// Emits a redacted diagnostic at trace level when the `trace` feature is enabled.
+///
+/// The message is built lazily so disabled builds never format sensitive values.
+/// Diagnostics always go through `log`, never stdout.
+#[cfg(feature = "trace")]
+pub fn trace<F: FnOnce() -> String>(target: &str, message: F) {
+    log::trace!(target: "bitvault_core::trace", "[{}] {}", target, redact(&message()));
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn trace<F: FnOnce() -> String>(_target: &str, _message: F) {}
+
+// In any BIP-39 wordlist, which are stored in NFKD form
+fn is_wordlist_token(token: &str) -> bool {
+    let word = token.trim_matches(|c: char| !c.is_alphabetic());
+    if word.is_empty() {
+        return false;
+    }
+    let word = nfkd(word).to_lowercase();
+    WordlistLanguage::ALL
+        .into_iter()
+        .any(|language| language.bip39().find_word(&word).is_some())
+}
+
+fn looks_like_secret(token: &str) -> bool {
+    let token = token.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    if token.len() < MIN_SECRET_LEN {
+        return false;
+    }
+
+    let is_hex = token.chars().all(|c| c.is_ascii_hexdigit());
+    // Base64 salts and keys mix digits and letters; plain long words don't
+    let is_base64 = token
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '=')
+        && token.chars().any(|c| c.is_ascii_digit())
+        && token.chars().any(|c| c.is_ascii_alphabetic());
+    is_hex || is_base64
+}
+
+/// Config key holding the [`LogConfig`].
+pub const LOG_CONFIG_KEY: &str = "logging";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn to_filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Log levels: one for everything, and overrides for single modules.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogConfig {
+    pub default_level: LogLevel,
+    /// Levels by module path, such as `utxo_selection`, `tx_builder::split`
+    /// or `bitvault_core::sync`. Paths are also looked up inside
+    /// `bitvault_core`, so its crate name can be left out.
+    #[serde(default)]
+    pub modules: BTreeMap<String, LogLevel>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            default_level: LogLevel::Info,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+// Whether `target` is `module` or inside it
+fn in_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+// Length of the full path `module` names when that contains `target`, so
+// longer paths are more specific whether or not the crate was written out
+fn matched_len(target: &str, module: &str) -> Option<usize> {
+    if in_module(target, module) {
+        return Some(module.len());
+    }
+    let expanded = format!("bitvault_core::{}", module);
+    in_module(target, &expanded).then_some(expanded.len())
+}
+
+// `module` as a config key: trimmed, without a trailing `::`
+fn module_path(module: &str) -> Result<&str, String> {
+    let path = module.trim().trim_end_matches("::");
+    if path.is_empty() || path.split("::").any(|part| part.is_empty()) {
+        return Err(format!("Invalid module path: {}", module));
+    }
+    Ok(path)
+}
+
+impl LogConfig {
+    /// The level for records from `target`: the most specific module
+    /// containing it, else the default.
+    pub fn level_for(&self, target: &str) -> LogLevel {
+        self.modules
+            .iter()
+            .filter_map(|(module, level)| Some((matched_len(target, module)?, *level)))
+            .max_by_key(|(len, _)| *len)
+            .map_or(self.default_level, |(_, level)| level)
+    }
+
+    /// The most verbose level anything is logged at.
+    pub fn max_level(&self) -> LogLevel {
+        self.modules
+            .values()
+            .copied()
+            .fold(self.default_level, LogLevel::max)
+    }
+
+    /// The config stored under [`LOG_CONFIG_KEY`], the default when unset.
+    pub fn from_config(config: &ConfigManager) -> Result<Self, String> {
+        match config.get(LOG_CONFIG_KEY) {
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid {} setting: {}", LOG_CONFIG_KEY, e)),
+            None => Ok(LogConfig::default()),
+        }
+    }
+
+    pub fn store_in(&self, config: &mut ConfigManager) -> Result<(), String> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| format!("Failed to serialize log config: {}", e))?;
+        config.set(LOG_CONFIG_KEY, value)
+    }
+}
+
+/// Writes records to stderr at the levels of a [`LogConfig`] that can be
+/// changed while running.
+struct RuntimeLogger {
+    config: RwLock<LogConfig>,
+}
+
+impl log::Log for RuntimeLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.config
+            .read()
+            .is_ok_and(|c| metadata.level() <= c.level_for(metadata.target()).to_filter())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(
+                std::io::stderr(),
+                "{:<5} [{}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+static LOGGER: OnceLock<RuntimeLogger> = OnceLock::new();
+
+/// Installs the logger with `config`. Fails if another logger is installed.
+pub fn init(config: LogConfig) -> Result<(), String> {
+    let max_level = config.max_level();
+    let logger = LOGGER.get_or_init(|| RuntimeLogger {
+        config: RwLock::new(config),
+    });
+    log::set_logger(logger).map_err(|e| format!("Failed to install logger: {}", e))?;
+    log::set_max_level(max_level.to_filter());
+    Ok(())
+}
+
+fn update(f: impl FnOnce(&mut LogConfig)) -> Result<(), String> {
+    let logger = LOGGER
+        .get()
+        .ok_or_else(|| "The logger is not installed".to_string())?;
+    let mut config = logger
+        .config
+        .write()
+        .map_err(|_| "Log config lock poisoned".to_string())?;
+    f(&mut config);
+    log::set_max_level(config.max_level().to_filter());
+    Ok(())
+}
+
+/// The levels in effect, to persist after changing them.
+pub fn log_config() -> Option<LogConfig> {
+    LOGGER.get()?.config.read().ok().map(|c| c.clone())
+}
+
+/// Replaces every level, e.g. with the config loaded at startup.
+pub fn configure(config: LogConfig) -> Result<(), String> {
+    update(|current| *current = config)
+}
+
+/// Logs `module` at `level` from now on, publishing `LogLevelChanged`.
+pub fn set_log_level(
+    module: &str,
+    level: LogLevel,
+    bus: Option<&MessageBus>,
+) -> Result<(), String> {
+    let module = module_path(module)?;
+    update(|config| {
+        config.modules.insert(module.to_string(), level);
+    })?;
+    log::info!("Log level for {} is now {:?}", module, level);
+    if let Some(bus) = bus {
+        bus.publish(Event::LogLevelChanged {
+            module: Some(module.to_string()),
+            level: format!("{:?}", level).to_lowercase(),
+        });
+    }
+    Ok(())
+}
+
+/// Drops the override for `module`, so it logs like the module containing
+/// it, or at the default level, again.
+pub fn clear_log_level(module: &str, bus: Option<&MessageBus>) -> Result<(), String> {
+    let module = module_path(module)?;
+    let mut level = None;
+    update(|config| {
+        if config.modules.remove(module).is_some() {
+            level = Some(config.level_for(module));
+        }
+    })?;
+    if let (Some(level), Some(bus)) = (level, bus) {
+        bus.publish(Event::LogLevelChanged {
+            module: Some(module.to_string()),
+            level: format!("{:?}", level).to_lowercase(),
+        });
+    }
+    Ok(())
+}
+
+/// Sets the level for modules without an override, publishing `LogLevelChanged`.
+pub fn set_default_log_level(level: LogLevel, bus: Option<&MessageBus>) -> Result<(), String> {
+    update(|config| config.default_level = level)?;
+    if let Some(bus) = bus {
+        bus.publish(Event::LogLevelChanged {
+            module: None,
+            level: format!("{:?}", level).to_lowercase(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("12: legal winner thank year wave 34"),
+            "12: [REDACTED] 34"
+        );
+        assert_eq!(
+            redact("derived key 000102030405060708090a0b0c0d0e0f"),
+            "derived key [REDACTED]"
+        );
+        assert_eq!(redact("salt c2FsdHNhbHRzYWx0c2FsdA1"), "salt [REDACTED]");
+        assert_eq!(redact("Decryption failed"), "Decryption failed");
+        // Typed precomposed, stored decomposed
+        assert_eq!(
+            redact("restored \u{e1}baco abdomen abeja abierto ok"),
+            "restored [REDACTED] ok"
+        );
+        assert_eq!(
+            redact("あいこくしん\u{3000}あいさつ\u{3000}あいだ"),
+            REDACTED
+        );
+    }
+
+    #[test]
+    fn test_per_module_levels() {
+        let mut config = LogConfig::default();
+        config
+            .modules
+            .insert("utxo_selection".to_string(), LogLevel::Debug);
+        config.modules.insert(
+            "bitvault_core::utxo_selection::dust".to_string(),
+            LogLevel::Warn,
+        );
+        config.modules.insert("ureq".to_string(), LogLevel::Error);
+
+        assert_eq!(
+            config.level_for("bitvault_core::utxo_selection::bnb"),
+            LogLevel::Debug
+        );
+        assert_eq!(
+            config.level_for("bitvault_core::utxo_selection::dust"),
+            LogLevel::Warn
+        );
+        assert_eq!(
+            config.level_for("bitvault_core::utxo_index"),
+            LogLevel::Info
+        );
+        assert_eq!(config.level_for("ureq::response"), LogLevel::Error);
+        assert_eq!(config.max_level(), LogLevel::Debug);
+
+        // The crate-less key names the longer path
+        let mut nested = LogConfig::default();
+        nested
+            .modules
+            .insert("bitvault_core".to_string(), LogLevel::Warn);
+        nested
+            .modules
+            .insert("tx_builder".to_string(), LogLevel::Debug);
+        nested
+            .modules
+            .insert("tx_builder::split".to_string(), LogLevel::Trace);
+        assert_eq!(
+            nested.level_for("bitvault_core::tx_builder"),
+            LogLevel::Debug
+        );
+        assert_eq!(
+            nested.level_for("bitvault_core::tx_builder::split"),
+            LogLevel::Trace
+        );
+        assert_eq!(nested.level_for("bitvault_core::sync"), LogLevel::Warn);
+        assert_eq!(module_path(" tx_builder:: "), Ok("tx_builder"));
+        assert!(module_path("tx_builder::::split").is_err());
+
+        let mut manager = ConfigManager::new().unwrap();
+        assert_eq!(LogConfig::from_config(&manager), Ok(LogConfig::default()));
+        config.store_in(&mut manager).unwrap();
+        assert_eq!(LogConfig::from_config(&manager), Ok(config));
+    }
+}
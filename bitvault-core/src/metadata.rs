@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::types::{OutPoint, Utxo};
+use crate::utxo_selection::dust::DustPolicy;
+
+pub mod address_book;
+pub mod labels;
+
+/// Non-secret information stored alongside the encrypted wallet.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletMetadata {
+    /// Name shown in wallet lists; the file name is used when unset.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Accent color as `#rrggbb`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Icon identifier chosen by the frontend.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Unix timestamp (seconds) the wallet was created or restored.
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    /// BIP-380 checksum of the wallet's descriptor, to tell wallets apart
+    /// without showing the descriptor itself.
+    #[serde(default)]
+    pub descriptor_checksum: Option<String>,
+    /// Unix timestamp (seconds) of the last successful backup verification.
+    #[serde(default)]
+    pub backup_verified_at: Option<u64>,
+    /// Unix timestamp (seconds) the metadata was last backed up.
+    #[serde(default)]
+    pub backed_up_at: Option<u64>,
+    /// What changed since the last backup.
+    #[serde(default)]
+    pub backup_changes: BackupChanges,
+    /// User labels keyed by address, txid or outpoint (`txid:vout`).
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Coins the user excluded from coin selection.
+    #[serde(default)]
+    pub frozen: BTreeSet<OutPoint>,
+    /// How change outputs pick up the labels of the coins they came from.
+    #[serde(default)]
+    pub label_inheritance: labels::LabelInheritance,
+    #[serde(default)]
+    pub contacts: Vec<address_book::Contact>,
+    /// Confirmations received coins need, by sender, before they're spent.
+    #[serde(default)]
+    pub confirmation_policy: address_book::ConfirmationPolicy,
+    /// The user's dust limits; the network's defaults when unset.
+    #[serde(default)]
+    pub dust_policy: Option<DustPolicy>,
+    /// Unsent transaction drafts by id, as JSON.
+    #[serde(default)]
+    pub drafts: BTreeMap<String, String>,
+}
+
+/// Changes a restore from the recovery phrase alone would lose.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupChanges {
+    /// Labels added or changed.
+    #[serde(default)]
+    pub labels: usize,
+    /// Highest address index handed out beyond the gap limit, by keychain.
+    /// A restore stops scanning before these.
+    #[serde(default)]
+    pub addresses_beyond_gap: BTreeMap<u32, u32>,
+    /// Fingerprints of keys added, such as cosigners.
+    #[serde(default)]
+    pub xpubs: BTreeSet<String>,
+}
+
+impl BackupChanges {
+    pub fn is_empty(&self) -> bool {
+        self.labels == 0 && self.addresses_beyond_gap.is_empty() && self.xpubs.is_empty()
+    }
+
+    /// e.g. `2 new labels, addresses beyond the gap limit (up to #45)`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.labels > 0 {
+            parts.push(format!(
+                "{} new label{}",
+                self.labels,
+                if self.labels == 1 { "" } else { "s" }
+            ));
+        }
+        if let Some(highest) = self.addresses_beyond_gap.values().max() {
+            parts.push(format!(
+                "addresses beyond the gap limit (up to #{})",
+                highest
+            ));
+        }
+        if !self.xpubs.is_empty() {
+            let keys: Vec<&str> = self.xpubs.iter().map(String::as_str).collect();
+            parts.push(format!(
+                "{} new key{} ({})",
+                keys.len(),
+                if keys.len() == 1 { "" } else { "s" },
+                keys.join(", ")
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+// Character sets from BIP-380; each input character maps to a 5-bit symbol
+// plus a group index folded in every three characters
+const DESCRIPTOR_INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}\
+IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const DESCRIPTOR_CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn descriptor_polymod(c: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ];
+    let top = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (i, g) in GENERATOR.iter().enumerate() {
+        if top >> i & 1 == 1 {
+            c ^= g;
+        }
+    }
+    c
+}
+
+/// The 8-character BIP-380 checksum of `descriptor`. A `#checksum` suffix
+/// already present is verified and returned.
+pub fn descriptor_checksum(descriptor: &str) -> Result<String, String> {
+    let (body, given) = match descriptor.split_once('#') {
+        Some((body, given)) => (body, Some(given)),
+        None => (descriptor, None),
+    };
+
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in body.chars() {
+        let position = DESCRIPTOR_INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| format!("Invalid character {:?} in descriptor", ch))?
+            as u64;
+        c = descriptor_polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = descriptor_polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = descriptor_polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = descriptor_polymod(c, 0);
+    }
+    c ^= 1;
+
+    let checksum: String = (0..8)
+        .map(|i| DESCRIPTOR_CHECKSUM_CHARSET[(c >> (5 * (7 - i)) & 31) as usize] as char)
+        .collect();
+    match given {
+        Some(given) if given != checksum => Err(format!(
+            "Descriptor checksum {} does not match {}",
+            given, checksum
+        )),
+        _ => Ok(checksum),
+    }
+}
+
+impl WalletMetadata {
+    /// Sets the accent color, accepting `#rrggbb` in either case.
+    pub fn set_color(&mut self, color: &str) -> Result<(), String> {
+        let valid = color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if !valid {
+            return Err(format!("Invalid color {}, expected #rrggbb", color));
+        }
+        self.color = Some(color.to_ascii_lowercase());
+        Ok(())
+    }
+
+    /// Records the checksum of the wallet's descriptor.
+    pub fn set_descriptor(&mut self, descriptor: &str) -> Result<(), String> {
+        self.descriptor_checksum = Some(descriptor_checksum(descriptor)?);
+        Ok(())
+    }
+
+    pub fn is_backup_verified(&self) -> bool {
+        self.backup_verified_at.is_some()
+    }
+
+    pub fn label(&self, reference: &str) -> Option<&str> {
+        self.labels.get(reference).map(String::as_str)
+    }
+
+    /// A coin's label: its own, or else its address's.
+    pub fn coin_label(&self, utxo: &Utxo) -> Option<&str> {
+        self.label(&utxo.outpoint.to_string())
+            .or_else(|| self.label(&utxo.address))
+    }
+
+    /// Labels an address, txid or outpoint with a category path such as
+    /// `Income/Salary`.
+    pub fn set_label(&mut self, reference: &str, label: &str) -> Result<(), String> {
+        let label = labels::normalize_label(label)?;
+        if self.labels.insert(reference.to_string(), label.clone()) != Some(label) {
+            self.backup_changes.labels += 1;
+        }
+        Ok(())
+    }
+
+    /// Notes that address `index` of `keychain` was handed out, tracking it
+    /// for backup when a restore scanning `gap_limit` past `last_used`
+    /// wouldn't find it.
+    pub fn note_address(
+        &mut self,
+        keychain: u32,
+        index: u32,
+        last_used: Option<u32>,
+        gap_limit: u32,
+    ) {
+        if index < last_used.map_or(0, |i| i + 1) + gap_limit {
+            return;
+        }
+        let highest = self
+            .backup_changes
+            .addresses_beyond_gap
+            .entry(keychain)
+            .or_insert(index);
+        *highest = (*highest).max(index);
+    }
+
+    /// Notes a key added to the wallet, by master fingerprint.
+    pub fn note_xpub(&mut self, fingerprint: &str) {
+        self.backup_changes.xpubs.insert(fingerprint.to_string());
+    }
+
+    /// Records a backup at `now`, clearing the tracked changes.
+    pub fn mark_backed_up(&mut self, now: u64) {
+        self.backed_up_at = Some(now);
+        self.backup_changes = BackupChanges::default();
+    }
+
+    /// References labeled with `category` or one of its subcategories.
+    pub fn labeled_within<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a str> {
+        self.labels
+            .iter()
+            .filter(move |(_, label)| labels::is_within(label, category))
+            .map(|(reference, _)| reference.as_str())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Failed to serialize metadata: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse metadata: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descriptor_checksum_and_color() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert!(descriptor_checksum("raw(deadbeef)#89f8spxm").is_ok());
+        assert!(descriptor_checksum("raw(deadbeef)#89f8spxn").is_err());
+
+        let mut metadata = WalletMetadata::default();
+        assert!(metadata.set_color("red").is_err());
+        metadata.set_color("#FF8800").unwrap();
+        let restored = WalletMetadata::from_json(&metadata.to_json().unwrap()).unwrap();
+        assert_eq!(restored.color.as_deref(), Some("#ff8800"));
+        // Metadata written before these fields existed still loads
+        assert!(WalletMetadata::from_json("{}")
+            .unwrap()
+            .display_name
+            .is_none());
+    }
+}
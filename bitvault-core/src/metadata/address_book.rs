@@ -0,0 +1,132 @@
+//! Counterparties the wallet deals with, and how many confirmations coins
+//! from each kind need before they count as spendable.
+//!
+//! A coin is attributed to a contact through the wallet address it arrived
+//! on, so each contact lists the addresses handed out to them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::WalletMetadata;
+use crate::types::Utxo;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    pub name: String,
+    /// Risk category such as `exchange` or `friend`.
+    pub category: String,
+    /// Wallet addresses given to this contact to pay into.
+    #[serde(default)]
+    pub deposit_addresses: BTreeSet<String>,
+}
+
+/// Coins of at least `threshold` sats need `confirmations`, whoever sent them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeAmountRule {
+    pub threshold: u64,
+    pub confirmations: u32,
+}
+
+/// Confirmations received coins need, by who sent them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfirmationPolicy {
+    /// By contact category.
+    #[serde(default)]
+    pub categories: BTreeMap<String, u32>,
+    /// For senders not in the address book or in an unlisted category.
+    #[serde(default)]
+    pub unknown: u32,
+    #[serde(default)]
+    pub large_amount: Option<LargeAmountRule>,
+}
+
+impl ConfirmationPolicy {
+    /// Exchanges after 1 confirmation, anyone else after 3, and a bitcoin
+    /// or more after 6.
+    pub fn recommended() -> Self {
+        ConfirmationPolicy {
+            categories: [("exchange".to_string(), 1)].into_iter().collect(),
+            unknown: 3,
+            large_amount: Some(LargeAmountRule {
+                threshold: 100_000_000,
+                confirmations: 6,
+            }),
+        }
+    }
+
+    /// Confirmations for `value` sats from a sender in `category`.
+    pub fn required(&self, category: Option<&str>, value: u64) -> u32 {
+        let by_sender = category
+            .and_then(|category| self.categories.get(category))
+            .copied()
+            .unwrap_or(self.unknown);
+        let by_amount = self
+            .large_amount
+            .filter(|rule| value >= rule.threshold)
+            .map_or(0, |rule| rule.confirmations);
+        by_sender.max(by_amount)
+    }
+}
+
+impl WalletMetadata {
+    /// The contact `utxo` was received from, if known.
+    pub fn sender(&self, utxo: &Utxo) -> Option<&Contact> {
+        self.contacts
+            .iter()
+            .find(|contact| contact.deposit_addresses.contains(&utxo.address))
+    }
+
+    /// Confirmations `utxo` needs under the wallet's policy.
+    pub fn required_confirmations(&self, utxo: &Utxo) -> u32 {
+        let category = self.sender(utxo).map(|contact| contact.category.as_str());
+        self.confirmation_policy.required(category, utxo.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+
+    #[test]
+    fn test_confirmations_by_sender() {
+        let mut metadata = WalletMetadata {
+            confirmation_policy: ConfirmationPolicy::recommended(),
+            ..Default::default()
+        };
+        metadata.contacts.push(Contact {
+            name: "Kraken".to_string(),
+            category: "exchange".to_string(),
+            deposit_addresses: BTreeSet::from(["bc1qdeposit".to_string()]),
+        });
+        let utxo = |address: &str, value: u64| Utxo {
+            outpoint: OutPoint::new("aa", 0),
+            address: address.to_string(),
+            value,
+            confirmations: 0,
+            is_coinbase: false,
+        };
+
+        assert_eq!(
+            metadata.required_confirmations(&utxo("bc1qdeposit", 50_000)),
+            1
+        );
+        assert_eq!(
+            metadata.required_confirmations(&utxo("bc1qother", 50_000)),
+            3
+        );
+        assert_eq!(
+            metadata.required_confirmations(&utxo("bc1qdeposit", 200_000_000)),
+            6
+        );
+        assert_eq!(
+            metadata.sender(&utxo("bc1qdeposit", 1)).unwrap().name,
+            "Kraken"
+        );
+        // Wallets without a policy keep spending unconfirmed coins
+        assert_eq!(
+            WalletMetadata::default().required_confirmations(&utxo("bc1qother", 1)),
+            0
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Hierarchical labels such as `Income/Salary`, shared by addresses, coins
+//! and transactions, and totals per label subtree.
+//!
+//! A label is a `/`-separated path. Plain labels from older wallets are
+//! single-segment paths, so nothing needs migrating.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::WalletMetadata;
+use crate::types::Utxo;
+
+pub const LABEL_SEPARATOR: char = '/';
+
+/// Trims each segment and rejects empty ones, so `" Income / Salary"` and
+/// `"Income/Salary"` are the same label.
+pub fn normalize_label(label: &str) -> Result<String, String> {
+    let segments: Vec<&str> = label.split(LABEL_SEPARATOR).map(str::trim).collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid label {:?}: empty category", label));
+    }
+    Ok(segments.join("/"))
+}
+
+/// `label` and every category containing it, outermost first:
+/// `Income/Salary` gives `Income` and `Income/Salary`.
+pub fn label_ancestors(label: &str) -> impl Iterator<Item = &str> {
+    label
+        .match_indices(LABEL_SEPARATOR)
+        .map(move |(i, _)| &label[..i])
+        .chain(std::iter::once(label))
+}
+
+/// Whether `label` is `category` or filed under it.
+pub fn is_within(label: &str, category: &str) -> bool {
+    label
+        .strip_prefix(category)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(LABEL_SEPARATOR))
+}
+
+/// Amounts in satoshis under one label, including its subcategories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelTotals {
+    pub incoming: u64,
+    pub outgoing: u64,
+    pub count: usize,
+}
+
+impl LabelTotals {
+    pub fn net(&self) -> i64 {
+        self.incoming as i64 - self.outgoing as i64
+    }
+
+    fn add(&mut self, amount: i64) {
+        if amount >= 0 {
+            self.incoming += amount as u64;
+        } else {
+            self.outgoing += amount.unsigned_abs();
+        }
+        self.count += 1;
+    }
+}
+
+/// Sums `(reference, amount)` pairs by label, counting each amount toward its
+/// label and every category above it. Unlabeled references are skipped.
+pub fn aggregate<'a>(
+    metadata: &WalletMetadata,
+    items: impl IntoIterator<Item = (&'a str, i64)>,
+) -> BTreeMap<String, LabelTotals> {
+    let mut totals: BTreeMap<String, LabelTotals> = BTreeMap::new();
+    for (reference, amount) in items {
+        let Some(label) = metadata.label(reference) else {
+            continue;
+        };
+        for category in label_ancestors(label) {
+            totals.entry(category.to_string()).or_default().add(amount);
+        }
+    }
+    totals
+}
+
+/// Coin values by label. A coin's own label wins over its address's.
+pub fn aggregate_utxos(metadata: &WalletMetadata, utxos: &[Utxo]) -> BTreeMap<String, LabelTotals> {
+    let references: Vec<(String, i64)> = utxos
+        .iter()
+        .map(|utxo| {
+            let outpoint = utxo.outpoint.to_string();
+            let reference = if metadata.label(&outpoint).is_some() {
+                outpoint
+            } else {
+                utxo.address.clone()
+            };
+            (reference, utxo.value as i64)
+        })
+        .collect();
+    aggregate(
+        metadata,
+        references.iter().map(|(r, amount)| (r.as_str(), *amount)),
+    )
+}
+
+/// Which labels of the coins a transaction spends carry over to its change.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LabelInheritance {
+    /// The spent coins' label. When they differ, the category they share,
+    /// or nothing if they share none.
+    #[default]
+    All,
+    None,
+    /// Only labels within these categories, the first listed winning when
+    /// spent coins fall under several, e.g. `["Tainted", "KYC"]`.
+    Categories(Vec<String>),
+}
+
+impl LabelInheritance {
+    /// Label for change from spending coins labeled `spent`.
+    pub fn inherit(&self, spent: &[&str]) -> Option<String> {
+        match self {
+            LabelInheritance::None => None,
+            LabelInheritance::Categories(categories) => categories.iter().find_map(|category| {
+                spent
+                    .iter()
+                    .find(|label| is_within(label, category))
+                    .map(|label| label.to_string())
+            }),
+            LabelInheritance::All => {
+                let first = spent.first()?;
+                let shared = label_ancestors(first)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .find(|category| spent.iter().all(|label| is_within(label, category)));
+                if shared.is_none() {
+                    log::warn!("Change spends coins from unrelated labels; leaving it unlabeled");
+                }
+                shared.map(str::to_string)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+
+    #[test]
+    fn test_subtree_totals() {
+        let mut metadata = WalletMetadata::default();
+        metadata.set_label("tx1", " Income / Salary ").unwrap();
+        metadata.set_label("tx2", "Income/Gifts").unwrap();
+        metadata.set_label("tx3", "Spending/Rent").unwrap();
+        metadata
+            .labels
+            .insert("tx4".to_string(), "Legacy".to_string());
+        assert!(metadata.set_label("tx5", "Income//Salary").is_err());
+        assert_eq!(metadata.label("tx1"), Some("Income/Salary"));
+
+        let totals = aggregate(
+            &metadata,
+            [
+                ("tx1", 500_000),
+                ("tx2", 20_000),
+                ("tx3", -150_000),
+                ("tx4", 1),
+                ("tx9", 7),
+            ],
+        );
+        assert_eq!(totals["Income"].incoming, 520_000);
+        assert_eq!(totals["Income"].count, 2);
+        assert_eq!(totals["Spending/Rent"].net(), -150_000);
+        assert_eq!(totals["Legacy"].count, 1);
+        assert!(is_within("Income/Salary", "Income") && !is_within("Incomes", "Income"));
+
+        metadata.set_label("bc1qsavings", "Savings").unwrap();
+        let utxo = Utxo {
+            outpoint: OutPoint::new("aa", 0),
+            address: "bc1qsavings".to_string(),
+            value: 40_000,
+            confirmations: 1,
+            is_coinbase: false,
+        };
+        assert_eq!(
+            aggregate_utxos(&metadata, &[utxo])["Savings"].incoming,
+            40_000
+        );
+    }
+
+    #[test]
+    fn test_label_inheritance_rules() {
+        let spent = ["Income/Salary", "Income/Gifts/Birthday", "Tainted/Mixer"];
+        assert_eq!(
+            LabelInheritance::All.inherit(&spent[..2]),
+            Some("Income".to_string())
+        );
+        assert_eq!(
+            LabelInheritance::All.inherit(&["Income/Salary", "Income/Salary"]),
+            Some("Income/Salary".to_string())
+        );
+        assert_eq!(LabelInheritance::All.inherit(&spent), None);
+        assert_eq!(LabelInheritance::All.inherit(&[]), None);
+        assert_eq!(LabelInheritance::None.inherit(&spent), None);
+
+        let taint = LabelInheritance::Categories(vec!["Tainted".to_string(), "Income".to_string()]);
+        assert_eq!(taint.inherit(&spent), Some("Tainted/Mixer".to_string()));
+        assert_eq!(
+            taint.inherit(&spent[..2]),
+            Some("Income/Salary".to_string())
+        );
+        assert_eq!(taint.inherit(&["Spending"]), None);
+    }
+}
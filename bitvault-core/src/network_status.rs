@@ -0,0 +1,284 @@
+//! Recent block headers for live block feeds and reorg detection.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::backend::{BlockFeeStats, BlockHeader, BlockchainBackend};
+use crate::connection_mode::{ConnectionMode, SharedConnectionMode};
+use crate::events::{Event, MessageBus};
+
+/// About a day of blocks.
+pub const DEFAULT_CACHED_BLOCKS: usize = 144;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedBlock {
+    pub header: BlockHeader,
+    pub fee_stats: Option<BlockFeeStats>,
+}
+
+/// What changed at the chain tip since the last update.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TipUpdate {
+    Unchanged,
+    /// New blocks on top of the cached chain, lowest first.
+    Extended {
+        connected: Vec<u32>,
+    },
+    /// The cached blocks from `fork_height` up were replaced.
+    Reorg {
+        fork_height: u32,
+        disconnected: Vec<BlockHeader>,
+        connected: Vec<u32>,
+    },
+}
+
+/// Cache of the most recent headers on the best chain.
+///
+/// Fed either by polling the backend with [`poll`](Self::poll) or by header
+/// notifications passed to [`on_header`](Self::on_header); both follow
+/// `prev_hash` links back to the cached chain, so switching branches is
+/// noticed however the new tip arrives.
+///
+/// Also tracks whether the connection is metered, announcing switches with
+/// `ConnectionModeChanged` events.
+#[derive(Clone, Debug)]
+pub struct NetworkStatus {
+    blocks: VecDeque<CachedBlock>,
+    capacity: usize,
+    stale_tips: Vec<BlockHeader>,
+    connection: SharedConnectionMode,
+}
+
+impl Default for NetworkStatus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHED_BLOCKS)
+    }
+}
+
+impl NetworkStatus {
+    pub fn new(capacity: usize) -> Self {
+        NetworkStatus {
+            blocks: VecDeque::new(),
+            capacity: capacity.max(1),
+            stale_tips: Vec::new(),
+            connection: SharedConnectionMode::default(),
+        }
+    }
+
+    /// Shares the connection mode with the scheduler and services that
+    /// adapt to it.
+    pub fn with_connection_mode(mut self, connection: SharedConnectionMode) -> Self {
+        self.connection = connection;
+        self
+    }
+
+    pub fn connection_mode(&self) -> ConnectionMode {
+        self.connection.get()
+    }
+
+    /// Switches the connection mode, publishing `ConnectionModeChanged` if
+    /// it changed. Returns whether it did.
+    pub fn set_connection_mode(&mut self, mode: ConnectionMode, bus: Option<&MessageBus>) -> bool {
+        if !self.connection.set(mode) {
+            return false;
+        }
+        log::info!("Connection mode is now {:?}", mode);
+        if let Some(bus) = bus {
+            bus.publish(Event::ConnectionModeChanged {
+                metered: mode.is_metered(),
+            });
+        }
+        true
+    }
+
+    /// How long to wait between tip polls, `interval_secs` when not metered.
+    pub fn poll_interval(&self, interval_secs: u64) -> u64 {
+        self.connection_mode().interval(interval_secs)
+    }
+
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.blocks.back().map(|b| &b.header)
+    }
+
+    /// The `n` most recent blocks, newest first.
+    pub fn get_recent_blocks(&self, n: usize) -> Vec<&CachedBlock> {
+        self.blocks.iter().rev().take(n).collect()
+    }
+
+    /// Former tips of branches that were reorganized away, oldest first.
+    pub fn stale_tips(&self) -> &[BlockHeader] {
+        &self.stale_tips
+    }
+
+    fn cached(&self, height: u32) -> Option<&CachedBlock> {
+        let first = self.blocks.front()?.header.height;
+        self.blocks
+            .get(height.checked_sub(first)? as usize)
+            .filter(|b| b.header.height == height)
+    }
+
+    /// Fetches the backend's tip and folds it into the cache.
+    pub fn poll(
+        &mut self,
+        backend: &dyn BlockchainBackend,
+        bus: Option<&MessageBus>,
+    ) -> Result<TipUpdate, String> {
+        let tip = backend.tip()?;
+        self.on_header(tip, backend, bus)
+    }
+
+    /// Folds a header announced by a subscription into the cache, fetching
+    /// any blocks between it and the cached chain from `backend`.
+    pub fn on_header(
+        &mut self,
+        header: BlockHeader,
+        backend: &dyn BlockchainBackend,
+        bus: Option<&MessageBus>,
+    ) -> Result<TipUpdate, String> {
+        // Also covers a lagging server announcing a block already cached
+        if self
+            .cached(header.height)
+            .is_some_and(|b| b.header.hash == header.hash)
+        {
+            return Ok(TipUpdate::Unchanged);
+        }
+
+        // Walk back until the new branch links into the cache or reaches
+        // below it; an empty cache is filled to capacity
+        let mut branch = vec![header];
+        loop {
+            let lowest = &branch[branch.len() - 1];
+            if lowest.height == 0 || branch.len() >= self.capacity {
+                break;
+            }
+            let below = lowest.height - 1;
+            match self.cached(below) {
+                Some(block) if block.header.hash == lowest.prev_hash => break,
+                None if self.blocks.front().is_some_and(|b| below < b.header.height) => break,
+                _ => {
+                    let header = backend
+                        .header(below)?
+                        .ok_or_else(|| format!("Backend has no header at height {}", below))?;
+                    branch.push(header);
+                }
+            }
+        }
+        branch.reverse();
+
+        let fork_height = branch[0].height;
+        let linked = self
+            .cached(fork_height.wrapping_sub(1))
+            .is_some_and(|b| fork_height > 0 && b.header.hash == branch[0].prev_hash);
+        if !linked {
+            // Nothing to compare against; start over from the new branch
+            if !self.blocks.is_empty() {
+                log::warn!(
+                    "New tip at {} does not connect to cached blocks",
+                    fork_height
+                );
+            }
+            self.blocks.clear();
+        }
+        let mut disconnected = Vec::new();
+        while let Some(block) = self.blocks.pop_back() {
+            if block.header.height < fork_height {
+                self.blocks.push_back(block);
+                break;
+            }
+            disconnected.push(block.header);
+        }
+        disconnected.reverse();
+
+        let connected: Vec<u32> = branch.iter().map(|h| h.height).collect();
+        for header in branch {
+            let fee_stats = backend.block_fee_stats(&header.hash).unwrap_or_else(|e| {
+                log::debug!("No fee stats for block {}: {}", header.height, e);
+                None
+            });
+            if let Some(bus) = bus {
+                bus.publish(Event::BlockConnected {
+                    height: header.height,
+                    hash: header.hash.clone(),
+                    time: header.time,
+                });
+            }
+            self.blocks.push_back(CachedBlock { header, fee_stats });
+        }
+        while self.blocks.len() > self.capacity {
+            self.blocks.pop_front();
+        }
+
+        if disconnected.is_empty() {
+            return Ok(TipUpdate::Extended { connected });
+        }
+        log::warn!(
+            "Chain reorganized: {} blocks replaced from height {}",
+            disconnected.len(),
+            fork_height
+        );
+        if let Some(bus) = bus {
+            bus.publish(Event::ChainReorg {
+                fork_height,
+                depth: disconnected.len() as u32,
+            });
+        }
+        self.stale_tips.extend(disconnected.last().cloned());
+        Ok(TipUpdate::Reorg {
+            fork_height,
+            disconnected,
+            connected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    #[test]
+    fn test_block_feed_and_reorg_detection() {
+        let backend = MockBackend::new();
+        backend.mine_blocks(10);
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+
+        let mut status = NetworkStatus::new(5);
+        status.poll(&backend, Some(&bus)).unwrap();
+        let recent = status.get_recent_blocks(3);
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].header.height, 10);
+        assert_eq!(status.get_recent_blocks(100).len(), 5);
+        assert_eq!(status.poll(&backend, None).unwrap(), TipUpdate::Unchanged);
+
+        backend.mine_blocks(2);
+        assert_eq!(
+            status.poll(&backend, None).unwrap(),
+            TipUpdate::Extended {
+                connected: vec![11, 12]
+            }
+        );
+
+        let old_tip = status.tip().cloned().unwrap();
+        backend.reorg(2).unwrap();
+        backend.mine_blocks(1);
+        match status.poll(&backend, Some(&bus)).unwrap() {
+            TipUpdate::Reorg {
+                fork_height,
+                disconnected,
+                connected,
+            } => {
+                assert_eq!(fork_height, 11);
+                assert_eq!(disconnected.len(), 2);
+                assert_eq!(connected, vec![11, 12, 13]);
+            }
+            other => panic!("expected reorg, got {:?}", other),
+        }
+        assert_eq!(status.stale_tips(), &[old_tip]);
+        assert!(events.try_iter().any(|e| e
+            == Event::ChainReorg {
+                fork_height: 11,
+                depth: 2
+            }));
+    }
+}
@@ -0,0 +1,297 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::clock::SharedClock;
+use crate::events::{Event, Subscription};
+use crate::logging;
+
+/// Header carrying the hex HMAC-SHA256 of the request body.
+pub const SIGNATURE_HEADER: &str = "X-BitVault-Signature";
+
+/// Event types that can be forwarded externally; everything else stays local.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEventKind {
+    PaymentReceived,
+    TransactionConfirmed,
+    SecurityAlert,
+}
+
+impl NotifyEventKind {
+    fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::PaymentReceived { .. } => Some(NotifyEventKind::PaymentReceived),
+            Event::TransactionConfirmed { .. } => Some(NotifyEventKind::TransactionConfirmed),
+            Event::SecurityAlert { .. } => Some(NotifyEventKind::SecurityAlert),
+            _ => None,
+        }
+    }
+}
+
+/// Where notifications are delivered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotifyTarget {
+    /// POSTs the JSON summary, signed with `secret` when one is set.
+    Webhook { url: String, secret: Option<String> },
+    /// Runs a local program with the JSON summary on stdin.
+    Command { program: String, args: Vec<String> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifierConfig {
+    pub targets: Vec<NotifyTarget>,
+    /// Event types the user opted in to; empty means nothing is sent.
+    pub enabled: HashSet<NotifyEventKind>,
+    /// Minimum time between two notifications of the same type.
+    pub min_interval_secs: u64,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        NotifierConfig {
+            targets: Vec::new(),
+            enabled: HashSet::new(),
+            min_interval_secs: 60,
+        }
+    }
+}
+
+/// What leaves the wallet: the event type, when it happened and a short message.
+/// Amounts, addresses and txids are deliberately left out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationSummary {
+    pub event: NotifyEventKind,
+    pub at: u64,
+    pub message: String,
+}
+
+impl NotificationSummary {
+    pub fn from_event(event: &Event, at: u64) -> Option<Self> {
+        let kind = NotifyEventKind::of(event)?;
+        let message = match event {
+            Event::PaymentReceived { .. } => "Payment received".to_string(),
+            Event::TransactionConfirmed { .. } => "Transaction confirmed".to_string(),
+            Event::SecurityAlert { message } => logging::redact(message),
+            _ => return None,
+        };
+        Some(NotificationSummary {
+            event: kind,
+            at,
+            message,
+        })
+    }
+}
+
+/// Delivery mechanism, swappable so tests don't touch the network or processes.
+pub trait NotifyTransport: Send + Sync {
+    fn post(&self, url: &str, body: &str, signature: Option<&str>) -> Result<(), String>;
+
+    fn run_command(&self, program: &str, args: &[String], body: &str) -> Result<(), String>;
+}
+
+/// Delivers over HTTP (with the `webhooks` feature) and by spawning processes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTransport;
+
+impl NotifyTransport for SystemTransport {
+    #[cfg(feature = "webhooks")]
+    fn post(&self, url: &str, body: &str, signature: Option<&str>) -> Result<(), String> {
+        let mut request = ureq::post(url).set("Content-Type", "application/json");
+        if let Some(signature) = signature {
+            request = request.set(SIGNATURE_HEADER, signature);
+        }
+        request
+            .send_string(body)
+            .map(|_| ())
+            .map_err(|e| format!("Webhook request failed: {}", e))
+    }
+
+    #[cfg(not(feature = "webhooks"))]
+    fn post(&self, _url: &str, _body: &str, _signature: Option<&str>) -> Result<(), String> {
+        Err("Built without webhook support".to_string())
+    }
+
+    fn run_command(&self, program: &str, args: &[String], body: &str) -> Result<(), String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(body.as_bytes())
+                .map_err(|e| format!("Failed to write to {}: {}", program, e))?;
+        }
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to wait for {}: {}", program, e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("{} exited with {}", program, status))
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `body` under `secret`, sent in [`SIGNATURE_HEADER`] so
+/// receivers can check a payload really came from this wallet.
+pub fn sign_payload(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Forwards opted-in wallet events to external webhooks and commands.
+pub struct Notifier {
+    config: NotifierConfig,
+    transport: Box<dyn NotifyTransport>,
+    clock: SharedClock,
+    last_sent: HashMap<NotifyEventKind, u64>,
+}
+
+impl Notifier {
+    pub fn new(config: NotifierConfig, clock: SharedClock) -> Self {
+        Self::with_transport(config, Box::new(SystemTransport), clock)
+    }
+
+    pub fn with_transport(
+        config: NotifierConfig,
+        transport: Box<dyn NotifyTransport>,
+        clock: SharedClock,
+    ) -> Self {
+        Notifier {
+            config,
+            transport,
+            clock,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Sends `event` to every target if its type is enabled and not rate limited.
+    /// Returns whether anything was sent; delivery failures are logged and don't
+    /// stop the remaining targets.
+    pub fn handle(&mut self, event: &Event) -> bool {
+        let now = self.clock.now();
+        let Some(summary) = NotificationSummary::from_event(event, now) else {
+            return false;
+        };
+        if !self.config.enabled.contains(&summary.event) {
+            return false;
+        }
+        if let Some(last) = self.last_sent.get(&summary.event) {
+            if now < last.saturating_add(self.config.min_interval_secs) {
+                log::debug!("Notification {:?} rate limited", summary.event);
+                return false;
+            }
+        }
+
+        let body = match serde_json::to_string(&summary) {
+            Ok(body) => body,
+            Err(e) => {
+                log::warn!("Failed to serialize notification: {}", e);
+                return false;
+            }
+        };
+
+        for target in &self.config.targets {
+            let result = match target {
+                NotifyTarget::Webhook { url, secret } => {
+                    let signature = secret.as_deref().map(|s| sign_payload(s, &body));
+                    self.transport.post(url, &body, signature.as_deref())
+                }
+                NotifyTarget::Command { program, args } => {
+                    self.transport.run_command(program, args, &body)
+                }
+            };
+            if let Err(e) = result {
+                log::warn!("Notification delivery failed: {}", e);
+            }
+        }
+
+        self.last_sent.insert(summary.event, now);
+        true
+    }
+
+    /// Handles every event already queued on `receiver` without blocking.
+    pub fn drain(&mut self, receiver: &Subscription) -> usize {
+        receiver
+            .try_iter()
+            .filter(|event| self.handle(event))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::{Arc, Mutex};
+
+    // URL, body and signature of each post
+    type Post = (String, String, Option<String>);
+
+    #[derive(Clone, Default)]
+    struct RecordingTransport(Arc<Mutex<Vec<Post>>>);
+
+    impl NotifyTransport for RecordingTransport {
+        fn post(&self, url: &str, body: &str, signature: Option<&str>) -> Result<(), String> {
+            self.0.lock().unwrap().push((
+                url.to_string(),
+                body.to_string(),
+                signature.map(str::to_string),
+            ));
+            Ok(())
+        }
+
+        fn run_command(&self, program: &str, _args: &[String], body: &str) -> Result<(), String> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((program.to_string(), body.to_string(), None));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_opt_in_signing_and_rate_limit() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let transport = RecordingTransport::default();
+        let config = NotifierConfig {
+            targets: vec![NotifyTarget::Webhook {
+                url: "https://example.com/hook".to_string(),
+                secret: Some("s3cret".to_string()),
+            }],
+            enabled: HashSet::from([NotifyEventKind::PaymentReceived]),
+            min_interval_secs: 60,
+        };
+        let mut notifier =
+            Notifier::with_transport(config, Box::new(transport.clone()), clock.clone());
+
+        let payment = Event::PaymentReceived {
+            txid: "ab".repeat(32),
+            amount: 50_000,
+        };
+        assert!(notifier.handle(&payment));
+        assert!(!notifier.handle(&payment));
+        assert!(!notifier.handle(&Event::SecurityAlert {
+            message: "not opted in".to_string()
+        }));
+        clock.advance(60);
+        assert!(notifier.handle(&payment));
+
+        let sent = transport.0.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        let (_, body, signature) = &sent[0];
+        assert!(!body.contains("50000") && !body.contains(&"ab".repeat(32)));
+        assert_eq!(
+            signature.as_deref(),
+            Some(sign_payload("s3cret", body).as_str())
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Read-only feed of wallet lifecycle events for external monitoring tools,
+//! written as newline-delimited JSON to a file or a local socket.
+//!
+//! Records are sanitized: amounts, txids, outpoints and addresses never
+//! appear, and free-text messages go through [`logging::redact`]. Nothing is
+//! ever read back from the feed, so it can't be used to control the wallet.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use crate::clock::SharedClock;
+use crate::events::{Event, Subscription};
+use crate::logging;
+
+/// One line of the feed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObserverRecord {
+    pub at: u64,
+    pub event: String,
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+impl ObserverRecord {
+    /// The non-sensitive part of `event`.
+    pub fn from_event(event: &Event, at: u64) -> Self {
+        let details = match event {
+            Event::BackupRequired { .. } | Event::PaymentReceived { .. } => json!({}),
+            Event::BackupVerified { verified_at } => json!({ "verified_at": verified_at }),
+            Event::SecurityAlert { message } => json!({ "message": logging::redact(message) }),
+            Event::SyncProgress {
+                keychain,
+                scanned,
+                used,
+                ..
+            } => json!({ "keychain": keychain, "scanned": scanned, "used": used }),
+            Event::TransactionConfirmed { height, .. } => json!({ "height": height }),
+            Event::FeeBumpSuggested {
+                target_blocks,
+                blocks_waiting,
+                ..
+            } => json!({ "target_blocks": target_blocks, "blocks_waiting": blocks_waiting }),
+            Event::InheritanceRefreshDue {
+                blocks_remaining, ..
+            } => json!({ "blocks_remaining": blocks_remaining }),
+            Event::UnvaultDetected {
+                blocks_until_spendable,
+                expected,
+                ..
+            } => json!({
+                "blocks_until_spendable": blocks_until_spendable,
+                "expected": expected,
+            }),
+            // Request ids are txids, so they stay out of the feed
+            Event::ApprovalRequested { .. } | Event::ApprovalExpired { .. } => json!({}),
+            Event::ApprovalDecided { approved, .. } => json!({ "approved": approved }),
+            Event::WalletWiped {
+                trigger,
+                files_erased,
+                ..
+            } => json!({ "trigger": trigger, "files_erased": files_erased }),
+            Event::BlockConnected { height, hash, time } => {
+                json!({ "height": height, "hash": hash, "time": time })
+            }
+            Event::ChainReorg { fork_height, depth } => {
+                json!({ "fork_height": fork_height, "depth": depth })
+            }
+            Event::DeviceConnected { vendor, product }
+            | Event::DeviceDisconnected { vendor, product } => {
+                json!({ "vendor": vendor, "product": product })
+            }
+            Event::DeviceActionRequired { vendor, action } => {
+                json!({ "vendor": vendor, "action": action })
+            }
+            Event::SigningProgress { signer, stage } => json!({ "signer": signer, "stage": stage }),
+            Event::OperationProgress {
+                operation,
+                phase,
+                percent,
+                ..
+            } => json!({ "operation": operation, "phase": phase, "percent": percent }),
+            Event::RestoreHint {
+                description,
+                used_addresses,
+            } => json!({ "description": description, "used_addresses": used_addresses }),
+            Event::PrivacyWarning { message } => json!({ "message": message }),
+            Event::ConnectionModeChanged { metered } => json!({ "metered": metered }),
+            Event::LogLevelChanged { module, level } => {
+                json!({ "module": module, "level": level })
+            }
+            Event::System { component, message } => json!({
+                "component": component,
+                "message": logging::redact(message),
+            }),
+        };
+        ObserverRecord {
+            at,
+            event: event.name().to_string(),
+            details: match details {
+                Value::Object(details) => details,
+                _ => Map::new(),
+            },
+        }
+    }
+}
+
+enum Sink {
+    Writer(Box<dyn Write + Send>),
+    #[cfg(unix)]
+    Socket {
+        listener: UnixListener,
+        path: PathBuf,
+        clients: Vec<UnixStream>,
+    },
+}
+
+/// Writes sanitized events to a monitoring feed.
+pub struct ObserverStream {
+    sink: Sink,
+    clock: SharedClock,
+}
+
+impl ObserverStream {
+    pub fn with_writer(writer: Box<dyn Write + Send>, clock: SharedClock) -> Self {
+        ObserverStream {
+            sink: Sink::Writer(writer),
+            clock,
+        }
+    }
+
+    /// Appends to `path`, for tools that tail a file.
+    pub fn to_file(path: &Path, clock: SharedClock) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Self::with_writer(Box::new(file), clock))
+    }
+
+    /// Listens on a Unix socket at `path`, readable by the current user only.
+    /// Every connected client gets each record; clients that fall behind are
+    /// disconnected rather than allowed to stall the wallet.
+    #[cfg(unix)]
+    pub fn to_socket(path: &Path, clock: SharedClock) -> Result<Self, String> {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        // A socket left behind by an earlier run would make bind fail
+        if path.exists() {
+            std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove stale {}: {}", path.display(), e))?;
+        }
+        // Bound and restricted inside a directory only we can enter, then moved
+        // into place, so the socket is never reachable with looser permissions
+        let staging = path.with_extension("staging");
+        let _ = std::fs::remove_dir_all(&staging);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&staging)
+            .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+        let staged = staging.join("feed.sock");
+        let listener = UnixListener::bind(&staged)
+            .map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))
+            .and_then(|listener| {
+                std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))
+                    .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+                std::fs::rename(&staged, path)
+                    .map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+                Ok(listener)
+            });
+        let _ = std::fs::remove_dir_all(&staging);
+        let listener = listener?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure {}: {}", path.display(), e))?;
+        Ok(ObserverStream {
+            sink: Sink::Socket {
+                listener,
+                path: path.to_path_buf(),
+                clients: Vec::new(),
+            },
+            clock,
+        })
+    }
+
+    /// Number of clients connected to a socket feed.
+    pub fn clients(&self) -> usize {
+        match &self.sink {
+            Sink::Writer(_) => 0,
+            #[cfg(unix)]
+            Sink::Socket { clients, .. } => clients.len(),
+        }
+    }
+
+    /// Writes the sanitized form of `event`. Returns whether it was written.
+    pub fn handle(&mut self, event: &Event) -> bool {
+        let record = ObserverRecord::from_event(event, self.clock.now());
+        let mut line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to serialize observer record: {}", e);
+                return false;
+            }
+        };
+        line.push('\n');
+
+        match &mut self.sink {
+            Sink::Writer(writer) => match writer
+                .write_all(line.as_bytes())
+                .and_then(|_| writer.flush())
+            {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("Failed to write observer feed: {}", e);
+                    false
+                }
+            },
+            #[cfg(unix)]
+            Sink::Socket {
+                listener, clients, ..
+            } => {
+                while let Ok((client, _)) = listener.accept() {
+                    if client.set_nonblocking(true).is_ok() {
+                        clients.push(client);
+                    }
+                }
+                clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+                !clients.is_empty()
+            }
+        }
+    }
+
+    /// Handles every event already queued on `receiver` without blocking.
+    pub fn drain(&mut self, receiver: &Subscription) -> usize {
+        receiver
+            .try_iter()
+            .filter(|event| self.handle(event))
+            .count()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ObserverStream {
+    fn drop(&mut self) {
+        if let Sink::Socket { path, .. } = &self.sink {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::events::MessageBus;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_feed_is_sanitized_ndjson() {
+        let buffer = SharedBuffer::default();
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut stream = ObserverStream::with_writer(Box::new(buffer.clone()), clock);
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        bus.publish(Event::PaymentReceived {
+            txid: "ab".repeat(32),
+            amount: 123_456,
+        });
+        bus.publish(Event::TransactionConfirmed {
+            txid: "cd".repeat(32),
+            height: 800_000,
+        });
+        assert_eq!(stream.drain(&events), 2);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(!output.contains("abab") && !output.contains("123456"));
+        let records: Vec<ObserverRecord> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[0].event, "PaymentReceived");
+        assert_eq!(records[1].details["height"], json!(800_000));
+        assert_eq!(records[1].at, 1_700_000_000);
+
+        bus.publish(Event::ApprovalDecided {
+            request_id: "ef".repeat(32),
+            approved: true,
+            at: 1_700_000_000,
+        });
+        assert_eq!(stream.drain(&events), 1);
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(!output.contains("efef"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("bitvault_feed_{}.sock", std::process::id()));
+        let clock = Arc::new(MockClock::new(0));
+        let mut stream = ObserverStream::to_socket(&path, clock).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _client = UnixStream::connect(&path).unwrap();
+        assert!(stream.handle(&Event::ConnectionModeChanged { metered: false }));
+        drop(stream);
+        assert!(!path.exists());
+    }
+}
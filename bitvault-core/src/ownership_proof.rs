@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+use crate::signer::MessageSigner;
+use crate::types::{Network, Utxo};
+
+// Shortest auditor challenge accepted, so statements can't be prepared in advance
+const MIN_CHALLENGE_LEN: usize = 16;
+
+/// One address's balance and its signature over [`attestation_message`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressAttestation {
+    pub address: String,
+    pub balance: u64,
+    pub signature: String,
+}
+
+/// Signed statement proving control of a set of addresses at a block height,
+/// for proof-of-reserves style audits.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipStatement {
+    pub challenge: String,
+    pub network: Network,
+    pub block_height: u32,
+    pub created_at: u64,
+    pub attestations: Vec<AddressAttestation>,
+    pub total_balance: u64,
+}
+
+/// The exact message signed for each address; auditors rebuild it to verify.
+pub fn attestation_message(challenge: &str, address: &str, block_height: u32) -> String {
+    format!(
+        "BitVault ownership proof\nChallenge: {}\nAddress: {}\nBlock height: {}",
+        challenge, address, block_height
+    )
+}
+
+/// Signs `challenge` with the key of every address in `addresses` and
+/// attaches each address's confirmed balance from `utxos`.
+pub fn build_ownership_statement(
+    challenge: &str,
+    addresses: &[String],
+    utxos: &[Utxo],
+    signer: &dyn MessageSigner,
+    network: Network,
+    block_height: u32,
+    created_at: u64,
+) -> Result<OwnershipStatement, String> {
+    if challenge.trim().len() < MIN_CHALLENGE_LEN {
+        return Err(format!(
+            "Challenge must be at least {} characters",
+            MIN_CHALLENGE_LEN
+        ));
+    }
+    if addresses.is_empty() {
+        return Err("Select at least one address".to_string());
+    }
+
+    let mut attestations = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let balance = utxos
+            .iter()
+            .filter(|u| u.address == *address && u.confirmations > 0)
+            .map(|u| u.value)
+            .sum();
+        let message = attestation_message(challenge, address, block_height);
+        let signature = signer
+            .sign_message(address, &message)
+            .map_err(|e| format!("Failed to sign for {}: {}", address, e))?;
+        attestations.push(AddressAttestation {
+            address: address.clone(),
+            balance,
+            signature,
+        });
+    }
+
+    Ok(OwnershipStatement {
+        challenge: challenge.to_string(),
+        network,
+        block_height,
+        created_at,
+        total_balance: attestations.iter().map(|a| a.balance).sum(),
+        attestations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+
+    struct EchoSigner;
+
+    impl MessageSigner for EchoSigner {
+        fn sign_message(&self, address: &str, message: &str) -> Result<String, String> {
+            if address == "bcrt1qnotours" {
+                return Err("unknown address".to_string());
+            }
+            Ok(format!("sig({})", message.len()))
+        }
+    }
+
+    #[test]
+    fn test_statement_balances_and_signatures() {
+        let utxo = |vout, value, confirmations| Utxo {
+            outpoint: OutPoint::new("aa", vout),
+            address: "bcrt1qcold".to_string(),
+            value,
+            confirmations,
+            is_coinbase: false,
+        };
+        let utxos = vec![utxo(0, 70_000, 6), utxo(1, 5_000, 0)];
+        let challenge = "audit-2026-q3-7f3a9c";
+
+        let statement = build_ownership_statement(
+            challenge,
+            &["bcrt1qcold".to_string()],
+            &utxos,
+            &EchoSigner,
+            Network::Regtest,
+            900,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(statement.total_balance, 70_000);
+        let message = attestation_message(challenge, "bcrt1qcold", 900);
+        assert_eq!(
+            statement.attestations[0].signature,
+            format!("sig({})", message.len())
+        );
+
+        assert!(build_ownership_statement(
+            "short",
+            &["bcrt1qcold".to_string()],
+            &utxos,
+            &EchoSigner,
+            Network::Regtest,
+            900,
+            1_000,
+        )
+        .is_err());
+        assert!(build_ownership_statement(
+            challenge,
+            &["bcrt1qnotours".to_string()],
+            &utxos,
+            &EchoSigner,
+            Network::Regtest,
+            900,
+            1_000,
+        )
+        .is_err());
+    }
+}
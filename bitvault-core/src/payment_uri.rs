@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::Network;
+
+/// URI scheme registered with the operating system.
+pub const BITCOIN_SCHEME: &str = "bitcoin";
+
+// Longest label or message kept from an untrusted URI
+const MAX_TEXT_LEN: usize = 140;
+// Longest URI accepted at all, to bound work on hostile input
+const MAX_URI_LEN: usize = 2_048;
+const SATS_PER_BTC: u64 = 100_000_000;
+
+/// A send prefilled from a BIP-21 URI, sanitized and checked against the wallet's
+/// network. Nothing in it has been confirmed by the user yet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentDraft {
+    pub address: String,
+    /// Requested amount in satoshis.
+    pub amount: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+impl PaymentDraft {
+    /// Builds the BIP-21 URI for this draft, e.g. for a receive QR code.
+    pub fn to_uri(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(amount) = self.amount {
+            params.push(format!("amount={}", format_btc(amount)));
+        }
+        if let Some(label) = &self.label {
+            params.push(format!("label={}", percent_encode(label)));
+        }
+        if let Some(message) = &self.message {
+            params.push(format!("message={}", percent_encode(message)));
+        }
+
+        if params.is_empty() {
+            format!("{}:{}", BITCOIN_SCHEME, self.address)
+        } else {
+            format!("{}:{}?{}", BITCOIN_SCHEME, self.address, params.join("&"))
+        }
+    }
+}
+
+/// Parses a `bitcoin:` URI received from the OS or a QR code into a draft.
+///
+/// Rejects URIs for another network, malformed amounts and unknown `req-`
+/// parameters (which BIP-21 says must not be ignored). Labels and messages are
+/// stripped of control characters and truncated.
+pub fn parse_payment_uri(uri: &str, network: Network) -> Result<PaymentDraft, String> {
+    let uri = uri.trim();
+    if uri.len() > MAX_URI_LEN {
+        return Err("Payment URI is too long".to_string());
+    }
+
+    let (scheme, rest) = uri
+        .split_once(':')
+        .ok_or_else(|| "Not a bitcoin: URI".to_string())?;
+    if !scheme.eq_ignore_ascii_case(BITCOIN_SCHEME) {
+        return Err(format!("Unsupported URI scheme: {}", sanitize_text(scheme)));
+    }
+    let rest = rest.trim_start_matches("//");
+
+    let (address, query) = match rest.split_once('?') {
+        Some((address, query)) => (address, Some(query)),
+        None => (rest, None),
+    };
+    check_address(address, network)?;
+
+    let mut draft = PaymentDraft {
+        address: address.to_string(),
+        ..Default::default()
+    };
+
+    for pair in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        match key.to_ascii_lowercase().as_str() {
+            "amount" => draft.amount = Some(parse_btc_amount(&value)?),
+            "label" => draft.label = Some(sanitize_text(&value)),
+            "message" => draft.message = Some(sanitize_text(&value)),
+            key if key.starts_with("req-") => {
+                return Err(format!(
+                    "Unsupported required parameter: {}",
+                    sanitize_text(key)
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(draft)
+}
+
+/// Checks that `address` is plausibly an address on `network`.
+///
+/// This looks at the prefix and character set only; full checksum validation
+/// happens when the draft is turned into a transaction.
+pub fn check_address(address: &str, network: Network) -> Result<(), String> {
+    if address.is_empty() {
+        return Err("Payment URI has no address".to_string());
+    }
+    if !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Address contains invalid characters".to_string());
+    }
+
+    let lower = address.to_ascii_lowercase();
+    let detected = if lower.starts_with("bcrt1") {
+        Some(Network::Regtest)
+    } else if lower.starts_with("bc1") || address.starts_with('1') || address.starts_with('3') {
+        Some(Network::Bitcoin)
+    } else if lower.starts_with("tb1")
+        || address.starts_with('m')
+        || address.starts_with('n')
+        || address.starts_with('2')
+    {
+        Some(Network::Testnet)
+    } else {
+        None
+    };
+
+    // Testnet and signet share address formats, as do legacy regtest addresses
+    let matches = match (detected, network) {
+        (None, _) => return Err("Unrecognized address format".to_string()),
+        (Some(Network::Testnet), Network::Signet | Network::Regtest) => true,
+        (Some(detected), network) => detected == network,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(format!(
+            "Address is for {:?}, but the wallet is on {:?}",
+            detected.unwrap_or_default(),
+            network
+        ))
+    }
+}
+
+/// Parses a decimal BTC amount ("0.001") into satoshis without going through floats.
+pub fn parse_btc_amount(amount: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid amount: {}", sanitize_text(amount));
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > 8
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: u64 = format!("{:0<8}", fraction).parse().map_err(|_| invalid())?;
+    whole
+        .checked_mul(SATS_PER_BTC)
+        .and_then(|sats| sats.checked_add(fraction))
+        .filter(|sats| *sats <= 21_000_000 * SATS_PER_BTC)
+        .ok_or_else(invalid)
+}
+
+fn format_btc(sats: u64) -> String {
+    let formatted = format!("{}.{:08}", sats / SATS_PER_BTC, sats % SATS_PER_BTC);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn sanitize_text(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_TEXT_LEN)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .ok_or_else(|| "Truncated percent-encoding".to_string())?;
+                let byte = u8::from_str_radix(hex, 16)
+                    .map_err(|_| format!("Invalid percent-encoding: %{}", hex))?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "Payment URI is not valid UTF-8".to_string())
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_round_trip() {
+        let uri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.0015&label=Caf%C3%A9%0A&foo=bar";
+        let draft = parse_payment_uri(uri, Network::Bitcoin).unwrap();
+        assert_eq!(draft.amount, Some(150_000));
+        assert_eq!(draft.label.as_deref(), Some("Café"));
+        assert_eq!(
+            parse_payment_uri(&draft.to_uri(), Network::Bitcoin).unwrap(),
+            draft
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsafe_uris() {
+        let address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        assert!(parse_payment_uri(&format!("bitcoin:{}", address), Network::Testnet).is_err());
+        assert!(
+            parse_payment_uri(&format!("bitcoin:{}?req-pay=1", address), Network::Bitcoin).is_err()
+        );
+        assert!(
+            parse_payment_uri(&format!("bitcoin:{}?amount=1e3", address), Network::Bitcoin)
+                .is_err()
+        );
+        assert!(parse_payment_uri("javascript:alert(1)", Network::Bitcoin).is_err());
+        assert_eq!(
+            parse_btc_amount("21000001"),
+            Err("Invalid amount: 21000001".to_string())
+        );
+    }
+}
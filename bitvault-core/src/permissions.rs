@@ -0,0 +1,353 @@
+//! Capability checks for commands invoked from a frontend.
+//!
+//! The webview (or any FFI caller) is treated as untrusted: each command it
+//! sends is checked here against the session and the approval engine before
+//! it runs, so script injected into the UI can't sign or loosen policy on
+//! its own. Unlocking and re-authenticating happen after the PIN has been
+//! verified by the caller. Frontends reach the wallet only through a
+//! [`CommandDispatcher`], which holds the gate.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::approval::ApprovalEngine;
+use crate::clock::SharedClock;
+use crate::signer::{SignedTransaction, TransactionSigner};
+use crate::types::{Transaction, Utxo};
+
+/// Commands a frontend can invoke.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    ReadBalance,
+    ReadHistory,
+    DeriveAddress,
+    /// Signing `tx`. The amount it sends is worked out from the outputs,
+    /// never taken from the frontend.
+    Sign {
+        tx: &'a Transaction,
+    },
+    /// Changing spending, approval or confirmation policy.
+    ChangePolicy,
+}
+
+impl Command<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::ReadBalance => "read balance",
+            Command::ReadHistory => "read history",
+            Command::DeriveAddress => "derive address",
+            Command::Sign { .. } => "sign",
+            Command::ChangePolicy => "change policy",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPolicy {
+    /// The session locks after this long without an authorized command.
+    pub idle_timeout_secs: u64,
+    /// Policy changes need the PIN entered again within this long.
+    pub reauth_window_secs: u64,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        SessionPolicy {
+            idle_timeout_secs: 5 * 60,
+            reauth_window_secs: 60,
+        }
+    }
+}
+
+/// Why a command was refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PermissionError {
+    Locked,
+    /// The session was idle too long and has been locked.
+    Expired,
+    ReauthenticationRequired,
+    /// The approval engine refused the spend.
+    NotApproved(String),
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionError::Locked => write!(f, "Wallet is locked"),
+            PermissionError::Expired => write!(f, "Session expired, unlock the wallet again"),
+            PermissionError::ReauthenticationRequired => {
+                write!(f, "Enter your PIN again to continue")
+            }
+            PermissionError::NotApproved(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Session state that every frontend command is checked against.
+pub struct CommandGate {
+    policy: SessionPolicy,
+    clock: SharedClock,
+    unlocked: bool,
+    last_activity: u64,
+    reauthenticated_at: Option<u64>,
+}
+
+impl CommandGate {
+    /// Starts locked.
+    pub fn new(policy: SessionPolicy, clock: SharedClock) -> Self {
+        CommandGate {
+            policy,
+            clock,
+            unlocked: false,
+            last_activity: 0,
+            reauthenticated_at: None,
+        }
+    }
+
+    /// Opens the session once the PIN has been checked.
+    pub fn unlock(&mut self) {
+        self.unlocked = true;
+        self.last_activity = self.clock.now();
+    }
+
+    pub fn lock(&mut self) {
+        self.unlocked = false;
+        self.reauthenticated_at = None;
+    }
+
+    /// Records that the PIN was entered again for a sensitive command.
+    pub fn reauthenticate(&mut self) -> Result<(), PermissionError> {
+        self.check_session()?;
+        self.reauthenticated_at = Some(self.clock.now());
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked && self.clock.now() < self.last_activity + self.policy.idle_timeout_secs
+    }
+
+    fn check_session(&mut self) -> Result<(), PermissionError> {
+        if !self.unlocked {
+            return Err(PermissionError::Locked);
+        }
+        if !self.is_unlocked() {
+            self.lock();
+            return Err(PermissionError::Expired);
+        }
+        Ok(())
+    }
+
+    /// Checks `command` against the session and, for signing, `approvals`.
+    /// Authorized commands count as activity.
+    pub fn authorize(
+        &mut self,
+        command: Command<'_>,
+        approvals: &ApprovalEngine,
+    ) -> Result<(), PermissionError> {
+        let result = self.check(command, approvals);
+        match &result {
+            Ok(()) => self.last_activity = self.clock.now(),
+            Err(e) => log::warn!(target: "security", "Refused {}: {}", command.name(), e),
+        }
+        result
+    }
+
+    fn check(
+        &mut self,
+        command: Command<'_>,
+        approvals: &ApprovalEngine,
+    ) -> Result<(), PermissionError> {
+        self.check_session()?;
+        match command {
+            Command::ReadBalance | Command::ReadHistory | Command::DeriveAddress => Ok(()),
+            Command::Sign { tx } => approvals
+                .authorize_signing(tx)
+                .map_err(PermissionError::NotApproved),
+            Command::ChangePolicy => {
+                let recent = self
+                    .reauthenticated_at
+                    .is_some_and(|at| self.clock.now() < at + self.policy.reauth_window_secs);
+                if !recent {
+                    return Err(PermissionError::ReauthenticationRequired);
+                }
+                // One re-entry of the PIN allows one change
+                self.reauthenticated_at = None;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Runs frontend commands once the gate allows them.
+pub struct CommandDispatcher {
+    gate: CommandGate,
+    approvals: ApprovalEngine,
+}
+
+impl CommandDispatcher {
+    pub fn new(gate: CommandGate, approvals: ApprovalEngine) -> Self {
+        CommandDispatcher { gate, approvals }
+    }
+
+    /// For unlocking, locking and re-authenticating after a PIN check.
+    pub fn gate_mut(&mut self) -> &mut CommandGate {
+        &mut self.gate
+    }
+
+    /// For requesting and granting approvals outside the frontend.
+    pub fn approvals_mut(&mut self) -> &mut ApprovalEngine {
+        &mut self.approvals
+    }
+
+    /// Runs `run` if `command` is allowed. Use [`CommandDispatcher::sign`]
+    /// for signing.
+    pub fn dispatch<T>(
+        &mut self,
+        command: Command<'_>,
+        run: impl FnOnce(&mut ApprovalEngine) -> T,
+    ) -> Result<T, PermissionError> {
+        self.gate.authorize(command, &self.approvals)?;
+        Ok(run(&mut self.approvals))
+    }
+
+    /// Hands `tx` to `signer` only if the session is open and the spend is
+    /// approved.
+    pub fn sign(
+        &mut self,
+        tx: &Transaction,
+        prevouts: &[Utxo],
+        signer: &dyn TransactionSigner,
+    ) -> Result<SignedTransaction, String> {
+        self.gate
+            .authorize(Command::Sign { tx }, &self.approvals)
+            .map_err(|e| e.to_string())?;
+        signer.sign(tx, prevouts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::ApprovalPolicy;
+    use crate::clock::MockClock;
+    use crate::crypto;
+    use crate::types::{OutPoint, TxIn, TxOut, SEQUENCE_FINAL};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingSigner(AtomicUsize);
+
+    impl TransactionSigner for CountingSigner {
+        fn id(&self) -> &str {
+            "counting"
+        }
+
+        fn sign(&self, tx: &Transaction, _prevouts: &[Utxo]) -> Result<SignedTransaction, String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(SignedTransaction {
+                transaction: tx.clone(),
+                witnesses: vec![vec!["00".to_string()]; tx.inputs.len()],
+            })
+        }
+    }
+
+    fn spend(value: u64) -> Transaction {
+        Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new("aa", 0),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: "bcrt1qpayee".to_string(),
+                value,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_commands_gated_by_session_and_approval() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let approvals = ApprovalEngine::new(
+            ApprovalPolicy::default(),
+            crypto::hash_credential("approver pass").unwrap(),
+            clock.clone(),
+        );
+        let mut gate = CommandGate::new(SessionPolicy::default(), clock.clone());
+        let small = spend(1_000);
+        let large = spend(50_000_000);
+
+        assert_eq!(
+            gate.authorize(Command::ReadBalance, &approvals),
+            Err(PermissionError::Locked)
+        );
+        gate.unlock();
+        assert!(gate.authorize(Command::DeriveAddress, &approvals).is_ok());
+        assert!(gate
+            .authorize(Command::Sign { tx: &small }, &approvals)
+            .is_ok());
+        assert_eq!(
+            gate.authorize(Command::Sign { tx: &large }, &approvals),
+            Err(PermissionError::NotApproved(
+                "This spend needs approval before signing".to_string()
+            ))
+        );
+
+        assert_eq!(
+            gate.authorize(Command::ChangePolicy, &approvals),
+            Err(PermissionError::ReauthenticationRequired)
+        );
+        gate.reauthenticate().unwrap();
+        assert!(gate.authorize(Command::ChangePolicy, &approvals).is_ok());
+        assert!(gate.authorize(Command::ChangePolicy, &approvals).is_err());
+
+        clock.advance(SessionPolicy::default().idle_timeout_secs);
+        assert_eq!(
+            gate.authorize(Command::ReadHistory, &approvals),
+            Err(PermissionError::Expired)
+        );
+        assert_eq!(
+            gate.authorize(Command::Sign { tx: &small }, &approvals),
+            Err(PermissionError::Locked)
+        );
+    }
+
+    #[test]
+    fn test_dispatcher_signs_only_through_the_gate() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let approvals = ApprovalEngine::new(
+            ApprovalPolicy::default(),
+            crypto::hash_credential("approver pass").unwrap(),
+            clock.clone(),
+        );
+        let gate = CommandGate::new(SessionPolicy::default(), clock.clone());
+        let mut dispatcher = CommandDispatcher::new(gate, approvals);
+        let signer = CountingSigner(AtomicUsize::new(0));
+        let small = spend(1_000);
+        let large = spend(50_000_000);
+
+        assert_eq!(
+            dispatcher.sign(&small, &[], &signer),
+            Err("Wallet is locked".to_string())
+        );
+        assert_eq!(
+            dispatcher.dispatch(Command::ReadBalance, |_| 42),
+            Err(PermissionError::Locked)
+        );
+        assert_eq!(signer.0.load(Ordering::SeqCst), 0);
+
+        dispatcher.gate_mut().unlock();
+        assert_eq!(dispatcher.dispatch(Command::ReadBalance, |_| 42), Ok(42));
+        assert!(dispatcher.sign(&small, &[], &signer).unwrap().is_complete());
+        assert!(dispatcher.sign(&large, &[], &signer).is_err());
+        assert_eq!(signer.0.load(Ordering::SeqCst), 1);
+
+        let id = dispatcher.approvals_mut().request(&large).unwrap();
+        dispatcher
+            .approvals_mut()
+            .approve(&id, "approver pass")
+            .unwrap();
+        assert!(dispatcher.sign(&large, &[], &signer).is_ok());
+        assert_eq!(signer.0.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::config_manager::SecretStore;
+use crate::events::{Event, Subscription};
+use crate::notifier::{NotificationSummary, NotifyEventKind};
+
+pub mod capabilities;
+pub mod instance_lock;
+
+use capabilities::{Capability, PlatformCapabilities, Support};
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// How insistently a notification should be presented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+/// Operating system integration shared by every frontend.
+pub trait PlatformProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Shows a desktop notification.
+    fn notify(&self, title: &str, body: &str, urgency: Urgency) -> Result<(), String>;
+
+    /// Registers `executable` as the handler for `scheme:` URIs (e.g. `bitcoin:`),
+    /// so the OS launches it with the URI as its argument.
+    fn register_uri_handler(&self, scheme: &str, _executable: &Path) -> Result<(), String> {
+        Err(format!(
+            "Registering {}: URIs is not supported on {}",
+            scheme,
+            self.name()
+        ))
+    }
+
+    /// Overwrites a file before deleting it so its contents can't be recovered
+    /// from the filesystem. Platforms with a native secure delete can override this.
+    fn secure_erase(&self, path: &Path) -> Result<(), String> {
+        overwrite_and_remove(path, SECURE_ERASE_PASSES)
+    }
+
+    /// Saves a secret in the OS credential store (keychain, credential
+    /// manager, secret service), replacing any existing entry.
+    fn store_secret(&self, key: &str, _value: &str) -> Result<(), String> {
+        Err(format!(
+            "Cannot store {}: no secure storage on {}",
+            key,
+            self.name()
+        ))
+    }
+
+    fn load_secret(&self, _key: &str) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    /// Removes a secret from the OS credential store. Returns whether an entry existed.
+    fn delete_secret(&self, _key: &str) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    /// Whether a process with this id is running, or `None` if it can't be told.
+    fn is_process_alive(&self, _pid: u32) -> Option<bool> {
+        None
+    }
+
+    /// The user's locale for dates and times, e.g. `en_US.UTF-8`.
+    fn locale(&self) -> Option<String> {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+    }
+
+    /// Whether `capability` is usable here. Providers override this for the
+    /// capabilities they know how to probe.
+    fn capability(&self, capability: Capability) -> Support {
+        capabilities::default_support(self.name(), capability)
+    }
+
+    /// The local timezone's current offset east of UTC, in seconds.
+    fn utc_offset_secs(&self) -> Option<i32> {
+        None
+    }
+
+    /// Whether the active network connection is metered (mobile data, a
+    /// capped hotspot), or `None` if it can't be told.
+    fn is_metered_connection(&self) -> Option<bool> {
+        None
+    }
+}
+
+/// Parses the `+hhmm` offset printed by `date +%z`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn parse_utc_offset(output: &str) -> Option<i32> {
+    let output = output.trim();
+    let sign = match output.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i32 = output.get(1..3)?.parse().ok()?;
+    let minutes: i32 = output.get(3..5)?.parse().ok()?;
+    Some(sign * (hours * 3_600 + minutes * 60))
+}
+
+/// Random overwrite passes made by the default [`PlatformProvider::secure_erase`].
+pub const SECURE_ERASE_PASSES: usize = 3;
+
+/// Overwrites `path` with `passes` rounds of random data and a final round of
+/// zeros, syncing each to disk, then truncates and deletes it.
+///
+/// Copy-on-write filesystems and SSD wear levelling may keep old blocks around;
+/// this removes the data from the file itself, which is all user space can do.
+pub fn overwrite_and_remove(path: &Path, passes: usize) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?
+        .len() as usize;
+
+    let mut buffer = vec![0u8; len];
+    for pass in 0..=passes {
+        if pass < passes {
+            getrandom::fill(&mut buffer)
+                .map_err(|e| format!("Failed to get random bytes: {}", e))?;
+        } else {
+            buffer.fill(0);
+        }
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(&buffer))
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to overwrite {}: {}", path.display(), e))?;
+    }
+
+    file.set_len(0)
+        .map_err(|e| format!("Failed to truncate {}: {}", path.display(), e))?;
+    drop(file);
+    fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+/// Provider for environments without a desktop, such as CI or the CLI over SSH.
+/// Notifications are only logged.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeadlessPlatform;
+
+impl PlatformProvider for HeadlessPlatform {
+    fn name(&self) -> &str {
+        "headless"
+    }
+
+    fn notify(&self, title: &str, body: &str, _urgency: Urgency) -> Result<(), String> {
+        log::info!("Notification: {}: {}", title, body);
+        Ok(())
+    }
+
+    fn capability(&self, capability: Capability) -> Support {
+        match capability {
+            Capability::Notifications => Support::unavailable("Notifications are only logged"),
+            _ => capabilities::default_support(self.name(), capability),
+        }
+    }
+}
+
+/// Adapts a provider's credential store for [`ConfigManager`](crate::config_manager::ConfigManager).
+pub struct PlatformSecretStore(pub Box<dyn PlatformProvider>);
+
+impl SecretStore for PlatformSecretStore {
+    fn store_secret(&self, key: &str, value: &str) -> Result<(), String> {
+        self.0.store_secret(key, value)
+    }
+
+    fn load_secret(&self, key: &str) -> Result<Option<String>, String> {
+        self.0.load_secret(key)
+    }
+}
+
+/// The provider for the operating system this build targets.
+pub fn current() -> Box<dyn PlatformProvider> {
+    #[cfg(target_os = "linux")]
+    let provider: Box<dyn PlatformProvider> = Box::new(linux::LinuxPlatform);
+    #[cfg(target_os = "macos")]
+    let provider: Box<dyn PlatformProvider> = Box::new(macos::MacOsPlatform);
+    #[cfg(target_os = "windows")]
+    let provider: Box<dyn PlatformProvider> = Box::new(windows::WindowsPlatform);
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    let provider: Box<dyn PlatformProvider> = Box::new(HeadlessPlatform);
+    provider
+}
+
+/// What the current platform supports, for frontends to hide the rest.
+pub fn capabilities() -> PlatformCapabilities {
+    PlatformCapabilities::detect(current().as_ref())
+}
+
+/// Raises an OS notification for events users care about (payments,
+/// confirmations, security alerts), using the same sanitized text as webhooks.
+/// Returns whether a notification was shown.
+pub fn notify_event(
+    provider: &dyn PlatformProvider,
+    event: &Event,
+    now: u64,
+) -> Result<bool, String> {
+    let Some(summary) = NotificationSummary::from_event(event, now) else {
+        return Ok(false);
+    };
+    let (title, urgency) = match summary.event {
+        NotifyEventKind::PaymentReceived => ("Payment received", Urgency::Normal),
+        NotifyEventKind::TransactionConfirmed => ("Transaction confirmed", Urgency::Low),
+        NotifyEventKind::SecurityAlert => ("Security alert", Urgency::Critical),
+    };
+    provider.notify(title, &summary.message, urgency)?;
+    Ok(true)
+}
+
+/// Shows notifications for every event already queued on `receiver`.
+pub fn drain_events(provider: &dyn PlatformProvider, receiver: &Subscription, now: u64) {
+    for event in receiver.try_iter() {
+        if let Err(e) = notify_event(provider, &event, now) {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingPlatform(Mutex<Vec<(String, Urgency)>>);
+
+    impl PlatformProvider for RecordingPlatform {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn notify(&self, title: &str, _body: &str, urgency: Urgency) -> Result<(), String> {
+            self.0.lock().unwrap().push((title.to_string(), urgency));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_notify_event() {
+        let platform = RecordingPlatform::default();
+        let alert = Event::SecurityAlert {
+            message: "3 consecutive failed unlock attempts".to_string(),
+        };
+        assert!(notify_event(&platform, &alert, 0).unwrap());
+        assert!(!notify_event(&platform, &Event::BackupVerified { verified_at: 0 }, 0).unwrap());
+        assert_eq!(
+            *platform.0.lock().unwrap(),
+            vec![("Security alert".to_string(), Urgency::Critical)]
+        );
+    }
+}
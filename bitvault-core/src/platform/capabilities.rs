@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use super::PlatformProvider;
+
+/// Tor's default SOCKS port on localhost.
+pub const TOR_SOCKS_ADDR: &str = "127.0.0.1:9050";
+
+/// Features that depend on the operating system, hardware or build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Talking to hardware wallets over USB.
+    HardwareWalletUsb,
+    /// A camera for scanning QR codes.
+    Camera,
+    /// An OS credential store for secrets.
+    SecureStorage,
+    /// A local Tor SOCKS proxy.
+    Tor,
+    /// Desktop notifications.
+    Notifications,
+    /// The OS random number generator that keys are made from.
+    OsEntropy,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 6] = [
+        Capability::HardwareWalletUsb,
+        Capability::Camera,
+        Capability::SecureStorage,
+        Capability::Tor,
+        Capability::Notifications,
+        Capability::OsEntropy,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Support {
+    Available,
+    /// Not usable, with a reason the frontend can show.
+    Unavailable {
+        reason: String,
+    },
+}
+
+impl Support {
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Support::Unavailable {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        matches!(self, Support::Available)
+    }
+}
+
+/// Whether `program` is an executable file on `PATH`.
+pub(crate) fn command_exists(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Whether a Tor SOCKS proxy accepts connections on its default port.
+pub fn detect_tor() -> Support {
+    let Ok(addr) = TOR_SOCKS_ADDR.parse::<SocketAddr>() else {
+        return Support::unavailable("Invalid Tor proxy address");
+    };
+    match TcpStream::connect_timeout(&addr, Duration::from_millis(200)) {
+        Ok(_) => Support::Available,
+        Err(_) => Support::unavailable(format!("No Tor proxy listening on {}", TOR_SOCKS_ADDR)),
+    }
+}
+
+/// Answers for capabilities a provider has no specific knowledge of.
+pub fn default_support(platform: &str, capability: Capability) -> Support {
+    match capability {
+        Capability::Notifications => Support::Available,
+        Capability::Tor => detect_tor(),
+        Capability::OsEntropy => match getrandom::fill(&mut [0u8; 32]) {
+            Ok(()) => Support::Available,
+            Err(e) => Support::unavailable(format!("The OS random number generator failed: {}", e)),
+        },
+        Capability::HardwareWalletUsb if cfg!(feature = "usb") => Support::Available,
+        Capability::HardwareWalletUsb => Support::unavailable("Built without USB support"),
+        _ => Support::unavailable(format!("Not supported on {}", platform)),
+    }
+}
+
+/// What this installation can do, so frontends hide features up front
+/// instead of failing when they're used.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformCapabilities {
+    entries: BTreeMap<Capability, Support>,
+}
+
+impl PlatformCapabilities {
+    /// Probes every capability through `provider`.
+    pub fn detect(provider: &dyn PlatformProvider) -> Self {
+        PlatformCapabilities {
+            entries: Capability::ALL
+                .into_iter()
+                .map(|capability| (capability, provider.capability(capability)))
+                .collect(),
+        }
+    }
+
+    pub fn support(&self, capability: Capability) -> Support {
+        self.entries
+            .get(&capability)
+            .cloned()
+            .unwrap_or_else(|| Support::unavailable("Not detected"))
+    }
+
+    pub fn is_available(&self, capability: Capability) -> bool {
+        self.support(capability).is_available()
+    }
+
+    /// Records what a frontend found out itself, such as camera permission.
+    pub fn set(&mut self, capability: Capability, support: Support) {
+        self.entries.insert(capability, support);
+    }
+
+    pub fn available(&self) -> impl Iterator<Item = Capability> + '_ {
+        self.entries
+            .iter()
+            .filter(|(_, support)| support.is_available())
+            .map(|(capability, _)| *capability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::HeadlessPlatform;
+
+    #[test]
+    fn test_registry_query_and_override() {
+        let mut capabilities = PlatformCapabilities::detect(&HeadlessPlatform);
+        assert!(!capabilities.is_available(Capability::Notifications));
+        assert!(!capabilities.is_available(Capability::SecureStorage));
+
+        capabilities.set(Capability::Camera, Support::Available);
+        assert!(capabilities.available().any(|c| c == Capability::Camera));
+
+        let json = serde_json::to_string(&capabilities).unwrap();
+        assert!(json.contains("\"secure_storage\":{\"status\":\"unavailable\""));
+        let restored: PlatformCapabilities = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, capabilities);
+    }
+}
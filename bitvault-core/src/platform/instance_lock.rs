@@ -0,0 +1,165 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::PlatformProvider;
+
+/// Name of the lock file inside the wallet data directory.
+pub const LOCK_FILE_NAME: &str = "bitvault.lock";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InstanceLockError {
+    /// Another running BitVault process has the data directory open.
+    HeldBy {
+        pid: u32,
+    },
+    /// A lock was left behind by a process that is gone (or unreadable).
+    /// [`InstanceLock::take_over`] may be used after asking the user.
+    Stale {
+        pid: Option<u32>,
+    },
+    Io(String),
+}
+
+impl fmt::Display for InstanceLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceLockError::HeldBy { pid } => write!(
+                f,
+                "The wallet is already open in another BitVault window (process {})",
+                pid
+            ),
+            InstanceLockError::Stale { pid: Some(pid) } => {
+                write!(f, "The wallet was not closed cleanly by process {}", pid)
+            }
+            InstanceLockError::Stale { pid: None } => {
+                write!(f, "The wallet lock file is unreadable")
+            }
+            InstanceLockError::Io(e) => write!(f, "Failed to lock the wallet: {}", e),
+        }
+    }
+}
+
+/// Exclusive claim on a wallet data directory, released on drop.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Claims `dir` for this process. Fails if another live process holds it,
+    /// or if a stale lock is found, so the user can decide whether to take over.
+    pub fn acquire(
+        dir: &Path,
+        platform: &dyn PlatformProvider,
+    ) -> Result<InstanceLock, InstanceLockError> {
+        let path = dir.join(LOCK_FILE_NAME);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                write!(file, "{}", std::process::id())
+                    .and_then(|_| file.sync_all())
+                    .map_err(|e| InstanceLockError::Io(e.to_string()))?;
+                Ok(InstanceLock { path })
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(Self::inspect(&path, platform)),
+            Err(e) => Err(InstanceLockError::Io(e.to_string())),
+        }
+    }
+
+    // Why an existing lock file blocks us
+    fn inspect(path: &Path, platform: &dyn PlatformProvider) -> InstanceLockError {
+        let pid = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u32>().ok());
+        match pid {
+            // If liveness can't be checked, assume the worst
+            Some(pid) if platform.is_process_alive(pid) != Some(false) => {
+                InstanceLockError::HeldBy { pid }
+            }
+            pid => InstanceLockError::Stale { pid },
+        }
+    }
+
+    /// Removes a stale lock and claims `dir`. Still refuses when the holder is
+    /// known to be running.
+    pub fn take_over(
+        dir: &Path,
+        platform: &dyn PlatformProvider,
+    ) -> Result<InstanceLock, InstanceLockError> {
+        let path = dir.join(LOCK_FILE_NAME);
+        if let InstanceLockError::HeldBy { pid } = Self::inspect(&path, platform) {
+            if platform.is_process_alive(pid) == Some(true) {
+                return Err(InstanceLockError::HeldBy { pid });
+            }
+        }
+        log::warn!("Taking over wallet lock {}", path.display());
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(InstanceLockError::Io(e.to_string())),
+        }
+        Self::acquire(dir, platform)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::{HeadlessPlatform, Urgency};
+
+    struct LivenessPlatform(Option<bool>);
+
+    impl PlatformProvider for LivenessPlatform {
+        fn name(&self) -> &str {
+            "liveness"
+        }
+
+        fn notify(&self, _title: &str, _body: &str, _urgency: Urgency) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn is_process_alive(&self, _pid: u32) -> Option<bool> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_single_instance_and_take_over() {
+        let dir = std::env::temp_dir().join(format!("bitvault_lock_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let lock = InstanceLock::acquire(&dir, &HeadlessPlatform).unwrap();
+        let pid = std::process::id();
+        assert_eq!(
+            InstanceLock::acquire(&dir, &LivenessPlatform(Some(true))).unwrap_err(),
+            InstanceLockError::HeldBy { pid }
+        );
+        // Leave the lock file behind as a crashed process would
+        std::mem::forget(lock);
+
+        let dead = LivenessPlatform(Some(false));
+        assert_eq!(
+            InstanceLock::acquire(&dir, &dead).unwrap_err(),
+            InstanceLockError::Stale { pid: Some(pid) }
+        );
+        assert!(InstanceLock::take_over(&dir, &LivenessPlatform(Some(true))).is_err());
+        let lock = InstanceLock::take_over(&dir, &dead).unwrap();
+        let path = lock.path().to_path_buf();
+        drop(lock);
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,161 @@
+use notify_rust::{Notification, Urgency as DbusUrgency};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use super::capabilities::{self, Capability, Support};
+use super::{PlatformProvider, Urgency};
+
+// Attribute identifying BitVault's items in the secret service
+const SECRET_SERVICE: &str = "bitvault";
+
+/// Freedesktop notifications over DBus.
+pub struct LinuxPlatform;
+
+impl PlatformProvider for LinuxPlatform {
+    fn name(&self) -> &str {
+        "linux"
+    }
+
+    fn notify(&self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        let urgency = match urgency {
+            Urgency::Low => DbusUrgency::Low,
+            Urgency::Normal => DbusUrgency::Normal,
+            Urgency::Critical => DbusUrgency::Critical,
+        };
+        Notification::new()
+            .appname("BitVault")
+            .summary(title)
+            .body(body)
+            .urgency(urgency)
+            .show()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to send DBus notification: {}", e))
+    }
+
+    fn capability(&self, capability: Capability) -> Support {
+        match capability {
+            Capability::SecureStorage if !capabilities::command_exists("secret-tool") => {
+                Support::unavailable("secret-tool (libsecret) is not installed")
+            }
+            Capability::SecureStorage => Support::Available,
+            Capability::Camera if Path::new("/dev/video0").exists() => Support::Available,
+            Capability::Camera => Support::unavailable("No video device found"),
+            Capability::OsEntropy if !Path::new("/dev/urandom").exists() => {
+                Support::unavailable("/dev/urandom is missing")
+            }
+            _ => capabilities::default_support(self.name(), capability),
+        }
+    }
+
+    fn utc_offset_secs(&self) -> Option<i32> {
+        let output = Command::new("date").arg("+%z").output().ok()?;
+        super::parse_utc_offset(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// NetworkManager's `Metered` property, printed by busctl as `u <value>`.
+    fn is_metered_connection(&self) -> Option<bool> {
+        let output = Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match stdout.trim().strip_prefix("u ")? {
+            // Yes, and guessed yes
+            "1" | "3" => Some(true),
+            "2" | "4" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Stores the secret through libsecret's `secret-tool`, passing it on stdin.
+    fn store_secret(&self, key: &str, value: &str) -> Result<(), String> {
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label", &format!("BitVault {}", key)])
+            .args(["service", SECRET_SERVICE, "key", key])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(value.as_bytes())
+                .map_err(|e| format!("Failed to pass secret to secret-tool: {}", e))?;
+        }
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("secret-tool store exited with {}", status))
+        }
+    }
+
+    fn load_secret(&self, key: &str) -> Result<Option<String>, String> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "service", SECRET_SERVICE, "key", key])
+            .output()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        // lookup exits non-zero when there is no matching item
+        Ok(output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    fn delete_secret(&self, key: &str) -> Result<bool, String> {
+        let existed = self.load_secret(key)?.is_some();
+        let status = Command::new("secret-tool")
+            .args(["clear", "service", SECRET_SERVICE, "key", key])
+            .status()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        if status.success() {
+            Ok(existed)
+        } else {
+            Err(format!("secret-tool clear exited with {}", status))
+        }
+    }
+
+    fn is_process_alive(&self, pid: u32) -> Option<bool> {
+        Some(Path::new("/proc").join(pid.to_string()).exists())
+    }
+
+    /// Installs a desktop entry declaring the scheme handler and makes it the default.
+    fn register_uri_handler(&self, scheme: &str, executable: &Path) -> Result<(), String> {
+        let applications = dirs::data_dir()
+            .ok_or_else(|| "Could not find the data directory".to_string())?
+            .join("applications");
+        fs::create_dir_all(&applications)
+            .map_err(|e| format!("Failed to create {}: {}", applications.display(), e))?;
+
+        let desktop_file = format!("bitvault-{}-handler.desktop", scheme);
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=BitVault\nExec=\"{}\" %u\nNoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+            executable.display(),
+            scheme
+        );
+        fs::write(applications.join(&desktop_file), entry)
+            .map_err(|e| format!("Failed to write desktop entry: {}", e))?;
+
+        let status = Command::new("xdg-mime")
+            .args([
+                "default",
+                &desktop_file,
+                &format!("x-scheme-handler/{}", scheme),
+            ])
+            .status()
+            .map_err(|e| format!("Failed to run xdg-mime: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("xdg-mime exited with {}", status))
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use notify_rust::Notification;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use super::capabilities::{self, Capability, Support};
+use super::{PlatformProvider, Urgency};
+
+// Service name of BitVault's generic passwords in the login keychain
+const KEYCHAIN_SERVICE: &str = "bitvault";
+
+/// Notification Center alerts through the UserNotifications framework.
+pub struct MacOsPlatform;
+
+impl PlatformProvider for MacOsPlatform {
+    fn name(&self) -> &str {
+        "macos"
+    }
+
+    fn notify(&self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification.summary(title).body(body);
+        // macOS has no urgency levels; critical alerts get a sound instead
+        if urgency == Urgency::Critical {
+            notification.sound_name("Basso");
+        }
+        notification
+            .show()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to show notification: {}", e))
+    }
+
+    fn capability(&self, capability: Capability) -> Support {
+        match capability {
+            // The login keychain is always there
+            Capability::SecureStorage => Support::Available,
+            // Camera access is granted per app when first used
+            Capability::Camera => Support::Available,
+            _ => capabilities::default_support(self.name(), capability),
+        }
+    }
+
+    fn utc_offset_secs(&self) -> Option<i32> {
+        let output = Command::new("date").arg("+%z").output().ok()?;
+        super::parse_utc_offset(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn store_secret(&self, key: &str, value: &str) -> Result<(), String> {
+        // Commands are fed to `security -i` on stdin so the secret never
+        // appears in the process list
+        let mut child = Command::new("security")
+            .arg("-i")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(
+                stdin,
+                "add-generic-password -U -s {} -a \"{}\" -w \"{}\"",
+                KEYCHAIN_SERVICE,
+                key.replace('"', "\\\""),
+                value.replace('"', "\\\"")
+            )
+            .map_err(|e| format!("Failed to pass secret to security: {}", e))?;
+        }
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("security exited with {}", status))
+        }
+    }
+
+    fn load_secret(&self, key: &str) -> Result<Option<String>, String> {
+        let output = Command::new("security")
+            .args([
+                "find-generic-password",
+                "-s",
+                KEYCHAIN_SERVICE,
+                "-a",
+                key,
+                "-w",
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        Ok(output.status.success().then(|| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim_end_matches('\n')
+                .to_string()
+        }))
+    }
+
+    fn delete_secret(&self, key: &str) -> Result<bool, String> {
+        let status = Command::new("security")
+            .args(["delete-generic-password", "-s", KEYCHAIN_SERVICE, "-a", key])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        // Exit status 44 means the item was not found
+        Ok(status.success())
+    }
+
+    fn is_process_alive(&self, pid: u32) -> Option<bool> {
+        // Signal 0 only checks that the process exists
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .ok()
+            .map(|status| status.success())
+    }
+
+    fn register_uri_handler(&self, scheme: &str, _executable: &Path) -> Result<(), String> {
+        // Launch Services reads CFBundleURLTypes from the bundle's Info.plist when
+        // the app is installed; there is nothing to do at runtime
+        Err(format!(
+            "{}: URIs are registered through the app bundle's Info.plist",
+            scheme
+        ))
+    }
+}
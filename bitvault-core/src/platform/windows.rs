@@ -0,0 +1,170 @@
+use notify_rust::{Notification, Timeout};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{PlatformProvider, Urgency};
+
+// Protected secrets live here, one file per key
+fn secret_path(key: &str) -> Result<PathBuf, String> {
+    let dir = dirs::data_local_dir()
+        .ok_or_else(|| "No local app data directory".to_string())?
+        .join("BitVault")
+        .join("secrets");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir.join(hex::encode(key)))
+}
+
+// Runs a PowerShell script with `input` on stdin, so secrets never appear in
+// the process list
+fn powershell(script: &str, input: &str) -> Result<String, String> {
+    let mut child = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run powershell: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to pass secret to powershell: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run powershell: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("powershell exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Toast notifications through the Windows notification platform.
+pub struct WindowsPlatform;
+
+impl PlatformProvider for WindowsPlatform {
+    fn name(&self) -> &str {
+        "windows"
+    }
+
+    fn notify(&self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification.summary(title).body(body);
+        // Critical toasts stay in the action center until dismissed
+        if urgency == Urgency::Critical {
+            notification.timeout(Timeout::Never);
+        }
+        notification
+            .show()
+            .map_err(|e| format!("Failed to show toast: {}", e))
+    }
+
+    fn is_process_alive(&self, pid: u32) -> Option<bool> {
+        let output = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+            .output()
+            .ok()?;
+        let listing = String::from_utf8_lossy(&output.stdout);
+        Some(listing.contains(&format!("\"{}\"", pid)))
+    }
+
+    fn utc_offset_secs(&self) -> Option<i32> {
+        let output = Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                "[TimeZoneInfo]::Local.GetUtcOffset([DateTime]::Now).TotalMinutes",
+            ])
+            .output()
+            .ok()?;
+        let minutes: f64 = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .ok()?;
+        Some((minutes * 60.0) as i32)
+    }
+
+    /// The internet connection profile's cost: `Fixed` and `Variable` plans
+    /// are metered.
+    fn is_metered_connection(&self) -> Option<bool> {
+        let output = Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                "[Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,\
+                 ContentType=WindowsRuntime]::GetInternetConnectionProfile().GetConnectionCost().NetworkCostType",
+            ])
+            .output()
+            .ok()?;
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "Fixed" | "Variable" => Some(true),
+            "Unrestricted" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Encrypts the secret with DPAPI for the current user and keeps the
+    /// result under the local app data directory. Only this Windows account
+    /// can decrypt it.
+    fn store_secret(&self, key: &str, value: &str) -> Result<(), String> {
+        let protected = powershell(
+            "ConvertTo-SecureString -String ([Console]::In.ReadToEnd()) -AsPlainText -Force \
+             | ConvertFrom-SecureString",
+            value,
+        )?;
+        let path = secret_path(key)?;
+        fs::write(&path, protected.trim())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn load_secret(&self, key: &str) -> Result<Option<String>, String> {
+        let path = secret_path(key)?;
+        let protected = match fs::read_to_string(&path) {
+            Ok(protected) => protected,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        powershell(
+            "$secure = ConvertTo-SecureString ([Console]::In.ReadToEnd().Trim()); \
+             [Console]::Out.Write([System.Net.NetworkCredential]::new('', $secure).Password)",
+            &protected,
+        )
+        .map(Some)
+    }
+
+    fn delete_secret(&self, key: &str) -> Result<bool, String> {
+        let path = secret_path(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(format!("Failed to remove {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes the per-user URL protocol keys under `HKCU\Software\Classes`.
+    fn register_uri_handler(&self, scheme: &str, executable: &Path) -> Result<(), String> {
+        let key = format!(r"HKCU\Software\Classes\{}", scheme);
+        let command = format!("\"{}\" \"%1\"", executable.display());
+        let entries = [
+            (key.clone(), None, format!("URL:{} Protocol", scheme)),
+            (key.clone(), Some("URL Protocol"), String::new()),
+            (format!(r"{}\shell\open\command", key), None, command),
+        ];
+
+        for (key, name, value) in entries {
+            let mut reg = Command::new("reg");
+            reg.args(["add", &key, "/f", "/d", &value]);
+            match name {
+                Some(name) => reg.args(["/v", name]),
+                None => reg.arg("/ve"),
+            };
+            let status = reg
+                .status()
+                .map_err(|e| format!("Failed to run reg: {}", e))?;
+            if !status.success() {
+                return Err(format!("reg add {} exited with {}", key, status));
+            }
+        }
+        Ok(())
+    }
+}
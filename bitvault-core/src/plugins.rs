@@ -0,0 +1,335 @@
+//! Extension points for third-party selection strategies and fee providers.
+//!
+//! Plugins are partially trusted: they only ever receive the [`CoinView`]s and
+//! fee targets the host hands them, never keys, descriptors or the wallet
+//! itself. Coin addresses and outpoints are withheld unless the user granted
+//! the matching [`Capability`]. Whatever a plugin returns is validated and fed
+//! through the regular selection and fee code, so a misbehaving plugin can
+//! degrade a result but can't produce an invalid transaction.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::fee_estimation::FeeProvider;
+use crate::types::Utxo;
+use crate::utxo_selection::{self, SelectionParams, SelectionResult, SelectionStrategy};
+
+/// Version of the plugin traits; plugins built against another version are refused.
+pub const PLUGIN_API_VERSION: u32 = 1;
+/// Rates above this are treated as a plugin bug rather than a fee market.
+pub const MAX_PLUGIN_FEE_RATE: f64 = 10_000.0;
+
+/// Data a plugin may ask to see beyond values and confirmation counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Capability {
+    /// Addresses of the candidate coins.
+    CoinAddresses,
+    /// Outpoints of the candidate coins.
+    CoinOutpoints,
+}
+
+/// What a plugin declares about itself when registered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    pub api_version: u32,
+    pub capabilities: BTreeSet<Capability>,
+}
+
+/// A candidate coin as shown to a plugin.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinView {
+    /// Position in the candidate list; orderings refer to coins by this.
+    pub index: usize,
+    pub value: u64,
+    pub confirmations: u32,
+    pub is_coinbase: bool,
+    pub address: Option<String>,
+    pub outpoint: Option<String>,
+}
+
+/// Orders candidate coins for selection.
+pub trait SelectionPlugin: Send + Sync {
+    fn manifest(&self) -> PluginManifest;
+
+    /// Returns coin indices in the order they should be spent. Coins left out
+    /// are not spent.
+    fn order(&self, coins: &[CoinView], target: u64, fee_rate: u64) -> Result<Vec<usize>, String>;
+
+    /// Built-in strategy used when the plugin fails or returns an invalid order.
+    fn fallback(&self) -> SelectionStrategy {
+        SelectionStrategy::LargestFirst
+    }
+}
+
+/// Supplies fee rates in sat/vB keyed by confirmation target.
+pub trait FeePlugin: Send + Sync {
+    fn manifest(&self) -> PluginManifest;
+
+    fn fetch(&self) -> Result<BTreeMap<u32, f64>, String>;
+}
+
+// A selection plugin and the capabilities it was admitted with. Later
+// manifests are never consulted, so a plugin can't widen its access.
+struct AdmittedSelection {
+    plugin: Arc<dyn SelectionPlugin>,
+    capabilities: BTreeSet<Capability>,
+}
+
+/// Registers plugins against the capabilities the user granted.
+pub struct PluginHost {
+    granted: BTreeSet<Capability>,
+    selection: BTreeMap<String, AdmittedSelection>,
+    fees: BTreeMap<String, Arc<dyn FeePlugin>>,
+}
+
+impl PluginHost {
+    pub fn new(granted: BTreeSet<Capability>) -> Self {
+        PluginHost {
+            granted,
+            selection: BTreeMap::new(),
+            fees: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the capabilities the plugin is admitted with.
+    fn admit(&self, manifest: &PluginManifest) -> Result<BTreeSet<Capability>, String> {
+        if manifest.api_version != PLUGIN_API_VERSION {
+            return Err(format!(
+                "Plugin {} targets API version {}, expected {}",
+                manifest.name, manifest.api_version, PLUGIN_API_VERSION
+            ));
+        }
+        if let Some(missing) = manifest.capabilities.difference(&self.granted).next() {
+            return Err(format!(
+                "Plugin {} requests {:?}, which was not granted",
+                manifest.name, missing
+            ));
+        }
+        Ok(manifest
+            .capabilities
+            .intersection(&self.granted)
+            .copied()
+            .collect())
+    }
+
+    pub fn register_selection(&mut self, plugin: Arc<dyn SelectionPlugin>) -> Result<(), String> {
+        let manifest = plugin.manifest();
+        let capabilities = self.admit(&manifest)?;
+        if self.selection.contains_key(&manifest.name) {
+            return Err(format!("Plugin {} is already registered", manifest.name));
+        }
+        self.selection.insert(
+            manifest.name,
+            AdmittedSelection {
+                plugin,
+                capabilities,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn register_fee(&mut self, plugin: Arc<dyn FeePlugin>) -> Result<(), String> {
+        let manifest = plugin.manifest();
+        self.admit(&manifest)?;
+        if self.fees.contains_key(&manifest.name) {
+            return Err(format!("Plugin {} is already registered", manifest.name));
+        }
+        self.fees.insert(manifest.name, plugin);
+        Ok(())
+    }
+
+    pub fn selection_plugins(&self) -> Vec<String> {
+        self.selection.keys().cloned().collect()
+    }
+
+    /// Runs a selection with the coin order chosen by plugin `name`.
+    ///
+    /// Falls back to the plugin's built-in strategy if it errors, panics, or
+    /// returns indices that are out of range or repeated.
+    pub fn select(&self, name: &str, utxos: &[Utxo], params: &SelectionParams) -> SelectionResult {
+        let Some(AdmittedSelection {
+            plugin,
+            capabilities,
+        }) = self.selection.get(name)
+        else {
+            return SelectionResult::ConstraintViolation {
+                reason: format!("Unknown selection plugin {}", name),
+            };
+        };
+        let fallback = plugin.fallback();
+        let mut ordered_by_plugin = false;
+
+        let mut result = utxo_selection::select_in_order(utxos, params, fallback, |candidates| {
+            let views: Vec<CoinView> = candidates
+                .iter()
+                .enumerate()
+                .map(|(index, utxo)| coin_view(index, utxo, capabilities))
+                .collect();
+            let order = panic::catch_unwind(AssertUnwindSafe(|| {
+                plugin.order(&views, params.target, params.fee_rate)
+            }));
+            match order {
+                Ok(Ok(order)) if is_valid_order(&order, candidates, params) => {
+                    let original = std::mem::take(candidates);
+                    candidates.extend(order.into_iter().map(|i| original[i]));
+                    ordered_by_plugin = true;
+                }
+                outcome => {
+                    log::warn!(
+                        "Selection plugin {} failed ({}), using {:?}",
+                        name,
+                        match outcome {
+                            Ok(Err(e)) => e,
+                            Ok(Ok(_)) => "invalid order".to_string(),
+                            Err(_) => "panicked".to_string(),
+                        },
+                        fallback
+                    );
+                    utxo_selection::order_candidates(
+                        candidates,
+                        fallback,
+                        params.deterministic_seed,
+                    );
+                }
+            }
+        });
+
+        if let SelectionResult::Success { metadata, .. } = &mut result {
+            metadata.plugin = ordered_by_plugin.then(|| name.to_string());
+        }
+        result
+    }
+
+    /// Wraps every fee plugin as a [`FeeProvider`] for the estimation service.
+    pub fn fee_providers(&self) -> Vec<Box<dyn FeeProvider>> {
+        self.fees
+            .values()
+            .map(|plugin| {
+                Box::new(SandboxedFeeProvider {
+                    name: plugin.manifest().name,
+                    plugin: plugin.clone(),
+                }) as Box<dyn FeeProvider>
+            })
+            .collect()
+    }
+}
+
+fn coin_view(index: usize, utxo: &Utxo, capabilities: &BTreeSet<Capability>) -> CoinView {
+    CoinView {
+        index,
+        value: utxo.value,
+        confirmations: utxo.confirmations,
+        is_coinbase: utxo.is_coinbase,
+        address: capabilities
+            .contains(&Capability::CoinAddresses)
+            .then(|| utxo.address.clone()),
+        outpoint: capabilities
+            .contains(&Capability::CoinOutpoints)
+            .then(|| utxo.outpoint.to_string()),
+    }
+}
+
+// Every index in range, none repeated, and no coin the constraints require left out
+fn is_valid_order(order: &[usize], candidates: &[&Utxo], params: &SelectionParams) -> bool {
+    let mut seen = vec![false; candidates.len()];
+    order
+        .iter()
+        .all(|&i| i < candidates.len() && !std::mem::replace(&mut seen[i], true))
+        && candidates
+            .iter()
+            .zip(&seen)
+            .all(|(utxo, seen)| *seen || !params.constraints.must_include.contains(&utxo.outpoint))
+}
+
+// Fee plugin output is bounds-checked before it reaches the consensus logic
+struct SandboxedFeeProvider {
+    name: String,
+    plugin: Arc<dyn FeePlugin>,
+}
+
+impl FeeProvider for SandboxedFeeProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch(&self) -> Result<BTreeMap<u32, f64>, String> {
+        let name = &self.name;
+        let rates = panic::catch_unwind(AssertUnwindSafe(|| self.plugin.fetch()))
+            .map_err(|_| format!("Fee plugin {} panicked", name))??;
+        if rates.is_empty() {
+            return Err(format!("Fee plugin {} returned no rates", name));
+        }
+        if let Some((target, rate)) = rates.iter().find(|(target, rate)| {
+            **target == 0 || !(rate.is_finite() && **rate > 0.0 && **rate <= MAX_PLUGIN_FEE_RATE)
+        }) {
+            return Err(format!(
+                "Fee plugin {} returned {} sat/vB for target {}",
+                name, rate, target
+            ));
+        }
+        Ok(rates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+    use std::sync::Mutex;
+
+    struct Smallest(Mutex<BTreeSet<Capability>>);
+
+    impl SelectionPlugin for Smallest {
+        fn manifest(&self) -> PluginManifest {
+            PluginManifest {
+                name: "smallest".to_string(),
+                version: "0.1.0".to_string(),
+                api_version: PLUGIN_API_VERSION,
+                capabilities: self.0.lock().unwrap().clone(),
+            }
+        }
+
+        fn order(&self, coins: &[CoinView], _: u64, _: u64) -> Result<Vec<usize>, String> {
+            assert!(coins.iter().all(|c| c.address.is_none()));
+            let mut coins = coins.to_vec();
+            coins.sort_by_key(|c| c.value);
+            Ok(coins.iter().map(|c| c.index).collect())
+        }
+    }
+
+    #[test]
+    fn test_plugin_capabilities_and_ordering() {
+        let utxo = |vout, value| Utxo {
+            outpoint: OutPoint::new("aa", vout),
+            address: "bcrt1qtest".to_string(),
+            value,
+            confirmations: 6,
+            is_coinbase: false,
+        };
+        let utxos = vec![utxo(0, 100_000), utxo(1, 60_000)];
+
+        let mut host = PluginHost::new(BTreeSet::new());
+        let greedy = BTreeSet::from([Capability::CoinAddresses]);
+        assert!(host
+            .register_selection(Arc::new(Smallest(Mutex::new(greedy.clone()))))
+            .is_err());
+        let plugin = Arc::new(Smallest(Mutex::new(BTreeSet::new())));
+        host.register_selection(plugin.clone()).unwrap();
+        // Claiming more after registration gets nothing more
+        *plugin.0.lock().unwrap() = greedy;
+
+        match host.select("smallest", &utxos, &SelectionParams::new(50_000, 2)) {
+            SelectionResult::Success {
+                selected, metadata, ..
+            } => {
+                assert_eq!(selected[0].value, 60_000);
+                assert_eq!(metadata.plugin.as_deref(), Some("smallest"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
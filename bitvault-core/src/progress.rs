@@ -0,0 +1,169 @@
+//! Progress of long operations (scans, backups, planning) for whoever is
+//! watching: the UI through events, or a terminal.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{Event, MessageBus};
+
+/// Where an operation stands.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// e.g. `sync` or `backup`.
+    pub operation: String,
+    /// What it is doing now, e.g. `Scanning change addresses`.
+    pub phase: String,
+    /// 0 to 100.
+    pub percent: f64,
+    /// Seconds left, once there is enough to go on.
+    pub eta_secs: Option<u64>,
+}
+
+impl Progress {
+    /// `done` of `total` units after `elapsed`, with the ETA extrapolated
+    /// from the rate so far.
+    pub fn from_counts(
+        operation: &str,
+        phase: &str,
+        done: u64,
+        total: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let fraction = if total == 0 {
+            1.0
+        } else {
+            (done as f64 / total as f64).min(1.0)
+        };
+        let eta_secs = (done > 0 && done < total)
+            .then(|| (elapsed.as_secs_f64() * (total - done) as f64 / done as f64).ceil() as u64);
+        Progress {
+            operation: operation.to_string(),
+            phase: phase.to_string(),
+            percent: fraction * 100.0,
+            eta_secs,
+        }
+    }
+
+    /// Step `step` of `steps` of an operation whose steps take too uneven a
+    /// time to estimate.
+    pub fn step(operation: &str, phase: &str, step: u32, steps: u32) -> Self {
+        Progress {
+            operation: operation.to_string(),
+            phase: phase.to_string(),
+            percent: f64::from(step.min(steps)) * 100.0 / f64::from(steps.max(1)),
+            eta_secs: None,
+        }
+    }
+
+    /// `sync: Scanning change addresses 42% (about 1m 20s left)`
+    pub fn to_line(&self) -> String {
+        let mut line = format!("{}: {} {:.0}%", self.operation, self.phase, self.percent);
+        if let Some(eta) = self.eta_secs {
+            let time = if eta >= 60 {
+                format!("{}m {}s", eta / 60, eta % 60)
+            } else {
+                format!("{}s", eta)
+            };
+            line.push_str(&format!(" (about {} left)", time));
+        }
+        line
+    }
+}
+
+/// Receives progress from long operations.
+pub trait ProgressSink: Send + Sync {
+    fn report(&self, progress: &Progress);
+}
+
+/// Reports `progress` to `sink` if there is one.
+pub fn report(sink: Option<&dyn ProgressSink>, progress: impl FnOnce() -> Progress) {
+    if let Some(sink) = sink {
+        sink.report(&progress());
+    }
+}
+
+/// Publishes progress as `OperationProgress` events for UIs.
+pub struct EventProgress<'a>(pub &'a MessageBus);
+
+impl ProgressSink for EventProgress<'_> {
+    fn report(&self, progress: &Progress) {
+        self.0.publish(Event::OperationProgress {
+            operation: progress.operation.clone(),
+            phase: progress.phase.clone(),
+            percent: progress.percent,
+            eta_secs: progress.eta_secs,
+        });
+    }
+}
+
+/// Writes one line per report, for command-line tools.
+pub struct ConsoleProgress<W: Write + Send>(Mutex<W>);
+
+impl<W: Write + Send> ConsoleProgress<W> {
+    pub fn new(out: W) -> Self {
+        ConsoleProgress(Mutex::new(out))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ConsoleProgress<std::io::Stderr> {
+    pub fn stderr() -> Self {
+        ConsoleProgress::new(std::io::stderr())
+    }
+}
+
+impl<W: Write + Send> ProgressSink for ConsoleProgress<W> {
+    fn report(&self, progress: &Progress) {
+        if let Ok(mut out) = self.0.lock() {
+            let _ = writeln!(out, "{}", progress.to_line());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_eta_and_sinks() {
+        let progress = Progress::from_counts(
+            "sync",
+            "Scanning change addresses",
+            42,
+            100,
+            Duration::from_secs(58),
+        );
+        assert_eq!(progress.percent, 42.0);
+        assert_eq!(progress.eta_secs, Some(81));
+        assert_eq!(
+            progress.to_line(),
+            "sync: Scanning change addresses 42% (about 1m 21s left)"
+        );
+        let done = Progress::from_counts("backup", "Uploading", 3, 3, Duration::from_secs(2));
+        assert_eq!((done.percent, done.eta_secs), (100.0, None));
+        assert_eq!(Progress::step("plan", "Merging", 1, 4).percent, 25.0);
+
+        let console = ConsoleProgress::new(Vec::new());
+        report(Some(&console), || done.clone());
+        assert_eq!(
+            String::from_utf8(console.into_inner()).unwrap(),
+            "backup: Uploading 100%\n"
+        );
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        EventProgress(&bus).report(&progress);
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::OperationProgress {
+                eta_secs: Some(81),
+                ..
+            })
+        ));
+    }
+}
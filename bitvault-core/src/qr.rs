@@ -0,0 +1,157 @@
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::payment_uri::PaymentDraft;
+
+pub mod scan;
+
+/// How much of the code can be damaged or obscured and still scan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCorrection {
+    /// ~7%; fits the most data, for large PSBTs.
+    Low,
+    /// ~15%.
+    #[default]
+    Medium,
+    /// ~25%.
+    Quartile,
+    /// ~30%; for codes that get printed or photographed.
+    High,
+}
+
+impl ErrorCorrection {
+    fn level(self) -> EcLevel {
+        match self {
+            ErrorCorrection::Low => EcLevel::L,
+            ErrorCorrection::Medium => EcLevel::M,
+            ErrorCorrection::Quartile => EcLevel::Q,
+            ErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// Data that can be shown as a QR code.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QrPayload {
+    /// A receive request, encoded as a BIP-21 URI.
+    Receive(PaymentDraft),
+    /// A serialized PSBT, encoded as base64.
+    Psbt(Vec<u8>),
+    /// An output descriptor, as is.
+    Descriptor(String),
+}
+
+impl QrPayload {
+    /// The string encoded into the QR code.
+    ///
+    /// Plain address requests are upper-cased: bech32 is case-insensitive and QR
+    /// alphanumeric mode stores upper-case text much more compactly.
+    pub fn to_qr_string(&self) -> String {
+        match self {
+            QrPayload::Receive(draft) => {
+                let uri = draft.to_uri();
+                let address = draft.address.to_ascii_lowercase();
+                let is_bech32 = ["bc1", "tb1", "bcrt1"]
+                    .iter()
+                    .any(|hrp| address.starts_with(hrp));
+                if is_bech32 && !uri.contains('?') {
+                    uri.to_ascii_uppercase()
+                } else {
+                    uri
+                }
+            }
+            QrPayload::Psbt(psbt) => base64::engine::general_purpose::STANDARD.encode(psbt),
+            QrPayload::Descriptor(descriptor) => descriptor.clone(),
+        }
+    }
+}
+
+/// Rendering options shared by the SVG and PNG output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QrOptions {
+    pub error_correction: ErrorCorrection,
+    /// Size of one QR module in pixels (PNG) or user units (SVG).
+    pub module_size: u32,
+    /// Include the four-module white border scanners need.
+    pub quiet_zone: bool,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        QrOptions {
+            error_correction: ErrorCorrection::default(),
+            module_size: 8,
+            quiet_zone: true,
+        }
+    }
+}
+
+fn encode(payload: &QrPayload, options: &QrOptions) -> Result<QrCode, String> {
+    QrCode::with_error_correction_level(payload.to_qr_string(), options.error_correction.level())
+        .map_err(|e| format!("Failed to encode QR code: {}", e))
+}
+
+/// Renders `payload` as an SVG document.
+pub fn render_svg(payload: &QrPayload, options: &QrOptions) -> Result<Vec<u8>, String> {
+    let code = encode(payload, options)?;
+    let svg = code
+        .render::<svg::Color>()
+        .module_dimensions(options.module_size, options.module_size)
+        .quiet_zone(options.quiet_zone)
+        .build();
+    Ok(svg.into_bytes())
+}
+
+/// Renders `payload` as a grayscale PNG image.
+pub fn render_png(payload: &QrPayload, options: &QrOptions) -> Result<Vec<u8>, String> {
+    let code = encode(payload, options)?;
+    let image = code
+        .render::<Luma<u8>>()
+        .module_dimensions(options.module_size, options.module_size)
+        .quiet_zone(options.quiet_zone)
+        .build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to write PNG: {}", e))?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_formats() {
+        let payload = QrPayload::Receive(PaymentDraft {
+            address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(
+            payload.to_qr_string(),
+            "BITCOIN:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ"
+        );
+
+        let svg = render_svg(&payload, &QrOptions::default()).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("<svg"));
+        let png = render_png(&payload, &QrOptions::default()).unwrap();
+        assert_eq!(&png[..4], b"\x89PNG");
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(serde_json::from_str::<QrPayload>(&json).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_oversized_payload_is_an_error() {
+        let options = QrOptions {
+            error_correction: ErrorCorrection::High,
+            ..Default::default()
+        };
+        assert!(render_svg(&QrPayload::Psbt(vec![0xab; 4_000]), &options).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::payment_uri::{self, PaymentDraft};
+use crate::types::Network;
+
+// Magic bytes at the start of every serialized PSBT
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// What a scanned QR code turned out to contain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScannedInput {
+    /// A BIP-21 payment request.
+    PaymentRequest(PaymentDraft),
+    /// A bare address, lower-cased if it was an upper-case bech32 address.
+    Address(String),
+    /// One part of a (possibly multi-part) Uniform Resource, e.g. an animated PSBT.
+    UrFragment {
+        ur_type: String,
+        /// Sequence number and total for multi-part URs.
+        part: Option<(u32, u32)>,
+        data: String,
+    },
+    /// A PSBT encoded as base64 or hex.
+    Psbt(Vec<u8>),
+    /// An extended public key (xpub, tpub and SLIP-132 variants).
+    ExtendedPublicKey(String),
+}
+
+/// Classifies a raw scanned QR string, so every scan flow shares one entry point.
+///
+/// Payment requests and addresses are checked against `network`; anything that
+/// doesn't match a known format is an error rather than being passed through.
+pub fn decode_scanned(raw: &str, network: Network) -> Result<ScannedInput, String> {
+    let payload = raw.trim();
+    if payload.is_empty() {
+        return Err("Scanned code is empty".to_string());
+    }
+    let lower = payload.to_ascii_lowercase();
+
+    if lower.starts_with("bitcoin:") {
+        let mut draft = payment_uri::parse_payment_uri(payload, network)?;
+        draft.address = normalize_address(&draft.address);
+        return Ok(ScannedInput::PaymentRequest(draft));
+    }
+    if lower.starts_with("ur:") {
+        return parse_ur(&lower);
+    }
+    if let Some(psbt) = decode_psbt(payload) {
+        return Ok(ScannedInput::Psbt(psbt));
+    }
+    if is_extended_public_key(payload, network) {
+        return Ok(ScannedInput::ExtendedPublicKey(payload.to_string()));
+    }
+
+    let address = normalize_address(payload);
+    payment_uri::check_address(&address, network)?;
+    Ok(ScannedInput::Address(address))
+}
+
+// Upper-case bech32 (used to shrink QR codes) is folded back to lower case;
+// base58 addresses are case-sensitive and left alone
+fn normalize_address(address: &str) -> String {
+    let lower = address.to_ascii_lowercase();
+    if ["bc1", "tb1", "bcrt1"]
+        .iter()
+        .any(|hrp| lower.starts_with(hrp))
+    {
+        lower
+    } else {
+        address.to_string()
+    }
+}
+
+// ur:<type>/<seq>-<total>/<data> or ur:<type>/<data>
+fn parse_ur(payload: &str) -> Result<ScannedInput, String> {
+    let parts: Vec<&str> = payload["ur:".len()..].split('/').collect();
+    let invalid = || "Malformed UR".to_string();
+
+    let (ur_type, part, data) = match parts.as_slice() {
+        [ur_type, data] => (*ur_type, None, *data),
+        [ur_type, sequence, data] => {
+            let (seq, total) = sequence.split_once('-').ok_or_else(invalid)?;
+            let seq: u32 = seq.parse().map_err(|_| invalid())?;
+            let total: u32 = total.parse().map_err(|_| invalid())?;
+            if seq == 0 || total == 0 {
+                return Err(invalid());
+            }
+            (*ur_type, Some((seq, total)), *data)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let valid_type = !ur_type.is_empty()
+        && ur_type
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_type || data.is_empty() || !data.chars().all(|c| c.is_ascii_lowercase()) {
+        return Err(invalid());
+    }
+
+    Ok(ScannedInput::UrFragment {
+        ur_type: ur_type.to_string(),
+        part,
+        data: data.to_string(),
+    })
+}
+
+fn decode_psbt(payload: &str) -> Option<Vec<u8>> {
+    let bytes = if payload.starts_with("cHNidP8") {
+        base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .ok()?
+    } else if payload.to_ascii_lowercase().starts_with("70736274ff") {
+        hex::decode(payload).ok()?
+    } else {
+        return None;
+    };
+    bytes.starts_with(PSBT_MAGIC).then_some(bytes)
+}
+
+fn is_extended_public_key(payload: &str, network: Network) -> bool {
+    let mainnet = ["xpub", "ypub", "zpub", "Ypub", "Zpub"];
+    let testnet = ["tpub", "upub", "vpub", "Upub", "Vpub"];
+    let prefixes: &[&str] = match network {
+        Network::Bitcoin => &mainnet,
+        Network::Testnet | Network::Signet | Network::Regtest => &testnet,
+    };
+
+    // Base58check of 78 bytes is always 111 characters
+    payload.len() == 111
+        && prefixes.iter().any(|p| payload.starts_with(p))
+        && payload
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !"0OIl".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_scans() {
+        let address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        assert_eq!(
+            decode_scanned(&address.to_ascii_uppercase(), Network::Bitcoin),
+            Ok(ScannedInput::Address(address.to_string()))
+        );
+        assert!(matches!(
+            decode_scanned(
+                "BITCOIN:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ",
+                Network::Bitcoin
+            ),
+            Ok(ScannedInput::PaymentRequest(PaymentDraft { address: a, .. })) if a == address
+        ));
+        assert_eq!(
+            decode_scanned("UR:CRYPTO-PSBT/2-3/LPAOAXCF", Network::Bitcoin),
+            Ok(ScannedInput::UrFragment {
+                ur_type: "crypto-psbt".to_string(),
+                part: Some((2, 3)),
+                data: "lpaoaxcf".to_string(),
+            })
+        );
+        assert_eq!(
+            decode_scanned("cHNidP8BAA==", Network::Bitcoin),
+            Ok(ScannedInput::Psbt(b"psbt\xff\x01\x00".to_vec()))
+        );
+        assert!(decode_scanned(address, Network::Testnet).is_err());
+        assert!(decode_scanned("hello world", Network::Bitcoin).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::clock::SharedClock;
+use crate::connection_mode::SharedConnectionMode;
+use crate::events::{Event, MessageBus};
+
+/// Cooperative cancellation flag shared between a task and whoever may stop it.
+/// Long-running jobs should check it between steps.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Snapshot of a task's schedule and outcome, for status displays.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    pub last_run: Option<u64>,
+    pub next_run: u64,
+    pub last_error: Option<String>,
+    pub runs: u64,
+    pub failures: u64,
+    pub cancelled: bool,
+    /// Held back by a metered connection rather than run when last due.
+    #[serde(default)]
+    pub deferred: bool,
+}
+
+type Job = Box<dyn FnMut(&CancellationToken) -> Result<(), String> + Send>;
+
+struct ScheduledTask {
+    interval_secs: u64,
+    jitter_secs: u64,
+    /// Skipped while the connection is metered.
+    deferrable: bool,
+    token: CancellationToken,
+    job: Job,
+}
+
+/// Status of every task, readable while the scheduler runs on its own thread.
+#[derive(Clone, Default)]
+pub struct SchedulerStatus(Arc<Mutex<BTreeMap<String, TaskStatus>>>);
+
+impl SchedulerStatus {
+    pub fn list(&self) -> Vec<TaskStatus> {
+        self.0
+            .lock()
+            .map(|tasks| tasks.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<TaskStatus> {
+        self.0.lock().ok()?.get(name).cloned()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskStatus)) {
+        if let Some(status) = self.0.lock().ok().as_mut().and_then(|t| t.get_mut(name)) {
+            f(status);
+        }
+    }
+}
+
+/// Runs named recurring background jobs (sync, fee refresh, broadcast retry,
+/// backups). Failures are recorded in the status and published as `System` events.
+///
+/// On a metered connection intervals are stretched and deferrable tasks wait
+/// until it isn't metered any more.
+pub struct Scheduler {
+    tasks: BTreeMap<String, ScheduledTask>,
+    status: SchedulerStatus,
+    clock: SharedClock,
+    bus: Option<Arc<MessageBus>>,
+    connection: SharedConnectionMode,
+}
+
+impl Scheduler {
+    pub fn new(clock: SharedClock) -> Self {
+        Scheduler {
+            tasks: BTreeMap::new(),
+            status: SchedulerStatus::default(),
+            clock,
+            bus: None,
+            connection: SharedConnectionMode::default(),
+        }
+    }
+
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn with_connection_mode(mut self, connection: SharedConnectionMode) -> Self {
+        self.connection = connection;
+        self
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        self.status.clone()
+    }
+
+    /// Adds a job run every `interval_secs`, delayed by up to `jitter_secs` each
+    /// time so tasks (and wallets) don't all hit servers at once. The first run
+    /// is due immediately. Returns the token that cancels the task.
+    pub fn schedule<F>(
+        &mut self,
+        name: &str,
+        interval_secs: u64,
+        jitter_secs: u64,
+        job: F,
+    ) -> Result<CancellationToken, String>
+    where
+        F: FnMut(&CancellationToken) -> Result<(), String> + Send + 'static,
+    {
+        self.add(name, interval_secs, jitter_secs, false, Box::new(job))
+    }
+
+    /// Like [`schedule`](Self::schedule), for jobs that can wait, such as
+    /// historical backfills: while the connection is metered the job is
+    /// skipped and marked deferred instead of run.
+    pub fn schedule_deferrable<F>(
+        &mut self,
+        name: &str,
+        interval_secs: u64,
+        jitter_secs: u64,
+        job: F,
+    ) -> Result<CancellationToken, String>
+    where
+        F: FnMut(&CancellationToken) -> Result<(), String> + Send + 'static,
+    {
+        self.add(name, interval_secs, jitter_secs, true, Box::new(job))
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        interval_secs: u64,
+        jitter_secs: u64,
+        deferrable: bool,
+        job: Job,
+    ) -> Result<CancellationToken, String> {
+        if interval_secs == 0 {
+            return Err("Task interval must be at least one second".to_string());
+        }
+        if self.tasks.contains_key(name) {
+            return Err(format!("A task named {} is already scheduled", name));
+        }
+
+        let token = CancellationToken::new();
+        self.tasks.insert(
+            name.to_string(),
+            ScheduledTask {
+                interval_secs,
+                jitter_secs,
+                deferrable,
+                token: token.clone(),
+                job,
+            },
+        );
+        if let Ok(mut status) = self.status.0.lock() {
+            status.insert(
+                name.to_string(),
+                TaskStatus {
+                    name: name.to_string(),
+                    interval_secs,
+                    last_run: None,
+                    next_run: self.clock.now(),
+                    last_error: None,
+                    runs: 0,
+                    failures: 0,
+                    cancelled: false,
+                    deferred: false,
+                },
+            );
+        }
+        Ok(token)
+    }
+
+    /// Cancels a task by name. Returns whether it existed.
+    pub fn cancel(&mut self, name: &str) -> bool {
+        match self.tasks.get(name) {
+            Some(task) => {
+                task.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs every task that is due, in name order, and returns how many ran.
+    /// Cancelled tasks are removed.
+    pub fn run_due(&mut self) -> usize {
+        let now = self.clock.now();
+        let mode = self.connection.get();
+        let mut ran = 0;
+
+        let cancelled: Vec<String> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.token.is_cancelled())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in cancelled {
+            self.tasks.remove(&name);
+            self.status.update(&name, |status| status.cancelled = true);
+        }
+
+        for (name, task) in self.tasks.iter_mut() {
+            let due = self.status.get(name).is_none_or(|s| s.next_run <= now);
+            if !due {
+                continue;
+            }
+            if task.deferrable && mode.is_metered() {
+                log::debug!("Deferring task {} on a metered connection", name);
+                self.status.update(name, |status| {
+                    status.next_run = now + mode.interval(task.interval_secs);
+                    status.deferred = true;
+                });
+                continue;
+            }
+
+            let result = (task.job)(&task.token);
+            let jitter = if task.jitter_secs > 0 {
+                rand::rng().random_range(0..=task.jitter_secs)
+            } else {
+                0
+            };
+            let finished = self.clock.now();
+            self.status.update(name, |status| {
+                status.last_run = Some(finished);
+                status.next_run = finished + mode.interval(task.interval_secs) + jitter;
+                status.runs += 1;
+                status.deferred = false;
+                match &result {
+                    Ok(()) => status.last_error = None,
+                    Err(e) => {
+                        status.failures += 1;
+                        status.last_error = Some(e.clone());
+                    }
+                }
+            });
+
+            if let Err(e) = result {
+                log::warn!("Task {} failed: {}", name, e);
+                if let Some(bus) = &self.bus {
+                    bus.publish(Event::System {
+                        component: "scheduler".to_string(),
+                        message: format!("Task {} failed: {}", name, e),
+                    });
+                }
+            }
+            ran += 1;
+        }
+        ran
+    }
+
+    /// Moves the scheduler to a background thread that checks for due tasks
+    /// every `tick`.
+    pub fn spawn(mut self, tick: Duration) -> SchedulerHandle {
+        let shutdown = CancellationToken::new();
+        let status = self.status();
+        let stop = shutdown.clone();
+        let thread = thread::spawn(move || {
+            while !stop.is_cancelled() {
+                self.run_due();
+                thread::sleep(tick);
+            }
+        });
+        SchedulerHandle {
+            shutdown,
+            status,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Owner of a scheduler running on its own thread.
+pub struct SchedulerHandle {
+    shutdown: CancellationToken,
+    status: SchedulerStatus,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    pub fn status(&self) -> &SchedulerStatus {
+        &self.status
+    }
+
+    /// Stops the thread after the task currently running, if any, and waits for it.
+    pub fn stop(&mut self) {
+        self.shutdown.cancel();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Scheduler thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for SchedulerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::connection_mode::ConnectionMode;
+
+    #[test]
+    fn test_recurring_tasks_and_failures() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let mut scheduler = Scheduler::new(clock.clone()).with_event_bus(bus);
+
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        scheduler
+            .schedule("sync", 30, 0, move |_| {
+                *counter.lock().unwrap() += 1;
+                Ok(())
+            })
+            .unwrap();
+        let backups = Arc::new(Mutex::new(0));
+        scheduler
+            .schedule("backup", 60, 0, move |_| {
+                let mut backups = backups.lock().unwrap();
+                *backups += 1;
+                match *backups {
+                    1 => Err("disk full".to_string()),
+                    _ => Ok(()),
+                }
+            })
+            .unwrap();
+
+        assert_eq!(scheduler.run_due(), 2);
+        assert_eq!(scheduler.run_due(), 0);
+        clock.advance(30);
+        assert_eq!(scheduler.run_due(), 1);
+        assert_eq!(*count.lock().unwrap(), 2);
+
+        let status = scheduler.status();
+        let backup = status.get("backup").unwrap();
+        assert_eq!(backup.last_error.as_deref(), Some("disk full"));
+        assert_eq!(backup.next_run, 1_060);
+        assert!(matches!(events.try_recv(), Ok(Event::System { .. })));
+
+        assert!(scheduler.cancel("sync"));
+        clock.advance(60);
+        assert_eq!(scheduler.run_due(), 1);
+        assert!(status.get("sync").unwrap().cancelled);
+        // A later success clears the error
+        let backup = status.get("backup").unwrap();
+        assert_eq!(backup.last_error, None);
+        assert_eq!(backup.failures, 1);
+    }
+
+    #[test]
+    fn test_metered_connection_defers_and_stretches() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let connection = SharedConnectionMode::new(ConnectionMode::Metered);
+        let mut scheduler = Scheduler::new(clock.clone()).with_connection_mode(connection.clone());
+        scheduler.schedule("poll", 60, 0, |_| Ok(())).unwrap();
+        scheduler
+            .schedule_deferrable("backfill", 60, 0, |_| Ok(()))
+            .unwrap();
+
+        let status = scheduler.status();
+        assert_eq!(scheduler.run_due(), 1);
+        assert_eq!(status.get("poll").unwrap().next_run, 1_240);
+        let backfill = status.get("backfill").unwrap();
+        assert!(backfill.deferred);
+        assert_eq!(backfill.runs, 0);
+
+        connection.set(ConnectionMode::Normal);
+        clock.advance(240);
+        assert_eq!(scheduler.run_due(), 2);
+        let backfill = status.get("backfill").unwrap();
+        assert!(!backfill.deferred);
+        assert_eq!(backfill.next_run, 1_300);
+    }
+}
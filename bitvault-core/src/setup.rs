@@ -0,0 +1,402 @@
+//! First-run setup, shared by every frontend so they all walk the same
+//! validated flow: choose create or restore, enter the secret, verify the
+//! backup, choose network and backend.
+//!
+//! Progress is saved after every step so interrupted onboarding can resume.
+//! The recovery phrase and passphrase are never written; a wizard resumed
+//! past the secret step without them goes back to it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::backup_verification::BackupQuiz;
+use crate::clock::Clock;
+use crate::key_management::wordlist::WordlistLanguage;
+use crate::key_management::{self, EntropySize};
+use crate::metadata::WalletMetadata;
+use crate::types::Network;
+
+/// Questions and choices per question in the backup quiz.
+pub const QUIZ_CHALLENGES: usize = 3;
+pub const QUIZ_CHOICES: usize = 4;
+/// Minimum zxcvbn score for a non-empty BIP39 passphrase.
+pub const MIN_PASSPHRASE_SCORE: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SetupMode {
+    Create,
+    Restore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SetupStep {
+    ChooseMode,
+    /// Generate or enter the recovery phrase and optional passphrase.
+    Secret,
+    /// Only for new wallets: prove the phrase was written down.
+    VerifyBackup,
+    ChooseNetwork,
+    Done,
+}
+
+/// Everything about the flow that is safe to persist.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupProgress {
+    pub step: SetupStep,
+    pub mode: Option<SetupMode>,
+    pub word_count: Option<usize>,
+    /// Wordlist of the phrase, chosen when creating and detected when
+    /// restoring.
+    #[serde(default)]
+    pub language: Option<WordlistLanguage>,
+    pub backup_verified_at: Option<u64>,
+    pub network: Option<Network>,
+    /// Backend URL, `None` for the default for the network.
+    pub backend_url: Option<String>,
+}
+
+impl Default for SetupProgress {
+    fn default() -> Self {
+        SetupProgress {
+            step: SetupStep::ChooseMode,
+            mode: None,
+            word_count: None,
+            language: None,
+            backup_verified_at: None,
+            network: None,
+            backend_url: None,
+        }
+    }
+}
+
+/// What a finished setup hands over for creating the wallet.
+pub struct SetupOutcome {
+    pub mode: SetupMode,
+    pub mnemonic: Zeroizing<String>,
+    pub passphrase: Zeroizing<String>,
+    pub network: Network,
+    pub backend_url: Option<String>,
+    pub metadata: WalletMetadata,
+}
+
+pub struct SetupWizard {
+    progress: SetupProgress,
+    path: Option<PathBuf>,
+    mnemonic: Option<Zeroizing<String>>,
+    passphrase: Zeroizing<String>,
+    quiz: Option<BackupQuiz>,
+}
+
+impl Default for SetupWizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetupWizard {
+    /// A wizard that keeps its progress in memory only.
+    pub fn new() -> Self {
+        SetupWizard {
+            progress: SetupProgress::default(),
+            path: None,
+            mnemonic: None,
+            passphrase: Zeroizing::new(String::new()),
+            quiz: None,
+        }
+    }
+
+    /// Resumes the progress saved at `path`, or starts over if there is none.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut wizard = SetupWizard::new();
+        wizard.path = Some(path.to_path_buf());
+        if path.exists() {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            wizard.progress = serde_json::from_str(&json)
+                .map_err(|e| format!("Invalid setup progress: {}", e))?;
+            if wizard.progress.step > SetupStep::Secret {
+                log::info!("Resuming setup at the secret step; the phrase is not persisted");
+                wizard.progress.step = SetupStep::Secret;
+                wizard.progress.backup_verified_at = None;
+            }
+        }
+        Ok(wizard)
+    }
+
+    pub fn step(&self) -> SetupStep {
+        self.progress.step
+    }
+
+    pub fn progress(&self) -> &SetupProgress {
+        &self.progress
+    }
+
+    /// The generated phrase to show while backing it up.
+    pub fn mnemonic(&self) -> Option<&str> {
+        match self.progress.step {
+            SetupStep::VerifyBackup => self.mnemonic.as_deref().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn quiz(&self) -> Option<&BackupQuiz> {
+        self.quiz.as_ref()
+    }
+
+    fn expect(&self, step: SetupStep, action: &str) -> Result<(), String> {
+        if self.progress.step == step {
+            Ok(())
+        } else {
+            Err(format!("Can't {} during {:?}", action, self.progress.step))
+        }
+    }
+
+    fn advance(&mut self, step: SetupStep) -> Result<(), String> {
+        self.progress.step = step;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.progress)
+            .map_err(|e| format!("Failed to serialize setup progress: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn choose_mode(&mut self, mode: SetupMode) -> Result<(), String> {
+        self.expect(SetupStep::ChooseMode, "choose create or restore")?;
+        self.progress.mode = Some(mode);
+        self.advance(SetupStep::Secret)
+    }
+
+    fn set_passphrase(&mut self, passphrase: &str) -> Result<(), String> {
+        if !passphrase.is_empty() {
+            key_management::check_passphrase_strength(passphrase, &[], MIN_PASSPHRASE_SCORE)?;
+        }
+        self.passphrase = Zeroizing::new(passphrase.to_string());
+        Ok(())
+    }
+
+    /// Generates the phrase for a new wallet in `language`, mixing in
+    /// `extra_entropy` such as dice rolls, and moves on to the backup quiz.
+    pub fn generate(
+        &mut self,
+        word_count: usize,
+        language: WordlistLanguage,
+        extra_entropy: Option<&[u8]>,
+        passphrase: &str,
+    ) -> Result<(), String> {
+        self.expect(SetupStep::Secret, "generate a phrase")?;
+        if self.progress.mode != Some(SetupMode::Create) {
+            return Err("Restoring wallets enter their phrase instead".to_string());
+        }
+        let size = EntropySize::from_word_count(word_count)
+            .ok_or_else(|| format!("Unsupported word count {}", word_count))?;
+        self.set_passphrase(passphrase)?;
+
+        let mnemonic = Zeroizing::new(
+            key_management::generate_mnemonic_in(language, size, extra_entropy)?.to_string(),
+        );
+        self.quiz = Some(BackupQuiz::new(&mnemonic, QUIZ_CHALLENGES, QUIZ_CHOICES)?);
+        self.mnemonic = Some(mnemonic);
+        self.progress.word_count = Some(word_count);
+        self.progress.language = Some(language);
+        self.advance(SetupStep::VerifyBackup)
+    }
+
+    /// Takes the phrase of an existing wallet. Its backup already exists, so
+    /// the quiz is skipped.
+    pub fn restore(&mut self, phrase: &str, passphrase: &str) -> Result<(), String> {
+        self.expect(SetupStep::Secret, "restore a phrase")?;
+        if self.progress.mode != Some(SetupMode::Restore) {
+            return Err("New wallets generate their phrase instead".to_string());
+        }
+        let mnemonic = key_management::validate_mnemonic(phrase)?;
+        self.set_passphrase(passphrase)?;
+
+        self.progress.word_count = Some(mnemonic.word_count());
+        self.progress.language = Some(WordlistLanguage::from_bip39(mnemonic.language()));
+        self.mnemonic = Some(Zeroizing::new(mnemonic.to_string()));
+        self.advance(SetupStep::ChooseNetwork)
+    }
+
+    /// Checks the quiz answers as `(position, word)` pairs.
+    pub fn verify_backup(
+        &mut self,
+        answers: &[(usize, String)],
+        clock: &dyn Clock,
+    ) -> Result<(), String> {
+        self.expect(SetupStep::VerifyBackup, "verify the backup")?;
+        let quiz = self
+            .quiz
+            .as_ref()
+            .ok_or_else(|| "No backup quiz in progress".to_string())?;
+        if !quiz.verify(answers) {
+            return Err("One or more answers are incorrect".to_string());
+        }
+        self.quiz = None;
+        self.progress.backup_verified_at = Some(clock.now());
+        self.advance(SetupStep::ChooseNetwork)
+    }
+
+    pub fn choose_network(
+        &mut self,
+        network: Network,
+        backend_url: Option<String>,
+    ) -> Result<(), String> {
+        self.expect(SetupStep::ChooseNetwork, "choose a network")?;
+        if let Some(url) = &backend_url {
+            if !url
+                .split_once("://")
+                .is_some_and(|(scheme, host)| !scheme.is_empty() && !host.is_empty())
+            {
+                return Err(format!("Invalid backend URL {}", url));
+            }
+        }
+        self.progress.network = Some(network);
+        self.progress.backend_url = backend_url;
+        self.advance(SetupStep::Done)
+    }
+
+    /// Returns to the previous step. Going back past the secret discards it.
+    pub fn back(&mut self) -> Result<(), String> {
+        let previous = match (self.progress.step, self.progress.mode) {
+            (SetupStep::ChooseMode, _) => return Err("Already at the first step".to_string()),
+            (SetupStep::Secret, _) => SetupStep::ChooseMode,
+            (SetupStep::VerifyBackup, _) | (SetupStep::ChooseNetwork, Some(SetupMode::Restore)) => {
+                SetupStep::Secret
+            }
+            (SetupStep::ChooseNetwork, _) => SetupStep::VerifyBackup,
+            (SetupStep::Done, _) => SetupStep::ChooseNetwork,
+        };
+        match previous {
+            SetupStep::ChooseMode | SetupStep::Secret => {
+                self.mnemonic = None;
+                self.passphrase = Zeroizing::new(String::new());
+                self.quiz = None;
+                self.progress.word_count = None;
+                self.progress.language = None;
+                self.progress.backup_verified_at = None;
+            }
+            // Re-verifying needs a fresh quiz
+            SetupStep::VerifyBackup => {
+                let mnemonic = self.mnemonic.as_ref().ok_or("No phrase in progress")?;
+                self.quiz = Some(BackupQuiz::new(mnemonic, QUIZ_CHALLENGES, QUIZ_CHOICES)?);
+                self.progress.backup_verified_at = None;
+            }
+            _ => {}
+        }
+        self.advance(previous)
+    }
+
+    /// Hands over the results and removes the saved progress.
+    pub fn finish(mut self) -> Result<SetupOutcome, String> {
+        self.expect(SetupStep::Done, "finish setup")?;
+        let (Some(mode), Some(network), Some(mnemonic)) = (
+            self.progress.mode,
+            self.progress.network,
+            self.mnemonic.take(),
+        ) else {
+            return Err("Setup is incomplete".to_string());
+        };
+        if let Some(path) = &self.path {
+            if path.exists() {
+                fs::remove_file(path)
+                    .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            }
+        }
+        Ok(SetupOutcome {
+            mode,
+            mnemonic,
+            passphrase: std::mem::take(&mut self.passphrase),
+            network,
+            backend_url: self.progress.backend_url.take(),
+            metadata: WalletMetadata {
+                backup_verified_at: self.progress.backup_verified_at,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_create_flow_resumes_without_secrets() {
+        let path = std::env::temp_dir().join(format!("bitvault_setup_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let clock = MockClock::new(1_700_000_000);
+
+        let mut wizard = SetupWizard::open(&path).unwrap();
+        assert!(wizard
+            .generate(12, WordlistLanguage::English, None, "")
+            .is_err());
+        wizard.choose_mode(SetupMode::Create).unwrap();
+        assert!(wizard.restore("abandon", "").is_err());
+        assert!(wizard
+            .generate(12, WordlistLanguage::English, None, "password")
+            .is_err());
+        wizard
+            .generate(12, WordlistLanguage::English, None, "")
+            .unwrap();
+
+        let words: Vec<String> = wizard
+            .mnemonic()
+            .unwrap()
+            .split(' ')
+            .map(String::from)
+            .collect();
+        assert_eq!(words.len(), 12);
+        let answers: Vec<(usize, String)> = wizard
+            .quiz()
+            .unwrap()
+            .challenges()
+            .iter()
+            .map(|c| (c.position, words[c.position - 1].clone()))
+            .collect();
+        wizard.verify_backup(&answers, &clock).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains(&words[0]));
+
+        // Interrupted here: the phrase is gone, so setup goes back for it
+        let mut resumed = SetupWizard::open(&path).unwrap();
+        assert_eq!(resumed.step(), SetupStep::Secret);
+        assert_eq!(resumed.progress().mode, Some(SetupMode::Create));
+        resumed
+            .generate(24, WordlistLanguage::English, None, "")
+            .unwrap();
+        resumed.back().unwrap();
+        assert_eq!(resumed.step(), SetupStep::Secret);
+        assert!(resumed.mnemonic().is_none());
+
+        drop(wizard);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_restore_flow_skips_quiz() {
+        let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let mut wizard = SetupWizard::new();
+        wizard.choose_mode(SetupMode::Restore).unwrap();
+        wizard.restore(phrase, "").unwrap();
+        assert_eq!(wizard.step(), SetupStep::ChooseNetwork);
+        assert_eq!(wizard.progress().language, Some(WordlistLanguage::English));
+        assert!(wizard
+            .choose_network(Network::Signet, Some("localhost".to_string()))
+            .is_err());
+        wizard
+            .choose_network(
+                Network::Signet,
+                Some("ssl://electrum.example:60002".to_string()),
+            )
+            .unwrap();
+
+        let outcome = wizard.finish().unwrap();
+        assert_eq!(outcome.mode, SetupMode::Restore);
+        assert_eq!(outcome.mnemonic.as_str(), phrase);
+        assert_eq!(outcome.network, Network::Signet);
+        assert!(!outcome.metadata.is_backup_verified());
+    }
+}
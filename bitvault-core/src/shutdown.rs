@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::events::{Event, MessageBus};
+use crate::scheduler::SchedulerHandle;
+
+/// Order in which services are shut down; lower stages go first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ShutdownStage {
+    /// Stop producing work: sync, schedulers, the broadcast queue.
+    StopWorkers,
+    /// Persist caches and flush pending writes.
+    Flush,
+    /// Make the UTXO and transaction stores consistent and close them.
+    Storage,
+    /// Deliver the last events; the event bus goes last so others can still report.
+    Events,
+}
+
+/// A service that must be stopped cleanly before the process exits.
+pub trait ShutdownHook: Send {
+    fn name(&self) -> &str;
+
+    fn stage(&self) -> ShutdownStage;
+
+    fn shutdown(&mut self) -> Result<(), String>;
+}
+
+struct FnHook<F> {
+    name: String,
+    stage: ShutdownStage,
+    f: Option<F>,
+}
+
+impl<F> ShutdownHook for FnHook<F>
+where
+    F: FnOnce() -> Result<(), String> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stage(&self) -> ShutdownStage {
+        self.stage
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        self.f.take().map_or(Ok(()), |f| f())
+    }
+}
+
+impl ShutdownHook for SchedulerHandle {
+    fn name(&self) -> &str {
+        "scheduler"
+    }
+
+    fn stage(&self) -> ShutdownStage {
+        ShutdownStage::StopWorkers
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        self.stop();
+        Ok(())
+    }
+}
+
+/// Outcome of [`ShutdownCoordinator::shutdown`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    /// Hooks that completed, in the order they ran.
+    pub completed: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl ShutdownReport {
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Stops registered services in stage order when the application exits.
+#[derive(Default)]
+pub struct ShutdownCoordinator {
+    hooks: Vec<Box<dyn ShutdownHook>>,
+    bus: Option<Arc<MessageBus>>,
+    finished: bool,
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports failed hooks as `System` events before the bus itself shuts down.
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn register(&mut self, hook: Box<dyn ShutdownHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Registers a closure as a hook.
+    pub fn register_fn<F>(&mut self, name: &str, stage: ShutdownStage, f: F)
+    where
+        F: FnOnce() -> Result<(), String> + Send + 'static,
+    {
+        self.register(Box::new(FnHook {
+            name: name.to_string(),
+            stage,
+            f: Some(f),
+        }));
+    }
+
+    /// Runs every hook once, by stage and then registration order. A failing
+    /// hook doesn't stop later ones: storage must still be closed when a flush
+    /// failed. Calling it again does nothing.
+    pub fn shutdown(&mut self) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        if self.finished {
+            return report;
+        }
+        self.finished = true;
+
+        // Stable, so registration order is kept within a stage
+        self.hooks.sort_by_key(|hook| hook.stage());
+        for hook in self.hooks.iter_mut() {
+            let name = hook.name().to_string();
+            match hook.shutdown() {
+                Ok(()) => {
+                    log::debug!("Shut down {}", name);
+                    report.completed.push(name);
+                }
+                Err(e) => {
+                    log::error!("Failed to shut down {}: {}", name, e);
+                    // Hooks in the last stage may have taken the bus down already
+                    let bus = self
+                        .bus
+                        .as_ref()
+                        .filter(|_| hook.stage() < ShutdownStage::Events);
+                    if let Some(bus) = bus {
+                        bus.publish(Event::System {
+                            component: "shutdown".to_string(),
+                            message: format!("{} failed to shut down: {}", name, e),
+                        });
+                    }
+                    report.failed.push((name, e));
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_stage_order_and_failures() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut coordinator = ShutdownCoordinator::new();
+        for (name, stage, fails) in [
+            ("utxo_store", ShutdownStage::Storage, false),
+            ("fee_cache", ShutdownStage::Flush, true),
+            ("sync", ShutdownStage::StopWorkers, false),
+        ] {
+            let order = order.clone();
+            coordinator.register_fn(name, stage, move || {
+                order.lock().unwrap().push(name);
+                if fails {
+                    Err("read-only filesystem".to_string())
+                } else {
+                    Ok(())
+                }
+            });
+        }
+
+        let report = coordinator.shutdown();
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["sync", "fee_cache", "utxo_store"]
+        );
+        assert_eq!(report.completed, vec!["sync", "utxo_store"]);
+        assert!(!report.is_clean());
+        assert_eq!(coordinator.shutdown(), ShutdownReport::default());
+    }
+}
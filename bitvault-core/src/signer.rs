@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Transaction, Utxo};
+
+pub mod capabilities;
+#[cfg(feature = "native")]
+pub mod coldcard;
+#[cfg(feature = "usb")]
+pub mod ledger;
+pub mod psbt;
+pub mod qr_signer;
+#[cfg(feature = "usb")]
+pub mod trezor;
+pub mod ur;
+#[cfg(feature = "usb")]
+pub mod usb;
+
+/// Marks a hardened BIP-32 child index.
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// Parses a BIP-32 path such as `m/84'/0'/0'/0/5`; `h` may mark hardened
+/// steps instead of `'`.
+pub fn parse_derivation_path(path: &str) -> Result<Vec<u32>, String> {
+    let mut steps = path.split('/');
+    if steps.next() != Some("m") {
+        return Err(format!("Derivation path must start with m: {}", path));
+    }
+    steps
+        .map(|step| {
+            let (index, hardened) = match step.strip_suffix(['\'', 'h']) {
+                Some(index) => (index, true),
+                None => (step, false),
+            };
+            match index.parse::<u32>() {
+                Ok(index) if index < HARDENED => {
+                    Ok(if hardened { index | HARDENED } else { index })
+                }
+                _ => Err(format!(
+                    "Invalid step '{}' in derivation path {}",
+                    step, path
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Formats `path` the way descriptors write key origins, e.g. `84h/0h/0h`.
+pub fn format_derivation_path(path: &[u32]) -> String {
+    path.iter()
+        .map(|step| match step & HARDENED {
+            0 => step.to_string(),
+            _ => format!("{}h", step & !HARDENED),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Single-key output types a hardware signer can derive and sign for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2tr,
+}
+
+impl ScriptType {
+    /// Purpose field of the standard derivation (BIP-44, 49, 84 and 86).
+    pub fn purpose(&self) -> u32 {
+        match self {
+            ScriptType::P2pkh => 44,
+            ScriptType::P2shP2wpkh => 49,
+            ScriptType::P2wpkh => 84,
+            ScriptType::P2tr => 86,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2shP2wpkh => "p2sh-p2wpkh",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2tr => "p2tr",
+        }
+    }
+
+    /// The type of a wallet address. P2SH addresses are assumed to wrap
+    /// P2WPKH; script hash types such as P2WSH give `None`.
+    pub fn from_address(address: &str) -> Option<Self> {
+        let address = address.to_ascii_lowercase();
+        let program = ["bc1", "tb1", "bcrt1"]
+            .iter()
+            .find_map(|hrp| address.strip_prefix(hrp));
+        match program {
+            // 20-byte witness programs are 39 characters after the separator
+            Some(program) if program.starts_with('q') => {
+                (program.len() <= 39).then_some(ScriptType::P2wpkh)
+            }
+            Some(program) if program.starts_with('p') => Some(ScriptType::P2tr),
+            Some(_) => None,
+            None => match address.chars().next() {
+                Some('1' | 'm' | 'n') => Some(ScriptType::P2pkh),
+                Some('3' | '2') => Some(ScriptType::P2shP2wpkh),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// A transaction together with the witness data authorizing each input.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub transaction: Transaction,
+    /// Hex encoded witness stack items, one stack per input in input order.
+    pub witnesses: Vec<Vec<String>>,
+}
+
+impl SignedTransaction {
+    /// Whether every input has a witness.
+    pub fn is_complete(&self) -> bool {
+        self.witnesses.len() == self.transaction.inputs.len()
+            && self.witnesses.iter().all(|w| !w.is_empty())
+    }
+}
+
+/// Anything that can authorize spending wallet coins: the software keystore,
+/// a hardware device or an air-gapped signer.
+pub trait TransactionSigner: Send + Sync {
+    /// Stable identifier, such as the master key fingerprint.
+    fn id(&self) -> &str;
+
+    /// Signs `tx`, whose inputs spend `prevouts` in the same order.
+    fn sign(&self, tx: &Transaction, prevouts: &[Utxo]) -> Result<SignedTransaction, String>;
+}
+
+/// A signer keeping its keys on an external device, which shows what it is
+/// asked to approve on its own screen.
+pub trait HardwareSigner: TransactionSigner {
+    /// Extended public key at the account-level `path`, for the wallet descriptor.
+    fn xpub(&self, path: &[u32], script_type: ScriptType) -> Result<String, String>;
+
+    /// Shows the address at `path` on the device and returns it.
+    fn display_address(&self, path: &[u32], script_type: ScriptType) -> Result<String, String>;
+
+    /// Shows the address at `path` on the device and checks it is the one
+    /// the wallet derived, so a compromised host can't substitute its own.
+    fn verify_address(
+        &self,
+        path: &[u32],
+        script_type: ScriptType,
+        expected: &str,
+    ) -> Result<(), String> {
+        let shown = self.display_address(path, script_type)?;
+        if shown == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "The device shows {} but the wallet expected {}",
+                shown, expected
+            ))
+        }
+    }
+}
+
+/// Signs arbitrary messages with the key behind a wallet address (BIP-322
+/// or legacy `signmessage` style, depending on the address type).
+pub trait MessageSigner: Send + Sync {
+    /// Returns the base64 encoded signature of `message` by `address`'s key.
+    fn sign_message(&self, address: &str, message: &str) -> Result<String, String>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivation_paths_and_script_types() {
+        let path = parse_derivation_path("m/84'/1h/0'/0/5").unwrap();
+        assert_eq!(path, vec![84 | HARDENED, 1 | HARDENED, HARDENED, 0, 5]);
+        assert_eq!(format_derivation_path(&path), "84h/1h/0h/0/5");
+        assert!(parse_derivation_path("84'/0'").is_err());
+        assert!(parse_derivation_path("m/2147483648").is_err());
+
+        assert_eq!(
+            ScriptType::from_address("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+            Some(ScriptType::P2wpkh)
+        );
+        assert_eq!(
+            ScriptType::from_address(
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3"
+            ),
+            None
+        );
+        assert_eq!(
+            ScriptType::from_address(
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c"
+            ),
+            Some(ScriptType::P2tr)
+        );
+        assert_eq!(
+            ScriptType::from_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+            Some(ScriptType::P2shP2wpkh)
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! What each signer can do, so a send is matched to a signer that can
+//! finish it before the user starts confirming anything.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::qr_signer::QrSigner;
+use super::ScriptType;
+use crate::types::Utxo;
+
+/// How a signer receives transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Connection {
+    Usb,
+    QrCode,
+    SdCard,
+}
+
+impl Connection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Connection::Usb => "USB",
+            Connection::QrCode => "QR code",
+            Connection::SdCard => "SD card",
+        }
+    }
+
+    pub fn is_air_gapped(&self) -> bool {
+        !matches!(self, Connection::Usb)
+    }
+}
+
+/// What one signer supports, as implemented here rather than everything
+/// the device itself could do.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignerCapabilities {
+    pub name: String,
+    /// In order of preference.
+    pub connections: Vec<Connection>,
+    pub script_types: Vec<ScriptType>,
+    /// Most inputs one transaction may have, if the device limits it.
+    pub max_inputs: Option<usize>,
+    pub message_signing: bool,
+}
+
+impl SignerCapabilities {
+    pub fn supports_taproot(&self) -> bool {
+        self.script_types.contains(&ScriptType::P2tr)
+    }
+
+    pub fn ledger() -> Self {
+        SignerCapabilities {
+            name: "Ledger".to_string(),
+            connections: vec![Connection::Usb],
+            script_types: vec![
+                ScriptType::P2pkh,
+                ScriptType::P2shP2wpkh,
+                ScriptType::P2wpkh,
+                ScriptType::P2tr,
+            ],
+            max_inputs: None,
+            message_signing: false,
+        }
+    }
+
+    /// Legacy inputs aren't signed over USB, and taproot depends on the
+    /// firmware installed.
+    #[cfg(feature = "usb")]
+    pub fn trezor(features: &super::trezor::TrezorFeatures) -> Self {
+        SignerCapabilities {
+            name: "Trezor".to_string(),
+            connections: vec![Connection::Usb],
+            script_types: [ScriptType::P2shP2wpkh, ScriptType::P2wpkh, ScriptType::P2tr]
+                .into_iter()
+                .filter(|script_type| features.supports(*script_type).is_ok())
+                .collect(),
+            max_inputs: None,
+            message_signing: false,
+        }
+    }
+
+    pub fn coldcard() -> Self {
+        SignerCapabilities {
+            name: "Coldcard".to_string(),
+            connections: vec![Connection::SdCard],
+            script_types: vec![
+                ScriptType::P2pkh,
+                ScriptType::P2shP2wpkh,
+                ScriptType::P2wpkh,
+                ScriptType::P2tr,
+            ],
+            max_inputs: None,
+            message_signing: false,
+        }
+    }
+
+    pub fn qr(signer: QrSigner) -> Self {
+        SignerCapabilities {
+            name: signer.name().to_string(),
+            connections: vec![Connection::QrCode],
+            script_types: vec![ScriptType::P2shP2wpkh, ScriptType::P2wpkh, ScriptType::P2tr],
+            max_inputs: None,
+            message_signing: false,
+        }
+    }
+
+    /// The connection to sign `request` over, or the first reason this
+    /// signer can't.
+    pub fn check(&self, request: &SigningRequest) -> Result<Connection, Unsupported> {
+        let connection = self
+            .connections
+            .iter()
+            .copied()
+            .find(|c| request.connections.is_empty() || request.connections.contains(c))
+            .ok_or_else(|| Unsupported::Connection(self.connections.clone()))?;
+        if let Some(script_type) = request
+            .script_types
+            .iter()
+            .find(|script_type| !self.script_types.contains(script_type))
+        {
+            return Err(Unsupported::ScriptType(*script_type));
+        }
+        if let Some(max) = self.max_inputs.filter(|max| request.inputs > *max) {
+            return Err(Unsupported::TooManyInputs {
+                inputs: request.inputs,
+                max,
+            });
+        }
+        if request.message_signing && !self.message_signing {
+            return Err(Unsupported::MessageSigning);
+        }
+        Ok(connection)
+    }
+}
+
+/// What a send or message signature needs from its signer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningRequest {
+    pub script_types: Vec<ScriptType>,
+    pub inputs: usize,
+    pub message_signing: bool,
+    /// Connections usable right now; empty allows any.
+    pub connections: Vec<Connection>,
+}
+
+impl SigningRequest {
+    /// Spending `prevouts`, which must all be single-key outputs.
+    pub fn for_spend(prevouts: &[Utxo]) -> Result<Self, String> {
+        let mut script_types = Vec::new();
+        for utxo in prevouts {
+            let script_type = ScriptType::from_address(&utxo.address).ok_or_else(|| {
+                format!(
+                    "{} is not a single-key address; no hardware signer can spend it",
+                    utxo.address
+                )
+            })?;
+            if !script_types.contains(&script_type) {
+                script_types.push(script_type);
+            }
+        }
+        Ok(SigningRequest {
+            script_types,
+            inputs: prevouts.len(),
+            ..Default::default()
+        })
+    }
+
+    /// Signing a message with the key behind `address`.
+    pub fn for_message(address: &str) -> Result<Self, String> {
+        let script_type = ScriptType::from_address(address)
+            .ok_or_else(|| format!("{} is not a single-key address", address))?;
+        Ok(SigningRequest {
+            script_types: vec![script_type],
+            message_signing: true,
+            ..Default::default()
+        })
+    }
+
+    /// Limits the flow to `connections`, e.g. air-gapped ones only.
+    pub fn with_connections(mut self, connections: Vec<Connection>) -> Self {
+        self.connections = connections;
+        self
+    }
+}
+
+/// Why a signer can't handle a request.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unsupported {
+    ScriptType(ScriptType),
+    TooManyInputs {
+        inputs: usize,
+        max: usize,
+    },
+    MessageSigning,
+    /// None of the signer's connections is allowed.
+    Connection(Vec<Connection>),
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unsupported::ScriptType(script_type) => {
+                write!(f, "can't sign {} inputs", script_type.name())
+            }
+            Unsupported::TooManyInputs { inputs, max } => write!(
+                f,
+                "signs at most {} inputs and this transaction has {}",
+                max, inputs
+            ),
+            Unsupported::MessageSigning => write!(f, "can't sign messages"),
+            Unsupported::Connection(connections) => {
+                let names: Vec<&str> = connections.iter().map(Connection::name).collect();
+                write!(f, "needs {}, which isn't available", names.join(" or "))
+            }
+        }
+    }
+}
+
+/// The signer and connection chosen for a request.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningFlow {
+    pub signer_id: String,
+    pub connection: Connection,
+}
+
+/// No registered signer can handle a request; lists why for each one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionError {
+    pub rejected: Vec<(String, Unsupported)>,
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rejected.is_empty() {
+            return write!(f, "No signer is set up for this wallet");
+        }
+        let reasons: Vec<String> = self
+            .rejected
+            .iter()
+            .map(|(name, reason)| format!("{} {}", name, reason))
+            .collect();
+        write!(f, "No signer can handle this: {}", reasons.join("; "))
+    }
+}
+
+/// Signers set up for a wallet, keyed by signer id.
+#[derive(Clone, Debug, Default)]
+pub struct SignerRegistry {
+    signers: Vec<(String, SignerCapabilities)>,
+}
+
+impl SignerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a signer, replacing any registered under the same id.
+    pub fn register(&mut self, id: &str, capabilities: SignerCapabilities) {
+        match self.signers.iter_mut().find(|(existing, _)| existing == id) {
+            Some((_, entry)) => *entry = capabilities,
+            None => self.signers.push((id.to_string(), capabilities)),
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<SignerCapabilities> {
+        let index = self
+            .signers
+            .iter()
+            .position(|(existing, _)| existing == id)?;
+        Some(self.signers.remove(index).1)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SignerCapabilities> {
+        self.signers
+            .iter()
+            .find(|(existing, _)| existing == id)
+            .map(|(_, capabilities)| capabilities)
+    }
+
+    /// The first registered signer able to handle `request`.
+    pub fn select(&self, request: &SigningRequest) -> Result<SigningFlow, SelectionError> {
+        let mut rejected = Vec::new();
+        for (id, capabilities) in &self.signers {
+            match capabilities.check(request) {
+                Ok(connection) => {
+                    return Ok(SigningFlow {
+                        signer_id: id.clone(),
+                        connection,
+                    })
+                }
+                Err(reason) => rejected.push((capabilities.name.clone(), reason)),
+            }
+        }
+        Err(SelectionError { rejected })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutPoint;
+
+    fn utxo(address: &str) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(&"00".repeat(32), 0),
+            address: address.to_string(),
+            value: 10_000,
+            confirmations: 6,
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_selects_compatible_flow() {
+        let mut registry = SignerRegistry::new();
+        assert_eq!(
+            registry
+                .select(&SigningRequest::default())
+                .unwrap_err()
+                .to_string(),
+            "No signer is set up for this wallet"
+        );
+        registry.register("jade", SignerCapabilities::qr(QrSigner::Jade));
+        registry.register("coldcard", SignerCapabilities::coldcard());
+        registry.register("ledger", SignerCapabilities::ledger());
+
+        let legacy = SigningRequest::for_spend(&[
+            utxo("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+            utxo("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"),
+        ])
+        .unwrap();
+        assert_eq!(legacy.script_types.len(), 2);
+        assert_eq!(
+            registry.select(&legacy),
+            Ok(SigningFlow {
+                signer_id: "coldcard".to_string(),
+                connection: Connection::SdCard,
+            })
+        );
+
+        // Only USB available: the Ledger is next in line
+        let usb_only = legacy.clone().with_connections(vec![Connection::Usb]);
+        assert_eq!(registry.select(&usb_only).unwrap().signer_id, "ledger");
+
+        registry.remove("ledger");
+        let mut limited = SignerCapabilities::coldcard();
+        limited.max_inputs = Some(1);
+        registry.register("coldcard", limited);
+        assert_eq!(
+            registry.select(&legacy).unwrap_err().to_string(),
+            "No signer can handle this: Jade can't sign p2pkh inputs; \
+             Coldcard signs at most 1 inputs and this transaction has 2"
+        );
+
+        let message = SigningRequest::for_message("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+            .unwrap()
+            .with_connections(vec![Connection::QrCode]);
+        assert_eq!(
+            registry.select(&message).unwrap_err().rejected,
+            vec![
+                ("Jade".to_string(), Unsupported::MessageSigning),
+                (
+                    "Coldcard".to_string(),
+                    Unsupported::Connection(vec![Connection::SdCard])
+                ),
+            ]
+        );
+        assert!(SigningRequest::for_spend(&[utxo(
+            "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3"
+        )])
+        .is_err());
+    }
+}
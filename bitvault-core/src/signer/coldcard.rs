@@ -0,0 +1,303 @@
+//! Air-gapped signing with a Coldcard through files on its SD card.
+//!
+//! The wallet writes the descriptor and `<name>.psbt` to the card; the
+//! Coldcard signs and writes `<name>-signed.psbt` (or `-part.psbt` when
+//! other cosigners remain) and, if it finalized the transaction,
+//! `<name>-final.txn`. Anything read back is checked to spend and pay
+//! exactly what was exported before it is accepted.
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::psbt::{
+    Psbt, PsbtMap, RawTx, GLOBAL_FALLBACK_LOCKTIME, GLOBAL_TX_VERSION, IN_OUTPUT_INDEX,
+    IN_PREVIOUS_TXID, IN_SEQUENCE, OUT_AMOUNT, OUT_SCRIPT,
+};
+use crate::events::{Event, MessageBus};
+use crate::metadata::descriptor_checksum;
+
+/// Longest file base name; the Coldcard menu truncates longer ones.
+const MAX_BASE_NAME: usize = 20;
+
+/// Signed files larger than this are rejected without parsing.
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+const SIGNER_NAME: &str = "Coldcard";
+
+/// An unsigned PSBT written to the card.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AirGapExport {
+    pub path: PathBuf,
+    /// File name without extension; signed files are named after it.
+    pub base: String,
+    pub psbt: Psbt,
+}
+
+/// What came back from the Coldcard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignedImport {
+    Psbt {
+        psbt: Psbt,
+        complete: bool,
+    },
+    /// Finalized and ready to broadcast.
+    Transaction(Vec<u8>),
+}
+
+/// Files exchanged with a Coldcard in `dir`, usually the SD card's mount point.
+pub struct ColdcardAirGap {
+    dir: PathBuf,
+    bus: Option<Arc<MessageBus>>,
+}
+
+impl ColdcardAirGap {
+    pub fn new(dir: &Path) -> Result<Self, String> {
+        if !dir.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        Ok(ColdcardAirGap {
+            dir: dir.to_path_buf(),
+            bus: None,
+        })
+    }
+
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    fn progress(&self, stage: &str) {
+        log::info!("Coldcard: {}", stage);
+        if let Some(bus) = &self.bus {
+            bus.publish(Event::SigningProgress {
+                signer: SIGNER_NAME.to_string(),
+                stage: stage.to_string(),
+            });
+        }
+    }
+
+    /// Writes `descriptor`, with its checksum, for the Coldcard's descriptor import.
+    pub fn export_descriptor(
+        &self,
+        wallet_name: &str,
+        descriptor: &str,
+    ) -> Result<PathBuf, String> {
+        let body = descriptor.split('#').next().unwrap_or_default();
+        let checksum = descriptor_checksum(descriptor)?;
+        let path = self.dir.join(format!("{}.txt", base_name(wallet_name)));
+        write_synced(&path, format!("{}#{}\n", body, checksum).as_bytes())?;
+        self.progress("Descriptor exported");
+        Ok(path)
+    }
+
+    /// Writes `psbt` for signing, named after the wallet and transaction.
+    pub fn export_psbt(&self, wallet_name: &str, psbt: &Psbt) -> Result<AirGapExport, String> {
+        let bytes = psbt.serialize();
+        let digest = hex::encode(Sha256::digest(&bytes));
+        let base = format!(
+            "{}-{}",
+            base_name(wallet_name)
+                .chars()
+                .take(MAX_BASE_NAME - 9)
+                .collect::<String>(),
+            &digest[..8]
+        );
+        let path = self.dir.join(format!("{}.psbt", base));
+        write_synced(&path, &bytes)?;
+        self.progress("PSBT exported; sign it on the Coldcard");
+        Ok(AirGapExport {
+            path,
+            base,
+            psbt: psbt.clone(),
+        })
+    }
+
+    /// The file the Coldcard wrote for `export`, preferring a finalized transaction.
+    pub fn find_signed(&self, export: &AirGapExport) -> Option<PathBuf> {
+        ["-final.txn", "-signed.psbt", "-part.psbt"]
+            .iter()
+            .map(|suffix| self.dir.join(format!("{}{}", export.base, suffix)))
+            .find(|path| path.is_file())
+    }
+
+    /// Reads back what the Coldcard wrote for `export` and checks it is
+    /// the same transaction.
+    pub fn import_signed(&self, export: &AirGapExport) -> Result<SignedImport, String> {
+        let path = self.find_signed(export).ok_or_else(|| {
+            format!(
+                "No signed file for {} found in {}",
+                export.base,
+                self.dir.display()
+            )
+        })?;
+        let size = fs::metadata(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            .len();
+        if size > MAX_FILE_SIZE {
+            return Err(format!(
+                "{} is too large to be a transaction",
+                path.display()
+            ));
+        }
+        let data =
+            fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.progress("Signed file found");
+
+        let original = export.psbt.to_v2()?;
+        let import = if path.extension().is_some_and(|ext| ext == "txn") {
+            // Hex text, though binary is accepted too
+            let raw = std::str::from_utf8(&data)
+                .ok()
+                .and_then(|text| hex::decode(text.trim()).ok())
+                .unwrap_or(data);
+            let tx = RawTx::parse(&raw)?;
+            if !same_as_raw(&original, &tx) {
+                return Err("The Coldcard returned a different transaction".to_string());
+            }
+            let unsigned = tx.inputs.iter().enumerate().any(|(i, input)| {
+                input.script_sig.is_empty() && tx.witnesses.get(i).is_none_or(Vec::is_empty)
+            });
+            if unsigned {
+                return Err("The returned transaction is missing signatures".to_string());
+            }
+            SignedImport::Transaction(raw)
+        } else {
+            let psbt = Psbt::decode(&data)?;
+            if !export.psbt.same_transaction(&psbt)? {
+                return Err("The Coldcard returned a PSBT for a different transaction".to_string());
+            }
+            let signed = (0..psbt.inputs.len())
+                .filter(|i| psbt.is_input_signed(*i) && !export.psbt.is_input_signed(*i))
+                .count();
+            if signed == 0 {
+                return Err("The Coldcard added no signatures".to_string());
+            }
+            let complete = (0..psbt.inputs.len()).all(|i| psbt.is_input_signed(i));
+            SignedImport::Psbt { psbt, complete }
+        };
+        self.progress("Signed transaction verified");
+        Ok(import)
+    }
+}
+
+/// File-system and Coldcard-friendly version of a wallet name.
+fn base_name(name: &str) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .take(MAX_BASE_NAME)
+        .collect();
+    if base.is_empty() {
+        "wallet".to_string()
+    } else {
+        base
+    }
+}
+
+/// Writes and flushes to the card, so it is safe to eject right after.
+fn write_synced(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut file = fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Whether a finalized transaction is the one described by a version 2 PSBT.
+fn same_as_raw(psbt: &Psbt, tx: &RawTx) -> bool {
+    let value = |map: &PsbtMap, key: u8| map.get(&[key][..]).cloned();
+    value(&psbt.global, GLOBAL_TX_VERSION) == Some(tx.version.to_le_bytes().to_vec())
+        && value(&psbt.global, GLOBAL_FALLBACK_LOCKTIME).unwrap_or_else(|| vec![0; 4])
+            == tx.lock_time.to_le_bytes()
+        && psbt.inputs.len() == tx.inputs.len()
+        && psbt.outputs.len() == tx.outputs.len()
+        && psbt.inputs.iter().zip(&tx.inputs).all(|(map, input)| {
+            value(map, IN_PREVIOUS_TXID) == Some(input.prev_hash.to_vec())
+                && value(map, IN_OUTPUT_INDEX) == Some(input.prev_index.to_le_bytes().to_vec())
+                && value(map, IN_SEQUENCE).unwrap_or_else(|| vec![0xff; 4])
+                    == input.sequence.to_le_bytes()
+        })
+        && psbt.outputs.iter().zip(&tx.outputs).all(|(map, output)| {
+            value(map, OUT_AMOUNT) == Some(output.amount.to_le_bytes().to_vec())
+                && value(map, OUT_SCRIPT).as_ref() == Some(&output.script_pubkey)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::psbt::{GLOBAL_UNSIGNED_TX, IN_PARTIAL_SIG};
+
+    fn unsigned_tx(amount: u64) -> Vec<u8> {
+        let mut tx = vec![2, 0, 0, 0, 1];
+        tx.extend([0x11; 32]);
+        tx.extend([0, 0, 0, 0, 0, 0xfd, 0xff, 0xff, 0xff, 1]);
+        tx.extend(amount.to_le_bytes());
+        tx.extend([2, 0x51, 0x00, 0, 0, 0, 0]);
+        tx
+    }
+
+    #[test]
+    fn test_export_and_verified_import() {
+        let dir = std::env::temp_dir().join(format!("bitvault_coldcard_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let card = ColdcardAirGap::new(&dir).unwrap().with_event_bus(bus);
+
+        let descriptor = card
+            .export_descriptor("My wallet!", "raw(deadbeef)")
+            .unwrap();
+        assert_eq!(descriptor, dir.join("My_wallet_.txt"));
+        assert_eq!(
+            fs::read_to_string(&descriptor).unwrap(),
+            "raw(deadbeef)#89f8spxm\n"
+        );
+
+        let psbt = Psbt {
+            global: PsbtMap::from([(vec![GLOBAL_UNSIGNED_TX], unsigned_tx(9_000))]),
+            inputs: vec![PsbtMap::new()],
+            outputs: vec![PsbtMap::new()],
+        };
+        let export = card.export_psbt("My wallet!", &psbt).unwrap();
+        assert!(export.base.starts_with("My_wallet_-") && export.base.len() <= MAX_BASE_NAME);
+        assert!(card.import_signed(&export).is_err());
+
+        // A signed PSBT paying a different amount is refused
+        let mut tampered = psbt.clone();
+        tampered
+            .global
+            .insert(vec![GLOBAL_UNSIGNED_TX], unsigned_tx(8_000));
+        tampered.inputs[0].insert(vec![IN_PARTIAL_SIG, 0x02], vec![0x30]);
+        let part = dir.join(format!("{}-part.psbt", export.base));
+        fs::write(&part, tampered.serialize()).unwrap();
+        assert!(card
+            .import_signed(&export)
+            .unwrap_err()
+            .contains("different"));
+
+        let mut signed = psbt.clone();
+        signed.inputs[0].insert(vec![IN_PARTIAL_SIG, 0x02], vec![0x30]);
+        fs::write(&part, signed.to_base64()).unwrap();
+        assert_eq!(
+            card.import_signed(&export).unwrap(),
+            SignedImport::Psbt {
+                psbt: signed,
+                complete: true
+            }
+        );
+        let stages = events
+            .try_iter()
+            .filter(|e| e.name() == "SigningProgress")
+            .count();
+        assert!(stages >= 4);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,822 @@
+//! Ledger devices running the Bitcoin app, version 2.1 or later.
+//!
+//! Commands are APDUs split over HID reports. Wallet policies and PSBTs are
+//! too large to send whole, so the host sends Merkle roots and the app asks
+//! for the pieces it needs while it runs ("client commands"), checking each
+//! against the roots.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::psbt::{
+    read_compact_size, write_compact_size, Psbt, PsbtMap, IN_PARTIAL_SIG, IN_TAP_KEY_SIG,
+    IN_TAP_SCRIPT_SIG,
+};
+use super::usb::{HardwareVendor, HidChannel, UsbBus, UsbDevice, HID_REPORT_SIZE};
+use super::{
+    format_derivation_path, HardwareSigner, ScriptType, SignedTransaction, TransactionSigner,
+    HARDENED,
+};
+use crate::events::{Event, MessageBus};
+use crate::types::{Network, Transaction, Utxo};
+
+const CLA_BITCOIN: u8 = 0xe1;
+const CLA_FRAMEWORK: u8 = 0xf8;
+const INS_GET_EXTENDED_PUBKEY: u8 = 0x00;
+const INS_REGISTER_WALLET: u8 = 0x02;
+const INS_GET_WALLET_ADDRESS: u8 = 0x03;
+const INS_SIGN_PSBT: u8 = 0x04;
+const INS_GET_MASTER_FINGERPRINT: u8 = 0x05;
+const INS_CONTINUE_INTERRUPTED: u8 = 0x01;
+const PROTOCOL_VERSION: u8 = 1;
+
+const SW_OK: u16 = 0x9000;
+const SW_INTERRUPTED: u16 = 0xe000;
+const SW_DENIED: u16 = 0x6985;
+const SW_LOCKED: u16 = 0x5515;
+const SW_SECURITY_STATUS: u16 = 0x6982;
+// Answers from the dashboard or another app
+const SW_APP_NOT_OPEN: [u16; 5] = [0x6e00, 0x6e01, 0x6d00, 0x6d02, 0x6511];
+
+const CMD_YIELD: u8 = 0x10;
+const CMD_GET_PREIMAGE: u8 = 0x40;
+const CMD_GET_MERKLE_LEAF_PROOF: u8 = 0x41;
+const CMD_GET_MERKLE_LEAF_INDEX: u8 = 0x42;
+const CMD_GET_MORE_ELEMENTS: u8 = 0xa0;
+
+// HID framing: channel, APDU tag and a big-endian sequence number per report
+const HID_CHANNEL: [u8; 2] = [0x01, 0x01];
+const TAG_APDU: u8 = 0x05;
+const HID_HEADER_LEN: usize = 5;
+
+/// How long the user has to confirm on the device.
+const USER_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LedgerError {
+    Transport(String),
+    /// The device sent something this implementation doesn't understand.
+    Protocol(String),
+    /// The dashboard or another app is open instead of the Bitcoin app.
+    AppNotOpen,
+    Locked,
+    /// The user rejected the request on the device.
+    Rejected,
+    /// Any other status word.
+    Status(u16),
+}
+
+impl LedgerError {
+    /// What the user has to do before retrying, for states they can fix.
+    pub fn guidance(&self) -> Option<&'static str> {
+        match self {
+            LedgerError::AppNotOpen => Some("Open the Bitcoin app on your Ledger"),
+            LedgerError::Locked => Some("Unlock your Ledger with its PIN"),
+            _ => None,
+        }
+    }
+
+    fn from_status(status: u16) -> Self {
+        match status {
+            SW_LOCKED | SW_SECURITY_STATUS => LedgerError::Locked,
+            SW_DENIED => LedgerError::Rejected,
+            status if SW_APP_NOT_OPEN.contains(&status) => LedgerError::AppNotOpen,
+            status => LedgerError::Status(status),
+        }
+    }
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::Transport(e) => write!(f, "Ledger connection failed: {}", e),
+            LedgerError::Protocol(e) => write!(f, "Unexpected response from Ledger: {}", e),
+            LedgerError::AppNotOpen => write!(f, "The Bitcoin app is not open on the Ledger"),
+            LedgerError::Locked => write!(f, "The Ledger is locked"),
+            LedgerError::Rejected => write!(f, "The request was rejected on the Ledger"),
+            LedgerError::Status(status) => write!(f, "Ledger error {:04x}", status),
+        }
+    }
+}
+
+/// A wallet described to the Bitcoin app: a descriptor template with `@n`
+/// placeholders for the keys, such as `wpkh(@0/**)`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletPolicy {
+    pub name: String,
+    pub template: String,
+    /// Keys as `[fingerprint/origin]xpub`.
+    pub keys: Vec<String>,
+}
+
+impl WalletPolicy {
+    /// The standard single-key policy of `script_type`, which the app
+    /// accepts without registration.
+    pub fn single_key(script_type: ScriptType, key: String) -> Self {
+        let template = match script_type {
+            ScriptType::P2pkh => "pkh(@0/**)",
+            ScriptType::P2shP2wpkh => "sh(wpkh(@0/**))",
+            ScriptType::P2wpkh => "wpkh(@0/**)",
+            ScriptType::P2tr => "tr(@0/**)",
+        };
+        WalletPolicy {
+            name: String::new(),
+            template: template.to_string(),
+            keys: vec![key],
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![2, self.name.len() as u8];
+        out.extend_from_slice(self.name.as_bytes());
+        write_compact_size(&mut out, self.template.len() as u64);
+        out.extend_from_slice(&Sha256::digest(self.template.as_bytes()));
+        write_compact_size(&mut out, self.keys.len() as u64);
+        let leaves: Vec<[u8; 32]> = self
+            .keys
+            .iter()
+            .map(|key| element_hash(key.as_bytes()))
+            .collect();
+        out.extend_from_slice(&merkle_root(&leaves));
+        out
+    }
+
+    /// Identifier the app computes for this policy.
+    pub fn id(&self) -> [u8; 32] {
+        Sha256::digest(self.serialize()).into()
+    }
+
+    fn commit(&self, commands: &mut ClientCommands) {
+        commands.add_preimage(&self.serialize());
+        commands.add_preimage(self.template.as_bytes());
+        let keys: Vec<Vec<u8>> = self.keys.iter().map(|k| k.as_bytes().to_vec()).collect();
+        commands.add_list(&keys);
+    }
+}
+
+/// A Ledger as a wallet signer.
+///
+/// Commands that fail because the device is locked or the Bitcoin app is
+/// closed publish a `DeviceActionRequired` event telling the user what to
+/// do, so the caller can simply retry.
+pub struct LedgerSigner {
+    channel: Mutex<Box<dyn HidChannel>>,
+    network: Network,
+    bus: Option<Arc<MessageBus>>,
+    id: String,
+    // Last guidance published, so retries don't repeat it
+    guidance: Mutex<Option<&'static str>>,
+}
+
+impl LedgerSigner {
+    pub fn open(
+        usb: &dyn UsbBus,
+        device: &UsbDevice,
+        network: Network,
+    ) -> Result<Self, LedgerError> {
+        if device.vendor != HardwareVendor::Ledger {
+            return Err(LedgerError::Transport(format!(
+                "{} is not a Ledger",
+                device.product
+            )));
+        }
+        let channel = usb.open(device).map_err(LedgerError::Transport)?;
+        Ok(Self::new(channel, network))
+    }
+
+    pub fn new(channel: Box<dyn HidChannel>, network: Network) -> Self {
+        LedgerSigner {
+            channel: Mutex::new(channel),
+            network,
+            bus: None,
+            id: String::new(),
+            guidance: Mutex::new(None),
+        }
+    }
+
+    pub fn with_event_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Waits for the Bitcoin app to be ready, trying up to `attempts` times
+    /// while the device is locked or the app closed, and reads the master
+    /// key fingerprint that identifies this signer.
+    pub fn connect(mut self, attempts: u32, interval: Duration) -> Result<Self, LedgerError> {
+        let mut attempt = 1;
+        loop {
+            match self.master_fingerprint() {
+                Ok(fingerprint) => {
+                    self.id = hex::encode(fingerprint);
+                    return Ok(self);
+                }
+                Err(e) if e.guidance().is_some() && attempt < attempts => {
+                    attempt += 1;
+                    std::thread::sleep(interval);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn master_fingerprint(&self) -> Result<[u8; 4], LedgerError> {
+        let response = self.exchange(
+            INS_GET_MASTER_FINGERPRINT,
+            &[],
+            &mut ClientCommands::default(),
+        )?;
+        response
+            .try_into()
+            .map_err(|_| LedgerError::Protocol("fingerprint is not 4 bytes".to_string()))
+    }
+
+    /// The xpub at `path`, optionally shown on the device for checking.
+    pub fn extended_pubkey(&self, path: &[u32], display: bool) -> Result<String, LedgerError> {
+        let mut data = vec![display as u8, path.len() as u8];
+        for step in path {
+            data.extend_from_slice(&step.to_be_bytes());
+        }
+        let response = self.exchange(
+            INS_GET_EXTENDED_PUBKEY,
+            &data,
+            &mut ClientCommands::default(),
+        )?;
+        String::from_utf8(response).map_err(|_| LedgerError::Protocol("invalid xpub".to_string()))
+    }
+
+    /// The standard single-key policy for account `account` of `script_type`.
+    pub fn standard_policy(
+        &self,
+        script_type: ScriptType,
+        account: u32,
+    ) -> Result<WalletPolicy, LedgerError> {
+        let coin = match self.network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        };
+        let path = [
+            script_type.purpose() | HARDENED,
+            coin | HARDENED,
+            account | HARDENED,
+        ];
+        let fingerprint = self.master_fingerprint()?;
+        let xpub = self.extended_pubkey(&path, false)?;
+        let key = format!(
+            "[{}/{}]{}",
+            hex::encode(fingerprint),
+            format_derivation_path(&path).replace('h', "'"),
+            xpub
+        );
+        Ok(WalletPolicy::single_key(script_type, key))
+    }
+
+    /// Has the user approve `policy` on the device. The returned HMAC must
+    /// be stored and passed along whenever the policy is used later.
+    pub fn register_wallet(&self, policy: &WalletPolicy) -> Result<[u8; 32], LedgerError> {
+        let serialized = policy.serialize();
+        let mut data = Vec::with_capacity(serialized.len() + 1);
+        write_compact_size(&mut data, serialized.len() as u64);
+        data.extend_from_slice(&serialized);
+        let mut commands = ClientCommands::default();
+        policy.commit(&mut commands);
+        let response = self.exchange(INS_REGISTER_WALLET, &data, &mut commands)?;
+        if response.len() != 64 || response[..32] != policy.id() {
+            return Err(LedgerError::Protocol(
+                "registration returned a different wallet".to_string(),
+            ));
+        }
+        Ok(response[32..].try_into().unwrap_or([0; 32]))
+    }
+
+    /// Address `index` of the receive or change branch of `policy`. With
+    /// `display` the device shows it for the user to compare.
+    pub fn wallet_address(
+        &self,
+        policy: &WalletPolicy,
+        hmac: Option<&[u8; 32]>,
+        change: bool,
+        index: u32,
+        display: bool,
+    ) -> Result<String, LedgerError> {
+        let mut data = vec![display as u8];
+        data.extend_from_slice(&policy.id());
+        data.extend_from_slice(hmac.unwrap_or(&[0; 32]));
+        data.push(change as u8);
+        data.extend_from_slice(&index.to_be_bytes());
+        let mut commands = ClientCommands::default();
+        policy.commit(&mut commands);
+        let response = self.exchange(INS_GET_WALLET_ADDRESS, &data, &mut commands)?;
+        String::from_utf8(response)
+            .map_err(|_| LedgerError::Protocol("invalid address".to_string()))
+    }
+
+    /// Signs the inputs of `psbt` that belong to `policy` and returns it
+    /// with the signatures added.
+    pub fn sign_psbt(
+        &self,
+        psbt: &Psbt,
+        policy: &WalletPolicy,
+        hmac: Option<&[u8; 32]>,
+    ) -> Result<Psbt, LedgerError> {
+        let v2 = psbt.to_v2().map_err(LedgerError::Protocol)?;
+        let mut commands = ClientCommands::default();
+        policy.commit(&mut commands);
+
+        let mut data = commands.add_map(&v2.global);
+        let inputs: Vec<Vec<u8>> = v2.inputs.iter().map(|m| commands.add_map(m)).collect();
+        let outputs: Vec<Vec<u8>> = v2.outputs.iter().map(|m| commands.add_map(m)).collect();
+        write_compact_size(&mut data, inputs.len() as u64);
+        data.extend_from_slice(&commands.add_list(&inputs));
+        write_compact_size(&mut data, outputs.len() as u64);
+        data.extend_from_slice(&commands.add_list(&outputs));
+        data.extend_from_slice(&policy.id());
+        data.extend_from_slice(hmac.unwrap_or(&[0; 32]));
+        self.exchange(INS_SIGN_PSBT, &data, &mut commands)?;
+
+        // Each yield is: input index, pubkey length, pubkey, signature
+        let mut signed = psbt.clone();
+        for item in &commands.yielded {
+            let malformed = || LedgerError::Protocol("malformed signature".to_string());
+            let (index, used) = read_compact_size(item).ok_or_else(malformed)?;
+            let key_len = *item.get(used).ok_or_else(malformed)? as usize;
+            let pubkey = item
+                .get(used + 1..used + 1 + key_len)
+                .ok_or_else(malformed)?;
+            let signature = item[used + 1 + key_len..].to_vec();
+            let key_type = match key_len {
+                33 => IN_PARTIAL_SIG,
+                32 => IN_TAP_KEY_SIG,
+                64 => IN_TAP_SCRIPT_SIG,
+                _ => return Err(malformed()),
+            };
+            let mut key = vec![key_type];
+            // A taproot key path signature is keyed by type alone
+            if key_type != IN_TAP_KEY_SIG {
+                key.extend_from_slice(pubkey);
+            }
+            signed
+                .inputs
+                .get_mut(index as usize)
+                .ok_or_else(malformed)?
+                .insert(key, signature);
+        }
+        Ok(signed)
+    }
+
+    /// Sends a command, answering the app's client commands until it completes.
+    fn exchange(
+        &self,
+        ins: u8,
+        data: &[u8],
+        commands: &mut ClientCommands,
+    ) -> Result<Vec<u8>, LedgerError> {
+        let (mut status, mut response) = self.apdu(CLA_BITCOIN, ins, data)?;
+        while status == SW_INTERRUPTED {
+            let reply = commands.execute(&response)?;
+            (status, response) = self.apdu(CLA_FRAMEWORK, INS_CONTINUE_INTERRUPTED, &reply)?;
+        }
+        let mut shown = self.guidance.lock().unwrap_or_else(|e| e.into_inner());
+        if status == SW_OK {
+            *shown = None;
+            return Ok(response);
+        }
+        let error = LedgerError::from_status(status);
+        if let Some(action) = error.guidance().filter(|action| *shown != Some(*action)) {
+            *shown = Some(action);
+            log::info!("Ledger needs attention: {}", action);
+            if let Some(bus) = &self.bus {
+                bus.publish(Event::DeviceActionRequired {
+                    vendor: HardwareVendor::Ledger.name().to_string(),
+                    action: action.to_string(),
+                });
+            }
+        }
+        Err(error)
+    }
+
+    /// One APDU round trip, returning the status word and response data.
+    fn apdu(&self, cla: u8, ins: u8, data: &[u8]) -> Result<(u16, Vec<u8>), LedgerError> {
+        if data.len() > 255 {
+            return Err(LedgerError::Protocol(format!(
+                "command of {} bytes is too long",
+                data.len()
+            )));
+        }
+        let mut command = vec![cla, ins, 0, PROTOCOL_VERSION, data.len() as u8];
+        command.extend_from_slice(data);
+        let channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        for report in frame(&command) {
+            channel.write(&report).map_err(LedgerError::Transport)?;
+        }
+        let response = receive(channel.as_ref())?;
+        if response.len() < 2 {
+            return Err(LedgerError::Protocol("response without status".to_string()));
+        }
+        let (body, status) = response.split_at(response.len() - 2);
+        Ok((u16::from_be_bytes([status[0], status[1]]), body.to_vec()))
+    }
+}
+
+impl TransactionSigner for LedgerSigner {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sign(&self, _tx: &Transaction, _prevouts: &[Utxo]) -> Result<SignedTransaction, String> {
+        Err("Ledger signs PSBTs; export the transaction as a PSBT to sign it".to_string())
+    }
+}
+
+impl HardwareSigner for LedgerSigner {
+    fn xpub(&self, path: &[u32], _script_type: ScriptType) -> Result<String, String> {
+        self.extended_pubkey(path, false).map_err(|e| e.to_string())
+    }
+
+    /// Only standard `purpose'/coin'/account'/change/index` paths can be
+    /// shown, as the app derives addresses from a wallet policy.
+    fn display_address(&self, path: &[u32], script_type: ScriptType) -> Result<String, String> {
+        let [purpose, _, account, change, index] = path else {
+            return Err(format!(
+                "Ledger can only show addresses at standard 5-step paths, not {}",
+                format_derivation_path(path)
+            ));
+        };
+        if *purpose != (script_type.purpose() | HARDENED) || *change > 1 {
+            return Err(format!(
+                "{} is not a standard {} path",
+                format_derivation_path(path),
+                script_type.name()
+            ));
+        }
+        let policy = self
+            .standard_policy(script_type, account & !HARDENED)
+            .map_err(|e| e.to_string())?;
+        self.wallet_address(&policy, None, *change == 1, *index, true)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Splits an APDU (or response) into HID reports.
+fn frame(payload: &[u8]) -> Vec<Vec<u8>> {
+    let mut data = (payload.len() as u16).to_be_bytes().to_vec();
+    data.extend_from_slice(payload);
+    data.chunks(HID_REPORT_SIZE - HID_HEADER_LEN)
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut report = HID_CHANNEL.to_vec();
+            report.push(TAG_APDU);
+            report.extend_from_slice(&(sequence as u16).to_be_bytes());
+            report.extend_from_slice(chunk);
+            report
+        })
+        .collect()
+}
+
+fn receive(channel: &dyn HidChannel) -> Result<Vec<u8>, LedgerError> {
+    let mut data = Vec::new();
+    let mut expected = None;
+    for sequence in 0u16.. {
+        let report = channel.read(USER_TIMEOUT).map_err(LedgerError::Transport)?;
+        if report.len() < HID_HEADER_LEN
+            || report[..2] != HID_CHANNEL
+            || report[2] != TAG_APDU
+            || report[3..5] != sequence.to_be_bytes()
+        {
+            return Err(LedgerError::Protocol("malformed HID report".to_string()));
+        }
+        data.extend_from_slice(&report[HID_HEADER_LEN..]);
+        if expected.is_none() && data.len() >= 2 {
+            expected = Some(u16::from_be_bytes([data[0], data[1]]) as usize + 2);
+        }
+        if let Some(len) = expected.filter(|len| data.len() >= *len) {
+            data.truncate(len);
+            return Ok(data.split_off(2));
+        }
+    }
+    Err(LedgerError::Protocol("response too long".to_string()))
+}
+
+fn element_hash(element: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(element);
+    hasher.finalize().into()
+}
+
+fn combine(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// The left subtree holds the largest power of two leaves below the total
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => [0; 32],
+        1 => leaves[0],
+        n => {
+            let (left, right) = leaves.split_at(split(n));
+            combine(&merkle_root(left), &merkle_root(right))
+        }
+    }
+}
+
+/// Sibling hashes from `index` up to the root.
+fn merkle_proof(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let (left, right) = leaves.split_at(split(leaves.len()));
+    let (mut proof, sibling) = if index < left.len() {
+        (merkle_proof(left, index), merkle_root(right))
+    } else {
+        (merkle_proof(right, index - left.len()), merkle_root(left))
+    };
+    proof.push(sibling);
+    proof
+}
+
+/// Everything the host committed to for one command, for answering the
+/// app's requests.
+#[derive(Default)]
+struct ClientCommands {
+    preimages: HashMap<[u8; 32], Vec<u8>>,
+    trees: HashMap<[u8; 32], Vec<[u8; 32]>>,
+    queue: VecDeque<Vec<u8>>,
+    yielded: Vec<Vec<u8>>,
+}
+
+impl ClientCommands {
+    fn add_preimage(&mut self, preimage: &[u8]) {
+        self.preimages
+            .insert(Sha256::digest(preimage).into(), preimage.to_vec());
+    }
+
+    /// Commits to a list and returns its Merkle root.
+    fn add_list(&mut self, elements: &[Vec<u8>]) -> [u8; 32] {
+        let leaves: Vec<[u8; 32]> = elements
+            .iter()
+            .map(|element| {
+                let mut preimage = vec![0x00];
+                preimage.extend_from_slice(element);
+                self.add_preimage(&preimage);
+                element_hash(element)
+            })
+            .collect();
+        let root = merkle_root(&leaves);
+        self.trees.insert(root, leaves);
+        root
+    }
+
+    /// Commits to a PSBT map: its size and the roots of its sorted keys and values.
+    fn add_map(&mut self, map: &PsbtMap) -> Vec<u8> {
+        let keys: Vec<Vec<u8>> = map.keys().cloned().collect();
+        let values: Vec<Vec<u8>> = map.values().cloned().collect();
+        let mut commitment = Vec::with_capacity(65);
+        write_compact_size(&mut commitment, map.len() as u64);
+        commitment.extend_from_slice(&self.add_list(&keys));
+        commitment.extend_from_slice(&self.add_list(&values));
+        commitment
+    }
+
+    fn execute(&mut self, request: &[u8]) -> Result<Vec<u8>, LedgerError> {
+        let unknown = |what: &str| LedgerError::Protocol(format!("app asked for unknown {}", what));
+        let hash_at = |offset: usize| -> Result<[u8; 32], LedgerError> {
+            request
+                .get(offset..offset + 32)
+                .and_then(|h| h.try_into().ok())
+                .ok_or_else(|| LedgerError::Protocol("truncated client command".to_string()))
+        };
+        match request.first() {
+            Some(&CMD_YIELD) => {
+                self.yielded.push(request[1..].to_vec());
+                Ok(Vec::new())
+            }
+            Some(&CMD_GET_PREIMAGE) => {
+                let preimage = self
+                    .preimages
+                    .get(&hash_at(2)?)
+                    .ok_or_else(|| unknown("preimage"))?;
+                let mut response = Vec::new();
+                write_compact_size(&mut response, preimage.len() as u64);
+                let fits = (255 - response.len() - 1).min(preimage.len());
+                response.push(fits as u8);
+                response.extend_from_slice(&preimage[..fits]);
+                self.queue
+                    .extend(preimage[fits..].iter().map(|byte| vec![*byte]));
+                Ok(response)
+            }
+            Some(&CMD_GET_MERKLE_LEAF_PROOF) => {
+                let leaves = self
+                    .trees
+                    .get(&hash_at(1)?)
+                    .ok_or_else(|| unknown("tree"))?;
+                let rest = &request[33..];
+                let (size, used) = read_compact_size(rest).ok_or_else(|| unknown("tree size"))?;
+                let (index, _) = read_compact_size(&rest[used..]).ok_or_else(|| unknown("leaf"))?;
+                let index = index as usize;
+                if size as usize != leaves.len() || index >= leaves.len() {
+                    return Err(unknown("leaf"));
+                }
+                let proof = merkle_proof(leaves, index);
+                let fits = proof.len().min((255 - 32 - 2) / 32);
+                let mut response = leaves[index].to_vec();
+                response.push(proof.len() as u8);
+                response.push(fits as u8);
+                for hash in &proof[..fits] {
+                    response.extend_from_slice(hash);
+                }
+                self.queue
+                    .extend(proof[fits..].iter().map(|hash| hash.to_vec()));
+                Ok(response)
+            }
+            Some(&CMD_GET_MERKLE_LEAF_INDEX) => {
+                let leaves = self
+                    .trees
+                    .get(&hash_at(1)?)
+                    .ok_or_else(|| unknown("tree"))?;
+                let leaf = hash_at(33)?;
+                let mut response = Vec::new();
+                match leaves.iter().position(|l| *l == leaf) {
+                    Some(index) => {
+                        response.push(1);
+                        write_compact_size(&mut response, index as u64);
+                    }
+                    None => response.extend_from_slice(&[0, 0]),
+                }
+                Ok(response)
+            }
+            Some(&CMD_GET_MORE_ELEMENTS) => {
+                let size = self
+                    .queue
+                    .front()
+                    .map(Vec::len)
+                    .ok_or_else(|| unknown("elements"))?;
+                let mut response = vec![0, size as u8];
+                while response.len() + size <= 255
+                    && self.queue.front().is_some_and(|e| e.len() == size)
+                {
+                    response.extend(self.queue.pop_front().unwrap_or_default());
+                    response[0] += 1;
+                }
+                Ok(response)
+            }
+            other => Err(LedgerError::Protocol(format!(
+                "unknown client command {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::psbt::GLOBAL_UNSIGNED_TX;
+
+    #[test]
+    fn test_merkle_commands() {
+        let elements: Vec<Vec<u8>> = (0u8..5).map(|i| vec![i; 3]).collect();
+        let leaves: Vec<[u8; 32]> = elements.iter().map(|e| element_hash(e)).collect();
+        let mut commands = ClientCommands::default();
+        let root = commands.add_list(&elements);
+        let left = combine(
+            &combine(&leaves[0], &leaves[1]),
+            &combine(&leaves[2], &leaves[3]),
+        );
+        assert_eq!(root, combine(&left, &leaves[4]));
+
+        let mut request = vec![CMD_GET_MERKLE_LEAF_PROOF];
+        request.extend_from_slice(&root);
+        request.extend_from_slice(&[5, 3]);
+        let response = commands.execute(&request).unwrap();
+        assert_eq!(response[..32], leaves[3]);
+        assert_eq!(response[32..34], [3, 3]);
+        assert_eq!(response[34..66], leaves[2]);
+        assert_eq!(response[66..98], combine(&leaves[0], &leaves[1]));
+        assert_eq!(response[98..], leaves[4]);
+
+        let mut request = vec![CMD_GET_MERKLE_LEAF_INDEX];
+        request.extend_from_slice(&root);
+        request.extend_from_slice(&leaves[4]);
+        assert_eq!(commands.execute(&request).unwrap(), vec![1, 4]);
+
+        // Long preimages continue through GET_MORE_ELEMENTS
+        let template = "x".repeat(300);
+        commands.add_preimage(template.as_bytes());
+        let mut request = vec![CMD_GET_PREIMAGE, 0];
+        request.extend_from_slice(&Sha256::digest(template.as_bytes()));
+        let response = commands.execute(&request).unwrap();
+        assert_eq!(response[..4], [0xfd, 0x2c, 0x01, 251]);
+        let more = commands.execute(&[CMD_GET_MORE_ELEMENTS]).unwrap();
+        assert_eq!(more[..2], [49, 1]);
+    }
+
+    /// Answers each complete APDU with the next scripted response.
+    struct FakeLedger {
+        responses: Mutex<VecDeque<Vec<u8>>>,
+        pending: Mutex<VecDeque<Vec<u8>>>,
+        received: Arc<Mutex<Vec<u8>>>,
+        partial: Mutex<Vec<u8>>,
+    }
+
+    impl HidChannel for FakeLedger {
+        fn write(&self, report: &[u8]) -> Result<(), String> {
+            let mut partial = self.partial.lock().unwrap();
+            partial.extend_from_slice(&report[HID_HEADER_LEN..]);
+            let len = u16::from_be_bytes([partial[0], partial[1]]) as usize;
+            if partial.len() >= len + 2 {
+                self.received.lock().unwrap().push(partial[3]);
+                partial.clear();
+                let response = self.responses.lock().unwrap().pop_front().unwrap();
+                self.pending.lock().unwrap().extend(frame(&response));
+            }
+            Ok(())
+        }
+
+        fn read(&self, _timeout: Duration) -> Result<Vec<u8>, String> {
+            self.pending
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| "Timed out".to_string())
+        }
+    }
+
+    #[test]
+    fn test_connect_retry_and_signing() {
+        let pubkey = [0x02; 33];
+        let mut signature_yield = vec![CMD_YIELD, 0, 33];
+        signature_yield.extend_from_slice(&pubkey);
+        signature_yield.extend_from_slice(&[0x30, 0x44, 0x01]);
+        signature_yield.extend_from_slice(&SW_INTERRUPTED.to_be_bytes());
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let channel = FakeLedger {
+            responses: Mutex::new(VecDeque::from([
+                vec![0x6e, 0x01],
+                vec![0x6e, 0x01],
+                vec![0xf5, 0xac, 0xc2, 0xfd, 0x90, 0x00],
+                signature_yield,
+                vec![0x90, 0x00],
+            ])),
+            pending: Mutex::new(VecDeque::new()),
+            received: received.clone(),
+            partial: Mutex::new(Vec::new()),
+        };
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+        let signer = LedgerSigner::new(Box::new(channel), Network::Testnet)
+            .with_event_bus(bus)
+            .connect(3, Duration::ZERO)
+            .unwrap();
+        assert_eq!(signer.id(), "f5acc2fd");
+        // Guidance is published once, not on every retry
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![Event::DeviceActionRequired {
+                vendor: "Ledger".to_string(),
+                action: "Open the Bitcoin app on your Ledger".to_string(),
+            }]
+        );
+
+        // version 2, one input, no outputs, locktime 0
+        let mut tx = vec![2, 0, 0, 0, 1];
+        tx.extend([0x11; 32]);
+        tx.extend([0, 0, 0, 0, 0, 0xfd, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0]);
+        let psbt = Psbt {
+            global: PsbtMap::from([(vec![GLOBAL_UNSIGNED_TX], tx)]),
+            inputs: vec![PsbtMap::new()],
+            outputs: Vec::new(),
+        };
+        let policy = WalletPolicy::single_key(
+            ScriptType::P2wpkh,
+            "[f5acc2fd/84'/1'/0']tpubexample".to_string(),
+        );
+        let signed = signer.sign_psbt(&psbt, &policy, None).unwrap();
+        let mut key = vec![IN_PARTIAL_SIG];
+        key.extend_from_slice(&pubkey);
+        assert_eq!(signed.inputs[0][&key], vec![0x30, 0x44, 0x01]);
+        assert!(signed.is_input_signed(0));
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                INS_GET_MASTER_FINGERPRINT,
+                INS_GET_MASTER_FINGERPRINT,
+                INS_GET_MASTER_FINGERPRINT,
+                INS_SIGN_PSBT,
+                INS_CONTINUE_INTERRUPTED,
+            ]
+        );
+    }
+}
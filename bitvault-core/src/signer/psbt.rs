@@ -0,0 +1,402 @@
+//! Partially signed transactions (BIP-174 and BIP-370) as raw key-value
+//! maps. Fields are only interpreted where signers need them.
+
+use base64::Engine;
+use std::collections::BTreeMap;
+
+/// Magic bytes at the start of every serialized PSBT.
+pub const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+pub const GLOBAL_UNSIGNED_TX: u8 = 0x00;
+pub const GLOBAL_TX_VERSION: u8 = 0x02;
+pub const GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+pub const GLOBAL_INPUT_COUNT: u8 = 0x04;
+pub const GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+pub const GLOBAL_VERSION: u8 = 0xfb;
+
+pub const IN_PARTIAL_SIG: u8 = 0x02;
+pub const IN_FINAL_SCRIPTSIG: u8 = 0x07;
+pub const IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+pub const IN_PREVIOUS_TXID: u8 = 0x0e;
+pub const IN_OUTPUT_INDEX: u8 = 0x0f;
+pub const IN_SEQUENCE: u8 = 0x10;
+pub const IN_TAP_KEY_SIG: u8 = 0x13;
+pub const IN_TAP_SCRIPT_SIG: u8 = 0x14;
+
+pub const OUT_AMOUNT: u8 = 0x03;
+pub const OUT_SCRIPT: u8 = 0x04;
+
+/// Keys (type byte followed by key data) to values; unique and sorted, as
+/// BIP-174 requires.
+pub type PsbtMap = BTreeMap<Vec<u8>, Vec<u8>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Psbt {
+    pub global: PsbtMap,
+    pub inputs: Vec<PsbtMap>,
+    pub outputs: Vec<PsbtMap>,
+}
+
+impl Psbt {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let rest = data
+            .strip_prefix(PSBT_MAGIC)
+            .ok_or_else(|| "Not a PSBT".to_string())?;
+        let mut reader = Reader { data: rest, pos: 0 };
+        let global = reader.map()?;
+        let (input_count, output_count) = match global.get(&[GLOBAL_UNSIGNED_TX][..]) {
+            Some(tx) => {
+                let tx = RawTx::parse(tx)?;
+                (tx.inputs.len(), tx.outputs.len())
+            }
+            None => {
+                // Every map takes at least its separator byte
+                let count = |key: u8| {
+                    let value = global
+                        .get(&[key][..])
+                        .ok_or_else(|| "PSBT has no unsigned transaction".to_string())?;
+                    let n = Reader {
+                        data: value,
+                        pos: 0,
+                    }
+                    .compact_size_raw()?;
+                    usize::try_from(n)
+                        .ok()
+                        .filter(|n| *n <= rest.len())
+                        .ok_or_else(|| "Invalid map count in PSBT".to_string())
+                };
+                (count(GLOBAL_INPUT_COUNT)?, count(GLOBAL_OUTPUT_COUNT)?)
+            }
+        };
+        let inputs = (0..input_count)
+            .map(|_| reader.map())
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = (0..output_count)
+            .map(|_| reader.map())
+            .collect::<Result<Vec<_>, _>>()?;
+        if reader.pos != rest.len() {
+            return Err("Trailing bytes after PSBT".to_string());
+        }
+        Ok(Psbt {
+            global,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Parses binary or base64 encoded PSBTs.
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.starts_with(PSBT_MAGIC) {
+            return Self::parse(data);
+        }
+        let text = std::str::from_utf8(data).map_err(|_| "Not a PSBT".to_string())?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(text.trim())
+            .map_err(|_| "Not a PSBT".to_string())?;
+        Self::parse(&bytes)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = PSBT_MAGIC.to_vec();
+        for map in std::iter::once(&self.global)
+            .chain(&self.inputs)
+            .chain(&self.outputs)
+        {
+            for (key, value) in map {
+                write_compact_size(&mut out, key.len() as u64);
+                out.extend_from_slice(key);
+                write_compact_size(&mut out, value.len() as u64);
+                out.extend_from_slice(value);
+            }
+            out.push(0);
+        }
+        out
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.serialize())
+    }
+
+    /// The unsigned transaction of a version 0 PSBT.
+    pub fn unsigned_tx(&self) -> Result<RawTx, String> {
+        let tx = self
+            .global
+            .get(&[GLOBAL_UNSIGNED_TX][..])
+            .ok_or_else(|| "PSBT has no unsigned transaction".to_string())?;
+        RawTx::parse(tx)
+    }
+
+    /// The same PSBT as version 2, with the transaction spread over the maps.
+    pub fn to_v2(&self) -> Result<Psbt, String> {
+        if self.global.get(&[GLOBAL_VERSION][..]) == Some(&2u32.to_le_bytes().to_vec()) {
+            return Ok(self.clone());
+        }
+        let tx = self.unsigned_tx()?;
+        let mut v2 = self.clone();
+        v2.global.remove(&[GLOBAL_UNSIGNED_TX][..]);
+        let mut count = |key: u8, n: usize| {
+            let mut value = Vec::new();
+            write_compact_size(&mut value, n as u64);
+            v2.global.insert(vec![key], value);
+        };
+        count(GLOBAL_INPUT_COUNT, tx.inputs.len());
+        count(GLOBAL_OUTPUT_COUNT, tx.outputs.len());
+        v2.global
+            .insert(vec![GLOBAL_TX_VERSION], tx.version.to_le_bytes().to_vec());
+        v2.global.insert(
+            vec![GLOBAL_FALLBACK_LOCKTIME],
+            tx.lock_time.to_le_bytes().to_vec(),
+        );
+        v2.global
+            .insert(vec![GLOBAL_VERSION], 2u32.to_le_bytes().to_vec());
+        for (map, input) in v2.inputs.iter_mut().zip(&tx.inputs) {
+            map.insert(vec![IN_PREVIOUS_TXID], input.prev_hash.to_vec());
+            map.insert(
+                vec![IN_OUTPUT_INDEX],
+                input.prev_index.to_le_bytes().to_vec(),
+            );
+            map.insert(vec![IN_SEQUENCE], input.sequence.to_le_bytes().to_vec());
+        }
+        for (map, output) in v2.outputs.iter_mut().zip(&tx.outputs) {
+            map.insert(vec![OUT_AMOUNT], output.amount.to_le_bytes().to_vec());
+            map.insert(vec![OUT_SCRIPT], output.script_pubkey.clone());
+        }
+        Ok(v2)
+    }
+
+    /// Whether both PSBTs spend the same inputs to the same outputs with the
+    /// same locktime, whatever signatures or metadata either carries.
+    pub fn same_transaction(&self, other: &Psbt) -> Result<bool, String> {
+        let (a, b) = (self.to_v2()?, other.to_v2()?);
+        let same = |x: &PsbtMap, y: &PsbtMap, keys: &[u8]| {
+            keys.iter()
+                .all(|key| x.get(&[*key][..]) == y.get(&[*key][..]))
+        };
+        let global = [
+            GLOBAL_TX_VERSION,
+            GLOBAL_FALLBACK_LOCKTIME,
+            GLOBAL_INPUT_COUNT,
+            GLOBAL_OUTPUT_COUNT,
+        ];
+        Ok(same(&a.global, &b.global, &global)
+            && a.inputs.len() == b.inputs.len()
+            && a.outputs.len() == b.outputs.len()
+            && a.inputs
+                .iter()
+                .zip(&b.inputs)
+                .all(|(x, y)| same(x, y, &[IN_PREVIOUS_TXID, IN_OUTPUT_INDEX, IN_SEQUENCE]))
+            && a.outputs
+                .iter()
+                .zip(&b.outputs)
+                .all(|(x, y)| same(x, y, &[OUT_AMOUNT, OUT_SCRIPT])))
+    }
+
+    /// Whether input `index` carries a signature or is already finalized.
+    pub fn is_input_signed(&self, index: usize) -> bool {
+        self.inputs.get(index).is_some_and(|map| {
+            map.keys().any(|key| {
+                key.first().is_some_and(|key_type| {
+                    matches!(
+                        *key_type,
+                        IN_PARTIAL_SIG
+                            | IN_FINAL_SCRIPTSIG
+                            | IN_FINAL_SCRIPTWITNESS
+                            | IN_TAP_KEY_SIG
+                            | IN_TAP_SCRIPT_SIG
+                    )
+                })
+            })
+        })
+    }
+}
+
+pub(crate) fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+/// Reads a compact size from the start of `data`, returning it with the
+/// number of bytes it took.
+#[cfg(feature = "usb")]
+pub(crate) fn read_compact_size(data: &[u8]) -> Option<(u64, usize)> {
+    let mut reader = Reader { data, pos: 0 };
+    let n = reader.compact_size_raw().ok()?;
+    Some((n, reader.pos))
+}
+
+pub struct RawInput {
+    /// In serialization order, the reverse of how txids are displayed.
+    pub prev_hash: [u8; 32],
+    pub prev_index: u32,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+}
+
+pub struct RawOutput {
+    pub amount: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// A transaction in consensus serialization.
+pub struct RawTx {
+    pub version: u32,
+    pub inputs: Vec<RawInput>,
+    pub outputs: Vec<RawOutput>,
+    pub witnesses: Vec<Vec<Vec<u8>>>,
+    pub lock_time: u32,
+}
+
+impl RawTx {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data, pos: 0 };
+        let version = reader.u32()?;
+        let segwit = data.get(4..6) == Some(&[0u8, 1][..]);
+        if segwit {
+            reader.take(2)?;
+        }
+        let inputs = (0..reader.compact_size()?)
+            .map(|_| {
+                Ok(RawInput {
+                    prev_hash: reader.take(32)?.try_into().unwrap_or([0; 32]),
+                    prev_index: reader.u32()?,
+                    script_sig: reader.var_bytes()?,
+                    sequence: reader.u32()?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let outputs = (0..reader.compact_size()?)
+            .map(|_| {
+                Ok(RawOutput {
+                    amount: u64::from_le_bytes(reader.take(8)?.try_into().unwrap_or_default()),
+                    script_pubkey: reader.var_bytes()?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut witnesses = Vec::new();
+        if segwit {
+            for _ in 0..inputs.len() {
+                let stack = (0..reader.compact_size()?)
+                    .map(|_| reader.var_bytes())
+                    .collect::<Result<Vec<_>, _>>()?;
+                witnesses.push(stack);
+            }
+        }
+        let lock_time = reader.u32()?;
+        if reader.pos != data.len() {
+            return Err("Trailing bytes after transaction".to_string());
+        }
+        Ok(RawTx {
+            version,
+            inputs,
+            outputs,
+            witnesses,
+            lock_time,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| "Truncated data".to_string())?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().unwrap_or_default(),
+        ))
+    }
+
+    fn compact_size_raw(&mut self) -> Result<u64, String> {
+        Ok(match self.take(1)?[0] {
+            0xfd => u16::from_le_bytes(self.take(2)?.try_into().unwrap_or_default()) as u64,
+            0xfe => self.u32()? as u64,
+            0xff => u64::from_le_bytes(self.take(8)?.try_into().unwrap_or_default()),
+            n => n as u64,
+        })
+    }
+
+    fn compact_size(&mut self) -> Result<usize, String> {
+        let value = self.compact_size_raw()?;
+        // Bounded by the remaining data, so a bogus count can't allocate much
+        usize::try_from(value)
+            .ok()
+            .filter(|n| *n <= self.data.len() - self.pos)
+            .ok_or_else(|| "Invalid length".to_string())
+    }
+
+    fn var_bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.compact_size()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn map(&mut self) -> Result<PsbtMap, String> {
+        let mut map = PsbtMap::new();
+        loop {
+            let key = self.var_bytes()?;
+            if key.is_empty() {
+                return Ok(map);
+            }
+            let value = self.var_bytes()?;
+            if map.insert(key, value).is_some() {
+                return Err("Duplicate key in PSBT".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_and_v2_conversion() {
+        // version 2, one input, one output, locktime 0
+        let mut tx = vec![2, 0, 0, 0, 1];
+        tx.extend([0x11; 32]);
+        tx.extend([1, 0, 0, 0, 0, 0xfd, 0xff, 0xff, 0xff, 1]);
+        tx.extend(9_000u64.to_le_bytes());
+        tx.extend([2, 0x51, 0x00]);
+        tx.extend([0, 0, 0, 0]);
+
+        let mut psbt = Psbt {
+            global: PsbtMap::from([(vec![GLOBAL_UNSIGNED_TX], tx)]),
+            inputs: vec![PsbtMap::new()],
+            outputs: vec![PsbtMap::new()],
+        };
+        let parsed = Psbt::decode(psbt.to_base64().as_bytes()).unwrap();
+        assert_eq!(parsed, psbt);
+        assert!(!parsed.is_input_signed(0));
+
+        let v2 = psbt.to_v2().unwrap();
+        assert_eq!(Psbt::parse(&v2.serialize()).unwrap(), v2);
+        assert_eq!(v2.inputs[0][&vec![IN_OUTPUT_INDEX]], vec![1, 0, 0, 0]);
+        assert_eq!(v2.outputs[0][&vec![OUT_SCRIPT]], vec![0x51, 0x00]);
+
+        psbt.inputs[0].insert(vec![IN_PARTIAL_SIG, 0x02], vec![0x30]);
+        assert!(psbt.is_input_signed(0));
+        assert!(Psbt::parse(b"psbt\xff\x00").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Air-gapped signers that talk through animated QR codes.
+//!
+//! Each device gets the wallet descriptor as a `bytes` UR and the PSBT as a
+//! `crypto-psbt` UR, and answers with the signed PSBT the same way. What
+//! differs between them is how much each QR frame may carry.
+
+use serde::{Deserialize, Serialize};
+
+use super::psbt::Psbt;
+use super::ur::{self, UrDecoder};
+use crate::metadata::descriptor_checksum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QrSigner {
+    /// Blockstream Jade, whose small camera needs sparse frames.
+    Jade,
+    /// Foundation Passport.
+    Passport,
+}
+
+impl QrSigner {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QrSigner::Jade => "Jade",
+            QrSigner::Passport => "Passport",
+        }
+    }
+
+    /// Largest fragment per frame the device's camera reads reliably.
+    pub fn max_fragment_len(&self) -> usize {
+        match self {
+            QrSigner::Jade => 60,
+            QrSigner::Passport => 120,
+        }
+    }
+
+    /// Frames registering the wallet `descriptor` on the device, with its
+    /// checksum added if missing.
+    pub fn export_descriptor(&self, descriptor: &str) -> Result<Vec<String>, String> {
+        let body = descriptor.split('#').next().unwrap_or_default();
+        let text = format!("{}#{}", body, descriptor_checksum(descriptor)?);
+        Ok(ur::encode(
+            "bytes",
+            text.as_bytes(),
+            self.max_fragment_len(),
+        ))
+    }
+
+    /// Frames carrying `psbt` to the device for signing.
+    pub fn export_psbt(&self, psbt: &Psbt) -> Vec<String> {
+        ur::encode("crypto-psbt", &psbt.serialize(), self.max_fragment_len())
+    }
+
+    /// The signed PSBT scanned from the device, once it's checked to be
+    /// `unsigned` with signatures added.
+    pub fn import_signed(&self, scanned: &UrDecoder, unsigned: &Psbt) -> Result<Psbt, String> {
+        if scanned.ur_type() != Some("crypto-psbt") {
+            return Err(format!(
+                "Expected a signed PSBT from the {}, got {}",
+                self.name(),
+                scanned.ur_type().unwrap_or("nothing")
+            ));
+        }
+        let signed = Psbt::parse(&scanned.payload()?)?;
+        if !unsigned.same_transaction(&signed)? {
+            return Err(format!(
+                "The {} returned a PSBT for a different transaction",
+                self.name()
+            ));
+        }
+        let added = (0..signed.inputs.len())
+            .any(|i| signed.is_input_signed(i) && !unsigned.is_input_signed(i));
+        if !added {
+            return Err(format!("The {} added no signatures", self.name()));
+        }
+        Ok(signed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    // Produced by a separate implementation of the UR specs, so these catch
+    // encoding regressions rather than just round-tripping our own output
+    const FIXTURES: [(QrSigner, &str); 2] = [
+        (QrSigner::Jade, include_str!("fixtures/jade.json")),
+        (QrSigner::Passport, include_str!("fixtures/passport.json")),
+    ];
+
+    fn frames(value: &Value) -> Vec<String> {
+        value["frames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| frame.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn psbt(value: &Value) -> Psbt {
+        Psbt::decode(value["base64"].as_str().unwrap().as_bytes()).unwrap()
+    }
+
+    fn scan(frames: &[String]) -> UrDecoder {
+        let mut decoder = UrDecoder::new();
+        for frame in frames {
+            decoder.receive(frame).unwrap();
+        }
+        decoder
+    }
+
+    #[test]
+    fn test_fixture_conformance() {
+        for (signer, fixture) in FIXTURES {
+            let fixture: Value = serde_json::from_str(fixture).unwrap();
+            assert_eq!(fixture["profile"], signer.name().to_ascii_lowercase());
+
+            let descriptor = fixture["descriptor"]["text"].as_str().unwrap();
+            let expected = frames(&fixture["descriptor"]);
+            assert_eq!(signer.export_descriptor(descriptor).unwrap(), expected);
+            let body = descriptor.split('#').next().unwrap();
+            assert_eq!(signer.export_descriptor(body).unwrap(), expected);
+            assert_eq!(scan(&expected).payload().unwrap(), descriptor.as_bytes());
+
+            let unsigned = psbt(&fixture["psbt"]);
+            assert_eq!(signer.export_psbt(&unsigned), frames(&fixture["psbt"]));
+
+            // Animated codes loop, so parts arrive in any order and repeat
+            let mut response = frames(&fixture["signed"]);
+            response.reverse();
+            response.push(response[0].clone());
+            let signed = signer.import_signed(&scan(&response), &unsigned).unwrap();
+            assert_eq!(signed, psbt(&fixture["signed"]));
+        }
+    }
+
+    #[test]
+    fn test_rejects_wrong_responses() {
+        let jade: Value = serde_json::from_str(FIXTURES[0].1).unwrap();
+        let passport: Value = serde_json::from_str(FIXTURES[1].1).unwrap();
+        let signer = QrSigner::Jade;
+
+        let echoed = scan(&frames(&jade["psbt"]));
+        let err = signer.import_signed(&echoed, &psbt(&jade["psbt"]));
+        assert_eq!(err, Err("The Jade added no signatures".to_string()));
+
+        let foreign = scan(&frames(&passport["signed"]));
+        let err = signer
+            .import_signed(&foreign, &psbt(&jade["psbt"]))
+            .unwrap_err();
+        assert!(err.contains("different transaction"));
+
+        let descriptor = scan(&frames(&jade["descriptor"]));
+        assert!(signer
+            .import_signed(&descriptor, &psbt(&jade["psbt"]))
+            .is_err());
+
+        // A frame from another message mid-scan is caught, not merged
+        let mut decoder = UrDecoder::new();
+        decoder.receive(&frames(&jade["signed"])[0]).unwrap();
+        assert!(decoder.receive(&frames(&jade["psbt"])[1]).is_err());
+    }
+}
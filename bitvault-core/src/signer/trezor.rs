@@ -0,0 +1,1028 @@
+//! Trezor devices over the USB HID transport.
+//!
+//! Messages are protobuf encoded and split into 64-byte reports: the first
+//! starts with `?##`, the message type and payload length, later ones with
+//! `?`. Only the few messages needed for xpubs, address display and signing
+//! are implemented, with a minimal protobuf codec instead of generated code.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::psbt::RawTx;
+use super::usb::{HardwareVendor, HidChannel, UsbBus, UsbDevice, HID_REPORT_SIZE};
+use super::{
+    format_derivation_path, HardwareSigner, ScriptType, SignedTransaction, TransactionSigner,
+    HARDENED,
+};
+use crate::metadata::descriptor_checksum;
+use crate::types::{Network, Transaction, Utxo};
+
+// Message types from the Trezor protobuf definitions
+const INITIALIZE: u16 = 0;
+const FAILURE: u16 = 3;
+const GET_PUBLIC_KEY: u16 = 11;
+const PUBLIC_KEY: u16 = 12;
+const SIGN_TX: u16 = 15;
+const FEATURES: u16 = 17;
+const PIN_MATRIX_REQUEST: u16 = 18;
+const CANCEL: u16 = 20;
+const TX_REQUEST: u16 = 21;
+const TX_ACK: u16 = 22;
+const BUTTON_REQUEST: u16 = 26;
+const BUTTON_ACK: u16 = 27;
+const GET_ADDRESS: u16 = 29;
+const ADDRESS: u16 = 30;
+const PASSPHRASE_REQUEST: u16 = 41;
+
+// TxRequest.request_type values
+const TX_INPUT: u64 = 0;
+const TX_OUTPUT: u64 = 1;
+const TX_META: u64 = 2;
+const TX_FINISHED: u64 = 3;
+
+// Failure.code for the user rejecting on the device
+const FAILURE_ACTION_CANCELLED: u64 = 4;
+
+// Features.capabilities value of firmware that can handle bitcoin
+const CAPABILITY_BITCOIN: u64 = 1;
+
+/// Longest reply accepted from a device, well above any real message.
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// How long the user has to confirm on the device.
+const USER_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrezorError {
+    Transport(String),
+    /// The device sent something this implementation doesn't understand.
+    Protocol(String),
+    /// The user rejected the request on the device.
+    Cancelled,
+    /// A Trezor One asked for its PIN, which must be entered on the host.
+    PinRequired,
+    /// The device asked for a passphrase, which must be entered on the host.
+    PassphraseRequired,
+    NotInitialized,
+    BootloaderMode,
+    /// The model or firmware can't handle this kind of script.
+    UnsupportedScriptType {
+        script_type: String,
+        reason: String,
+    },
+    /// No derivation path is known for an input's address.
+    UnknownAddress(String),
+    /// A previous transaction the device asked for is not available.
+    MissingPrevTx(String),
+    AddressMismatch {
+        expected: String,
+        shown: String,
+    },
+    Failure {
+        code: u64,
+        message: String,
+    },
+}
+
+impl fmt::Display for TrezorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrezorError::Transport(e) => write!(f, "Trezor connection failed: {}", e),
+            TrezorError::Protocol(e) => write!(f, "Unexpected response from Trezor: {}", e),
+            TrezorError::Cancelled => write!(f, "The request was rejected on the Trezor"),
+            TrezorError::PinRequired => write!(f, "Unlock the Trezor with its PIN first"),
+            TrezorError::PassphraseRequired => {
+                write!(
+                    f,
+                    "The Trezor asks for a passphrase, which is not supported yet"
+                )
+            }
+            TrezorError::NotInitialized => write!(f, "The Trezor has not been set up yet"),
+            TrezorError::BootloaderMode => {
+                write!(f, "The Trezor is in bootloader mode; restart it normally")
+            }
+            TrezorError::UnsupportedScriptType {
+                script_type,
+                reason,
+            } => write!(f, "Trezor can't sign {} scripts: {}", script_type, reason),
+            TrezorError::UnknownAddress(address) => {
+                write!(f, "No derivation path known for address {}", address)
+            }
+            TrezorError::MissingPrevTx(txid) => {
+                write!(f, "Previous transaction {} is not available", txid)
+            }
+            TrezorError::AddressMismatch { expected, shown } => write!(
+                f,
+                "The Trezor shows {} but the wallet expected {}",
+                shown, expected
+            ),
+            TrezorError::Failure { code, message } => {
+                write!(f, "Trezor error {}: {}", code, message)
+            }
+        }
+    }
+}
+
+/// Supplies full serialized transactions; Trezor checks input amounts
+/// against the previous transactions before signing.
+pub trait RawTransactionSource: Send + Sync {
+    fn raw_transaction(&self, txid: &str) -> Result<Vec<u8>, String>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrezorModel {
+    One,
+    T,
+    Safe3,
+    Safe5,
+    Other(String),
+}
+
+impl TrezorModel {
+    fn parse(model: &str) -> Self {
+        match model {
+            "1" => TrezorModel::One,
+            "T" => TrezorModel::T,
+            "Safe 3" => TrezorModel::Safe3,
+            "Safe 5" => TrezorModel::Safe5,
+            other => TrezorModel::Other(other.to_string()),
+        }
+    }
+}
+
+/// What the device reported about itself when connecting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrezorFeatures {
+    pub model: TrezorModel,
+    pub firmware: (u32, u32, u32),
+    pub device_id: Option<String>,
+    pub label: Option<String>,
+    pub initialized: bool,
+    pub bootloader_mode: bool,
+    capabilities: Vec<u64>,
+}
+
+impl TrezorFeatures {
+    fn parse(payload: &[u8]) -> Result<Self, TrezorError> {
+        let fields = parse_fields(payload)?;
+        let mut capabilities = Vec::new();
+        for (number, value) in &fields {
+            match (*number, value) {
+                (30, Field::Varint(v)) => capabilities.push(*v),
+                // Packed encoding, as newer firmware may send it
+                (30, Field::Bytes(packed)) => {
+                    let mut pos = 0;
+                    while pos < packed.len() {
+                        capabilities.push(read_varint(packed, &mut pos)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let version = |n| get_varint(&fields, n).unwrap_or(0) as u32;
+        Ok(TrezorFeatures {
+            model: TrezorModel::parse(&get_string(&fields, 21).unwrap_or_else(|| "1".to_string())),
+            firmware: (version(2), version(3), version(4)),
+            device_id: get_string(&fields, 6),
+            label: get_string(&fields, 10),
+            initialized: get_varint(&fields, 12) == Some(1),
+            bootloader_mode: get_varint(&fields, 5) == Some(1),
+            capabilities,
+        })
+    }
+
+    /// Whether this model and firmware can derive and sign `script_type`.
+    pub fn supports(&self, script_type: ScriptType) -> Result<(), TrezorError> {
+        let unsupported = |reason: String| TrezorError::UnsupportedScriptType {
+            script_type: script_type.name().to_string(),
+            reason,
+        };
+        // Older firmware doesn't report capabilities at all
+        if !self.capabilities.is_empty() && !self.capabilities.contains(&CAPABILITY_BITCOIN) {
+            return Err(unsupported(
+                "the installed firmware has no bitcoin support".to_string(),
+            ));
+        }
+        if script_type == ScriptType::P2tr {
+            let minimum = match self.model {
+                TrezorModel::One => (1, 10, 4),
+                TrezorModel::T => (2, 4, 3),
+                _ => (0, 0, 0),
+            };
+            if self.firmware < minimum {
+                return Err(unsupported(format!(
+                    "taproot needs firmware {}.{}.{} or later",
+                    minimum.0, minimum.1, minimum.2
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A Trezor as a wallet signer.
+///
+/// Inputs are matched to derivation paths registered with
+/// [`add_path`](Self::add_path). Only segwit inputs can be signed, since the
+/// signatures come back as witnesses.
+pub struct TrezorSigner {
+    channel: Mutex<Box<dyn HidChannel>>,
+    features: TrezorFeatures,
+    network: Network,
+    id: String,
+    paths: BTreeMap<String, Vec<u32>>,
+    prev_txs: Option<Arc<dyn RawTransactionSource>>,
+}
+
+impl TrezorSigner {
+    pub fn open(
+        usb: &dyn UsbBus,
+        device: &UsbDevice,
+        network: Network,
+    ) -> Result<Self, TrezorError> {
+        if device.vendor != HardwareVendor::Trezor {
+            return Err(TrezorError::Transport(format!(
+                "{} is not a Trezor",
+                device.product
+            )));
+        }
+        let channel = usb.open(device).map_err(TrezorError::Transport)?;
+        Self::connect(channel, network)
+    }
+
+    /// Starts a session on an already opened channel.
+    pub fn connect(channel: Box<dyn HidChannel>, network: Network) -> Result<Self, TrezorError> {
+        let (msg_type, payload) = call(channel.as_ref(), INITIALIZE, &[])?;
+        expect(msg_type, FEATURES)?;
+        let features = TrezorFeatures::parse(&payload)?;
+        if features.bootloader_mode {
+            return Err(TrezorError::BootloaderMode);
+        }
+        if !features.initialized {
+            return Err(TrezorError::NotInitialized);
+        }
+        log::info!(
+            "Connected to Trezor {:?} with firmware {}.{}.{}",
+            features.model,
+            features.firmware.0,
+            features.firmware.1,
+            features.firmware.2
+        );
+        Ok(TrezorSigner {
+            channel: Mutex::new(channel),
+            id: features.device_id.clone().unwrap_or_default(),
+            features,
+            network,
+            paths: BTreeMap::new(),
+            prev_txs: None,
+        })
+    }
+
+    pub fn with_prev_txs(mut self, source: Arc<dyn RawTransactionSource>) -> Self {
+        self.prev_txs = Some(source);
+        self
+    }
+
+    pub fn features(&self) -> &TrezorFeatures {
+        &self.features
+    }
+
+    /// Registers the derivation path of a wallet address, so coins on it can be signed.
+    pub fn add_path(&mut self, address: &str, path: Vec<u32>) {
+        self.paths.insert(address.to_string(), path);
+    }
+
+    fn coin_name(&self) -> &'static str {
+        match self.network {
+            Network::Bitcoin => "Bitcoin",
+            Network::Testnet | Network::Signet => "Testnet",
+            Network::Regtest => "Regtest",
+        }
+    }
+
+    fn call(&self, msg_type: u16, payload: &[u8]) -> Result<(u16, Vec<u8>), TrezorError> {
+        let channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        call(channel.as_ref(), msg_type, payload)
+    }
+
+    /// The xpub at `path` and the master key fingerprint.
+    pub fn public_key(
+        &self,
+        path: &[u32],
+        script_type: ScriptType,
+    ) -> Result<(String, u32), TrezorError> {
+        self.features.supports(script_type)?;
+        let mut request = ProtoWriter::default();
+        for step in path {
+            request = request.varint(1, *step as u64);
+        }
+        let request = request
+            .string(4, self.coin_name())
+            .varint(5, input_script_type(script_type))
+            // Plain xpub/tpub rather than SLIP-132 prefixes, as descriptors expect
+            .varint(6, 1);
+        let (msg_type, payload) = self.call(GET_PUBLIC_KEY, &request.0)?;
+        expect(msg_type, PUBLIC_KEY)?;
+        let fields = parse_fields(&payload)?;
+        let xpub = get_string(&fields, 2)
+            .ok_or_else(|| TrezorError::Protocol("public key without xpub".to_string()))?;
+        let fingerprint = get_varint(&fields, 3).ok_or_else(|| {
+            TrezorError::Protocol("no master fingerprint; update the firmware".to_string())
+        })?;
+        Ok((xpub, fingerprint as u32))
+    }
+
+    /// Output descriptor for the standard account of `script_type`, with
+    /// receive and change branches and its checksum.
+    pub fn descriptor(&self, script_type: ScriptType, account: u32) -> Result<String, TrezorError> {
+        let coin = match self.network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        };
+        let path = [
+            script_type.purpose() | HARDENED,
+            coin | HARDENED,
+            account | HARDENED,
+        ];
+        let (xpub, fingerprint) = self.public_key(&path, script_type)?;
+        let key = format!(
+            "[{:08x}/{}]{}/<0;1>/*",
+            fingerprint,
+            format_derivation_path(&path),
+            xpub
+        );
+        let descriptor = match script_type {
+            ScriptType::P2pkh => format!("pkh({})", key),
+            ScriptType::P2shP2wpkh => format!("sh(wpkh({}))", key),
+            ScriptType::P2wpkh => format!("wpkh({})", key),
+            ScriptType::P2tr => format!("tr({})", key),
+        };
+        let checksum = descriptor_checksum(&descriptor).map_err(TrezorError::Protocol)?;
+        Ok(format!("{}#{}", descriptor, checksum))
+    }
+
+    /// Shows the address at `path` on the device screen and returns it.
+    pub fn show_address(
+        &self,
+        path: &[u32],
+        script_type: ScriptType,
+    ) -> Result<String, TrezorError> {
+        self.features.supports(script_type)?;
+        let mut request = ProtoWriter::default();
+        for step in path {
+            request = request.varint(1, *step as u64);
+        }
+        let request = request
+            .string(2, self.coin_name())
+            .varint(3, 1)
+            .varint(5, input_script_type(script_type));
+        let (msg_type, payload) = self.call(GET_ADDRESS, &request.0)?;
+        expect(msg_type, ADDRESS)?;
+        get_string(&parse_fields(&payload)?, 1)
+            .ok_or_else(|| TrezorError::Protocol("empty address".to_string()))
+    }
+
+    pub fn check_address(
+        &self,
+        path: &[u32],
+        script_type: ScriptType,
+        expected: &str,
+    ) -> Result<(), TrezorError> {
+        let shown = self.show_address(path, script_type)?;
+        if shown == expected {
+            Ok(())
+        } else {
+            Err(TrezorError::AddressMismatch {
+                expected: expected.to_string(),
+                shown,
+            })
+        }
+    }
+
+    /// Streams `tx` to the device input by input, answering its requests
+    /// until it returns the signed serialization.
+    pub fn sign_transaction(
+        &self,
+        tx: &Transaction,
+        prevouts: &[Utxo],
+    ) -> Result<SignedTransaction, TrezorError> {
+        if prevouts.len() != tx.inputs.len() {
+            return Err(TrezorError::Protocol(format!(
+                "{} inputs but {} previous outputs",
+                tx.inputs.len(),
+                prevouts.len()
+            )));
+        }
+        // Check every input before the user is asked to confirm anything
+        let mut inputs = Vec::with_capacity(prevouts.len());
+        for utxo in prevouts {
+            let script_type = ScriptType::from_address(&utxo.address).ok_or_else(|| {
+                TrezorError::UnsupportedScriptType {
+                    script_type: "script hash".to_string(),
+                    reason: format!("{} is not a single-key address", utxo.address),
+                }
+            })?;
+            if script_type == ScriptType::P2pkh {
+                return Err(TrezorError::UnsupportedScriptType {
+                    script_type: script_type.name().to_string(),
+                    reason: "legacy inputs are signed without witnesses".to_string(),
+                });
+            }
+            self.features.supports(script_type)?;
+            let path = self
+                .paths
+                .get(&utxo.address)
+                .ok_or_else(|| TrezorError::UnknownAddress(utxo.address.clone()))?;
+            inputs.push((path, script_type));
+        }
+
+        let request = ProtoWriter::default()
+            .varint(1, tx.outputs.len() as u64)
+            .varint(2, tx.inputs.len() as u64)
+            .string(3, self.coin_name())
+            .varint(4, 2)
+            .varint(5, tx.lock_time as u64);
+        let (mut msg_type, mut payload) = self.call(SIGN_TX, &request.0)?;
+        let mut serialized = Vec::new();
+        let mut prev_txs: BTreeMap<String, RawTx> = BTreeMap::new();
+        loop {
+            expect(msg_type, TX_REQUEST)?;
+            let fields = parse_fields(&payload)?;
+            if let Some(chunk) = get_bytes(&fields, 3) {
+                serialized.extend_from_slice(get_bytes(&parse_fields(chunk)?, 3).unwrap_or(&[]));
+            }
+            let details = match get_bytes(&fields, 2) {
+                Some(details) => parse_fields(details)?,
+                None => Vec::new(),
+            };
+            let index = get_varint(&details, 1).unwrap_or(0) as usize;
+            let tx_hash = get_bytes(&details, 2).map(hex::encode);
+            let out_of_range = || TrezorError::Protocol(format!("index {} out of range", index));
+
+            let ack = match (get_varint(&fields, 1).unwrap_or(TX_INPUT), tx_hash) {
+                (TX_FINISHED, _) => break,
+                (TX_INPUT, None) => {
+                    let input = tx.inputs.get(index).ok_or_else(out_of_range)?;
+                    let (path, script_type) = inputs[index];
+                    let mut message = ProtoWriter::default();
+                    for step in path {
+                        message = message.varint(1, *step as u64);
+                    }
+                    let message = message
+                        .bytes(2, &decode_txid(&input.previous_output.txid)?)
+                        .varint(3, input.previous_output.vout as u64)
+                        .varint(5, input.sequence as u64)
+                        .varint(6, input_script_type(script_type))
+                        .varint(8, prevouts[index].value);
+                    ProtoWriter::default().message(2, &message)
+                }
+                (TX_OUTPUT, None) => {
+                    let output = tx.outputs.get(index).ok_or_else(out_of_range)?;
+                    // PAYTOADDRESS
+                    let message = ProtoWriter::default()
+                        .string(1, &output.address)
+                        .varint(3, output.value)
+                        .varint(4, 0);
+                    ProtoWriter::default().message(5, &message)
+                }
+                (TX_META, Some(txid)) => {
+                    let prev = self.prev_tx(&mut prev_txs, &txid)?;
+                    ProtoWriter::default()
+                        .varint(1, prev.version as u64)
+                        .varint(4, prev.lock_time as u64)
+                        .varint(6, prev.inputs.len() as u64)
+                        .varint(7, prev.outputs.len() as u64)
+                }
+                (TX_INPUT, Some(txid)) => {
+                    let prev = self.prev_tx(&mut prev_txs, &txid)?;
+                    let input = prev.inputs.get(index).ok_or_else(out_of_range)?;
+                    let mut prev_hash = input.prev_hash;
+                    prev_hash.reverse();
+                    let message = ProtoWriter::default()
+                        .bytes(2, &prev_hash)
+                        .varint(3, input.prev_index as u64)
+                        .bytes(4, &input.script_sig)
+                        .varint(5, input.sequence as u64);
+                    ProtoWriter::default().message(2, &message)
+                }
+                (TX_OUTPUT, Some(txid)) => {
+                    let prev = self.prev_tx(&mut prev_txs, &txid)?;
+                    let output = prev.outputs.get(index).ok_or_else(out_of_range)?;
+                    let message = ProtoWriter::default()
+                        .varint(1, output.amount)
+                        .bytes(2, &output.script_pubkey);
+                    ProtoWriter::default().message(3, &message)
+                }
+                (other, _) => {
+                    return Err(TrezorError::Protocol(format!(
+                        "unsupported transaction request type {}",
+                        other
+                    )))
+                }
+            };
+            let ack = ProtoWriter::default().message(1, &ack);
+            (msg_type, payload) = self.call(TX_ACK, &ack.0)?;
+        }
+
+        let signed = RawTx::parse(&serialized).map_err(TrezorError::Protocol)?;
+        if signed.witnesses.len() != tx.inputs.len() {
+            return Err(TrezorError::Protocol(format!(
+                "signed transaction has {} witnesses for {} inputs",
+                signed.witnesses.len(),
+                tx.inputs.len()
+            )));
+        }
+        Ok(SignedTransaction {
+            transaction: tx.clone(),
+            witnesses: signed
+                .witnesses
+                .iter()
+                .map(|stack| stack.iter().map(hex::encode).collect())
+                .collect(),
+        })
+    }
+
+    fn prev_tx<'a>(
+        &self,
+        cache: &'a mut BTreeMap<String, RawTx>,
+        txid: &str,
+    ) -> Result<&'a RawTx, TrezorError> {
+        if !cache.contains_key(txid) {
+            let source = self
+                .prev_txs
+                .as_ref()
+                .ok_or_else(|| TrezorError::MissingPrevTx(txid.to_string()))?;
+            let raw = source.raw_transaction(txid).map_err(|e| {
+                log::warn!("Failed to fetch previous transaction {}: {}", txid, e);
+                TrezorError::MissingPrevTx(txid.to_string())
+            })?;
+            cache.insert(
+                txid.to_string(),
+                RawTx::parse(&raw).map_err(TrezorError::Protocol)?,
+            );
+        }
+        Ok(&cache[txid])
+    }
+}
+
+impl TransactionSigner for TrezorSigner {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sign(&self, tx: &Transaction, prevouts: &[Utxo]) -> Result<SignedTransaction, String> {
+        self.sign_transaction(tx, prevouts)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl HardwareSigner for TrezorSigner {
+    fn xpub(&self, path: &[u32], script_type: ScriptType) -> Result<String, String> {
+        self.public_key(path, script_type)
+            .map(|(xpub, _)| xpub)
+            .map_err(|e| e.to_string())
+    }
+
+    fn display_address(&self, path: &[u32], script_type: ScriptType) -> Result<String, String> {
+        self.show_address(path, script_type)
+            .map_err(|e| e.to_string())
+    }
+
+    fn verify_address(
+        &self,
+        path: &[u32],
+        script_type: ScriptType,
+        expected: &str,
+    ) -> Result<(), String> {
+        self.check_address(path, script_type, expected)
+            .map_err(|e| e.to_string())
+    }
+}
+
+// InputScriptType values
+fn input_script_type(script_type: ScriptType) -> u64 {
+    match script_type {
+        ScriptType::P2pkh => 0,
+        ScriptType::P2wpkh => 3,
+        ScriptType::P2shP2wpkh => 4,
+        ScriptType::P2tr => 5,
+    }
+}
+
+fn decode_txid(txid: &str) -> Result<Vec<u8>, TrezorError> {
+    hex::decode(txid)
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+        .ok_or_else(|| TrezorError::Protocol(format!("invalid txid {}", txid)))
+}
+
+fn expect(msg_type: u16, expected: u16) -> Result<(), TrezorError> {
+    if msg_type == expected {
+        Ok(())
+    } else {
+        Err(TrezorError::Protocol(format!(
+            "expected message {} but got {}",
+            expected, msg_type
+        )))
+    }
+}
+
+/// Splits a message into HID reports.
+fn frame(msg_type: u16, payload: &[u8]) -> Vec<Vec<u8>> {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend_from_slice(b"##");
+    data.extend_from_slice(&msg_type.to_be_bytes());
+    data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(payload);
+    data.chunks(HID_REPORT_SIZE - 1)
+        .map(|chunk| {
+            let mut report = Vec::with_capacity(HID_REPORT_SIZE);
+            report.push(b'?');
+            report.extend_from_slice(chunk);
+            report
+        })
+        .collect()
+}
+
+fn receive(channel: &dyn HidChannel) -> Result<(u16, Vec<u8>), TrezorError> {
+    let first = channel.read(USER_TIMEOUT).map_err(TrezorError::Transport)?;
+    if first.len() < 9 || &first[..3] != b"?##" {
+        return Err(TrezorError::Protocol(
+            "malformed message header".to_string(),
+        ));
+    }
+    let msg_type = u16::from_be_bytes([first[3], first[4]]);
+    let len = u32::from_be_bytes([first[5], first[6], first[7], first[8]]) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(TrezorError::Protocol(format!("message of {} bytes", len)));
+    }
+    let mut payload = first[9..].to_vec();
+    while payload.len() < len {
+        let report = channel.read(USER_TIMEOUT).map_err(TrezorError::Transport)?;
+        match report.split_first() {
+            Some((b'?', rest)) => payload.extend_from_slice(rest),
+            _ => return Err(TrezorError::Protocol("malformed continuation".to_string())),
+        }
+    }
+    payload.truncate(len);
+    Ok((msg_type, payload))
+}
+
+/// Sends a message and returns the reply, acknowledging button prompts on
+/// the way and turning failures into errors.
+fn call(
+    channel: &dyn HidChannel,
+    msg_type: u16,
+    payload: &[u8],
+) -> Result<(u16, Vec<u8>), TrezorError> {
+    let send = |msg_type: u16, payload: &[u8]| {
+        frame(msg_type, payload)
+            .iter()
+            .try_for_each(|report| channel.write(report))
+            .map_err(TrezorError::Transport)
+    };
+    send(msg_type, payload)?;
+    loop {
+        let (reply, payload) = receive(channel)?;
+        match reply {
+            BUTTON_REQUEST => send(BUTTON_ACK, &[])?,
+            PIN_MATRIX_REQUEST | PASSPHRASE_REQUEST => {
+                // The device answers the cancel with a failure, which is dropped
+                send(CANCEL, &[])?;
+                let _ = receive(channel);
+                return Err(if reply == PIN_MATRIX_REQUEST {
+                    TrezorError::PinRequired
+                } else {
+                    TrezorError::PassphraseRequired
+                });
+            }
+            FAILURE => {
+                let fields = parse_fields(&payload)?;
+                let code = get_varint(&fields, 1).unwrap_or(0);
+                if code == FAILURE_ACTION_CANCELLED {
+                    return Err(TrezorError::Cancelled);
+                }
+                return Err(TrezorError::Failure {
+                    code,
+                    message: get_string(&fields, 2).unwrap_or_default(),
+                });
+            }
+            _ => return Ok((reply, payload)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ProtoWriter(Vec<u8>);
+
+impl ProtoWriter {
+    fn key(mut self, number: u32, wire_type: u8) -> Self {
+        put_varint(&mut self.0, ((number as u64) << 3) | wire_type as u64);
+        self
+    }
+
+    fn varint(self, number: u32, value: u64) -> Self {
+        let mut writer = self.key(number, 0);
+        put_varint(&mut writer.0, value);
+        writer
+    }
+
+    fn bytes(self, number: u32, value: &[u8]) -> Self {
+        let mut writer = self.key(number, 2);
+        put_varint(&mut writer.0, value.len() as u64);
+        writer.0.extend_from_slice(value);
+        writer
+    }
+
+    fn string(self, number: u32, value: &str) -> Self {
+        self.bytes(number, value.as_bytes())
+    }
+
+    fn message(self, number: u32, message: &ProtoWriter) -> Self {
+        self.bytes(number, &message.0)
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, TrezorError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| TrezorError::Protocol("truncated varint".to_string()))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(TrezorError::Protocol("varint too long".to_string()))
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+fn parse_fields(data: &[u8]) -> Result<Vec<(u32, Field<'_>)>, TrezorError> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let number = (key >> 3) as u32;
+        let skip = match key & 7 {
+            0 => {
+                fields.push((number, Field::Varint(read_varint(data, &mut pos)?)));
+                0
+            }
+            1 => 8,
+            2 => {
+                let len = read_varint(data, &mut pos)? as usize;
+                let value = data
+                    .get(pos..pos.saturating_add(len))
+                    .ok_or_else(|| TrezorError::Protocol("truncated field".to_string()))?;
+                fields.push((number, Field::Bytes(value)));
+                len
+            }
+            5 => 4,
+            other => return Err(TrezorError::Protocol(format!("wire type {}", other))),
+        };
+        pos += skip;
+    }
+    Ok(fields)
+}
+
+fn get_varint(fields: &[(u32, Field<'_>)], number: u32) -> Option<u64> {
+    fields.iter().find_map(|(n, value)| match value {
+        Field::Varint(v) if *n == number => Some(*v),
+        _ => None,
+    })
+}
+
+fn get_bytes<'a>(fields: &[(u32, Field<'a>)], number: u32) -> Option<&'a [u8]> {
+    fields.iter().find_map(|(n, value)| match value {
+        Field::Bytes(b) if *n == number => Some(*b),
+        _ => None,
+    })
+}
+
+fn get_string(fields: &[(u32, Field<'_>)], number: u32) -> Option<String> {
+    get_bytes(fields, number).map(|b| String::from_utf8_lossy(b).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::parse_derivation_path;
+    use crate::types::{OutPoint, TxIn, TxOut};
+    use std::collections::VecDeque;
+
+    /// Answers each complete message with the next scripted reply.
+    struct FakeTrezor {
+        replies: Mutex<VecDeque<(u16, Vec<u8>)>>,
+        pending: Mutex<VecDeque<Vec<u8>>>,
+        received: Arc<Mutex<Vec<u16>>>,
+        partial: Mutex<Vec<u8>>,
+    }
+
+    impl FakeTrezor {
+        fn new(replies: Vec<(u16, Vec<u8>)>) -> (Box<Self>, Arc<Mutex<Vec<u16>>>) {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let fake = FakeTrezor {
+                replies: Mutex::new(replies.into()),
+                pending: Mutex::new(VecDeque::new()),
+                received: received.clone(),
+                partial: Mutex::new(Vec::new()),
+            };
+            (Box::new(fake), received)
+        }
+    }
+
+    impl HidChannel for FakeTrezor {
+        fn write(&self, report: &[u8]) -> Result<(), String> {
+            let mut partial = self.partial.lock().unwrap();
+            partial.extend_from_slice(&report[1..]);
+            let len = u32::from_be_bytes(partial[4..8].try_into().unwrap()) as usize;
+            if partial.len() >= len + 8 {
+                self.received
+                    .lock()
+                    .unwrap()
+                    .push(u16::from_be_bytes([partial[2], partial[3]]));
+                partial.clear();
+                let (msg_type, payload) = self.replies.lock().unwrap().pop_front().unwrap();
+                self.pending
+                    .lock()
+                    .unwrap()
+                    .extend(frame(msg_type, &payload));
+            }
+            Ok(())
+        }
+
+        fn read(&self, _timeout: Duration) -> Result<Vec<u8>, String> {
+            self.pending
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| "Timed out".to_string())
+        }
+    }
+
+    fn features(model: &str, firmware: (u64, u64, u64)) -> (u16, Vec<u8>) {
+        let payload = ProtoWriter::default()
+            .varint(2, firmware.0)
+            .varint(3, firmware.1)
+            .varint(4, firmware.2)
+            .string(6, "ABCD1234")
+            .varint(12, 1)
+            .string(21, model)
+            .varint(30, CAPABILITY_BITCOIN);
+        (FEATURES, payload.0)
+    }
+
+    #[test]
+    fn test_descriptor_and_script_support() {
+        let public_key = ProtoWriter::default()
+            .string(2, &format!("tpub{}", "D".repeat(107)))
+            .varint(3, 0x0bad_f00d);
+        let (channel, received) = FakeTrezor::new(vec![
+            features("T", (2, 4, 2)),
+            (BUTTON_REQUEST, Vec::new()),
+            (PUBLIC_KEY, public_key.0),
+        ]);
+        let signer = TrezorSigner::connect(channel, Network::Testnet).unwrap();
+        assert_eq!(signer.features().model, TrezorModel::T);
+        assert_eq!(signer.id(), "ABCD1234");
+
+        let descriptor = signer.descriptor(ScriptType::P2wpkh, 0).unwrap();
+        assert!(descriptor.starts_with("wpkh([0badf00d/84h/1h/0h]tpubDDD"));
+        assert!(descriptor_checksum(&descriptor).is_ok());
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![INITIALIZE, GET_PUBLIC_KEY, BUTTON_ACK]
+        );
+
+        // Taproot needs 2.4.3 on the Model T; nothing is sent to the device
+        match signer.show_address(
+            &parse_derivation_path("m/86'/1'/0'/0/0").unwrap(),
+            ScriptType::P2tr,
+        ) {
+            Err(TrezorError::UnsupportedScriptType { script_type, .. }) => {
+                assert_eq!(script_type, "p2tr")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new(&"11".repeat(32), 0),
+                sequence: 0xffff_fffd,
+            }],
+            outputs: Vec::new(),
+        };
+        let legacy = Utxo {
+            outpoint: tx.inputs[0].previous_output.clone(),
+            address: "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn".to_string(),
+            value: 1_000,
+            confirmations: 1,
+            is_coinbase: false,
+        };
+        assert!(matches!(
+            signer.sign_transaction(&tx, &[legacy]),
+            Err(TrezorError::UnsupportedScriptType { .. })
+        ));
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    struct PrevTxs(Vec<u8>);
+
+    impl RawTransactionSource for PrevTxs {
+        fn raw_transaction(&self, _txid: &str) -> Result<Vec<u8>, String> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn tx_request(
+        request_type: u64,
+        index: u64,
+        tx_hash: Option<&[u8]>,
+        serialized: &[u8],
+    ) -> (u16, Vec<u8>) {
+        let mut details = ProtoWriter::default().varint(1, index);
+        if let Some(hash) = tx_hash {
+            details = details.bytes(2, hash);
+        }
+        let mut request = ProtoWriter::default()
+            .varint(1, request_type)
+            .message(2, &details);
+        if !serialized.is_empty() {
+            request = request.message(3, &ProtoWriter::default().bytes(3, serialized));
+        }
+        (TX_REQUEST, request.0)
+    }
+
+    #[test]
+    fn test_signing_returns_witnesses() {
+        let prev_txid = [0x22u8; 32];
+        // version 2, one input, one output, no witness, locktime 0
+        let mut prev = vec![2, 0, 0, 0, 1];
+        prev.extend([0x33; 32]);
+        prev.extend([0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 1]);
+        prev.extend(50_000u64.to_le_bytes());
+        prev.extend([22, 0, 20]);
+        prev.extend([0x44; 20]);
+        prev.extend([0, 0, 0, 0]);
+
+        // The same spend signed, with a two item witness
+        let mut signed = vec![2, 0, 0, 0, 0, 1, 1];
+        signed.extend(prev_txid.iter().rev());
+        signed.extend([0, 0, 0, 0, 0, 0xfd, 0xff, 0xff, 0xff, 0]);
+        signed.extend([2, 3, 0xaa, 0xbb, 0xcc, 2, 0x02, 0x03]);
+        signed.extend([0, 0, 0, 0]);
+
+        let (channel, received) = FakeTrezor::new(vec![
+            features("Safe 3", (2, 8, 0)),
+            tx_request(TX_INPUT, 0, None, &[]),
+            tx_request(TX_META, 0, Some(&prev_txid), &[]),
+            tx_request(TX_INPUT, 0, Some(&prev_txid), &[]),
+            tx_request(TX_OUTPUT, 0, Some(&prev_txid), &[]),
+            tx_request(TX_FINISHED, 0, None, &signed),
+        ]);
+        let mut signer = TrezorSigner::connect(channel, Network::Regtest)
+            .unwrap()
+            .with_prev_txs(Arc::new(PrevTxs(prev)));
+        let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+        signer.add_path(address, parse_derivation_path("m/84'/1'/0'/0/0").unwrap());
+
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new(&hex::encode(prev_txid), 0),
+                sequence: 0xffff_fffd,
+            }],
+            outputs: vec![TxOut {
+                address: address.to_string(),
+                value: 49_000,
+            }],
+        };
+        let prevout = Utxo {
+            outpoint: tx.inputs[0].previous_output.clone(),
+            address: address.to_string(),
+            value: 50_000,
+            confirmations: 6,
+            is_coinbase: false,
+        };
+        let result = signer.sign(&tx, &[prevout]).unwrap();
+        assert!(result.is_complete());
+        assert_eq!(result.witnesses[0], vec!["aabbcc", "0203"]);
+        assert_eq!(received.lock().unwrap().len(), 6);
+    }
+}
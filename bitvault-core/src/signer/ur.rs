@@ -0,0 +1,378 @@
+//! Uniform Resources (BCR-2020-005), the QR encoding air-gapped signers
+//! such as Jade and Passport use for PSBTs and wallet exports.
+//!
+//! Payloads are CBOR byte strings written as minimal bytewords. Messages
+//! too large for one QR code are split into numbered fragments; only the
+//! plain fragments are produced and understood, not the fountain-coded
+//! mixes some encoders add after cycling through them once.
+
+use crate::qr::scan::{decode_scanned, ScannedInput};
+use crate::types::Network;
+
+// First and last letters of the 256 bytewords, in byte order
+const BYTEWORDS: [&str; 8] = [
+    "aeadaoaxaaahamatayasbkbdbnbtbabsbebybgbwbbbzcmchcscfcycwcecackct",
+    "cxclcpcndkdadsdidedtdrdndwdpdmdldyeheyeoeeecenemetesftfrfnfsfmfh",
+    "fzfpfwfxfyfefgflfdgagegrgsgtglgwgdgygmgughgohfhghdhkhthphhhlhyhe",
+    "hnhsidiaieihiyioisinimjejzjnjtjljojsjpjkjykpkoktkskkknkgkekikblb",
+    "lalylflslrlplnltloldlelulklgmnmymhmemomumwmdmtmsmknlnyndnsntnnne",
+    "nboyoeotoxonolospdptpkpypspmplpepfpaprqdqzrerprlrorhrdrkrfryrnrs",
+    "rtsesasrssskswstspsosgsbsfsntotktitttdtetytltbtstptatnuyuoutueur",
+    "vtvyvovlvevwvavdvswlwdwmwpwewywswtwnwzwfwkykynylyaytzszoztzczezm",
+];
+
+/// Fragments are never made shorter than this.
+const MIN_FRAGMENT_LEN: usize = 10;
+/// Parts accepted in one UR; more than any PSBT a signer would show.
+const MAX_UR_PARTS: u32 = 10_000;
+
+fn byteword(byte: u8) -> &'static str {
+    let (row, column) = (byte as usize / 32, byte as usize % 32 * 2);
+    &BYTEWORDS[row][column..column + 2]
+}
+
+/// CRC-32 as used by zlib, which URs use for their checksums.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn bytewords_encode(data: &[u8]) -> String {
+    data.iter()
+        .chain(&crc32(data).to_be_bytes())
+        .map(|byte| byteword(*byte))
+        .collect()
+}
+
+fn bytewords_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.to_ascii_lowercase();
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err("Invalid bytewords".to_string());
+    }
+    let bytes = (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            (0..=255u8)
+                .find(|byte| byteword(*byte) == &text[i..i + 2])
+                .ok_or_else(|| format!("Invalid byteword {:?}", &text[i..i + 2]))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    if bytes.len() < 4 {
+        return Err("Bytewords too short for a checksum".to_string());
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32(data).to_be_bytes() != checksum {
+        return Err("Bytewords checksum mismatch".to_string());
+    }
+    Ok(data.to_vec())
+}
+
+fn cbor_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend([major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(n.to_be_bytes());
+        }
+    }
+}
+
+struct CborReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl CborReader<'_> {
+    fn head(&mut self, expected_major: u8) -> Result<u64, String> {
+        let invalid = || "Malformed CBOR in UR".to_string();
+        let first = *self.data.get(self.pos).ok_or_else(invalid)?;
+        if first >> 5 != expected_major {
+            return Err(invalid());
+        }
+        self.pos += 1;
+        let len = match first & 0x1f {
+            n @ 0..=23 => return Ok(n as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(invalid()),
+        };
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(invalid)?;
+        self.pos += len;
+        Ok(bytes.iter().fold(0u64, |n, b| (n << 8) | *b as u64))
+    }
+
+    fn uint(&mut self) -> Result<u64, String> {
+        self.head(0)
+    }
+
+    fn bytes(&mut self) -> Result<&[u8], String> {
+        let len = usize::try_from(self.head(2)?).map_err(|_| "CBOR string too long")?;
+        let start = self.pos;
+        let bytes = self
+            .data
+            .get(start..start.saturating_add(len))
+            .ok_or_else(|| "Truncated CBOR in UR".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+}
+
+fn cbor_bytes(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 9);
+    cbor_head(&mut out, 2, payload.len() as u64);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Fragment length splitting `message_len` into the fewest equal parts
+/// no longer than `max_len`.
+fn fragment_len(message_len: usize, max_len: usize) -> usize {
+    let max_count = (message_len / MIN_FRAGMENT_LEN).max(1);
+    (1..=max_count)
+        .map(|count| message_len.div_ceil(count))
+        .find(|len| *len <= max_len)
+        .unwrap_or(MIN_FRAGMENT_LEN.min(message_len))
+}
+
+/// Encodes `payload` as a CBOR byte string UR of `ur_type`, split into
+/// fragments of at most `max_fragment_len` bytes. Parts are upper-cased,
+/// which QR alphanumeric mode stores far more compactly.
+pub fn encode(ur_type: &str, payload: &[u8], max_fragment_len: usize) -> Vec<String> {
+    let message = cbor_bytes(payload);
+    if message.len() <= max_fragment_len {
+        return vec![format!("ur:{}/{}", ur_type, bytewords_encode(&message)).to_ascii_uppercase()];
+    }
+
+    let len = fragment_len(message.len(), max_fragment_len.max(MIN_FRAGMENT_LEN));
+    let checksum = crc32(&message);
+    let chunks: Vec<&[u8]> = message.chunks(len).collect();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut fragment = chunk.to_vec();
+            fragment.resize(len, 0);
+            let mut part = vec![0x85];
+            cbor_head(&mut part, 0, i as u64 + 1);
+            cbor_head(&mut part, 0, chunks.len() as u64);
+            cbor_head(&mut part, 0, message.len() as u64);
+            cbor_head(&mut part, 0, checksum as u64);
+            part.extend(cbor_bytes(&fragment));
+            format!(
+                "ur:{}/{}-{}/{}",
+                ur_type,
+                i + 1,
+                chunks.len(),
+                bytewords_encode(&part)
+            )
+            .to_ascii_uppercase()
+        })
+        .collect()
+}
+
+/// Collects scanned UR parts until the whole message is there.
+#[derive(Debug, Default)]
+pub struct UrDecoder {
+    ur_type: Option<String>,
+    /// Message length and checksum every fragment must agree on.
+    message: Option<(usize, u32)>,
+    fragments: Vec<Option<Vec<u8>>>,
+    complete: Option<Vec<u8>>,
+}
+
+impl UrDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one scanned part. Repeats and fountain-coded parts are ignored;
+    /// parts from a different UR are an error.
+    pub fn receive(&mut self, scanned: &str) -> Result<(), String> {
+        // Network only matters for addresses, never for URs
+        let (ur_type, part, data) = match decode_scanned(scanned, Network::Bitcoin)? {
+            ScannedInput::UrFragment {
+                ur_type,
+                part,
+                data,
+            } => (ur_type, part, data),
+            _ => return Err("Not a UR".to_string()),
+        };
+        if self.ur_type.get_or_insert_with(|| ur_type.clone()) != &ur_type {
+            return Err(format!(
+                "Expected a {} UR part, got {}",
+                self.ur_type.as_deref().unwrap_or_default(),
+                ur_type
+            ));
+        }
+        let body = bytewords_decode(&data)?;
+
+        let Some((seq, total)) = part else {
+            self.complete = Some(body);
+            return Ok(());
+        };
+        if total > MAX_UR_PARTS {
+            return Err(format!("UR has more than {} parts", MAX_UR_PARTS));
+        }
+        let mut reader = CborReader {
+            data: &body,
+            pos: 0,
+        };
+        if reader.head(4)? != 5 {
+            return Err("Malformed UR part".to_string());
+        }
+        let (part_seq, part_total) = (reader.uint()?, reader.uint()?);
+        let message_len = usize::try_from(reader.uint()?).map_err(|_| "UR too long")?;
+        let checksum =
+            u32::try_from(reader.uint()?).map_err(|_| "Malformed UR checksum".to_string())?;
+        let fragment = reader.bytes()?.to_vec();
+        if (part_seq, part_total) != (seq as u64, total as u64) {
+            return Err("UR part header doesn't match its sequence".to_string());
+        }
+        if seq > total || self.complete.is_some() {
+            return Ok(());
+        }
+        // Fragments are all the same length, so the total follows from it
+        if fragment.is_empty() || total as usize > message_len.div_ceil(fragment.len()) {
+            return Err("UR has more parts than its message needs".to_string());
+        }
+        if fragment.len().saturating_mul(total as usize) < message_len {
+            return Err("UR fragments too short for the message".to_string());
+        }
+        match self.message {
+            None => {
+                self.message = Some((message_len, checksum));
+                self.fragments = vec![None; total as usize];
+            }
+            Some(expected) if expected != (message_len, checksum) => {
+                return Err("UR part belongs to a different message".to_string())
+            }
+            Some(_) => {}
+        }
+        let slot = self
+            .fragments
+            .get_mut(seq as usize - 1)
+            .ok_or_else(|| "UR part belongs to a different message".to_string())?;
+        *slot = Some(fragment);
+
+        if self.fragments.iter().all(Option::is_some) {
+            let mut message: Vec<u8> = self.fragments.iter().flatten().flatten().copied().collect();
+            message.truncate(message_len);
+            if crc32(&message) != checksum {
+                self.fragments.iter_mut().for_each(|slot| *slot = None);
+                return Err("UR checksum mismatch; scan it again".to_string());
+            }
+            self.complete = Some(message);
+        }
+        Ok(())
+    }
+
+    pub fn ur_type(&self) -> Option<&str> {
+        self.ur_type.as_deref()
+    }
+
+    /// Parts received and parts needed.
+    pub fn progress(&self) -> (usize, usize) {
+        if self.complete.is_some() {
+            let total = self.fragments.len().max(1);
+            return (total, total);
+        }
+        (
+            self.fragments.iter().filter(|f| f.is_some()).count(),
+            self.fragments.len().max(1),
+        )
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete.is_some()
+    }
+
+    /// The payload of a complete byte string UR.
+    pub fn payload(&self) -> Result<Vec<u8>, String> {
+        let message = self
+            .complete
+            .as_ref()
+            .ok_or_else(|| "UR is incomplete".to_string())?;
+        let mut reader = CborReader {
+            data: message,
+            pos: 0,
+        };
+        let payload = reader.bytes()?.to_vec();
+        if reader.pos != message.len() {
+            return Err("Trailing bytes after UR payload".to_string());
+        }
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_in_any_order() {
+        assert_eq!(crc32(b"Wolf"), 0x598c_84dc);
+        assert_eq!(bytewords_encode(&[0, 1, 255]), "aeadzmsbhdatue");
+        assert!(bytewords_decode("aeadzmsbhdataa").is_err());
+
+        let payload: Vec<u8> = (0..=255).collect();
+        let parts = encode("bytes", &payload, 60);
+        assert_eq!(parts.len(), 5);
+        assert!(parts[0].starts_with("UR:BYTES/1-5/"));
+
+        let mut decoder = UrDecoder::new();
+        for part in parts.iter().rev().chain(&parts[..1]) {
+            decoder.receive(part).unwrap();
+        }
+        assert_eq!(decoder.progress(), (5, 5));
+        assert_eq!(decoder.payload().unwrap(), payload);
+        assert!(decoder
+            .receive(&encode("crypto-psbt", b"x", 60)[0])
+            .is_err());
+
+        // A header claiming far more parts than the message needs
+        let mut part = vec![0x85];
+        cbor_head(&mut part, 0, 1);
+        cbor_head(&mut part, 0, 5_000);
+        cbor_head(&mut part, 0, 20);
+        cbor_head(&mut part, 0, 0);
+        part.extend(cbor_bytes(&[0; 10]));
+        let inflated = format!("ur:bytes/1-5000/{}", bytewords_encode(&part));
+        assert!(UrDecoder::new().receive(&inflated).is_err());
+        assert!(UrDecoder::new()
+            .receive(&format!(
+                "ur:bytes/1-{}/{}",
+                MAX_UR_PARTS + 1,
+                bytewords_encode(&part)
+            ))
+            .is_err());
+
+        let mut single = UrDecoder::new();
+        single.receive(&encode("bytes", b"short", 60)[0]).unwrap();
+        assert_eq!(single.payload().unwrap(), b"short");
+    }
+}
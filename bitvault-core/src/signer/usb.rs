@@ -0,0 +1,336 @@
+//! USB HID access to hardware signers.
+//!
+//! Only the transport lives here: finding devices, opening a channel that
+//! exchanges raw HID reports, and reporting devices coming and going.
+//! Each vendor's protocol is layered on top of a [`HidChannel`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{Event, MessageBus};
+
+/// Size of the HID reports every supported device uses.
+pub const HID_REPORT_SIZE: usize = 64;
+
+// Usage page of the FIDO/U2F interface several devices also expose; its
+// reports belong to the browser, not to us
+const FIDO_USAGE_PAGE: u16 = 0xf1d0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum HardwareVendor {
+    Ledger,
+    Trezor,
+    BitBox,
+    Coldcard,
+}
+
+impl HardwareVendor {
+    /// The vendor behind a USB vendor/product id pair, if it's a known signer.
+    pub fn identify(vendor_id: u16, product_id: u16) -> Option<Self> {
+        match (vendor_id, product_id) {
+            (0x2c97, _) => Some(HardwareVendor::Ledger),
+            // Trezor One, and the Trezor Model T and later with their bootloader
+            (0x534c, 0x0001) | (0x1209, 0x53c0) | (0x1209, 0x53c1) => Some(HardwareVendor::Trezor),
+            (0x03eb, 0x2403) => Some(HardwareVendor::BitBox),
+            (0xd13e, 0xcc10) => Some(HardwareVendor::Coldcard),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardwareVendor::Ledger => "Ledger",
+            HardwareVendor::Trezor => "Trezor",
+            HardwareVendor::BitBox => "BitBox",
+            HardwareVendor::Coldcard => "Coldcard",
+        }
+    }
+}
+
+/// A connected hardware signer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbDevice {
+    /// Platform path of the HID interface, such as `/dev/hidraw3` on Linux.
+    pub path: String,
+    pub vendor: HardwareVendor,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product: String,
+    pub serial: Option<String>,
+}
+
+/// Raw report exchange with one opened device.
+pub trait HidChannel: Send {
+    /// Sends one report of at most [`HID_REPORT_SIZE`] bytes, without the
+    /// report id; shorter reports are zero padded.
+    fn write(&self, report: &[u8]) -> Result<(), String>;
+
+    /// Waits up to `timeout` for the next report from the device.
+    fn read(&self, timeout: Duration) -> Result<Vec<u8>, String>;
+}
+
+/// Source of connected devices; the OS HID stack, or a fake in tests.
+pub trait UsbBus {
+    /// Known hardware signers currently connected.
+    fn enumerate(&self) -> Result<Vec<UsbDevice>, String>;
+
+    fn open(&self, device: &UsbDevice) -> Result<Box<dyn HidChannel>, String>;
+}
+
+/// Whether this process may talk to a device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    Granted,
+    /// Access is blocked; `hint` tells the user how to allow it.
+    Denied {
+        hint: String,
+    },
+}
+
+/// Checks access to `device` the way its platform grants it.
+///
+/// Linux needs udev rules making the hidraw node accessible to the user;
+/// macOS and Windows let any user open vendor HID interfaces.
+pub fn check_permission(device: &UsbDevice) -> Permission {
+    #[cfg(target_os = "linux")]
+    {
+        use std::io::ErrorKind;
+
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&device.path)
+        {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => Permission::Denied {
+                hint: format!(
+                    "Install the {} udev rules so your user can access {}, then reconnect the device",
+                    device.vendor.name(),
+                    device.path
+                ),
+            },
+            _ => Permission::Granted,
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        Permission::Granted
+    }
+}
+
+/// The OS HID stack through `hidapi`.
+pub struct HidApiBus {
+    api: Mutex<hidapi::HidApi>,
+}
+
+impl HidApiBus {
+    pub fn new() -> Result<Self, String> {
+        let api =
+            hidapi::HidApi::new().map_err(|e| format!("Failed to initialize USB HID: {}", e))?;
+        Ok(HidApiBus {
+            api: Mutex::new(api),
+        })
+    }
+}
+
+impl UsbBus for HidApiBus {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>, String> {
+        let mut api = self.api.lock().unwrap_or_else(|e| e.into_inner());
+        api.refresh_devices()
+            .map_err(|e| format!("Failed to list USB devices: {}", e))?;
+        Ok(api
+            .device_list()
+            .filter(|info| info.usage_page() != FIDO_USAGE_PAGE)
+            .filter_map(|info| {
+                let vendor = HardwareVendor::identify(info.vendor_id(), info.product_id())?;
+                Some(UsbDevice {
+                    path: info.path().to_string_lossy().into_owned(),
+                    vendor,
+                    vendor_id: info.vendor_id(),
+                    product_id: info.product_id(),
+                    product: info.product_string().unwrap_or(vendor.name()).to_string(),
+                    serial: info.serial_number().map(str::to_string),
+                })
+            })
+            .collect())
+    }
+
+    fn open(&self, device: &UsbDevice) -> Result<Box<dyn HidChannel>, String> {
+        if let Permission::Denied { hint } = check_permission(device) {
+            return Err(format!(
+                "No permission to access {}: {}",
+                device.product, hint
+            ));
+        }
+        let path = CString::new(device.path.as_str())
+            .map_err(|_| format!("Invalid device path {}", device.path))?;
+        let api = self.api.lock().unwrap_or_else(|e| e.into_inner());
+        let handle = api
+            .open_path(&path)
+            .map_err(|e| format!("Failed to open {}: {}", device.product, e))?;
+        Ok(Box::new(HidApiChannel(handle)))
+    }
+}
+
+struct HidApiChannel(hidapi::HidDevice);
+
+impl HidChannel for HidApiChannel {
+    fn write(&self, report: &[u8]) -> Result<(), String> {
+        if report.len() > HID_REPORT_SIZE {
+            return Err(format!("HID report of {} bytes is too long", report.len()));
+        }
+        // Report id 0 first, as hidapi expects for devices without numbered reports
+        let mut buffer = [0u8; HID_REPORT_SIZE + 1];
+        buffer[1..=report.len()].copy_from_slice(report);
+        self.0
+            .write(&buffer)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write to device: {}", e))
+    }
+
+    fn read(&self, timeout: Duration) -> Result<Vec<u8>, String> {
+        let mut buffer = [0u8; HID_REPORT_SIZE];
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let read = self
+            .0
+            .read_timeout(&mut buffer, millis)
+            .map_err(|e| format!("Failed to read from device: {}", e))?;
+        if read == 0 {
+            return Err("Timed out waiting for the device".to_string());
+        }
+        Ok(buffer[..read].to_vec())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hotplug {
+    Connected(UsbDevice),
+    Disconnected(UsbDevice),
+}
+
+/// Notices devices being plugged in and out by comparing enumerations.
+#[derive(Debug, Default)]
+pub struct HotplugMonitor {
+    known: BTreeMap<String, UsbDevice>,
+}
+
+impl HotplugMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Devices seen connected by the last poll.
+    pub fn devices(&self) -> impl Iterator<Item = &UsbDevice> {
+        self.known.values()
+    }
+
+    /// Re-enumerates `usb` and publishes a `DeviceConnected` or
+    /// `DeviceDisconnected` event for each change.
+    pub fn poll(
+        &mut self,
+        usb: &dyn UsbBus,
+        bus: Option<&MessageBus>,
+    ) -> Result<Vec<Hotplug>, String> {
+        let current: BTreeMap<String, UsbDevice> = usb
+            .enumerate()?
+            .into_iter()
+            .map(|device| (device.path.clone(), device))
+            .collect();
+
+        let mut changes: Vec<Hotplug> = self
+            .known
+            .iter()
+            .filter(|(path, _)| !current.contains_key(*path))
+            .map(|(_, device)| Hotplug::Disconnected(device.clone()))
+            .collect();
+        changes.extend(
+            current
+                .iter()
+                .filter(|(path, _)| !self.known.contains_key(*path))
+                .map(|(_, device)| Hotplug::Connected(device.clone())),
+        );
+        self.known = current;
+
+        for change in &changes {
+            log::info!("Hardware signer {:?}", change);
+            if let Some(bus) = bus {
+                bus.publish(match change {
+                    Hotplug::Connected(device) => Event::DeviceConnected {
+                        vendor: device.vendor.name().to_string(),
+                        product: device.product.clone(),
+                    },
+                    Hotplug::Disconnected(device) => Event::DeviceDisconnected {
+                        vendor: device.vendor.name().to_string(),
+                        product: device.product.clone(),
+                    },
+                });
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeBus(Mutex<Vec<UsbDevice>>);
+
+    impl UsbBus for FakeBus {
+        fn enumerate(&self) -> Result<Vec<UsbDevice>, String> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn open(&self, device: &UsbDevice) -> Result<Box<dyn HidChannel>, String> {
+            Err(format!("{} can't be opened", device.product))
+        }
+    }
+
+    fn device(path: &str, vendor_id: u16, product_id: u16) -> UsbDevice {
+        let vendor = HardwareVendor::identify(vendor_id, product_id).unwrap();
+        UsbDevice {
+            path: path.to_string(),
+            vendor,
+            vendor_id,
+            product_id,
+            product: vendor.name().to_string(),
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn test_hotplug_events() {
+        assert_eq!(HardwareVendor::identify(0x1209, 0x0001), None);
+        let usb = FakeBus::default();
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut monitor = HotplugMonitor::new();
+
+        let trezor = device("/dev/hidraw1", 0x534c, 0x0001);
+        usb.0.lock().unwrap().push(trezor.clone());
+        assert_eq!(
+            monitor.poll(&usb, Some(&bus)).unwrap(),
+            vec![Hotplug::Connected(trezor.clone())]
+        );
+        assert!(monitor.poll(&usb, Some(&bus)).unwrap().is_empty());
+
+        let ledger = device("/dev/hidraw2", 0x2c97, 0x4011);
+        *usb.0.lock().unwrap() = vec![ledger.clone()];
+        let changes = monitor.poll(&usb, Some(&bus)).unwrap();
+        assert_eq!(
+            changes,
+            vec![Hotplug::Disconnected(trezor), Hotplug::Connected(ledger)]
+        );
+        assert_eq!(monitor.devices().count(), 1);
+
+        let names: Vec<&str> = events.try_iter().map(|e| e.name()).collect();
+        assert_eq!(
+            names,
+            vec!["DeviceConnected", "DeviceDisconnected", "DeviceConnected"]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Staged startup with lazily constructed services.
+//!
+//! Frontends run the work the first window needs as timed stages and wrap
+//! everything else (fee cache, sync, exchange rates) in [`Lazy`] services
+//! that are only built when first used. Both show up in the
+//! [`StartupReport`], which diagnostics exposes.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub name: String,
+    /// Microseconds since startup began when the stage started.
+    pub started_micros: u64,
+    pub elapsed_micros: u64,
+    /// Built on first use rather than during startup.
+    pub lazy: bool,
+    pub error: Option<String>,
+}
+
+/// Timings of every stage and every lazy service built so far, in the order
+/// they ran.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartupReport {
+    pub stages: Vec<StageTiming>,
+    /// Microseconds from the start to [`Startup::finish`], once called.
+    pub ready_micros: Option<u64>,
+}
+
+impl StartupReport {
+    /// The stage that took longest, lazy ones included.
+    pub fn slowest(&self) -> Option<&StageTiming> {
+        self.stages.iter().max_by_key(|s| s.elapsed_micros)
+    }
+
+    /// Time spent in lazy services since startup, which startup didn't wait for.
+    pub fn deferred_micros(&self) -> u64 {
+        self.stages
+            .iter()
+            .filter(|s| s.lazy)
+            .map(|s| s.elapsed_micros)
+            .sum()
+    }
+}
+
+impl fmt::Display for StartupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ready_micros {
+            Some(micros) => writeln!(f, "Ready after {} ms", micros / 1_000)?,
+            None => writeln!(f, "Still starting")?,
+        }
+        for stage in &self.stages {
+            write!(
+                f,
+                "  {}{}: {} ms",
+                stage.name,
+                if stage.lazy { " (lazy)" } else { "" },
+                stage.elapsed_micros / 1_000
+            )?;
+            if let Some(error) = &stage.error {
+                write!(f, " failed: {}", error)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+/// Records startup stages. Clones share the same report.
+#[derive(Clone, Debug)]
+pub struct Startup {
+    began: Instant,
+    report: Arc<Mutex<StartupReport>>,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Startup {
+            began: Instant::now(),
+            report: Arc::new(Mutex::new(StartupReport::default())),
+        }
+    }
+
+    fn record<T>(
+        &self,
+        name: &str,
+        lazy: bool,
+        f: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let started = Instant::now();
+        let result = f();
+        let timing = StageTiming {
+            name: name.to_string(),
+            started_micros: micros(started.duration_since(self.began)),
+            elapsed_micros: micros(started.elapsed()),
+            lazy,
+            error: result.as_ref().err().cloned(),
+        };
+        log::debug!("Startup stage {} took {} us", name, timing.elapsed_micros);
+        if let Ok(mut report) = self.report.lock() {
+            report.stages.push(timing);
+        }
+        result
+    }
+
+    /// Runs `f` now as the stage `name`.
+    pub fn stage<T>(&self, name: &str, f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        self.record(name, false, f)
+    }
+
+    /// A service built by `build` the first time it's used, timed as a lazy
+    /// stage.
+    pub fn lazy<T, F>(&self, name: &str, build: F) -> Lazy<T>
+    where
+        F: Fn() -> Result<T, String> + Send + Sync + 'static,
+    {
+        Lazy {
+            name: name.to_string(),
+            value: OnceLock::new(),
+            building: Mutex::new(()),
+            build: Box::new(build),
+            startup: self.clone(),
+        }
+    }
+
+    /// Marks startup as done: the first window is usable.
+    pub fn finish(&self) {
+        if let Ok(mut report) = self.report.lock() {
+            if report.ready_micros.is_none() {
+                report.ready_micros = Some(micros(self.began.elapsed()));
+            }
+        }
+    }
+
+    pub fn report(&self) -> StartupReport {
+        self.report.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+}
+
+type Build<T> = Box<dyn Fn() -> Result<T, String> + Send + Sync>;
+
+/// A service constructed on first use. A failed construction is retried on
+/// the next use.
+pub struct Lazy<T> {
+    name: String,
+    value: OnceLock<T>,
+    // Held while building so concurrent first uses build only once
+    building: Mutex<()>,
+    build: Build<T>,
+    startup: Startup,
+}
+
+impl<T> Lazy<T> {
+    pub fn get(&self) -> Result<&T, String> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let _building = self
+            .building
+            .lock()
+            .map_err(|_| format!("Building {} panicked", self.name))?;
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = self.startup.record(&self.name, true, &self.build)?;
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl<T> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy")
+            .field("name", &self.name)
+            .field("built", &self.is_built())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_stages_and_lazy_services() {
+        let startup = Startup::new();
+        assert_eq!(startup.stage("settings", || Ok(42)), Ok(42));
+        assert!(startup
+            .stage("lock", || Err::<(), _>("locked".to_string()))
+            .is_err());
+
+        let builds = Arc::new(AtomicU32::new(0));
+        let counter = builds.clone();
+        let fees = startup.lazy("fee cache", move || {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("disk busy".to_string()),
+                _ => Ok(vec![1u64, 2, 3]),
+            }
+        });
+        startup.finish();
+        let report = startup.report();
+        assert_eq!(report.stages.len(), 2);
+        assert!(report.ready_micros.is_some());
+        assert!(!fees.is_built());
+
+        assert!(fees.get().is_err());
+        assert_eq!(fees.get().unwrap().len(), 3);
+        assert_eq!(fees.get().unwrap().len(), 3);
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+
+        let report = startup.report();
+        let names: Vec<&str> = report.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["settings", "lock", "fee cache", "fee cache"]);
+        assert_eq!(report.stages[1].error.as_deref(), Some("locked"));
+        assert!(report.stages[3].lazy && report.stages[3].error.is_none());
+        assert!(report.to_string().contains("fee cache (lazy)"));
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::backend::BlockchainBackend;
+use crate::connection_mode::ConnectionMode;
+use crate::events::{Event, MessageBus};
+use crate::progress::{self, Progress, ProgressSink};
+
+pub mod alternatives;
+pub mod verification;
+
+/// Derives the address at `index` of a descriptor keychain (0 receive, 1 change).
+pub trait ScriptDeriver: Send + Sync {
+    fn derive(&self, keychain: u32, index: u32) -> Result<String, String>;
+}
+
+/// How many times larger scan batches get on a metered connection.
+pub const METERED_BATCH_FACTOR: u32 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanConfig {
+    /// Consecutive unused addresses after which a keychain is considered done.
+    pub gap_limit: u32,
+    /// Addresses per backend batch request.
+    pub batch_size: u32,
+    /// Batches derived and queried at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            gap_limit: 20,
+            batch_size: 50,
+            concurrency: 4,
+        }
+    }
+}
+
+impl ScanConfig {
+    /// This config adjusted for `mode`: on a metered connection addresses
+    /// are queried in fewer, larger batches, one at a time.
+    pub fn for_mode(&self, mode: ConnectionMode) -> ScanConfig {
+        match mode {
+            ConnectionMode::Normal => self.clone(),
+            ConnectionMode::Metered => ScanConfig {
+                batch_size: self.batch_size.saturating_mul(METERED_BATCH_FACTOR),
+                concurrency: 1,
+                ..self.clone()
+            },
+        }
+    }
+}
+
+/// Addresses with history found by the initial scan.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanResult {
+    /// `(keychain, index, address)` of every used address, in index order.
+    pub used: Vec<(u32, u32, String)>,
+    /// Highest used index per keychain.
+    pub last_used: [Option<u32>; 2],
+    pub addresses_scanned: u32,
+    pub addresses_per_sec: f64,
+}
+
+fn scan_batch(
+    deriver: &dyn ScriptDeriver,
+    backend: &dyn BlockchainBackend,
+    keychain: u32,
+    start: u32,
+    count: u32,
+) -> Result<Vec<(u32, String)>, String> {
+    let addresses = (start..start + count)
+        .map(|index| deriver.derive(keychain, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    let histories = backend.batch_address_history(&addresses)?;
+    Ok(addresses
+        .into_iter()
+        .zip(histories)
+        .zip(start..)
+        .filter(|((_, history), _)| !history.is_empty())
+        .map(|((address, _), index)| (index, address))
+        .collect())
+}
+
+/// Restore scan of both keychains up to the gap limit.
+///
+/// Each round derives and queries `concurrency` batches in parallel, so a
+/// round covers `concurrency * batch_size` indexes. Publishes `SyncProgress`
+/// and reports to `progress` after every round; each keychain counts for
+/// half, and the gap limit moving on finds can make the percentage step back.
+pub fn initial_scan(
+    deriver: &dyn ScriptDeriver,
+    backend: &dyn BlockchainBackend,
+    config: &ScanConfig,
+    bus: Option<&MessageBus>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<ScanResult, String> {
+    if config.gap_limit == 0 || config.batch_size == 0 || config.concurrency == 0 {
+        return Err("Scan limits must be positive".to_string());
+    }
+
+    let started = Instant::now();
+    let mut result = ScanResult::default();
+    for keychain in 0..2u32 {
+        let mut next = 0u32;
+        let mut used_count = 0u32;
+        loop {
+            let last_used = result.last_used[keychain as usize];
+            let scan_until = last_used.map_or(0, |i| i + 1) + config.gap_limit;
+            if next >= scan_until {
+                break;
+            }
+
+            let found = Mutex::new(Vec::new());
+            let round_start = next;
+            thread::scope(|scope| {
+                let handles: Vec<_> = (0..config.concurrency as u32)
+                    .map(|i| round_start + i * config.batch_size)
+                    .filter(|start| *start < scan_until)
+                    .map(|start| {
+                        let found = &found;
+                        scope.spawn(move || {
+                            let count = config.batch_size.min(scan_until - start);
+                            let batch = scan_batch(deriver, backend, keychain, start, count)?;
+                            found.lock().map_err(|e| e.to_string())?.extend(batch);
+                            Ok::<u32, String>(count)
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    let count = handle
+                        .join()
+                        .map_err(|_| "Scan worker panicked".to_string())??;
+                    next += count;
+                    result.addresses_scanned += count;
+                }
+                Ok::<(), String>(())
+            })?;
+
+            let mut found = found.into_inner().map_err(|e| e.to_string())?;
+            found.sort_by_key(|(index, _)| *index);
+            used_count += found.len() as u32;
+            if let Some((index, _)) = found.last() {
+                result.last_used[keychain as usize] = Some(*index);
+            }
+            result.used.extend(
+                found
+                    .into_iter()
+                    .map(|(index, address)| (keychain, index, address)),
+            );
+
+            result.addresses_per_sec =
+                result.addresses_scanned as f64 / started.elapsed().as_secs_f64().max(1e-3);
+            if let Some(bus) = bus {
+                bus.publish(Event::SyncProgress {
+                    keychain,
+                    scanned: next,
+                    used: used_count,
+                    addresses_per_sec: result.addresses_per_sec,
+                });
+            }
+            let scan_until =
+                result.last_used[keychain as usize].map_or(0, |i| i + 1) + config.gap_limit;
+            progress::report(progress, || {
+                // Per mille of the keychain, so both halves weigh the same
+                let done = u64::from(keychain) * 1_000
+                    + u64::from(next.min(scan_until)) * 1_000 / u64::from(scan_until);
+                let phase = if keychain == 0 {
+                    "Scanning receive addresses"
+                } else {
+                    "Scanning change addresses"
+                };
+                Progress::from_counts("sync", phase, done, 2_000, started.elapsed())
+            });
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::progress::EventProgress;
+
+    struct TestDeriver;
+
+    impl ScriptDeriver for TestDeriver {
+        fn derive(&self, keychain: u32, index: u32) -> Result<String, String> {
+            Ok(format!("bcrt1q{}x{}", keychain, index))
+        }
+    }
+
+    #[test]
+    fn test_scan_follows_gap_limit() {
+        let backend = MockBackend::new();
+        backend.fund_address("bcrt1q0x3", 10_000);
+        // Found in the second round only because index 3 extended the gap
+        backend.fund_address("bcrt1q0x21", 10_000);
+        backend.fund_address("bcrt1q1x0", 5_000);
+        backend.mine_blocks(1);
+
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let config = ScanConfig {
+            gap_limit: 20,
+            batch_size: 5,
+            concurrency: 3,
+        };
+        let progress = EventProgress(&bus);
+        let result =
+            initial_scan(&TestDeriver, &backend, &config, Some(&bus), Some(&progress)).unwrap();
+
+        assert_eq!(result.last_used, [Some(21), Some(0)]);
+        assert_eq!(result.used.len(), 3);
+        assert_eq!(result.addresses_scanned, 42 + 21);
+        let events: Vec<Event> = events.try_iter().collect();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::SyncProgress { keychain: 1, .. })));
+        let percents: Vec<f64> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::OperationProgress { percent, .. } => Some(*percent),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(percents.last(), Some(&100.0));
+    }
+}
@@ -0,0 +1,251 @@
+//! Looks for funds under other wallet layouts when a restored wallet comes
+//! up empty, since a mistyped passphrase or another wallet's derivation
+//! standard otherwise just looks like a wallet with no history.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use super::{initial_scan, ScanConfig, ScanResult, ScriptDeriver};
+use crate::backend::BlockchainBackend;
+use crate::events::{Event, MessageBus};
+use crate::signer::ScriptType;
+
+const SCRIPT_TYPES: [ScriptType; 4] = [
+    ScriptType::P2wpkh,
+    ScriptType::P2tr,
+    ScriptType::P2shP2wpkh,
+    ScriptType::P2pkh,
+];
+
+/// One way the same phrase could have been set up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletLayout {
+    /// Address type, which also fixes the BIP-44/49/84/86 derivation.
+    pub script_type: ScriptType,
+    pub account: u32,
+    /// Whether the entered BIP-39 passphrase is applied.
+    pub with_passphrase: bool,
+}
+
+impl WalletLayout {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} addresses (BIP-{} account {}){}",
+            self.script_type.name(),
+            self.script_type.purpose(),
+            self.account,
+            if self.with_passphrase {
+                ""
+            } else {
+                " without the passphrase"
+            }
+        )
+    }
+}
+
+/// Builds address derivers for the restored phrase under other layouts.
+pub trait LayoutDeriver: Send + Sync {
+    fn deriver(&self, layout: &WalletLayout) -> Result<Box<dyn ScriptDeriver>, String>;
+}
+
+/// A layout with history the restored one doesn't have.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreHint {
+    pub layout: WalletLayout,
+    pub used_addresses: u32,
+}
+
+impl RestoreHint {
+    pub fn message(&self) -> String {
+        format!(
+            "Funds found under a different configuration: {} used {}",
+            self.used_addresses,
+            self.layout.describe()
+        )
+    }
+}
+
+/// Layouts worth trying after `restored` found nothing, likeliest first:
+/// the same addresses without the passphrase, then the other standards.
+pub fn candidate_layouts(restored: &WalletLayout) -> Vec<WalletLayout> {
+    let passphrase_options: &[bool] = if restored.with_passphrase {
+        &[true, false]
+    } else {
+        &[false]
+    };
+    let mut candidates = Vec::new();
+    if restored.with_passphrase {
+        candidates.push(WalletLayout {
+            with_passphrase: false,
+            ..*restored
+        });
+    }
+    for with_passphrase in passphrase_options {
+        for script_type in SCRIPT_TYPES {
+            let layout = WalletLayout {
+                script_type,
+                account: 0,
+                with_passphrase: *with_passphrase,
+            };
+            if layout != *restored && !candidates.contains(&layout) {
+                candidates.push(layout);
+            }
+        }
+    }
+    candidates
+}
+
+/// Scans the candidate layouts when `scan` of the restored one came up
+/// empty, publishing a `RestoreHint` event for each that has history.
+pub fn find_alternatives(
+    restored: &WalletLayout,
+    scan: &ScanResult,
+    deriver: &dyn LayoutDeriver,
+    backend: &dyn BlockchainBackend,
+    config: &ScanConfig,
+    bus: Option<&MessageBus>,
+) -> Result<Vec<RestoreHint>, String> {
+    if !scan.used.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut hints = Vec::new();
+    for layout in candidate_layouts(restored) {
+        let found = initial_scan(
+            deriver.deriver(&layout)?.as_ref(),
+            backend,
+            config,
+            None,
+            None,
+        )?;
+        if found.used.is_empty() {
+            continue;
+        }
+        let hint = RestoreHint {
+            layout,
+            used_addresses: found.used.len() as u32,
+        };
+        log::info!("{}", hint.message());
+        if let Some(bus) = bus {
+            bus.publish(Event::RestoreHint {
+                description: hint.message(),
+                used_addresses: hint.used_addresses,
+            });
+        }
+        hints.push(hint);
+    }
+    Ok(hints)
+}
+
+/// Runs [`find_alternatives`] on a background thread so the restore flow
+/// can go on showing the (empty) wallet meanwhile.
+pub fn spawn_alternative_search(
+    restored: WalletLayout,
+    scan: ScanResult,
+    deriver: Arc<dyn LayoutDeriver>,
+    backend: Arc<dyn BlockchainBackend>,
+    config: ScanConfig,
+    bus: Option<Arc<MessageBus>>,
+) -> JoinHandle<Result<Vec<RestoreHint>, String>> {
+    thread::spawn(move || {
+        find_alternatives(
+            &restored,
+            &scan,
+            deriver.as_ref(),
+            backend.as_ref(),
+            &config,
+            bus.as_deref(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    struct Layouts;
+
+    struct Deriver(WalletLayout);
+
+    impl ScriptDeriver for Deriver {
+        fn derive(&self, keychain: u32, index: u32) -> Result<String, String> {
+            Ok(format!(
+                "bcrt1q{}{}x{}x{}",
+                self.0.script_type.purpose(),
+                if self.0.with_passphrase { "p" } else { "e" },
+                keychain,
+                index
+            ))
+        }
+    }
+
+    impl LayoutDeriver for Layouts {
+        fn deriver(&self, layout: &WalletLayout) -> Result<Box<dyn ScriptDeriver>, String> {
+            Ok(Box::new(Deriver(*layout)))
+        }
+    }
+
+    #[test]
+    fn test_finds_funds_without_passphrase() {
+        let restored = WalletLayout {
+            script_type: ScriptType::P2wpkh,
+            account: 0,
+            with_passphrase: true,
+        };
+        let candidates = candidate_layouts(&restored);
+        assert_eq!(candidates.len(), 7);
+        assert_eq!(
+            candidates[0],
+            WalletLayout {
+                with_passphrase: false,
+                ..restored
+            }
+        );
+
+        let backend = Arc::new(MockBackend::new());
+        backend.fund_address("bcrt1q84ex0x2", 10_000);
+        backend.fund_address("bcrt1q86px0x0", 10_000);
+        backend.mine_blocks(1);
+        let bus = Arc::new(MessageBus::new());
+        let events = bus.subscribe();
+
+        let empty = ScanResult::default();
+        let hints = spawn_alternative_search(
+            restored,
+            empty,
+            Arc::new(Layouts),
+            backend.clone(),
+            ScanConfig::default(),
+            Some(bus),
+        )
+        .join()
+        .unwrap()
+        .unwrap();
+        let found: Vec<(ScriptType, bool)> = hints
+            .iter()
+            .map(|h| (h.layout.script_type, h.layout.with_passphrase))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(ScriptType::P2wpkh, false), (ScriptType::P2tr, true)]
+        );
+        assert!(hints[0].message().contains("without the passphrase"));
+        assert_eq!(events.try_iter().count(), 2);
+
+        let used = ScanResult {
+            used: vec![(0, 0, "bcrt1q84px0x0".to_string())],
+            ..Default::default()
+        };
+        assert!(find_alternatives(
+            &restored,
+            &used,
+            &Layouts,
+            backend.as_ref(),
+            &ScanConfig::default(),
+            None
+        )
+        .unwrap()
+        .is_empty());
+    }
+}
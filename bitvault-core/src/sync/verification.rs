@@ -0,0 +1,202 @@
+//! Checks after a restore scan that the derived change keychain matches the
+//! change the wallet actually made on-chain.
+//!
+//! A phrase restored with the wrong derivation standard or passphrase can
+//! still find some receive addresses (e.g. ones shared with another wallet
+//! layout) while missing every change output, which silently shows a
+//! partial balance. Spends whose change never came back are the tell.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::ScanResult;
+use crate::backend::BlockchainBackend;
+use crate::types::Transaction;
+
+/// Outcome of comparing wallet spends against the derived addresses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeCheck {
+    /// The wallet never spent with change, so there is nothing to compare.
+    NothingToCheck,
+    /// Every spend returned its change to a derived address.
+    Verified { spends: usize },
+    /// Some spends returned change to addresses the scan never derived,
+    /// usually because the change keychain has a larger gap than scanned.
+    Partial {
+        matched: usize,
+        unmatched: Vec<String>,
+    },
+    /// No spend returned change to a derived address.
+    Mismatch { unmatched: Vec<String> },
+}
+
+impl ChangeCheck {
+    /// What to tell the user, if anything looks wrong.
+    pub fn diagnostic(&self) -> Option<String> {
+        match self {
+            ChangeCheck::NothingToCheck | ChangeCheck::Verified { .. } => None,
+            ChangeCheck::Partial { matched, unmatched } => Some(format!(
+                "{} of {} transactions sent change to addresses this wallet didn't derive. \
+                 Some funds may be past the gap limit; try rescanning with a larger one",
+                unmatched.len(),
+                matched + unmatched.len()
+            )),
+            ChangeCheck::Mismatch { unmatched } => Some(format!(
+                "None of the {} transactions spent from this wallet sent change back to it. \
+                 The wallet was probably created with a different derivation standard or \
+                 passphrase, so the balance shown is incomplete",
+                unmatched.len()
+            )),
+        }
+    }
+}
+
+/// Looks at every transaction touching the addresses `scan` found and
+/// checks that spends with change paid some of it back to the wallet.
+///
+/// Spends with a single output are skipped, since sweeps have no change.
+pub fn verify_change(
+    scan: &ScanResult,
+    backend: &dyn BlockchainBackend,
+) -> Result<ChangeCheck, String> {
+    let wallet: HashSet<&str> = scan
+        .used
+        .iter()
+        .map(|(_, _, address)| address.as_str())
+        .collect();
+
+    let mut txids = Vec::new();
+    for address in &wallet {
+        for txid in backend.address_history(address)? {
+            if !txids.contains(&txid) {
+                txids.push(txid);
+            }
+        }
+    }
+
+    let mut cache: HashMap<String, Option<Transaction>> = HashMap::new();
+    let mut fetch = |txid: &str| -> Result<Option<Transaction>, String> {
+        if let Some(tx) = cache.get(txid) {
+            return Ok(tx.clone());
+        }
+        let tx = backend.transaction(txid)?;
+        cache.insert(txid.to_string(), tx.clone());
+        Ok(tx)
+    };
+
+    let (mut matched, mut unmatched) = (0, Vec::new());
+    for txid in txids {
+        let Some(tx) = fetch(&txid)? else {
+            continue;
+        };
+        if tx.outputs.len() < 2 {
+            continue;
+        }
+        let mut spends_wallet = false;
+        for input in &tx.inputs {
+            let previous = fetch(&input.previous_output.txid)?;
+            let funded_by = previous
+                .as_ref()
+                .and_then(|prev| prev.outputs.get(input.previous_output.vout as usize));
+            if funded_by.is_some_and(|output| wallet.contains(output.address.as_str())) {
+                spends_wallet = true;
+                break;
+            }
+        }
+        if !spends_wallet {
+            continue;
+        }
+        if tx
+            .outputs
+            .iter()
+            .any(|output| wallet.contains(output.address.as_str()))
+        {
+            matched += 1;
+        } else {
+            unmatched.push(txid);
+        }
+    }
+
+    let check = match (matched, unmatched.is_empty()) {
+        (0, true) => ChangeCheck::NothingToCheck,
+        (spends, true) => ChangeCheck::Verified { spends },
+        (0, false) => ChangeCheck::Mismatch { unmatched },
+        (matched, false) => ChangeCheck::Partial { matched, unmatched },
+    };
+    if let Some(diagnostic) = check.diagnostic() {
+        log::warn!("Restore verification: {}", diagnostic);
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::types::{OutPoint, TxIn, TxOut};
+
+    fn spend(from: OutPoint, outputs: &[(&str, u64)]) -> Transaction {
+        Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: from,
+                sequence: 0xffff_fffd,
+            }],
+            outputs: outputs
+                .iter()
+                .map(|(address, value)| TxOut {
+                    address: address.to_string(),
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+
+    fn scan(used: &[(u32, u32, &str)]) -> ScanResult {
+        ScanResult {
+            used: used
+                .iter()
+                .map(|(keychain, index, address)| (*keychain, *index, address.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flags_change_outside_derivation() {
+        let backend = MockBackend::new();
+        let first = backend.fund_address("bcrt1qreceive0", 50_000);
+        let second = backend.fund_address("bcrt1qreceive1", 50_000);
+        backend.mine_blocks(1);
+        // Change to a keychain the restore didn't derive
+        let tx = spend(first, &[("bcrt1qpayee", 20_000), ("bcrt1qother", 29_000)]);
+        let unmatched = backend.broadcast(&tx).unwrap();
+        backend
+            .broadcast(&spend(second, &[("bcrt1qpayee", 49_000)]))
+            .unwrap();
+
+        let restored = scan(&[(0, 0, "bcrt1qreceive0"), (0, 1, "bcrt1qreceive1")]);
+        let check = verify_change(&restored, &backend).unwrap();
+        assert_eq!(
+            check,
+            ChangeCheck::Mismatch {
+                unmatched: vec![unmatched.clone()]
+            }
+        );
+        assert!(check.diagnostic().unwrap().contains("derivation standard"));
+
+        let correct = scan(&[
+            (0, 0, "bcrt1qreceive0"),
+            (0, 1, "bcrt1qreceive1"),
+            (1, 0, "bcrt1qother"),
+        ]);
+        assert_eq!(
+            verify_change(&correct, &backend).unwrap(),
+            ChangeCheck::Verified { spends: 1 }
+        );
+        assert_eq!(
+            verify_change(&scan(&[]), &backend).unwrap(),
+            ChangeCheck::NothingToCheck
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Shared test harnesses for downstream crates, enabled by the `test-support` feature.
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestRunner};
+
+use crate::types::{OutPoint, Utxo};
+use crate::utxo_selection::{
+    estimate_vsize, is_spendable, select_utxos, SelectionParams, SelectionResult,
+    SelectionStrategy, OUTPUT_VBYTES,
+};
+
+/// Checks a selection result against the invariants every strategy must uphold:
+/// inputs cover outputs plus fee, no frozen or unspendable coins, change is zero or
+/// above dust, and the fee stays within the bounds implied by the fee rate.
+pub fn check_selection_invariants(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+    result: &SelectionResult,
+) -> Result<(), String> {
+    let SelectionResult::Success {
+        selected,
+        fee,
+        change,
+        ..
+    } = result
+    else {
+        return Ok(());
+    };
+
+    if selected.is_empty() {
+        return Err("Success without any inputs".to_string());
+    }
+    for utxo in selected {
+        if params.frozen.contains(&utxo.outpoint) {
+            return Err(format!("Frozen UTXO {} was selected", utxo.outpoint));
+        }
+        if !utxos.contains(utxo) {
+            return Err(format!("UTXO {} is not a candidate", utxo.outpoint));
+        }
+        if !is_spendable(utxo, params) {
+            return Err(format!("Unspendable UTXO {} was selected", utxo.outpoint));
+        }
+    }
+
+    let input_total: u64 = selected.iter().map(|u| u.value).sum();
+    if input_total != params.target + fee + change {
+        return Err(format!(
+            "Inputs {} != target {} + fee {} + change {}",
+            input_total, params.target, fee, change
+        ));
+    }
+
+    let min_change = params
+        .change_policy
+        .min_change(&params.dust, params.fee_rate);
+    if *change != 0 && *change < min_change {
+        return Err(format!(
+            "Change {} is below the policy minimum {}",
+            change, min_change
+        ));
+    }
+
+    let outputs = params.recipient_outputs + usize::from(*change > 0);
+    let min_fee = estimate_vsize(selected.len(), outputs) * params.fee_rate;
+    // Dropping uneconomical change may overpay by at most the minimum change plus
+    // the change output cost
+    let max_fee = min_fee + min_change + OUTPUT_VBYTES * params.fee_rate;
+    if *fee < min_fee || *fee > max_fee {
+        return Err(format!(
+            "Fee {} outside [{}, {}] for rate {}",
+            fee, min_fee, max_fee, params.fee_rate
+        ));
+    }
+
+    Ok(())
+}
+
+/// Random candidate sets: values, confirmations and a frozen flag per coin.
+pub fn arbitrary_candidates() -> impl Strategy<Value = Vec<(Utxo, bool)>> {
+    vec(
+        (1u64..5_000_000, 0u32..200, any::<bool>(), any::<bool>()),
+        0..20,
+    )
+    .prop_map(|coins| {
+        coins
+            .into_iter()
+            .enumerate()
+            .map(|(i, (value, confirmations, is_coinbase, frozen))| {
+                let utxo = Utxo {
+                    outpoint: OutPoint::new(&format!("{:064x}", i), i as u32),
+                    address: "bcrt1qproptest".to_string(),
+                    value,
+                    confirmations,
+                    is_coinbase,
+                };
+                (utxo, frozen)
+            })
+            .collect()
+    })
+}
+
+/// Runs `strategy` against `cases` random inputs and checks every invariant.
+pub fn assert_selection_invariants(strategy: SelectionStrategy, cases: u32) -> Result<(), String> {
+    let mut runner = TestRunner::new(Config {
+        cases,
+        ..Config::default()
+    });
+    let inputs = (arbitrary_candidates(), 1u64..10_000_000, 1u64..500, 0u32..3);
+
+    runner
+        .run(
+            &inputs,
+            |(candidates, target, fee_rate, min_confirmations)| {
+                let mut params = SelectionParams::new(target, fee_rate);
+                params.min_confirmations = min_confirmations;
+                let utxos: Vec<Utxo> = candidates
+                    .iter()
+                    .map(|(utxo, frozen)| {
+                        if *frozen {
+                            params.frozen.insert(utxo.outpoint.clone());
+                        }
+                        utxo.clone()
+                    })
+                    .collect();
+
+                let result = select_utxos(&utxos, &params, strategy);
+                check_selection_invariants(&utxos, &params, &result).map_err(TestCaseError::fail)
+            },
+        )
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,527 @@
+//! Turns a coin selection into the unsigned transaction that gets signed.
+//!
+//! Field choices that don't affect what the transaction pays, like
+//! `lock_time`, follow Bitcoin Core so our transactions don't stand out
+//! from the most common wallet's.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::backend::BlockHeader;
+use crate::types::{OutPoint, Transaction, TxIn, TxOut, Utxo, LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
+use crate::utxo_selection::dust::DustPolicy;
+use crate::utxo_selection::SelectionResult;
+
+pub mod fingerprint;
+pub mod sequence;
+pub mod split;
+
+use sequence::{RelativeLock, SequencePolicy};
+
+/// Tip age past which Core assumes it is still syncing and skips
+/// anti-fee-sniping, since a stale height would itself be a fingerprint.
+pub const MAX_ANTI_FEE_SNIPING_TIP_AGE: u64 = 8 * 60 * 60;
+
+/// How `lock_time` is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LocktimePolicy {
+    /// The current height, sometimes up to 99 blocks earlier, so miners
+    /// gain nothing by reorging to take the fees.
+    #[default]
+    AntiFeeSniping,
+    /// This exact height or timestamp.
+    Fixed(u32),
+    /// Zero.
+    None,
+}
+
+/// Order of inputs and outputs. Always putting change last, or inputs in
+/// the order coins were selected, tells observers which output is change.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderingPolicy {
+    /// Shuffled, as Bitcoin Core does.
+    #[default]
+    Random,
+    /// BIP-69: inputs by previous txid then index, outputs by amount then
+    /// address (standing in for the script, which this model doesn't keep).
+    Bip69,
+    /// As given: recipients in order, then change.
+    Preserve,
+}
+
+impl OrderingPolicy {
+    fn apply<R: Rng + ?Sized>(&self, inputs: &mut [TxIn], outputs: &mut [TxOut], rng: &mut R) {
+        match self {
+            OrderingPolicy::Random => {
+                inputs.shuffle(rng);
+                outputs.shuffle(rng);
+            }
+            OrderingPolicy::Bip69 => {
+                inputs.sort_by(|a, b| {
+                    let (a, b) = (&a.previous_output, &b.previous_output);
+                    (&a.txid, a.vout).cmp(&(&b.txid, b.vout))
+                });
+                outputs.sort_by(|a, b| (a.value, &a.address).cmp(&(b.value, &b.address)));
+            }
+            OrderingPolicy::Preserve => {}
+        }
+    }
+}
+
+/// Bitcoin Core's anti-fee-sniping `lock_time` for spending at `tip`:
+/// the tip height, or with 1-in-10 chance a random 0..100 blocks before
+/// it, and 0 if the tip is older than [`MAX_ANTI_FEE_SNIPING_TIP_AGE`].
+pub fn anti_fee_sniping_locktime<R: Rng + ?Sized>(tip: &BlockHeader, now: u64, rng: &mut R) -> u32 {
+    if now.saturating_sub(tip.time) > MAX_ANTI_FEE_SNIPING_TIP_AGE {
+        return 0;
+    }
+    let mut lock_time = tip.height;
+    // Occasionally earlier, for transactions delayed after signing
+    // (e.g. by high-latency mixers) to blend in
+    if rng.random_range(0..10) == 0 {
+        lock_time = lock_time.saturating_sub(rng.random_range(0..100));
+    }
+    lock_time
+}
+
+/// Builds a transaction spending `inputs` to `recipients`, with any change.
+#[derive(Clone, Debug)]
+pub struct TxBuilder {
+    inputs: Vec<Utxo>,
+    recipients: Vec<TxOut>,
+    change: Option<TxOut>,
+    locktime: LocktimePolicy,
+    ordering: OrderingPolicy,
+    seed: Option<u64>,
+    /// Outputs below this are refused.
+    dust_limit: u64,
+    sequence: SequencePolicy,
+    input_sequences: HashMap<OutPoint, SequencePolicy>,
+    required_locks: HashMap<OutPoint, RelativeLock>,
+}
+
+impl TxBuilder {
+    pub fn new(inputs: Vec<Utxo>, recipients: Vec<TxOut>) -> Self {
+        TxBuilder {
+            inputs,
+            recipients,
+            change: None,
+            locktime: LocktimePolicy::default(),
+            ordering: OrderingPolicy::default(),
+            seed: None,
+            dust_limit: DustPolicy::default().threshold,
+            sequence: SequencePolicy::default(),
+            input_sequences: HashMap::new(),
+            required_locks: HashMap::new(),
+        }
+    }
+
+    /// Builder for a successful `selection`, sending its change, if any, to
+    /// `change_address`.
+    pub fn from_selection(
+        selection: &SelectionResult,
+        recipients: Vec<TxOut>,
+        change_address: &str,
+    ) -> Result<Self, String> {
+        let SelectionResult::Success {
+            selected, change, ..
+        } = selection
+        else {
+            return Err("Coin selection did not succeed".to_string());
+        };
+        let builder = TxBuilder::new(selected.clone(), recipients);
+        Ok(if *change > 0 {
+            builder.with_change(TxOut {
+                address: change_address.to_string(),
+                value: *change,
+            })
+        } else {
+            builder
+        })
+    }
+
+    pub fn with_change(mut self, change: TxOut) -> Self {
+        self.change = Some(change);
+        self
+    }
+
+    pub fn with_locktime(mut self, policy: LocktimePolicy) -> Self {
+        self.locktime = policy;
+        self
+    }
+
+    pub fn with_ordering(mut self, policy: OrderingPolicy) -> Self {
+        self.ordering = policy;
+        self
+    }
+
+    /// Refuses outputs that are dust under `dust` at `fee_rate`.
+    pub fn with_dust(mut self, dust: &DustPolicy, fee_rate: u64) -> Self {
+        self.dust_limit = dust.limit(fee_rate);
+        self
+    }
+
+    /// Signals BIP-125 replaceability on every input without its own
+    /// sequence (the default), or not.
+    pub fn with_rbf(mut self, rbf: bool) -> Self {
+        self.sequence = if rbf {
+            SequencePolicy::Rbf
+        } else {
+            SequencePolicy::NoRbf
+        };
+        self
+    }
+
+    /// Sets the sequence of the input spending `outpoint`.
+    pub fn with_input_sequence(mut self, outpoint: OutPoint, sequence: SequencePolicy) -> Self {
+        self.input_sequences.insert(outpoint, sequence);
+        self
+    }
+
+    /// Records that the coin at `outpoint` can only be spent with at least
+    /// `lock`, e.g. because its script uses `OP_CHECKSEQUENCEVERIFY`. The
+    /// input's sequence is set to it unless already given.
+    pub fn with_required_lock(mut self, outpoint: OutPoint, lock: RelativeLock) -> Self {
+        self.input_sequences
+            .entry(outpoint.clone())
+            .or_insert(SequencePolicy::Relative(lock));
+        self.required_locks.insert(outpoint, lock);
+        self
+    }
+
+    /// Fixes the randomness, for reproducible tests.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The unsigned transaction, for a chain whose tip is `tip` at unix
+    /// time `now`.
+    pub fn build(&self, tip: &BlockHeader, now: u64) -> Result<Transaction, String> {
+        if self.inputs.is_empty() {
+            return Err("Transaction has no inputs".to_string());
+        }
+        if self.recipients.is_empty() {
+            return Err("Transaction has no recipients".to_string());
+        }
+        if let Some(dust) = self
+            .recipients
+            .iter()
+            .chain(&self.change)
+            .find(|o| o.value < self.dust_limit)
+        {
+            return Err(format!(
+                "Output of {} sats to {} is below the {} sat dust limit",
+                dust.value, dust.address, self.dust_limit
+            ));
+        }
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        let lock_time = match self.locktime {
+            LocktimePolicy::AntiFeeSniping => anti_fee_sniping_locktime(tip, now, &mut rng),
+            LocktimePolicy::Fixed(lock_time) => {
+                if lock_time < LOCKTIME_THRESHOLD && lock_time > tip.height + 1 {
+                    return Err(format!(
+                        "Locktime {} is past the next block ({})",
+                        lock_time,
+                        tip.height + 1
+                    ));
+                }
+                lock_time
+            }
+            LocktimePolicy::None => 0,
+        };
+
+        let mut inputs = self.inputs_with_sequences()?;
+        if lock_time != 0 && inputs.iter().all(|i| i.sequence == SEQUENCE_FINAL) {
+            return Err(format!(
+                "Locktime {} would be ignored with every input's sequence final",
+                lock_time
+            ));
+        }
+        let mut outputs = self.recipients.clone();
+        outputs.extend(self.change.clone());
+        // Last, once amounts and fee are settled; order doesn't change size
+        self.ordering.apply(&mut inputs, &mut outputs, &mut rng);
+        Ok(Transaction {
+            lock_time,
+            inputs,
+            outputs,
+        })
+    }
+
+    // Each input's sequence, checked against the locks its coin requires
+    fn inputs_with_sequences(&self) -> Result<Vec<TxIn>, String> {
+        if let Some(outpoint) = self
+            .input_sequences
+            .keys()
+            .chain(self.required_locks.keys())
+            .find(|o| !self.inputs.iter().any(|u| u.outpoint == **o))
+        {
+            return Err(format!(
+                "Sequence given for {}, which is not an input",
+                outpoint
+            ));
+        }
+        self.inputs
+            .iter()
+            .map(|utxo| {
+                let policy = self
+                    .input_sequences
+                    .get(&utxo.outpoint)
+                    .copied()
+                    .unwrap_or(self.sequence);
+                let sequence = policy.to_sequence();
+                let lock = RelativeLock::from_sequence(sequence);
+                if let Some(required) = self.required_locks.get(&utxo.outpoint) {
+                    if !lock.is_some_and(|lock| lock.satisfies(*required)) {
+                        return Err(format!(
+                            "{} can only be spent with a relative lock of {}",
+                            utxo.outpoint, required
+                        ));
+                    }
+                }
+                if let Some(RelativeLock::Blocks(blocks)) = lock {
+                    let remaining = (blocks as u32).saturating_sub(utxo.confirmations);
+                    if remaining > 0 {
+                        return Err(format!(
+                            "{} is spendable in {} more blocks",
+                            utxo.outpoint, remaining
+                        ));
+                    }
+                }
+                Ok(TxIn {
+                    previous_output: utxo.outpoint.clone(),
+                    sequence,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SEQUENCE_ENABLE_LOCKTIME, SEQUENCE_RBF};
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn tip(height: u32, time: u64) -> BlockHeader {
+        BlockHeader {
+            height,
+            hash: "00".repeat(32),
+            prev_hash: "00".repeat(32),
+            time,
+        }
+    }
+
+    fn builder() -> TxBuilder {
+        let utxo = Utxo {
+            outpoint: OutPoint::new(&"11".repeat(32), 0),
+            address: "bcrt1qinput".to_string(),
+            value: 50_000,
+            confirmations: 6,
+            is_coinbase: false,
+        };
+        TxBuilder::new(
+            vec![utxo],
+            vec![TxOut {
+                address: "bcrt1qpayee".to_string(),
+                value: 40_000,
+            }],
+        )
+    }
+
+    #[test]
+    fn test_anti_fee_sniping_matches_core() {
+        let current = tip(800_000, NOW - 600);
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples: Vec<u32> = (0..10_000)
+            .map(|_| anti_fee_sniping_locktime(&current, NOW, &mut rng))
+            .collect();
+        assert!(samples
+            .iter()
+            .all(|lock_time| (800_000 - 99..=800_000).contains(lock_time)));
+        // About one in ten is moved back, and some by the full range
+        let earlier = samples.iter().filter(|l| **l < 800_000).count();
+        assert!((800..1_200).contains(&earlier), "{}", earlier);
+        assert!(samples.iter().any(|l| *l < 800_000 - 90));
+
+        // Near genesis the offset can't go below zero
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!((0..1_000).all(|_| anti_fee_sniping_locktime(&tip(5, NOW), NOW, &mut rng) <= 5));
+
+        let stale = tip(800_000, NOW - MAX_ANTI_FEE_SNIPING_TIP_AGE - 1);
+        assert_eq!(anti_fee_sniping_locktime(&stale, NOW, &mut rng), 0);
+    }
+
+    #[test]
+    fn test_build_applies_locktime_policy() {
+        let current = tip(800_000, NOW);
+        let tx = builder().with_seed(1).build(&current, NOW).unwrap();
+        assert!(tx.lock_time > 800_000 - 100 && tx.lock_time <= 800_000);
+        assert!(tx.inputs.iter().all(|input| input.sequence == SEQUENCE_RBF));
+        assert_eq!(builder().with_seed(1).build(&current, NOW).unwrap(), tx);
+
+        let fixed = builder().with_locktime(LocktimePolicy::Fixed(799_000));
+        assert_eq!(fixed.build(&current, NOW).unwrap().lock_time, 799_000);
+        let future = builder().with_locktime(LocktimePolicy::Fixed(900_000));
+        assert!(future.build(&current, NOW).is_err());
+        let none = builder().with_locktime(LocktimePolicy::None);
+        assert_eq!(none.build(&current, NOW).unwrap().lock_time, 0);
+
+        let change = TxOut {
+            address: "bcrt1qchange".to_string(),
+            value: 9_000,
+        };
+        let tx = builder()
+            .with_change(change.clone())
+            .with_ordering(OrderingPolicy::Preserve)
+            .build(&current, NOW)
+            .unwrap();
+        assert_eq!(tx.outputs.last(), Some(&change));
+        assert!(TxBuilder::new(Vec::new(), Vec::new())
+            .build(&current, NOW)
+            .is_err());
+    }
+
+    #[test]
+    fn test_input_sequences() {
+        let current = tip(800_000, NOW);
+        let coin = OutPoint::new(&"11".repeat(32), 0);
+        let sequence =
+            |builder: TxBuilder| builder.build(&current, NOW).map(|tx| tx.inputs[0].sequence);
+        assert_eq!(
+            sequence(builder().with_rbf(false)),
+            Ok(SEQUENCE_ENABLE_LOCKTIME)
+        );
+
+        // The coin has 6 confirmations
+        let csv = |blocks| builder().with_required_lock(coin.clone(), RelativeLock::Blocks(blocks));
+        assert_eq!(sequence(csv(6)), Ok(6));
+        assert_eq!(
+            sequence(csv(10)),
+            Err(format!("{} is spendable in 4 more blocks", coin))
+        );
+        assert!(sequence(csv(6).with_input_sequence(coin.clone(), SequencePolicy::Rbf)).is_err());
+        assert!(sequence(
+            builder().with_input_sequence(OutPoint::new("ff", 0), SequencePolicy::Final)
+        )
+        .is_err());
+
+        let all_final = builder().with_input_sequence(coin, SequencePolicy::Final);
+        assert!(sequence(all_final.clone()).is_err());
+        assert_eq!(
+            sequence(all_final.with_locktime(LocktimePolicy::None)),
+            Ok(SEQUENCE_FINAL)
+        );
+    }
+
+    #[test]
+    fn test_ordering_policies() {
+        let utxo = |txid: &str, vout: u32, value: u64| Utxo {
+            outpoint: OutPoint::new(&txid.repeat(32), vout),
+            address: "bcrt1qinput".to_string(),
+            value,
+            confirmations: 6,
+            is_coinbase: false,
+        };
+        let out = |address: &str, value: u64| TxOut {
+            address: address.to_string(),
+            value,
+        };
+        let builder = TxBuilder::new(
+            vec![
+                utxo("bb", 0, 30_000),
+                utxo("aa", 1, 20_000),
+                utxo("aa", 0, 10_000),
+            ],
+            vec![
+                out("bcrt1qb", 20_000),
+                out("bcrt1qa", 20_000),
+                out("bcrt1qc", 5_000),
+            ],
+        )
+        .with_change(out("bcrt1qchange", 14_000))
+        .with_locktime(LocktimePolicy::None);
+        let current = tip(800_000, NOW);
+        let fee = |tx: &Transaction| 60_000 - tx.outputs.iter().map(|o| o.value).sum::<u64>();
+
+        let preserved = builder
+            .clone()
+            .with_ordering(OrderingPolicy::Preserve)
+            .build(&current, NOW)
+            .unwrap();
+        assert_eq!(preserved.inputs[0].previous_output.txid, "bb".repeat(32));
+        assert_eq!(preserved.outputs[3].address, "bcrt1qchange");
+
+        let bip69 = builder
+            .clone()
+            .with_ordering(OrderingPolicy::Bip69)
+            .build(&current, NOW)
+            .unwrap();
+        let inputs: Vec<(String, u32)> = bip69
+            .inputs
+            .iter()
+            .map(|i| {
+                (
+                    i.previous_output.txid[..2].to_string(),
+                    i.previous_output.vout,
+                )
+            })
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![
+                ("aa".to_string(), 0),
+                ("aa".to_string(), 1),
+                ("bb".to_string(), 0)
+            ]
+        );
+        let outputs: Vec<&str> = bip69.outputs.iter().map(|o| o.address.as_str()).collect();
+        assert_eq!(
+            outputs,
+            vec!["bcrt1qc", "bcrt1qchange", "bcrt1qa", "bcrt1qb"]
+        );
+        // Deterministic regardless of the order given
+        let reversed = TxBuilder::new(
+            preserved
+                .inputs
+                .iter()
+                .rev()
+                .map(|i| utxo(&i.previous_output.txid[..2], i.previous_output.vout, 0))
+                .collect(),
+            preserved.outputs.iter().rev().cloned().collect(),
+        )
+        .with_ordering(OrderingPolicy::Bip69)
+        .with_locktime(LocktimePolicy::None);
+        assert_eq!(reversed.build(&current, NOW).unwrap(), bip69);
+
+        // Shuffling only reorders, and moves change around between builds
+        let mut change_positions = Vec::new();
+        for seed in 0..20 {
+            let shuffled = builder
+                .clone()
+                .with_seed(seed)
+                .build(&current, NOW)
+                .unwrap();
+            assert_eq!(fee(&shuffled), fee(&preserved));
+            let mut sorted = shuffled.clone();
+            OrderingPolicy::Bip69.apply(&mut sorted.inputs, &mut sorted.outputs, &mut rand::rng());
+            assert_eq!(sorted, bip69);
+            change_positions.push(
+                shuffled
+                    .outputs
+                    .iter()
+                    .position(|o| o.address == "bcrt1qchange"),
+            );
+        }
+        change_positions.dedup();
+        assert!(change_positions.len() > 1);
+    }
+}
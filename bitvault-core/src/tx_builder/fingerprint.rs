@@ -0,0 +1,268 @@
+//! Looks for habits in the wallet's own sends that let chain analysis tell
+//! its transactions apart from other wallets'.
+//!
+//! Each pattern is only judged once enough sends show it, since a handful
+//! of transactions can line up by chance.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::OrderingPolicy;
+use crate::types::Transaction;
+use crate::utxo_selection::estimate_vsize;
+
+/// Fewest relevant sends before a habit counts as one.
+pub const MIN_SAMPLE: usize = 5;
+
+/// Share of sends at one fee rate that makes it look fixed.
+const CONSTANT_FEE_SHARE: f64 = 0.8;
+
+/// A transaction the wallet sent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingTx {
+    pub transaction: Transaction,
+    pub fee: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Position {
+    First,
+    Last,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fingerprint {
+    /// Most sends pay the same fee rate, as with a fixed fee setting.
+    ConstantFeeRate { sat_per_vb: u64, share: usize },
+    /// Change always sits at the same output position.
+    ChangeAlwaysAt(Position),
+    /// Inputs and outputs are always in BIP-69 order, which few wallets use.
+    Bip69Ordering,
+    /// `lock_time` is always 0, unlike Core's anti-fee-sniping heights.
+    NoAntiFeeSniping,
+}
+
+impl Fingerprint {
+    pub fn recommendation(&self) -> String {
+        match self {
+            Fingerprint::ConstantFeeRate { sat_per_vb, share } => format!(
+                "{}% of sends paid {} sat/vB. Use fee estimates instead of a fixed rate",
+                share, sat_per_vb
+            ),
+            Fingerprint::ChangeAlwaysAt(position) => format!(
+                "Change is always the {} output, which gives it away. Use {:?} ordering",
+                match position {
+                    Position::First => "first",
+                    Position::Last => "last",
+                },
+                OrderingPolicy::Random
+            ),
+            Fingerprint::Bip69Ordering => format!(
+                "Every send is in BIP-69 order. Use {:?} ordering to blend in with most wallets",
+                OrderingPolicy::Random
+            ),
+            Fingerprint::NoAntiFeeSniping => {
+                "No send sets a locktime. Use the anti-fee-sniping locktime policy".to_string()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FingerprintReport {
+    /// Sends looked at.
+    pub analyzed: usize,
+    pub findings: Vec<Fingerprint>,
+}
+
+impl FingerprintReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn recommendations(&self) -> Vec<String> {
+        self.findings
+            .iter()
+            .map(Fingerprint::recommendation)
+            .collect()
+    }
+}
+
+/// A wallet's sends and addresses, as stored in CI fixtures.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureWallet {
+    pub wallet: Vec<String>,
+    pub transactions: Vec<OutgoingTx>,
+}
+
+impl FixtureWallet {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid fixture wallet: {}", e))
+    }
+
+    pub fn analyze(&self) -> FingerprintReport {
+        let wallet: HashSet<String> = self.wallet.iter().cloned().collect();
+        analyze(&self.transactions, &wallet)
+    }
+}
+
+fn is_sorted_bip69(tx: &Transaction) -> bool {
+    let mut sorted = tx.clone();
+    OrderingPolicy::Bip69.apply(&mut sorted.inputs, &mut sorted.outputs, &mut rand::rng());
+    sorted == *tx
+}
+
+/// Checks `sends` for habits, with `wallet` the wallet's own addresses so
+/// change can be told from payments.
+pub fn analyze(sends: &[OutgoingTx], wallet: &HashSet<String>) -> FingerprintReport {
+    let mut findings = Vec::new();
+
+    let mut rates: HashMap<u64, usize> = HashMap::new();
+    for send in sends {
+        let tx = &send.transaction;
+        let vsize = estimate_vsize(tx.inputs.len(), tx.outputs.len());
+        *rates
+            .entry((send.fee as f64 / vsize as f64).round() as u64)
+            .or_default() += 1;
+    }
+    if let Some((rate, count)) = rates
+        .into_iter()
+        .max_by_key(|(rate, count)| (*count, *rate))
+    {
+        let share = count as f64 / sends.len() as f64;
+        if sends.len() >= MIN_SAMPLE && share >= CONSTANT_FEE_SHARE {
+            findings.push(Fingerprint::ConstantFeeRate {
+                sat_per_vb: rate,
+                share: (share * 100.0).round() as usize,
+            });
+        }
+    }
+
+    // Only sends with both a payment and change say anything about position
+    let change_positions: Vec<Option<Position>> = sends
+        .iter()
+        .filter_map(|send| {
+            let outputs = &send.transaction.outputs;
+            let change: Vec<usize> = (0..outputs.len())
+                .filter(|i| wallet.contains(&outputs[*i].address))
+                .collect();
+            match change[..] {
+                [i] if outputs.len() > 1 => Some(if i == 0 {
+                    Some(Position::First)
+                } else if i == outputs.len() - 1 {
+                    Some(Position::Last)
+                } else {
+                    None
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    if change_positions.len() >= MIN_SAMPLE {
+        for position in [Position::First, Position::Last] {
+            if change_positions.iter().all(|p| *p == Some(position)) {
+                findings.push(Fingerprint::ChangeAlwaysAt(position));
+            }
+        }
+    }
+
+    // Single-input, single-output sends are in every order at once
+    let orderable: Vec<&Transaction> = sends
+        .iter()
+        .map(|send| &send.transaction)
+        .filter(|tx| tx.inputs.len() > 1 || tx.outputs.len() > 1)
+        .collect();
+    if orderable.len() >= MIN_SAMPLE && orderable.iter().all(|tx| is_sorted_bip69(tx)) {
+        findings.push(Fingerprint::Bip69Ordering);
+    }
+
+    if sends.len() >= MIN_SAMPLE && sends.iter().all(|send| send.transaction.lock_time == 0) {
+        findings.push(Fingerprint::NoAntiFeeSniping);
+    }
+
+    FingerprintReport {
+        analyzed: sends.len(),
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::BlockHeader;
+    use crate::tx_builder::TxBuilder;
+    use crate::types::{OutPoint, TxOut, Utxo};
+
+    #[test]
+    fn test_fixture_wallet_habits() {
+        let fixture = FixtureWallet::from_json(include_str!("fixtures/habitual.json")).unwrap();
+        let report = fixture.analyze();
+        assert_eq!(report.analyzed, 5);
+        assert_eq!(
+            report.findings,
+            vec![
+                Fingerprint::ConstantFeeRate {
+                    sat_per_vb: 10,
+                    share: 100
+                },
+                Fingerprint::ChangeAlwaysAt(Position::Last),
+                Fingerprint::Bip69Ordering,
+                Fingerprint::NoAntiFeeSniping,
+            ]
+        );
+        assert_eq!(report.recommendations().len(), 4);
+
+        // Too few sends to call anything a habit
+        let short = analyze(&fixture.transactions[..4], &HashSet::new());
+        assert!(short.is_clean());
+    }
+
+    #[test]
+    fn test_builder_defaults_are_clean() {
+        let tip = BlockHeader {
+            height: 800_000,
+            hash: "00".repeat(32),
+            prev_hash: "00".repeat(32),
+            time: 1_700_000_000,
+        };
+        let wallet: HashSet<String> = (0..10).map(|i| format!("bcrt1qchange{}", i)).collect();
+        let sends: Vec<OutgoingTx> = (0..10u64)
+            .map(|i| {
+                let utxos = (0..2)
+                    .map(|vout| Utxo {
+                        outpoint: OutPoint::new(&format!("{:02x}", i).repeat(32), vout),
+                        address: "bcrt1qinput".to_string(),
+                        value: 50_000,
+                        confirmations: 6,
+                        is_coinbase: false,
+                    })
+                    .collect();
+                let fee = estimate_vsize(2, 3) * (5 + i * 3);
+                let transaction = TxBuilder::new(
+                    utxos,
+                    vec![
+                        TxOut {
+                            address: format!("bcrt1qpayee{}", i),
+                            value: 30_000,
+                        },
+                        TxOut {
+                            address: format!("bcrt1qother{}", i),
+                            value: 20_000,
+                        },
+                    ],
+                )
+                .with_change(TxOut {
+                    address: format!("bcrt1qchange{}", i),
+                    value: 50_000 - fee,
+                })
+                .with_seed(i)
+                .build(&tip, tip.time)
+                .unwrap();
+                OutgoingTx { transaction, fee }
+            })
+            .collect();
+        let report = analyze(&sends, &wallet);
+        assert_eq!(report.analyzed, 10);
+        assert!(report.is_clean(), "{:?}", report.recommendations());
+    }
+}
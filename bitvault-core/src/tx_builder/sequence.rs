@@ -0,0 +1,115 @@
+//! Input `nSequence` values: BIP-125 replaceability and BIP-68 relative
+//! locktimes.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::types::{SEQUENCE_ENABLE_LOCKTIME, SEQUENCE_FINAL, SEQUENCE_RBF};
+
+/// Set on a sequence to turn its relative locktime off.
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+/// Set when the relative locktime is in units of 512 seconds, not blocks.
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0xffff;
+
+/// A BIP-68 relative locktime, as checked by `OP_CHECKSEQUENCEVERIFY`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelativeLock {
+    /// Blocks since the spent coin confirmed.
+    Blocks(u16),
+    /// Units of 512 seconds since the spent coin confirmed.
+    Time(u16),
+}
+
+impl RelativeLock {
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLock::Blocks(blocks) => blocks as u32,
+            RelativeLock::Time(units) => SEQUENCE_LOCKTIME_TYPE_FLAG | units as u32,
+        }
+    }
+
+    /// The relative lock `sequence` enforces, if any.
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        Some(if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            RelativeLock::Time(value)
+        } else {
+            RelativeLock::Blocks(value)
+        })
+    }
+
+    /// Whether this lock is at least as long as `required`, in the same unit.
+    pub fn satisfies(self, required: RelativeLock) -> bool {
+        match (self, required) {
+            (RelativeLock::Blocks(have), RelativeLock::Blocks(need))
+            | (RelativeLock::Time(have), RelativeLock::Time(need)) => have >= need,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RelativeLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelativeLock::Blocks(blocks) => write!(f, "{} blocks", blocks),
+            RelativeLock::Time(units) => write!(f, "{} seconds", *units as u32 * 512),
+        }
+    }
+}
+
+/// What an input's sequence signals.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SequencePolicy {
+    /// Replaceable under BIP-125, as Bitcoin Core does by default.
+    #[default]
+    Rbf,
+    /// Not replaceable; `lock_time` is still enforced.
+    NoRbf,
+    /// Neither replaceable nor enforcing `lock_time`.
+    Final,
+    /// A relative locktime, which also signals replaceability.
+    Relative(RelativeLock),
+}
+
+impl SequencePolicy {
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            SequencePolicy::Rbf => SEQUENCE_RBF,
+            SequencePolicy::NoRbf => SEQUENCE_ENABLE_LOCKTIME,
+            SequencePolicy::Final => SEQUENCE_FINAL,
+            SequencePolicy::Relative(lock) => lock.to_sequence(),
+        }
+    }
+
+    pub fn signals_rbf(self) -> bool {
+        self.to_sequence() < SEQUENCE_ENABLE_LOCKTIME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_encoding() {
+        assert_eq!(SequencePolicy::default().to_sequence(), 0xffff_fffd);
+        assert!(!SequencePolicy::NoRbf.signals_rbf());
+        assert_eq!(RelativeLock::from_sequence(SEQUENCE_RBF), None);
+
+        let csv = SequencePolicy::Relative(RelativeLock::Blocks(144));
+        assert_eq!(csv.to_sequence(), 144);
+        assert!(csv.signals_rbf());
+        let time = RelativeLock::Time(10);
+        assert_eq!(time.to_sequence(), 0x0040_000a);
+        assert_eq!(RelativeLock::from_sequence(0x0040_000a), Some(time));
+        assert_eq!(time.to_string(), "5120 seconds");
+
+        assert!(RelativeLock::Blocks(144).satisfies(RelativeLock::Blocks(100)));
+        assert!(!RelativeLock::Blocks(99).satisfies(RelativeLock::Blocks(100)));
+        assert!(!RelativeLock::Time(200).satisfies(RelativeLock::Blocks(100)));
+    }
+}
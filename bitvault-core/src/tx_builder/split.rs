@@ -0,0 +1,191 @@
+//! Splits one total between several recipients by percentage, e.g. 70/20/10,
+//! to the exact satoshi.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::types::TxOut;
+use crate::utxo_selection::dust::DustPolicy;
+
+/// Basis points in a whole; shares are given in hundredths of a percent.
+pub const WHOLE: u32 = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitShare {
+    pub address: String,
+    pub basis_points: u32,
+}
+
+impl SplitShare {
+    pub fn new(address: &str, basis_points: u32) -> Self {
+        SplitShare {
+            address: address.to_string(),
+            basis_points,
+        }
+    }
+
+    pub fn percent(address: &str, percent: u32) -> Self {
+        SplitShare::new(address, percent * 100)
+    }
+}
+
+/// One output per share, in the order given, summing to exactly `total`.
+///
+/// Each share gets its amount rounded down; the satoshis left over go one
+/// each to the shares with the largest rounded-off fractions, earlier
+/// shares first on ties. Outputs that still end up below `dust`'s limit at
+/// `fee_rate` are raised to it, taken from the largest outputs, as long as
+/// those stay above it.
+pub fn split_payment(
+    total: u64,
+    shares: &[SplitShare],
+    dust: &DustPolicy,
+    fee_rate: u64,
+) -> Result<Vec<TxOut>, String> {
+    if shares.is_empty() {
+        return Err("No recipients to split between".to_string());
+    }
+    let mut addresses = HashSet::new();
+    for share in shares {
+        if share.basis_points == 0 {
+            return Err(format!("{} has a 0% share", share.address));
+        }
+        if !addresses.insert(share.address.as_str()) {
+            return Err(format!("{} appears more than once", share.address));
+        }
+    }
+    let sum: u64 = shares.iter().map(|s| s.basis_points as u64).sum();
+    if sum != WHOLE as u64 {
+        return Err(format!(
+            "Shares add up to {}.{:02}%, not 100%",
+            sum / 100,
+            sum % 100
+        ));
+    }
+    let limit = dust.limit(fee_rate);
+    if total < limit * shares.len() as u64 {
+        return Err(format!(
+            "{} sat is too little to give {} recipients at least {} sat each",
+            total,
+            shares.len(),
+            limit
+        ));
+    }
+
+    let exact: Vec<u128> = shares
+        .iter()
+        .map(|s| total as u128 * s.basis_points as u128)
+        .collect();
+    let mut values: Vec<u64> = exact.iter().map(|e| (e / WHOLE as u128) as u64).collect();
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    // Stable, so ties keep the given order
+    by_remainder.sort_by_key(|i| std::cmp::Reverse(exact[*i] % WHOLE as u128));
+    let leftover = total - values.iter().sum::<u64>();
+    for i in by_remainder.into_iter().take(leftover as usize) {
+        values[i] += 1;
+    }
+
+    for i in 0..values.len() {
+        while values[i] < limit {
+            let shortfall = limit - values[i];
+            let largest = (0..values.len())
+                .max_by_key(|j| (values[*j], std::cmp::Reverse(*j)))
+                .unwrap_or(i);
+            let spare = values[largest].saturating_sub(limit);
+            if largest == i || spare == 0 {
+                return Err(format!(
+                    "{}'s share would be below the {} sat dust limit",
+                    shares[i].address, limit
+                ));
+            }
+            let moved = shortfall.min(spare);
+            values[largest] -= moved;
+            values[i] += moved;
+        }
+    }
+
+    Ok(shares
+        .iter()
+        .zip(values)
+        .map(|(share, value)| TxOut {
+            address: share.address.clone(),
+            value,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(outputs: &[TxOut]) -> Vec<u64> {
+        outputs.iter().map(|o| o.value).collect()
+    }
+
+    #[test]
+    fn test_split_rounding_and_dust() {
+        let dust = DustPolicy::default();
+        let thirds = [
+            SplitShare::new("bcrt1qa", 3_334),
+            SplitShare::new("bcrt1qb", 3_333),
+            SplitShare::new("bcrt1qc", 3_333),
+        ];
+        // The sat left over goes to the largest fraction, or the first on ties
+        assert_eq!(
+            values(&split_payment(10_001, &thirds, &dust, 1).unwrap()),
+            vec![3_335, 3_333, 3_333]
+        );
+        let halves = [
+            SplitShare::percent("bcrt1qa", 50),
+            SplitShare::percent("bcrt1qb", 50),
+        ];
+        assert_eq!(
+            values(&split_payment(2_001, &halves, &dust, 1).unwrap()),
+            vec![1_001, 1_000]
+        );
+        let outputs = split_payment(
+            100_000,
+            &[
+                SplitShare::percent("bcrt1qa", 70),
+                SplitShare::percent("bcrt1qb", 20),
+                SplitShare::percent("bcrt1qc", 10),
+            ],
+            &dust,
+            1,
+        )
+        .unwrap();
+        assert_eq!(values(&outputs), vec![70_000, 20_000, 10_000]);
+        assert_eq!(outputs[2].address, "bcrt1qc");
+
+        for total in [1_639, 12_345, 99_999_999] {
+            let outputs = split_payment(total, &thirds, &dust, 1).unwrap();
+            assert_eq!(values(&outputs).iter().sum::<u64>(), total);
+            assert_eq!(split_payment(total, &thirds, &dust, 1).unwrap(), outputs);
+        }
+
+        // 1% of 20_000 is dust, so the 99% share gives up the difference
+        let small = [
+            SplitShare::percent("bcrt1qa", 99),
+            SplitShare::percent("bcrt1qb", 1),
+        ];
+        assert_eq!(
+            values(&split_payment(20_000, &small, &dust, 1).unwrap()),
+            vec![19_454, 546]
+        );
+        assert!(split_payment(1_000, &small, &dust, 1).is_err());
+
+        let err =
+            split_payment(10_000, &[SplitShare::percent("bcrt1qa", 90)], &dust, 1).unwrap_err();
+        assert_eq!(err, "Shares add up to 90.00%, not 100%");
+        assert!(split_payment(
+            10_000,
+            &[
+                SplitShare::percent("bcrt1qa", 50),
+                SplitShare::percent("bcrt1qa", 50),
+            ],
+            &dust,
+            1,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::metadata::labels::{self, LabelTotals};
+use crate::metadata::WalletMetadata;
+use crate::types::OutPoint;
+
+pub mod search;
+
+use search::SearchIndex;
+
+/// Rows loaded from disk at a time.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+/// Chunks of rows kept in memory before the least recently used is dropped.
+pub const DEFAULT_MAX_CACHED_PAGES: usize = 20;
+
+/// One transaction in the wallet's history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub txid: String,
+    /// Confirmation height, `None` while in the mempool.
+    pub height: Option<u32>,
+    pub timestamp: u64,
+    /// Received minus sent, in satoshis.
+    pub net_amount: i64,
+    pub fee: Option<u64>,
+    /// Wallet coins this transaction spends.
+    pub spent: Vec<OutPoint>,
+    /// Txid of the transaction that spent the same coins instead of this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<String>,
+    /// Wallet addresses the transaction pays to or spends from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    /// Free-text note the user attached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn conflicts_with(&self, other: &HistoryEntry) -> bool {
+        self.txid != other.txid && self.spent.iter().any(|o| other.spent.contains(o))
+    }
+
+    fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.txid.len()
+            + self
+                .spent
+                .iter()
+                .map(|o| std::mem::size_of::<OutPoint>() + o.txid.len())
+                .sum::<usize>()
+    }
+}
+
+/// Transaction history for wallets too large to hold in memory.
+///
+/// Confirmed rows live in an append-only JSON-lines file; only their byte
+/// offsets are kept in memory, and rows are loaded in fixed chunks through a
+/// small LRU cache. Unconfirmed transactions, which still change, stay in memory.
+pub struct TxHistory {
+    path: PathBuf,
+    file: File,
+    offsets: Vec<u64>,
+    end: u64,
+    page_size: usize,
+    max_cached_pages: usize,
+    cache: HashMap<usize, Vec<HistoryEntry>>,
+    recently_used: VecDeque<usize>,
+    pending: Vec<HistoryEntry>,
+    conflicted: Vec<HistoryEntry>,
+    confirmed_net: i64,
+    index: SearchIndex,
+}
+
+impl TxHistory {
+    /// Opens (or creates) the history file, indexing row offsets and the
+    /// confirmed balance without keeping the rows in memory.
+    pub fn open(path: &Path, page_size: usize, max_cached_pages: usize) -> Result<Self, String> {
+        if page_size == 0 || max_cached_pages == 0 {
+            return Err("Page size and cache size must be positive".to_string());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open history: {}", e))?;
+
+        let mut offsets = Vec::new();
+        let mut end = 0u64;
+        let mut confirmed_net = 0i64;
+        let mut index = SearchIndex::default();
+        let mut torn = false;
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader
+                .read_line(&mut line)
+                .map_err(|e| format!("Failed to read history: {}", e))?;
+            if read == 0 {
+                break;
+            }
+            if !line.ends_with('\n') {
+                log::warn!("Dropping truncated history row at byte {}", end);
+                torn = true;
+                break;
+            }
+            let entry: HistoryEntry = serde_json::from_str(&line)
+                .map_err(|e| format!("Corrupt history row at byte {}: {}", end, e))?;
+            confirmed_net += entry.net_amount;
+            index.insert(&entry);
+            offsets.push(end);
+            end += read as u64;
+        }
+        // Appending after a torn row would glue the next row onto it
+        if torn {
+            file.set_len(end)
+                .map_err(|e| format!("Failed to truncate history: {}", e))?;
+        }
+
+        Ok(TxHistory {
+            path: path.to_path_buf(),
+            file,
+            offsets,
+            end,
+            page_size,
+            max_cached_pages,
+            cache: HashMap::new(),
+            recently_used: VecDeque::new(),
+            pending: Vec::new(),
+            conflicted: Vec::new(),
+            confirmed_net,
+            index,
+        })
+    }
+
+    /// Number of confirmed rows.
+    pub fn confirmed_len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Number of pages [`page`](Self::page) returns rows for.
+    pub fn page_count(&self) -> usize {
+        self.offsets.len().div_ceil(self.page_size)
+    }
+
+    pub fn pending(&self) -> &[HistoryEntry] {
+        &self.pending
+    }
+
+    /// Pending transactions that lost to a conflicting one.
+    pub fn conflicted(&self) -> &[HistoryEntry] {
+        &self.conflicted
+    }
+
+    /// Pending transactions spending any of the same coins as `entry`.
+    pub fn conflicts(&self, entry: &HistoryEntry) -> Vec<&HistoryEntry> {
+        self.pending
+            .iter()
+            .filter(|e| e.conflicts_with(entry))
+            .collect()
+    }
+
+    /// Adds or replaces an unconfirmed transaction. Pending transactions it
+    /// conflicts with are marked replaced, as the mempool only keeps the newest.
+    pub fn add_pending(&mut self, entry: HistoryEntry) {
+        self.pending.retain(|e| e.txid != entry.txid);
+        self.mark_conflicts(&entry);
+        self.pending.push(entry);
+    }
+
+    // Moves pending entries spending the same coins as `winner` to `conflicted`
+    fn mark_conflicts(&mut self, winner: &HistoryEntry) {
+        let (losers, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|e| e.conflicts_with(winner));
+        self.pending = pending;
+        for mut loser in losers {
+            log::info!("{} was replaced by {}", loser.txid, winner.txid);
+            loser.replaced_by = Some(winner.txid.clone());
+            self.conflicted.push(loser);
+        }
+    }
+
+    /// Net wallet balance from history: confirmed rows plus pending
+    /// transactions, ignoring conflicted ones.
+    pub fn balance(&self) -> i64 {
+        self.confirmed_net + self.pending.iter().map(|e| e.net_amount).sum::<i64>()
+    }
+
+    /// Moves a pending transaction to the confirmed history. Returns whether it was pending.
+    pub fn confirm(&mut self, txid: &str, height: u32) -> Result<bool, String> {
+        let Some(index) = self.pending.iter().position(|e| e.txid == txid) else {
+            return Ok(false);
+        };
+        let mut entry = self.pending.remove(index);
+        entry.height = Some(height);
+        self.append_confirmed(&entry)?;
+        Ok(true)
+    }
+
+    /// Appends a confirmed row, e.g. while restoring from a backend. Pending
+    /// transactions conflicting with it can never confirm and are marked replaced.
+    pub fn append_confirmed(&mut self, entry: &HistoryEntry) -> Result<(), String> {
+        self.mark_conflicts(entry);
+        let json = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize history row: {}", e))?;
+        writeln!(self.file, "{}", json).map_err(|e| format!("Failed to write history: {}", e))?;
+
+        // The last chunk is the only one that can change
+        let chunk = self.offsets.len() / self.page_size;
+        self.offsets.push(self.end);
+        self.end += json.len() as u64 + 1;
+        self.confirmed_net += entry.net_amount;
+        self.index.insert(entry);
+        self.cache.remove(&chunk);
+        Ok(())
+    }
+
+    /// Confirmed rows newest first, `page_size` per page.
+    pub fn page(&mut self, page: usize) -> Result<Vec<HistoryEntry>, String> {
+        let newest = self.offsets.len().saturating_sub(page * self.page_size);
+        let oldest = newest.saturating_sub(self.page_size);
+        let mut rows = Vec::with_capacity(newest - oldest);
+        for row in (oldest..newest).rev() {
+            let (chunk, slot) = (row / self.page_size, row % self.page_size);
+            rows.push(self.chunk(chunk)?[slot].clone());
+        }
+        Ok(rows)
+    }
+
+    fn chunk(&mut self, chunk: usize) -> Result<&Vec<HistoryEntry>, String> {
+        self.recently_used.retain(|c| *c != chunk);
+        self.recently_used.push_back(chunk);
+
+        if !self.cache.contains_key(&chunk) {
+            while self.cache.len() >= self.max_cached_pages {
+                let Some(evicted) = self.recently_used.pop_front() else {
+                    break;
+                };
+                self.cache.remove(&evicted);
+            }
+
+            let first = chunk * self.page_size;
+            let count = self.page_size.min(self.offsets.len() - first);
+            let mut file =
+                File::open(&self.path).map_err(|e| format!("Failed to open history: {}", e))?;
+            file.seek(SeekFrom::Start(self.offsets[first]))
+                .map_err(|e| format!("Failed to seek history: {}", e))?;
+            let rows = BufReader::new(file)
+                .lines()
+                .take(count)
+                .map(|line| {
+                    let line = line.map_err(|e| format!("Failed to read history: {}", e))?;
+                    serde_json::from_str(&line).map_err(|e| format!("Corrupt history row: {}", e))
+                })
+                .collect::<Result<Vec<HistoryEntry>, String>>()?;
+            self.cache.insert(chunk, rows);
+        }
+        Ok(&self.cache[&chunk])
+    }
+
+    /// Net amounts of transactions dated in `from..to`, confirmed or pending,
+    /// summed per label subtree.
+    pub fn label_report(
+        &mut self,
+        metadata: &WalletMetadata,
+        from: u64,
+        to: u64,
+    ) -> Result<BTreeMap<String, LabelTotals>, String> {
+        // Only the txid and amount of matching rows are kept, page by page
+        let in_range = |e: &HistoryEntry| (from..to).contains(&e.timestamp);
+        let mut amounts: Vec<(String, i64)> = self
+            .pending
+            .iter()
+            .filter(|e| in_range(e))
+            .map(|e| (e.txid.clone(), e.net_amount))
+            .collect();
+        for page in 0..self.page_count() {
+            amounts.extend(
+                self.page(page)?
+                    .into_iter()
+                    .filter(in_range)
+                    .map(|e| (e.txid, e.net_amount)),
+            );
+        }
+        Ok(labels::aggregate(
+            metadata,
+            amounts
+                .iter()
+                .map(|(txid, amount)| (txid.as_str(), *amount)),
+        ))
+    }
+
+    /// Confirmed rows currently held in memory.
+    pub fn cached_rows(&self) -> usize {
+        self.cache.values().map(Vec::len).sum()
+    }
+
+    /// Rough heap and inline size of everything held in memory.
+    pub fn approx_bytes(&self) -> usize {
+        self.offsets.capacity() * std::mem::size_of::<u64>()
+            + self.index.approx_bytes()
+            + self
+                .cache
+                .values()
+                .flatten()
+                .chain(&self.pending)
+                .map(HistoryEntry::approx_bytes)
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(i: u32) -> HistoryEntry {
+        HistoryEntry {
+            txid: format!("{:064x}", i),
+            height: None,
+            timestamp: 1_000 + i as u64,
+            net_amount: i as i64,
+            fee: None,
+            spent: Vec::new(),
+            replaced_by: None,
+            addresses: Vec::new(),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_paged_lazy_history() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_history_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut history = TxHistory::open(&path, 100, 1).unwrap();
+        for i in 0..250 {
+            history.add_pending(entry(i));
+            assert!(history.confirm(&entry(i).txid, i + 1).unwrap());
+        }
+        assert!(history.pending().is_empty());
+
+        let mut reopened = TxHistory::open(&path, 100, 1).unwrap();
+        assert_eq!(reopened.confirmed_len(), 250);
+        assert_eq!(reopened.cached_rows(), 0);
+
+        let newest = reopened.page(0).unwrap();
+        assert_eq!(newest.len(), 100);
+        assert_eq!(newest[0].height, Some(250));
+        let oldest = reopened.page(2).unwrap();
+        assert_eq!(oldest.len(), 50);
+        assert_eq!(oldest[49].net_amount, 0);
+        assert!(reopened.cached_rows() <= 100);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_conflicts_excluded_from_balance() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_conflicts_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut history = TxHistory::open(&path, 10, 1).unwrap();
+
+        let coin = OutPoint::new("aa", 0);
+        let mut send = entry(1);
+        send.net_amount = -30_000;
+        send.spent = vec![coin.clone()];
+        let mut double_spend = entry(2);
+        double_spend.net_amount = -80_000;
+        double_spend.spent = vec![coin];
+        history.add_pending(send.clone());
+        history.add_pending(entry(3));
+        assert_eq!(history.conflicts(&double_spend).len(), 1);
+
+        // The double spend confirms first; the original send can no longer
+        history
+            .append_confirmed(&HistoryEntry {
+                height: Some(10),
+                ..double_spend.clone()
+            })
+            .unwrap();
+        assert_eq!(history.conflicted()[0].txid, send.txid);
+        assert_eq!(
+            history.conflicted()[0].replaced_by.as_deref(),
+            Some(double_spend.txid.as_str())
+        );
+        assert_eq!(history.balance(), -80_000 + 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_page_across_chunks_with_eviction() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_chunks_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut history = TxHistory::open(&path, 4, 1).unwrap();
+        for i in 0..10 {
+            history.append_confirmed(&entry(i)).unwrap();
+        }
+
+        // Pages count back from the newest row, so with ten rows each spans
+        // two chunks, and only one chunk is cached at a time
+        let mut reopened = TxHistory::open(&path, 4, 1).unwrap();
+        let amounts: Vec<i64> = (0..reopened.page_count())
+            .flat_map(|page| reopened.page(page).unwrap())
+            .map(|e| e.net_amount)
+            .collect();
+        assert_eq!(amounts, (0..10).rev().collect::<Vec<i64>>());
+        assert!(reopened.cached_rows() <= 4);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_append_after_torn_row() {
+        let path = std::env::temp_dir().join(format!("bitvault_torn_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut history = TxHistory::open(&path, 10, 1).unwrap();
+        history.append_confirmed(&entry(1)).unwrap();
+        drop(history);
+
+        // A crash mid-write leaves half a row
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"txid\":\"ab").unwrap();
+        drop(file);
+
+        let mut history = TxHistory::open(&path, 10, 1).unwrap();
+        assert_eq!(history.confirmed_len(), 1);
+        history.append_confirmed(&entry(2)).unwrap();
+        drop(history);
+
+        let mut reopened = TxHistory::open(&path, 10, 1).unwrap();
+        assert_eq!(reopened.confirmed_len(), 2);
+        assert_eq!(reopened.page(0).unwrap().len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
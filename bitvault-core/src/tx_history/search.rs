@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use super::{HistoryEntry, TxHistory};
+use crate::metadata::labels;
+use crate::metadata::WalletMetadata;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConfirmationState {
+    Pending,
+    Confirmed,
+    /// Replaced by a conflicting transaction.
+    Conflicted,
+}
+
+/// Filters combined with AND; unset fields match everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxQuery {
+    /// Bounds on the absolute net amount, in satoshis.
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    pub direction: Option<Direction>,
+    /// Transactions labeled with this category or one below it, directly or
+    /// through one of their addresses.
+    pub label: Option<String>,
+    pub address: Option<String>,
+    pub confirmation: Option<ConfirmationState>,
+    /// Words that must all appear in the memo, case-insensitively.
+    pub text: Option<String>,
+    /// Timestamp range, `from` inclusive and `to` exclusive.
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// Most results to return, newest first.
+    pub limit: Option<usize>,
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+// Txids are hex hashes; their first 8 bytes are a compact, near-unique key
+fn txid_key(txid: &str) -> u64 {
+    u64::from_str_radix(txid.get(..16).unwrap_or(txid), 16).unwrap_or_else(|_| {
+        txid.bytes().fold(0u64, |key, b| {
+            key.wrapping_mul(31).wrapping_add(u64::from(b))
+        })
+    })
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RowSummary {
+    timestamp: u64,
+    net_amount: i64,
+}
+
+/// In-memory index over the confirmed rows, so queries only load the rows
+/// that can match from disk.
+#[derive(Debug, Default)]
+pub(super) struct SearchIndex {
+    rows: Vec<RowSummary>,
+    txids: HashMap<u64, Vec<u32>>,
+    addresses: HashMap<String, Vec<u32>>,
+    words: HashMap<String, Vec<u32>>,
+}
+
+impl SearchIndex {
+    pub(super) fn insert(&mut self, entry: &HistoryEntry) {
+        let row = self.rows.len() as u32;
+        self.rows.push(RowSummary {
+            timestamp: entry.timestamp,
+            net_amount: entry.net_amount,
+        });
+        self.txids
+            .entry(txid_key(&entry.txid))
+            .or_default()
+            .push(row);
+        for address in &entry.addresses {
+            self.addresses.entry(address.clone()).or_default().push(row);
+        }
+        let words: BTreeSet<String> = entry.memo.iter().flat_map(|m| tokenize(m)).collect();
+        for word in words {
+            self.words.entry(word).or_default().push(row);
+        }
+    }
+
+    pub(super) fn approx_bytes(&self) -> usize {
+        self.rows.capacity() * std::mem::size_of::<RowSummary>()
+            + self.txids.len() * (8 + std::mem::size_of::<Vec<u32>>() + 4)
+            + self
+                .addresses
+                .iter()
+                .chain(&self.words)
+                .map(|(key, rows)| key.len() + rows.capacity() * 4)
+                .sum::<usize>()
+    }
+
+    // Rows the indexed filters allow, or `None` when no indexed filter is set
+    fn candidates(&self, query: &TxQuery, metadata: &WalletMetadata) -> Option<BTreeSet<u32>> {
+        let mut sets: Vec<BTreeSet<u32>> = Vec::new();
+        let postings = |map: &HashMap<String, Vec<u32>>, key: &str| -> BTreeSet<u32> {
+            map.get(key).into_iter().flatten().copied().collect()
+        };
+
+        if let Some(address) = &query.address {
+            sets.push(postings(&self.addresses, address));
+        }
+        if let Some(text) = &query.text {
+            sets.extend(tokenize(text).map(|word| postings(&self.words, &word)));
+        }
+        if let Some(category) = &query.label {
+            let mut rows = BTreeSet::new();
+            for reference in metadata.labeled_within(category) {
+                rows.extend(postings(&self.addresses, reference));
+                rows.extend(self.txids.get(&txid_key(reference)).into_iter().flatten());
+            }
+            sets.push(rows);
+        }
+
+        let mut sets = sets.into_iter();
+        let first = sets.next()?;
+        Some(sets.fold(first, |acc, set| &acc & &set))
+    }
+}
+
+fn amount_matches(query: &TxQuery, timestamp: u64, net_amount: i64) -> bool {
+    let amount = net_amount.unsigned_abs();
+    query.min_amount.is_none_or(|min| amount >= min)
+        && query.max_amount.is_none_or(|max| amount <= max)
+        && query.from.is_none_or(|from| timestamp >= from)
+        && query.to.is_none_or(|to| timestamp < to)
+        && match query.direction {
+            Some(Direction::Incoming) => net_amount > 0,
+            Some(Direction::Outgoing) => net_amount < 0,
+            None => true,
+        }
+}
+
+/// Whether `entry` satisfies every filter of `query` other than confirmation state.
+pub fn matches(entry: &HistoryEntry, query: &TxQuery, metadata: &WalletMetadata) -> bool {
+    let labeled = |category: &str| {
+        std::iter::once(&entry.txid)
+            .chain(&entry.addresses)
+            .filter_map(|reference| metadata.label(reference))
+            .any(|label| labels::is_within(label, category))
+    };
+    let memo_words: BTreeSet<String> = entry.memo.iter().flat_map(|m| tokenize(m)).collect();
+
+    amount_matches(query, entry.timestamp, entry.net_amount)
+        && query
+            .address
+            .as_ref()
+            .is_none_or(|address| entry.addresses.contains(address))
+        && query.label.as_deref().is_none_or(labeled)
+        && query
+            .text
+            .as_deref()
+            .is_none_or(|text| tokenize(text).all(|word| memo_words.contains(&word)))
+}
+
+impl TxHistory {
+    /// Transactions matching `query`, newest first.
+    ///
+    /// Address, memo and label filters are answered from the in-memory index
+    /// and amount and date filters from per-row summaries, so only rows that
+    /// match are read from disk.
+    pub fn search(
+        &mut self,
+        query: &TxQuery,
+        metadata: &WalletMetadata,
+    ) -> Result<Vec<HistoryEntry>, String> {
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let wants = |state| query.confirmation.is_none_or(|s| s == state);
+        let mut results: Vec<HistoryEntry> = Vec::new();
+
+        for (state, entries) in [
+            (ConfirmationState::Pending, &self.pending),
+            (ConfirmationState::Conflicted, &self.conflicted),
+        ] {
+            if wants(state) {
+                results.extend(
+                    entries
+                        .iter()
+                        .filter(|e| matches(e, query, metadata))
+                        .cloned(),
+                );
+            }
+        }
+        results.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+        results.truncate(limit);
+
+        if wants(ConfirmationState::Confirmed) {
+            let rows: Vec<u32> = match self.index.candidates(query, metadata) {
+                Some(rows) => rows.into_iter().collect(),
+                None => (0..self.index.rows.len() as u32).collect(),
+            };
+            let page_size = self.page_size;
+            let mut confirmed = Vec::new();
+            for row in rows.into_iter().rev() {
+                let summary = self.index.rows[row as usize];
+                if !amount_matches(query, summary.timestamp, summary.net_amount) {
+                    continue;
+                }
+                let row = row as usize;
+                let entry = self.chunk(row / page_size)?[row % page_size].clone();
+                // Index keys can collide, so the loaded row is checked in full
+                if matches(&entry, query, metadata) {
+                    confirmed.push(entry);
+                    if confirmed.len() >= limit {
+                        break;
+                    }
+                }
+            }
+            results.extend(confirmed);
+            results.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+            results.truncate(limit);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compound_queries() {
+        let path =
+            std::env::temp_dir().join(format!("bitvault_search_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut history = TxHistory::open(&path, 16, 2).unwrap();
+
+        for i in 0..200u32 {
+            let incoming = i % 2 == 0;
+            history
+                .append_confirmed(&HistoryEntry {
+                    txid: format!("{:064x}", i + 1),
+                    height: Some(i + 1),
+                    timestamp: 1_000 + u64::from(i),
+                    net_amount: if incoming { 10_000 } else { -5_000 } * i64::from(i + 1),
+                    fee: None,
+                    spent: Vec::new(),
+                    replaced_by: None,
+                    addresses: vec![format!("tb1qaddr{}", i % 10)],
+                    memo: (i % 50 == 0).then(|| format!("Rent payment #{}", i)),
+                })
+                .unwrap();
+        }
+        let mut metadata = WalletMetadata::default();
+        metadata.set_label("tb1qaddr3", "Income/Salary").unwrap();
+
+        // Reopening rebuilds the index from disk
+        let mut history = TxHistory::open(&path, 16, 2).unwrap();
+        let rent = TxQuery {
+            text: Some("RENT".to_string()),
+            ..Default::default()
+        };
+        let found = history.search(&rent, &metadata).unwrap();
+        assert_eq!(found.len(), 4);
+        assert!(found[0].timestamp > found[1].timestamp);
+
+        let salary = TxQuery {
+            label: Some("Income".to_string()),
+            direction: Some(Direction::Outgoing),
+            min_amount: Some(500_000),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let found = history.search(&salary, &metadata).unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(found
+            .iter()
+            .all(|e| e.addresses[0] == "tb1qaddr3" && e.net_amount <= -500_000));
+
+        let pending = TxQuery {
+            confirmation: Some(ConfirmationState::Pending),
+            ..Default::default()
+        };
+        assert!(history.search(&pending, &metadata).unwrap().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
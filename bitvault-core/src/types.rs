@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Bitcoin network a wallet operates on.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+pub enum Network {
+    #[default]
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+/// Reference to a specific output of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String, // hex encoded
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn new(txid: &str, vout: u32) -> Self {
+        OutPoint {
+            txid: txid.to_string(),
+            vout,
+        }
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
+/// Sequence number that disables both RBF signaling and relative locktime.
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// Highest sequence number that still enforces `lock_time`, without signaling RBF.
+pub const SEQUENCE_ENABLE_LOCKTIME: u32 = 0xffff_fffe;
+
+/// Sequence that signals RBF without enabling a relative locktime.
+pub const SEQUENCE_RBF: u32 = 0xffff_fffd;
+
+/// `lock_time` values below this are block heights, at or above it unix timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// Txid used by the single input of coinbase transactions.
+pub const NULL_TXID: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    pub sequence: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxOut {
+    pub address: String,
+    pub value: u64, // satoshis
+}
+
+/// Simplified transaction model shared by the backends and wallet logic.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub lock_time: u32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+}
+
+impl Transaction {
+    /// Hex encoded identifier committing to every field of the transaction.
+    pub fn txid(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.lock_time.to_le_bytes());
+        for input in &self.inputs {
+            hasher.update(input.previous_output.txid.as_bytes());
+            hasher.update(input.previous_output.vout.to_le_bytes());
+            hasher.update(input.sequence.to_le_bytes());
+        }
+        for output in &self.outputs {
+            hasher.update((output.address.len() as u64).to_le_bytes());
+            hasher.update(output.address.as_bytes());
+            hasher.update(output.value.to_le_bytes());
+        }
+        hex::encode(Sha256::digest(hasher.finalize()))
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].previous_output.txid == NULL_TXID
+    }
+
+    pub fn output_value(&self) -> u64 {
+        self.outputs.iter().map(|o| o.value).sum()
+    }
+}
+
+/// An unspent output owned by the wallet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub address: String,
+    pub value: u64,
+    pub confirmations: u32,
+    pub is_coinbase: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txid_commits_to_contents() {
+        let tx = Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::new(NULL_TXID, 0),
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOut {
+                address: "bcrt1qexample".to_string(),
+                value: 5_000,
+            }],
+        };
+        let mut other = tx.clone();
+        other.outputs[0].value += 1;
+
+        assert!(tx.is_coinbase());
+        assert_eq!(tx.txid().len(), 64);
+        assert_ne!(tx.txid(), other.txid());
+    }
+}
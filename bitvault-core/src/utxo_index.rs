@@ -0,0 +1,156 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::{OutPoint, Utxo};
+
+// ~48 bytes per coin instead of a full `Utxo` with its two heap strings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CompactUtxo {
+    value: u64,
+    /// Confirmation height, 0 while unconfirmed.
+    height: u32,
+    address_id: u32,
+    is_coinbase: bool,
+}
+
+/// Memory-lean index of the wallet's coins for large wallets.
+///
+/// Txids are stored as 32 bytes and addresses are interned, so 100k coins
+/// take a few megabytes. Heights are stored instead of confirmation counts,
+/// so nothing needs rewriting when a block arrives.
+#[derive(Clone, Debug, Default)]
+pub struct UtxoIndex {
+    coins: BTreeMap<([u8; 32], u32), CompactUtxo>,
+    addresses: Vec<String>,
+    address_ids: HashMap<String, u32>,
+}
+
+fn txid_bytes(txid: &str) -> Result<[u8; 32], String> {
+    hex::decode(txid)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Invalid txid: {}", txid))
+}
+
+impl UtxoIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.coins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coins.is_empty()
+    }
+
+    /// Adds or updates a coin seen when the chain tip was at `tip_height`.
+    pub fn insert(&mut self, utxo: &Utxo, tip_height: u32) -> Result<(), String> {
+        let key = (txid_bytes(&utxo.outpoint.txid)?, utxo.outpoint.vout);
+        let address_id = match self.address_ids.get(&utxo.address) {
+            Some(id) => *id,
+            None => {
+                let id = self.addresses.len() as u32;
+                self.addresses.push(utxo.address.clone());
+                self.address_ids.insert(utxo.address.clone(), id);
+                id
+            }
+        };
+        let height = match utxo.confirmations {
+            0 => 0,
+            confirmations => (tip_height + 1).saturating_sub(confirmations),
+        };
+        self.coins.insert(
+            key,
+            CompactUtxo {
+                value: utxo.value,
+                height,
+                address_id,
+                is_coinbase: utxo.is_coinbase,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove(&mut self, outpoint: &OutPoint) -> bool {
+        txid_bytes(&outpoint.txid)
+            .map(|txid| self.coins.remove(&(txid, outpoint.vout)).is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn total_value(&self) -> u64 {
+        self.coins.values().map(|c| c.value).sum()
+    }
+
+    /// Materializes coins with at least `min_confirmations` at `tip_height`,
+    /// for coin selection.
+    pub fn utxos(&self, tip_height: u32, min_confirmations: u32) -> Vec<Utxo> {
+        self.coins
+            .iter()
+            .filter_map(|((txid, vout), coin)| {
+                let confirmations = match coin.height {
+                    0 => 0,
+                    height => (tip_height + 1).saturating_sub(height),
+                };
+                (confirmations >= min_confirmations).then(|| Utxo {
+                    outpoint: OutPoint::new(&hex::encode(txid), *vout),
+                    address: self.addresses[coin.address_id as usize].clone(),
+                    value: coin.value,
+                    confirmations,
+                    is_coinbase: coin.is_coinbase,
+                })
+            })
+            .collect()
+    }
+
+    /// Rough memory footprint in bytes.
+    pub fn approx_bytes(&self) -> usize {
+        let per_coin = std::mem::size_of::<([u8; 32], u32)>() + std::mem::size_of::<CompactUtxo>();
+        let addresses: usize = self
+            .addresses
+            .iter()
+            .map(|a| 2 * (std::mem::size_of::<String>() + a.len()) + std::mem::size_of::<u32>())
+            .sum();
+        self.coins.len() * per_coin + addresses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_confirmations() {
+        let utxo = Utxo {
+            outpoint: OutPoint::new(&"ab".repeat(32), 1),
+            address: "bcrt1qours".to_string(),
+            value: 25_000,
+            confirmations: 3,
+            is_coinbase: false,
+        };
+        let mut index = UtxoIndex::new();
+        index.insert(&utxo, 100).unwrap();
+        assert!(index
+            .insert(
+                &Utxo {
+                    outpoint: OutPoint::new("aa", 0),
+                    ..utxo.clone()
+                },
+                100
+            )
+            .is_err());
+
+        // Two blocks later the same coin has two more confirmations
+        let coins = index.utxos(102, 1);
+        assert_eq!(
+            coins,
+            vec![Utxo {
+                confirmations: 5,
+                ..utxo.clone()
+            }]
+        );
+        assert!(index.utxos(102, 6).is_empty());
+        assert!(index.remove(&utxo.outpoint));
+        assert!(index.is_empty());
+    }
+}
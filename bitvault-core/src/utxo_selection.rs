@@ -0,0 +1,600 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{OutPoint, Utxo};
+
+pub mod change;
+pub mod constraints;
+pub mod denominations;
+pub mod diagnostics;
+pub mod dust;
+pub mod privacy;
+
+use change::{ChangeOutcome, ChangePolicy};
+use constraints::SelectionConstraints;
+use diagnostics::{FundingSuggestion, FundsBreakdown};
+use dust::DustPolicy;
+
+/// Virtual size of a P2WPKH input.
+pub const INPUT_VBYTES: u64 = 68;
+/// Virtual size of a P2WPKH output.
+pub const OUTPUT_VBYTES: u64 = 31;
+/// Version, locktime, counts and segwit marker.
+pub const TX_OVERHEAD_VBYTES: u64 = 11;
+/// Outputs below this value are not relayed by default policy, whatever
+/// their script type. See [`DustPolicy`] for the limits actually applied.
+pub const DUST_THRESHOLD: u64 = 546;
+/// Blocks before a coinbase output can be spent.
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// Order in which candidate UTXOs are considered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SelectionStrategy {
+    /// Fewest inputs, lowest fee now.
+    LargestFirst,
+    /// Consolidates small coins.
+    SmallestFirst,
+    /// Spends the most confirmed coins first.
+    OldestFirst,
+    /// Random order, avoiding a predictable selection pattern.
+    Random,
+}
+
+impl SelectionStrategy {
+    /// Every strategy, used by the invariant suite so new strategies are covered.
+    pub fn all() -> Vec<SelectionStrategy> {
+        // The exhaustive match fails to compile when a variant is added but not listed
+        let listed = |s: SelectionStrategy| match s {
+            SelectionStrategy::LargestFirst
+            | SelectionStrategy::SmallestFirst
+            | SelectionStrategy::OldestFirst
+            | SelectionStrategy::Random => s,
+        };
+        vec![
+            listed(SelectionStrategy::LargestFirst),
+            listed(SelectionStrategy::SmallestFirst),
+            listed(SelectionStrategy::OldestFirst),
+            listed(SelectionStrategy::Random),
+        ]
+    }
+}
+
+/// What a selection has to pay for and which coins it may use.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionParams {
+    /// Total amount sent to recipients, in satoshis.
+    pub target: u64,
+    /// Fee rate in sat/vB.
+    pub fee_rate: u64,
+    pub recipient_outputs: usize,
+    pub frozen: HashSet<OutPoint>,
+    pub min_confirmations: u32,
+    /// Confirmations particular coins need, e.g. by who sent them; raises
+    /// the other requirements for that coin when higher.
+    pub coin_confirmations: HashMap<OutPoint, u32>,
+    /// Determinism mode: when set, identical inputs always produce identical
+    /// selections regardless of candidate order, and `Random` shuffles with an RNG
+    /// seeded from this value. Needed for multi-device coordination and audits.
+    pub deterministic_seed: Option<u64>,
+    pub change_policy: ChangePolicy,
+    /// Smallest outputs, change included, the selection may create.
+    pub dust: DustPolicy,
+    /// Coins are only used when their effective value (value minus spend cost)
+    /// is positive and at least this multiple of the spend cost. Raise it in high
+    /// fee environments to keep small coins for later.
+    pub min_effective_value_multiple: u64,
+    pub constraints: SelectionConstraints,
+}
+
+impl SelectionParams {
+    pub fn new(target: u64, fee_rate: u64) -> Self {
+        SelectionParams {
+            target,
+            fee_rate,
+            recipient_outputs: 1,
+            frozen: HashSet::new(),
+            min_confirmations: 0,
+            coin_confirmations: HashMap::new(),
+            deterministic_seed: None,
+            change_policy: ChangePolicy::default(),
+            dust: DustPolicy::default(),
+            min_effective_value_multiple: 0,
+            constraints: SelectionConstraints::default(),
+        }
+    }
+
+    /// Confirmations every input needs, taking the constraints into account.
+    pub fn required_confirmations(&self) -> u32 {
+        self.min_confirmations
+            .max(self.constraints.min_input_confirmations.unwrap_or(0))
+    }
+
+    /// Confirmations `utxo` needs, before coinbase maturity.
+    pub fn confirmations_for(&self, utxo: &Utxo) -> u32 {
+        self.required_confirmations().max(
+            self.coin_confirmations
+                .get(&utxo.outpoint)
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.deterministic_seed = Some(seed);
+        self
+    }
+}
+
+/// Why a UTXO was not considered for selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExclusionReason {
+    Frozen,
+    /// Fewer confirmations than required.
+    Unconfirmed {
+        confirmations: u32,
+    },
+    /// Coinbase output younger than [`COINBASE_MATURITY`].
+    Immature {
+        confirmations: u32,
+    },
+    /// Effective value non-positive or below the configured multiple of its spend cost.
+    Uneconomical,
+    /// On an address excluded by the selection constraints.
+    AddressExcluded,
+}
+
+/// What happened to the amount left over after paying target and fee.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeDecision {
+    /// Inputs matched target plus fee exactly.
+    NoChange,
+    /// A change output of this value was added.
+    Created(u64),
+    /// The remainder was below dust and was added to the fee instead.
+    DroppedToFee(u64),
+}
+
+/// How a selection was made, for audit and display.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SelectionMetadata {
+    pub strategy: SelectionStrategy,
+    /// Number of UTXOs passed in.
+    pub candidates_considered: usize,
+    pub excluded: Vec<(OutPoint, ExclusionReason)>,
+    /// Fee divided by the estimated size, in sat/vB.
+    pub effective_fee_rate: f64,
+    pub change_decision: ChangeDecision,
+    /// Selection plugin that ordered the candidates, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SelectionResult {
+    Success {
+        selected: Vec<Utxo>,
+        fee: u64,
+        change: u64,
+        metadata: SelectionMetadata,
+    },
+    InsufficientFunds {
+        available: u64,
+        required: u64,
+        breakdown: FundsBreakdown,
+        suggestions: Vec<FundingSuggestion>,
+    },
+    /// The constraints are contradictory or can't be met with these coins.
+    ConstraintViolation { reason: String },
+}
+
+pub fn estimate_vsize(inputs: usize, outputs: usize) -> u64 {
+    TX_OVERHEAD_VBYTES + inputs as u64 * INPUT_VBYTES + outputs as u64 * OUTPUT_VBYTES
+}
+
+/// Whether a UTXO may be used at all: not frozen, confirmed enough and mature.
+pub fn is_spendable(utxo: &Utxo, params: &SelectionParams) -> bool {
+    !params.frozen.contains(&utxo.outpoint)
+        && !params.constraints.excludes(utxo)
+        && utxo.confirmations >= params.confirmations_for(utxo)
+        && (!utxo.is_coinbase || utxo.confirmations >= COINBASE_MATURITY)
+}
+
+/// Value a coin adds to a transaction at `fee_rate` once its input is paid for.
+pub fn effective_value(utxo: &Utxo, fee_rate: u64) -> i64 {
+    utxo.value as i64 - (INPUT_VBYTES * fee_rate) as i64
+}
+
+fn is_economical(utxo: &Utxo, params: &SelectionParams) -> bool {
+    let spend_cost = INPUT_VBYTES * params.fee_rate;
+    let effective = effective_value(utxo, params.fee_rate);
+    effective > 0
+        && effective as u64 >= spend_cost.saturating_mul(params.min_effective_value_multiple)
+}
+
+/// Why `utxo` can't be used for this selection, if it can't.
+pub fn exclusion_reason(utxo: &Utxo, params: &SelectionParams) -> Option<ExclusionReason> {
+    if params.frozen.contains(&utxo.outpoint) {
+        Some(ExclusionReason::Frozen)
+    } else if utxo.is_coinbase && utxo.confirmations < COINBASE_MATURITY {
+        Some(ExclusionReason::Immature {
+            confirmations: utxo.confirmations,
+        })
+    } else if params.constraints.excludes(utxo) {
+        Some(ExclusionReason::AddressExcluded)
+    } else if utxo.confirmations < params.confirmations_for(utxo) {
+        Some(ExclusionReason::Unconfirmed {
+            confirmations: utxo.confirmations,
+        })
+    } else if !params.constraints.must_include.contains(&utxo.outpoint)
+        && !is_economical(utxo, params)
+    {
+        Some(ExclusionReason::Uneconomical)
+    } else {
+        None
+    }
+}
+
+/// Selects inputs covering `params.target` plus fee using the given strategy.
+///
+/// Whether leftover value becomes change, goes to the fee or pulls in another
+/// input is decided by `params.change_policy`. `params.constraints` are
+/// validated first and honored whatever the strategy: required coins are
+/// spent before any others.
+pub fn select_utxos(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+    strategy: SelectionStrategy,
+) -> SelectionResult {
+    select_in_order(utxos, params, strategy, |candidates| {
+        order_candidates(candidates, strategy, params.deterministic_seed)
+    })
+}
+
+// Shared by the built-in strategies and externally supplied orderings, so
+// constraints, exclusions and the change policy apply identically to both
+pub(crate) fn select_in_order(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+    strategy: SelectionStrategy,
+    order: impl FnOnce(&mut Vec<&Utxo>),
+) -> SelectionResult {
+    let constraints = &params.constraints;
+    if let Err(reason) = constraints.validate(utxos, params) {
+        return SelectionResult::ConstraintViolation { reason };
+    }
+
+    let mut candidates = Vec::new();
+    let mut excluded = Vec::new();
+    for utxo in utxos {
+        match exclusion_reason(utxo, params) {
+            Some(reason) => excluded.push((utxo.outpoint.clone(), reason)),
+            None => candidates.push(utxo),
+        }
+    }
+    order(&mut candidates);
+    // Stable, so the strategy's order is kept within both groups
+    candidates.sort_by_key(|u| !constraints.must_include.contains(&u.outpoint));
+    let forced = constraints.must_include.len();
+
+    let outputs = params.recipient_outputs;
+    let mut selected = Vec::new();
+    let mut total = 0u64;
+
+    for (i, utxo) in candidates.iter().enumerate() {
+        if constraints
+            .max_inputs
+            .is_some_and(|max| selected.len() >= max)
+        {
+            return SelectionResult::ConstraintViolation {
+                reason: format!(
+                    "Target can't be reached with at most {} inputs",
+                    selected.len()
+                ),
+            };
+        }
+        selected.push((*utxo).clone());
+        total += utxo.value;
+
+        let fee_without_change = estimate_vsize(selected.len(), outputs) * params.fee_rate;
+        if selected.len() < forced || total < params.target + fee_without_change {
+            continue;
+        }
+
+        let excess = total - params.target - fee_without_change;
+        let more_inputs = i + 1 < candidates.len()
+            && constraints
+                .max_inputs
+                .is_none_or(|max| selected.len() < max);
+        let (fee, change, change_decision, vsize) =
+            match params
+                .change_policy
+                .decide(&params.dust, excess, params.fee_rate, more_inputs)
+            {
+                ChangeOutcome::AddInput => continue,
+                ChangeOutcome::Create(change) => (
+                    total - params.target - change,
+                    change,
+                    ChangeDecision::Created(change),
+                    estimate_vsize(selected.len(), outputs + 1),
+                ),
+                ChangeOutcome::AddToFee(0) => (
+                    fee_without_change,
+                    0,
+                    ChangeDecision::NoChange,
+                    estimate_vsize(selected.len(), outputs),
+                ),
+                ChangeOutcome::AddToFee(excess) => (
+                    total - params.target,
+                    0,
+                    ChangeDecision::DroppedToFee(excess),
+                    estimate_vsize(selected.len(), outputs),
+                ),
+            };
+
+        if let Some(max_fee) = constraints.max_fee.filter(|max| fee > *max) {
+            return SelectionResult::ConstraintViolation {
+                reason: format!("Fee of {} sats exceeds the maximum of {}", fee, max_fee),
+            };
+        }
+
+        let metadata = SelectionMetadata {
+            strategy,
+            candidates_considered: utxos.len(),
+            excluded,
+            effective_fee_rate: fee as f64 / vsize as f64,
+            change_decision,
+            plugin: None,
+        };
+        return SelectionResult::Success {
+            selected,
+            fee,
+            change,
+            metadata,
+        };
+    }
+
+    let required = params.target + estimate_vsize(candidates.len(), outputs) * params.fee_rate;
+    let (breakdown, suggestions) = diagnostics::diagnose_shortfall(utxos, params, total, required);
+    SelectionResult::InsufficientFunds {
+        available: total,
+        required,
+        breakdown,
+        suggestions,
+    }
+}
+
+pub(crate) fn order_candidates(
+    candidates: &mut [&Utxo],
+    strategy: SelectionStrategy,
+    seed: Option<u64>,
+) {
+    // Start from a canonical order; the sorts below are stable, so ties are
+    // always broken by outpoint and the caller's ordering never matters
+    candidates.sort_by(|a, b| a.outpoint.cmp(&b.outpoint));
+
+    match strategy {
+        SelectionStrategy::LargestFirst => candidates.sort_by_key(|u| std::cmp::Reverse(u.value)),
+        SelectionStrategy::SmallestFirst => candidates.sort_by_key(|u| u.value),
+        SelectionStrategy::OldestFirst => {
+            candidates.sort_by_key(|u| std::cmp::Reverse(u.confirmations))
+        }
+        SelectionStrategy::Random => match seed {
+            Some(seed) => candidates.shuffle(&mut StdRng::seed_from_u64(seed)),
+            None => candidates.shuffle(&mut rand::rng()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn utxo(vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new("aa", vout),
+            address: "bcrt1qtest".to_string(),
+            value,
+            confirmations: 6,
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_change_and_dust() {
+        let utxos = vec![utxo(0, 100_000), utxo(1, 20_000)];
+        let params = SelectionParams::new(50_000, 2);
+
+        match select_utxos(&utxos, &params, SelectionStrategy::LargestFirst) {
+            SelectionResult::Success {
+                selected, change, ..
+            } => {
+                assert_eq!(selected.len(), 1);
+                assert_eq!(change, 100_000 - 50_000 - estimate_vsize(1, 2) * 2);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut params = SelectionParams::new(200_000, 2);
+        params.frozen.insert(OutPoint::new("aa", 1));
+        assert!(matches!(
+            select_utxos(&utxos, &params, SelectionStrategy::SmallestFirst),
+            SelectionResult::InsufficientFunds {
+                available: 100_000,
+                ..
+            }
+        ));
+
+        // Small remainders go to the fee, and the metadata says so
+        let exact = utxo(0, 50_000 + estimate_vsize(1, 1) * 2 + 100);
+        let uneconomical = utxo(2, 100);
+        let params = SelectionParams::new(50_000, 2);
+        match select_utxos(
+            &[exact, uneconomical],
+            &params,
+            SelectionStrategy::LargestFirst,
+        ) {
+            SelectionResult::Success { metadata, .. } => {
+                assert_eq!(metadata.change_decision, ChangeDecision::DroppedToFee(100));
+                assert_eq!(metadata.candidates_considered, 2);
+                assert_eq!(
+                    metadata.excluded,
+                    vec![(OutPoint::new("aa", 2), ExclusionReason::Uneconomical)]
+                );
+                assert!(metadata.effective_fee_rate > 2.0);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let utxos = vec![utxo(0, 100_000), utxo(1, 20_000)];
+        let params = SelectionParams::new(50_000, 2).deterministic(7);
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SelectionParams>(&json).unwrap(),
+            params
+        );
+
+        for target in [50_000, 500_000] {
+            let mut params = params.clone();
+            params.target = target;
+            let result = select_utxos(&utxos, &params, SelectionStrategy::Random);
+            let json = serde_json::to_string(&result).unwrap();
+            assert_eq!(
+                serde_json::from_str::<SelectionResult>(&json).unwrap(),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_insufficient_funds_suggestions() {
+        let mut pending = utxo(1, 40_000);
+        pending.confirmations = 1;
+        let utxos = vec![utxo(0, 60_000), pending, utxo(2, 30_000)];
+        let mut params = SelectionParams::new(80_000, 50);
+        params.min_confirmations = 3;
+        params.frozen.insert(OutPoint::new("aa", 2));
+
+        let SelectionResult::InsufficientFunds {
+            breakdown,
+            suggestions,
+            ..
+        } = select_utxos(&utxos, &params, SelectionStrategy::LargestFirst)
+        else {
+            panic!("selection should fail");
+        };
+        assert_eq!(breakdown.total, 130_000);
+        assert_eq!(breakdown.spendable, 60_000);
+        assert_eq!(breakdown.unconfirmed, 40_000);
+        assert_eq!(breakdown.frozen, 30_000);
+        assert_eq!(
+            suggestions,
+            vec![
+                FundingSuggestion::WaitForConfirmations {
+                    amount: 40_000 - INPUT_VBYTES * 50,
+                    confirmations_needed: 2,
+                },
+                FundingSuggestion::Unfreeze {
+                    outpoints: vec![OutPoint::new("aa", 2)],
+                    amount: 30_000 - INPUT_VBYTES * 50,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_effective_value_filter() {
+        let utxos = vec![utxo(0, 20_000), utxo(1, 2_000), utxo(2, 3_500)];
+        // At 10 sat/vB each input costs 680 sats
+        let mut params = SelectionParams::new(1_000, 10);
+        params.min_effective_value_multiple = 3;
+        assert_eq!(effective_value(&utxos[1], 10), 1_320);
+
+        let SelectionResult::Success { metadata, .. } =
+            select_utxos(&utxos, &params, SelectionStrategy::SmallestFirst)
+        else {
+            panic!("selection should succeed");
+        };
+        assert_eq!(
+            metadata.excluded,
+            vec![(OutPoint::new("aa", 1), ExclusionReason::Uneconomical)]
+        );
+
+        params.target = 30_000;
+        let SelectionResult::InsufficientFunds { breakdown, .. } =
+            select_utxos(&utxos, &params, SelectionStrategy::SmallestFirst)
+        else {
+            panic!("selection should fail");
+        };
+        assert_eq!(breakdown.uneconomical, 2_000);
+        assert_eq!(breakdown.spendable, 23_500);
+    }
+
+    #[test]
+    fn test_constraints_apply_to_every_strategy() {
+        let mut reused = utxo(3, 90_000);
+        reused.address = "bcrt1qreused".to_string();
+        let utxos = vec![utxo(0, 50_000), utxo(1, 40_000), utxo(2, 300), reused];
+        let mut params = SelectionParams::new(60_000, 2);
+        params
+            .constraints
+            .must_include
+            .insert(OutPoint::new("aa", 2));
+        params
+            .constraints
+            .exclude_addresses
+            .insert("bcrt1qreused".to_string());
+
+        for strategy in SelectionStrategy::all() {
+            let SelectionResult::Success { selected, .. } = select_utxos(&utxos, &params, strategy)
+            else {
+                panic!("{:?} should succeed", strategy);
+            };
+            assert_eq!(selected[0].outpoint, OutPoint::new("aa", 2));
+            assert!(selected.iter().all(|u| u.address != "bcrt1qreused"));
+        }
+
+        params.constraints.max_inputs = Some(2);
+        assert!(matches!(
+            select_utxos(&utxos, &params, SelectionStrategy::SmallestFirst),
+            SelectionResult::ConstraintViolation { .. }
+        ));
+        params.constraints.max_inputs = None;
+        params.constraints.max_fee = Some(100);
+        assert!(matches!(
+            select_utxos(&utxos, &params, SelectionStrategy::LargestFirst),
+            SelectionResult::ConstraintViolation { .. }
+        ));
+    }
+
+    #[test]
+    fn test_deterministic_mode() {
+        let utxos: Vec<Utxo> = (0..10).map(|i| utxo(i, 10_000 + (i as u64 % 3))).collect();
+        let mut reversed = utxos.clone();
+        reversed.reverse();
+
+        for strategy in SelectionStrategy::all() {
+            let params = SelectionParams::new(35_000, 3).deterministic(42);
+            assert_eq!(
+                select_utxos(&utxos, &params, strategy),
+                select_utxos(&reversed, &params, strategy),
+                "{:?} is not deterministic",
+                strategy
+            );
+        }
+    }
+
+    #[test]
+    fn test_all_strategies_uphold_invariants() {
+        for strategy in SelectionStrategy::all() {
+            test_support::assert_selection_invariants(strategy, 128)
+                .unwrap_or_else(|e| panic!("{:?} violates invariants: {}", strategy, e));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use super::dust::DustPolicy;
+use super::{INPUT_VBYTES, OUTPUT_VBYTES};
+
+/// When a selection creates change, shared by every strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangePolicy {
+    /// Fee rate (sat/vB) expected when the change is eventually spent.
+    pub long_term_fee_rate: u64,
+    /// When a sizeable remainder would otherwise go to the fee, add another
+    /// input so it can become change instead.
+    pub reselect_for_change: bool,
+}
+
+impl Default for ChangePolicy {
+    fn default() -> Self {
+        ChangePolicy {
+            long_term_fee_rate: 1,
+            reselect_for_change: false,
+        }
+    }
+}
+
+/// What to do with the value left after paying target and fee.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeOutcome {
+    /// Add a change output of this value.
+    Create(u64),
+    /// Give the excess to the miner.
+    AddToFee(u64),
+    /// Select one more input and decide again.
+    AddInput,
+}
+
+/// Smallest change worth creating: the output has to pay for itself now and
+/// the input spending it later at `long_term_fee_rate`.
+pub fn min_economical_change(fee_rate: u64, long_term_fee_rate: u64) -> u64 {
+    OUTPUT_VBYTES * fee_rate + INPUT_VBYTES * long_term_fee_rate
+}
+
+impl ChangePolicy {
+    /// Smallest change output this policy creates at `fee_rate`; never
+    /// dust under `dust`.
+    pub fn min_change(&self, dust: &DustPolicy, fee_rate: u64) -> u64 {
+        dust.limit(fee_rate)
+            .max(min_economical_change(fee_rate, self.long_term_fee_rate))
+    }
+
+    /// Decides what happens to `excess`: inputs minus target minus the fee of a
+    /// transaction without change. A change output costs `OUTPUT_VBYTES * fee_rate`
+    /// out of that excess.
+    pub fn decide(
+        &self,
+        dust: &DustPolicy,
+        excess: u64,
+        fee_rate: u64,
+        more_inputs: bool,
+    ) -> ChangeOutcome {
+        let change = excess.saturating_sub(OUTPUT_VBYTES * fee_rate);
+        if change >= self.min_change(dust, fee_rate) {
+            ChangeOutcome::Create(change)
+        } else if self.reselect_for_change && more_inputs && !dust.is_dust(excess, fee_rate) {
+            ChangeOutcome::AddInput
+        } else {
+            ChangeOutcome::AddToFee(excess)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let policy = ChangePolicy::default();
+        let dust = DustPolicy::default();
+        // At 20 sat/vB change must cover its own output plus a future input
+        assert_eq!(policy.min_change(&dust, 20), 31 * 20 + 68);
+        assert_eq!(
+            policy.decide(&dust, 2_000, 20, true),
+            ChangeOutcome::Create(2_000 - 620)
+        );
+        assert_eq!(
+            policy.decide(&dust, 1_000, 20, true),
+            ChangeOutcome::AddToFee(1_000)
+        );
+
+        let reselecting = ChangePolicy {
+            reselect_for_change: true,
+            ..policy
+        };
+        assert_eq!(
+            reselecting.decide(&dust, 1_000, 20, true),
+            ChangeOutcome::AddInput
+        );
+        assert_eq!(
+            reselecting.decide(&dust, 1_000, 20, false),
+            ChangeOutcome::AddToFee(1_000)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::{SelectionParams, COINBASE_MATURITY};
+use crate::types::{OutPoint, Utxo};
+
+/// Coin control rules layered on top of any strategy.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionConstraints {
+    pub max_inputs: Option<usize>,
+    /// Upper bound on the absolute fee, in satoshis.
+    pub max_fee: Option<u64>,
+    /// Coins that must be spent, even if uneconomical at the current fee rate.
+    pub must_include: HashSet<OutPoint>,
+    /// Coins received on these addresses are never spent.
+    pub exclude_addresses: HashSet<String>,
+    /// Minimum confirmations of every input; raises `min_confirmations` when higher.
+    pub min_input_confirmations: Option<u32>,
+}
+
+impl SelectionConstraints {
+    pub fn is_empty(&self) -> bool {
+        *self == SelectionConstraints::default()
+    }
+
+    pub fn excludes(&self, utxo: &Utxo) -> bool {
+        self.exclude_addresses.contains(&utxo.address)
+    }
+
+    /// Checks the constraints for contradictions, and against the available
+    /// coins, before any selection is attempted.
+    pub fn validate(&self, utxos: &[Utxo], params: &SelectionParams) -> Result<(), String> {
+        if let Some(max_inputs) = self.max_inputs {
+            if max_inputs == 0 {
+                return Err("At least one input must be allowed".to_string());
+            }
+            if self.must_include.len() > max_inputs {
+                return Err(format!(
+                    "{} coins must be included but at most {} inputs are allowed",
+                    self.must_include.len(),
+                    max_inputs
+                ));
+            }
+        }
+
+        let mut forced: Vec<&OutPoint> = self.must_include.iter().collect();
+        forced.sort();
+        for outpoint in forced {
+            let utxo = utxos
+                .iter()
+                .find(|u| u.outpoint == *outpoint)
+                .ok_or_else(|| format!("Required coin {} is not in the wallet", outpoint))?;
+            if params.frozen.contains(outpoint) {
+                return Err(format!("Required coin {} is frozen", outpoint));
+            }
+            if self.excludes(utxo) {
+                return Err(format!(
+                    "Required coin {} is on an excluded address",
+                    outpoint
+                ));
+            }
+            let required = params.confirmations_for(utxo).max(if utxo.is_coinbase {
+                COINBASE_MATURITY
+            } else {
+                0
+            });
+            if utxo.confirmations < required {
+                return Err(format!(
+                    "Required coin {} has {} of {} confirmations",
+                    outpoint, utxo.confirmations, required
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let utxo = Utxo {
+            outpoint: OutPoint::new("aa", 0),
+            address: "bcrt1qreused".to_string(),
+            value: 10_000,
+            confirmations: 2,
+            is_coinbase: false,
+        };
+        let mut params = SelectionParams::new(5_000, 1);
+        params
+            .constraints
+            .must_include
+            .insert(utxo.outpoint.clone());
+        let constraints = params.constraints.clone();
+        assert!(constraints
+            .validate(std::slice::from_ref(&utxo), &params)
+            .is_ok());
+
+        params.constraints.min_input_confirmations = Some(3);
+        assert!(params
+            .constraints
+            .validate(std::slice::from_ref(&utxo), &params)
+            .is_err());
+
+        params.constraints.min_input_confirmations = None;
+        params.constraints.max_inputs = Some(0);
+        assert!(params.constraints.validate(&[utxo], &params).is_err());
+    }
+}
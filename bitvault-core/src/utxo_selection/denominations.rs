@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use super::{effective_value, estimate_vsize, is_spendable, SelectionParams, OUTPUT_VBYTES};
+use crate::progress::{self, Progress, ProgressSink};
+use crate::types::{OutPoint, Utxo};
+
+// A standard transaction is at most 100,000 vbytes, so no split could
+// create more denomination outputs than this
+const MAX_DENOMINATION_COINS: usize = (100_000 / OUTPUT_VBYTES) as usize;
+
+/// Keep `count` coins worth `value` satoshis each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DenominationTarget {
+    pub value: u64,
+    pub count: usize,
+}
+
+impl DenominationTarget {
+    /// Coins up to 1% above the denomination still count as one.
+    pub fn matches(&self, value: u64) -> bool {
+        value >= self.value && value - self.value <= self.value / 100
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReshapeKind {
+    /// Breaks large coins into denomination outputs.
+    Split,
+    /// Consolidates small leftover coins into one.
+    Merge,
+}
+
+/// What a reshaping transaction reveals on chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyImpact {
+    /// Distinct addresses shown to belong together by being spent together.
+    pub linked_addresses: usize,
+    /// Outputs of identical value, which make the change harder to spot.
+    pub equal_outputs: usize,
+}
+
+/// A proposed transaction for the user to approve.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReshapeDraft {
+    pub kind: ReshapeKind,
+    pub inputs: Vec<OutPoint>,
+    /// Output values to own addresses, not counting change.
+    pub outputs: Vec<u64>,
+    pub change: Option<u64>,
+    pub vsize: u64,
+    pub fee: u64,
+    pub privacy: PrivacyImpact,
+}
+
+/// Transactions that move the wallet toward its target denominations.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReshapePlan {
+    pub drafts: Vec<ReshapeDraft>,
+    pub total_fee: u64,
+    /// Denomination coins that couldn't be funded.
+    pub unmet: Vec<DenominationTarget>,
+}
+
+/// Proposes a split creating the denomination coins the wallet is missing,
+/// then a merge of leftover coins smaller than every denomination.
+///
+/// Existing coins that already match a denomination are left alone. Inputs
+/// are taken largest first so the split links as few addresses as possible.
+pub fn plan_reshape(
+    utxos: &[Utxo],
+    targets: &[DenominationTarget],
+    params: &SelectionParams,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<ReshapePlan, String> {
+    let report = |phase: &str, step: u32| {
+        progress::report(progress, || Progress::step("consolidation", phase, step, 3))
+    };
+    if let Some(target) = targets
+        .iter()
+        .find(|t| t.count == 0 || params.dust.is_dust(t.value, params.fee_rate))
+    {
+        return Err(format!(
+            "Invalid denomination {} x {} sats",
+            target.count, target.value
+        ));
+    }
+    let total = targets
+        .iter()
+        .fold(0usize, |total, t| total.saturating_add(t.count));
+    if total > MAX_DENOMINATION_COINS {
+        return Err(format!(
+            "Can't keep {} denomination coins, at most {} fit in one transaction",
+            total, MAX_DENOMINATION_COINS
+        ));
+    }
+    let mut targets = targets.to_vec();
+    targets.sort_by_key(|t| std::cmp::Reverse(t.value));
+
+    let mut free: Vec<&Utxo> = utxos.iter().filter(|u| is_spendable(u, params)).collect();
+    free.sort_by_key(|u| std::cmp::Reverse(u.value));
+
+    // Coins already holding a denomination stay put
+    report("Matching denominations", 0);
+    let mut missing = Vec::new();
+    for target in &targets {
+        let mut have = 0;
+        free.retain(|utxo| {
+            let keep = have < target.count && target.matches(utxo.value);
+            have += usize::from(keep);
+            !keep
+        });
+        missing.extend(std::iter::repeat_n(target.value, target.count - have));
+    }
+
+    let mut plan = ReshapePlan::default();
+    let mut used = BTreeSet::new();
+    report("Planning split", 1);
+    while !missing.is_empty() {
+        if let Some(draft) = plan_split(&free, &missing, params) {
+            used.extend(draft.inputs.iter().cloned());
+            plan.drafts.push(draft);
+            break;
+        }
+        // Drop the smallest output and try again with what's left
+        let value = missing.pop().unwrap_or_default();
+        match plan.unmet.iter_mut().find(|t| t.value == value) {
+            Some(unmet) => unmet.count += 1,
+            None => plan.unmet.push(DenominationTarget { value, count: 1 }),
+        }
+    }
+
+    report("Planning merge", 2);
+    let smallest = targets.last().map_or(0, |t| t.value);
+    let leftovers: Vec<&Utxo> = free
+        .into_iter()
+        .filter(|u| {
+            !used.contains(&u.outpoint)
+                && u.value < smallest
+                && effective_value(u, params.fee_rate) > 0
+        })
+        .collect();
+    if leftovers.len() >= 2 {
+        let vsize = estimate_vsize(leftovers.len(), 1);
+        let fee = vsize * params.fee_rate;
+        let total: u64 = leftovers.iter().map(|u| u.value).sum();
+        if total >= fee + params.dust.limit(params.fee_rate) {
+            plan.drafts.push(ReshapeDraft {
+                kind: ReshapeKind::Merge,
+                inputs: leftovers.iter().map(|u| u.outpoint.clone()).collect(),
+                outputs: vec![total - fee],
+                change: None,
+                vsize,
+                fee,
+                privacy: privacy_impact(&leftovers, &[total - fee]),
+            });
+        }
+    }
+
+    plan.total_fee = plan.drafts.iter().map(|d| d.fee).sum();
+    report("Done", 3);
+    Ok(plan)
+}
+
+// One transaction paying every output in `outputs`, if the free coins cover it
+fn plan_split(free: &[&Utxo], outputs: &[u64], params: &SelectionParams) -> Option<ReshapeDraft> {
+    let fee_rate = params.fee_rate;
+    let needed: u64 = outputs.iter().sum();
+    let mut inputs = Vec::new();
+    let mut total = 0u64;
+    for utxo in free {
+        inputs.push(*utxo);
+        total += utxo.value;
+        let vsize = estimate_vsize(inputs.len(), outputs.len());
+        let fee = vsize * fee_rate;
+        if total < needed + fee {
+            continue;
+        }
+
+        // Leftovers too small to be worth a change output go to the fee
+        let excess = total - needed - fee;
+        let change_cost = OUTPUT_VBYTES * fee_rate;
+        let (change, vsize, fee) = if excess >= change_cost + params.dust.limit(fee_rate) {
+            (
+                Some(excess - change_cost),
+                vsize + OUTPUT_VBYTES,
+                fee + change_cost,
+            )
+        } else {
+            (None, vsize, fee + excess)
+        };
+        return Some(ReshapeDraft {
+            kind: ReshapeKind::Split,
+            inputs: inputs.iter().map(|u| u.outpoint.clone()).collect(),
+            outputs: outputs.to_vec(),
+            change,
+            vsize,
+            fee,
+            privacy: privacy_impact(&inputs, outputs),
+        });
+    }
+    None
+}
+
+fn privacy_impact(inputs: &[&Utxo], outputs: &[u64]) -> PrivacyImpact {
+    let addresses: BTreeSet<&str> = inputs.iter().map(|u| u.address.as_str()).collect();
+    let equal_outputs = outputs
+        .iter()
+        .filter(|v| outputs.iter().filter(|w| w == v).count() > 1)
+        .count();
+    PrivacyImpact {
+        linked_addresses: addresses.len(),
+        equal_outputs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utxo(vout: u32, address: &str, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new("aa", vout),
+            address: address.to_string(),
+            value,
+            confirmations: 6,
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_split_and_merge_plan() {
+        let utxos = vec![
+            utxo(0, "bc1qbig", 10_000_000),
+            utxo(1, "bc1qdenom", 1_000_000),
+            utxo(2, "bc1qsmall1", 20_000),
+            utxo(3, "bc1qsmall2", 30_000),
+        ];
+        let target = DenominationTarget {
+            value: 1_000_000,
+            count: 5,
+        };
+        let plan = plan_reshape(&utxos, &[target], &SelectionParams::new(0, 2), None).unwrap();
+        assert!(plan.unmet.is_empty());
+
+        let split = &plan.drafts[0];
+        assert_eq!(split.kind, ReshapeKind::Split);
+        assert_eq!(split.inputs, vec![OutPoint::new("aa", 0)]);
+        assert_eq!(split.outputs, vec![1_000_000; 4]);
+        assert_eq!(split.fee, split.vsize * 2);
+        assert_eq!(split.change, Some(10_000_000 - 4_000_000 - split.fee));
+        assert_eq!(split.privacy.equal_outputs, 4);
+
+        let merge = &plan.drafts[1];
+        assert_eq!(merge.kind, ReshapeKind::Merge);
+        assert_eq!(merge.privacy.linked_addresses, 2);
+        assert_eq!(merge.outputs, vec![50_000 - merge.fee]);
+        assert_eq!(plan.total_fee, split.fee + merge.fee);
+    }
+
+    #[test]
+    fn test_unfundable_denominations_reported() {
+        let utxos = vec![utxo(0, "bc1qa", 2_500_000)];
+        let target = DenominationTarget {
+            value: 1_000_000,
+            count: 4,
+        };
+        let plan = plan_reshape(&utxos, &[target], &SelectionParams::new(0, 1), None).unwrap();
+        assert_eq!(plan.drafts[0].outputs.len(), 2);
+        assert_eq!(
+            plan.unmet,
+            vec![DenominationTarget {
+                value: 1_000_000,
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_oversized_targets_rejected() {
+        let utxos = vec![utxo(0, "bc1qa", 2_500_000)];
+        let params = SelectionParams::new(0, 1);
+        let target = |count| DenominationTarget {
+            value: 1_000_000,
+            count,
+        };
+        assert!(plan_reshape(&utxos, &[target(usize::MAX)], &params, None).is_err());
+        assert!(plan_reshape(&utxos, &[target(usize::MAX), target(2)], &params, None).is_err());
+        assert!(
+            plan_reshape(&utxos, &[target(MAX_DENOMINATION_COINS + 1)], &params, None).is_err()
+        );
+        assert!(plan_reshape(&utxos, &[target(MAX_DENOMINATION_COINS)], &params, None).is_ok());
+    }
+}
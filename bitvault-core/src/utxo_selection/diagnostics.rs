@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    estimate_vsize, exclusion_reason, ExclusionReason, SelectionParams, COINBASE_MATURITY,
+    INPUT_VBYTES,
+};
+use crate::types::{OutPoint, Utxo};
+
+/// Where the wallet's funds are, in satoshis, when a selection can't be funded.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FundsBreakdown {
+    pub total: u64,
+    pub spendable: u64,
+    pub frozen: u64,
+    /// Below the required confirmation count.
+    pub unconfirmed: u64,
+    /// Coinbase outputs that haven't matured.
+    pub immature: u64,
+    /// Coins worth less than the fee to spend them.
+    pub uneconomical: u64,
+    /// On addresses excluded by coin control.
+    pub restricted: u64,
+}
+
+/// Something the user can do to make the payment possible.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FundingSuggestion {
+    /// Enough funds become spendable once pending coins reach the required depth.
+    WaitForConfirmations {
+        amount: u64,
+        confirmations_needed: u32,
+    },
+    /// Unfreezing these coins would cover the shortfall.
+    Unfreeze {
+        outpoints: Vec<OutPoint>,
+        amount: u64,
+    },
+    /// The spendable coins suffice at this fee rate or below.
+    LowerFeeRate { max_fee_rate: u64 },
+}
+
+/// Explains a failed selection: how the balance splits up and what would help.
+pub fn diagnose_shortfall(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+    available: u64,
+    required: u64,
+) -> (FundsBreakdown, Vec<FundingSuggestion>) {
+    let mut breakdown = FundsBreakdown::default();
+    let mut frozen = Vec::new();
+    let mut pending = Vec::new();
+    let mut spendable_count = 0usize;
+
+    for utxo in utxos {
+        breakdown.total += utxo.value;
+        match exclusion_reason(utxo, params) {
+            None => {
+                breakdown.spendable += utxo.value;
+                spendable_count += 1;
+            }
+            Some(ExclusionReason::Frozen) => {
+                breakdown.frozen += utxo.value;
+                frozen.push(utxo);
+            }
+            Some(ExclusionReason::Unconfirmed { confirmations }) => {
+                breakdown.unconfirmed += utxo.value;
+                pending.push((utxo, params.confirmations_for(utxo) - confirmations));
+            }
+            Some(ExclusionReason::Immature { confirmations }) => {
+                breakdown.immature += utxo.value;
+                let needed = COINBASE_MATURITY.max(params.confirmations_for(utxo)) - confirmations;
+                pending.push((utxo, needed));
+            }
+            Some(ExclusionReason::Uneconomical) => breakdown.uneconomical += utxo.value,
+            Some(ExclusionReason::AddressExcluded) => breakdown.restricted += utxo.value,
+        }
+    }
+
+    let shortfall = required.saturating_sub(available);
+    // What a coin adds once the cost of spending it is paid
+    let net = |utxo: &Utxo| utxo.value.saturating_sub(INPUT_VBYTES * params.fee_rate);
+    let mut suggestions = Vec::new();
+
+    // Soonest-maturing coins first, until the shortfall is covered
+    pending.sort_by_key(|(_, needed)| *needed);
+    let mut covered = 0u64;
+    for (utxo, needed) in &pending {
+        covered += net(utxo);
+        if covered >= shortfall {
+            suggestions.push(FundingSuggestion::WaitForConfirmations {
+                amount: covered,
+                confirmations_needed: *needed,
+            });
+            break;
+        }
+    }
+
+    // Fewest frozen coins that cover the shortfall
+    frozen.sort_by_key(|u| std::cmp::Reverse(u.value));
+    let mut covered = 0u64;
+    let mut outpoints = Vec::new();
+    for utxo in &frozen {
+        covered += net(utxo);
+        outpoints.push(utxo.outpoint.clone());
+        if covered >= shortfall {
+            suggestions.push(FundingSuggestion::Unfreeze {
+                outpoints,
+                amount: covered,
+            });
+            break;
+        }
+    }
+
+    // The highest rate at which all spendable coins still pay for target plus fee
+    if spendable_count > 0 && breakdown.spendable > params.target {
+        let vsize = estimate_vsize(spendable_count, params.recipient_outputs);
+        let max_fee_rate = (breakdown.spendable - params.target) / vsize;
+        if max_fee_rate >= 1 && max_fee_rate < params.fee_rate {
+            suggestions.push(FundingSuggestion::LowerFeeRate { max_fee_rate });
+        }
+    }
+
+    (breakdown, suggestions)
+}
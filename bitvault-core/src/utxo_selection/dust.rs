@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use super::{DUST_THRESHOLD, INPUT_VBYTES};
+use crate::types::Network;
+
+/// Dust limit for P2WPKH outputs at the default 3 sat/vB dust relay fee.
+pub const SEGWIT_DUST_THRESHOLD: u64 = 294;
+
+/// Which outputs are too small to create, shared by coin selection, change,
+/// payment splitting and the transaction builder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DustPolicy {
+    /// Outputs below this are never created, whatever the fee rate.
+    pub threshold: u64,
+    /// Also treat as dust any output that would cost more than half its
+    /// value to spend at the current fee rate.
+    pub fee_relative: bool,
+}
+
+impl Default for DustPolicy {
+    fn default() -> Self {
+        DustPolicy::for_network(Network::Bitcoin)
+    }
+}
+
+impl DustPolicy {
+    /// Mainnet keeps the legacy limit, since payees may use any script
+    /// type; test networks use the segwit one.
+    pub fn for_network(network: Network) -> Self {
+        DustPolicy {
+            threshold: match network {
+                Network::Bitcoin => DUST_THRESHOLD,
+                Network::Testnet | Network::Signet | Network::Regtest => SEGWIT_DUST_THRESHOLD,
+            },
+            fee_relative: false,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: u64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_fee_relative(mut self, fee_relative: bool) -> Self {
+        self.fee_relative = fee_relative;
+        self
+    }
+
+    /// Smallest output value allowed at `fee_rate` (sat/vB).
+    pub fn limit(&self, fee_rate: u64) -> u64 {
+        if self.fee_relative {
+            self.threshold.max(2 * INPUT_VBYTES * fee_rate)
+        } else {
+            self.threshold
+        }
+    }
+
+    pub fn is_dust(&self, value: u64, fee_rate: u64) -> bool {
+        value < self.limit(fee_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dust_limits() {
+        assert_eq!(DustPolicy::default().limit(100), 546);
+        assert_eq!(DustPolicy::for_network(Network::Regtest).limit(100), 294);
+
+        let policy = DustPolicy::default()
+            .with_threshold(1_000)
+            .with_fee_relative(true);
+        assert_eq!(policy.limit(2), 1_000);
+        // At 50 sat/vB spending the output costs 3_400 sats
+        assert_eq!(policy.limit(50), 6_800);
+        assert!(policy.is_dust(6_799, 50));
+        assert!(!policy.is_dust(6_799, 2));
+    }
+}
@@ -0,0 +1,284 @@
+//! What a draft transaction gives away to chain analysis, checked before
+//! signing while a different selection is still possible.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use super::{SelectionResult, SelectionStrategy};
+use crate::signer::ScriptType;
+use crate::types::{OutPoint, TxOut, Utxo};
+
+/// Amounts that are a multiple of this look typed in by a person.
+const ROUND_AMOUNT: u64 = 10_000;
+
+/// What the wallet's history already reveals.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyContext {
+    /// Payments received per address.
+    pub receives: HashMap<String, usize>,
+    /// Groups of addresses already shown to belong together, e.g. by being
+    /// spent in the same transaction.
+    pub clusters: Vec<BTreeSet<String>>,
+}
+
+impl PrivacyContext {
+    fn cluster_of(&self, address: &str) -> Option<usize> {
+        self.clusters.iter().position(|c| c.contains(address))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrivacyFinding {
+    /// Inputs come from addresses that received more than once.
+    AddressReuse { addresses: Vec<String> },
+    /// Spending these inputs together links clusters not linked before.
+    LinksClusters { clusters: usize },
+    /// The payment is a round amount and the change isn't, so the change
+    /// stands out.
+    RoundPayment { amount: u64 },
+    /// The change address type differs from every recipient's.
+    ChangeTypeMismatch {
+        change: ScriptType,
+        recipients: Vec<ScriptType>,
+    },
+    /// An input smaller than the change wasn't needed, which marks the
+    /// larger output as change.
+    UnnecessaryInputs { outpoints: Vec<OutPoint> },
+}
+
+impl PrivacyFinding {
+    /// Points taken off a perfect score of 100.
+    fn penalty(&self) -> u8 {
+        match self {
+            PrivacyFinding::AddressReuse { .. } => 30,
+            PrivacyFinding::LinksClusters { clusters } => (*clusters).min(3) as u8 * 10,
+            PrivacyFinding::RoundPayment { .. } => 10,
+            PrivacyFinding::ChangeTypeMismatch { .. } => 15,
+            PrivacyFinding::UnnecessaryInputs { .. } => 15,
+        }
+    }
+}
+
+/// Something to change about the draft for better privacy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrivacySuggestion {
+    /// Select with this strategy instead.
+    UseStrategy(SelectionStrategy),
+    /// Freeze these coins or leave them out with coin control.
+    AvoidCoins { outpoints: Vec<OutPoint> },
+    /// Send change to an address of this type.
+    ChangeType(ScriptType),
+    /// Pay a slightly less round amount, if the recipient allows it.
+    AdjustAmount,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyReport {
+    /// 100 reveals nothing beyond the payment itself; lower is worse.
+    pub score: u8,
+    pub findings: Vec<PrivacyFinding>,
+    pub suggestions: Vec<PrivacySuggestion>,
+}
+
+fn is_round(value: u64) -> bool {
+    value > 0 && value.is_multiple_of(ROUND_AMOUNT)
+}
+
+/// Reports on spending `inputs` to `recipients`, with `change` if any.
+pub fn privacy_report(
+    inputs: &[Utxo],
+    recipients: &[TxOut],
+    change: Option<&TxOut>,
+    context: &PrivacyContext,
+) -> PrivacyReport {
+    let mut findings = Vec::new();
+    let mut suggestions = Vec::new();
+
+    let reused: Vec<&Utxo> = inputs
+        .iter()
+        .filter(|u| context.receives.get(&u.address).is_some_and(|n| *n > 1))
+        .collect();
+    if !reused.is_empty() {
+        let addresses: BTreeSet<String> = reused.iter().map(|u| u.address.clone()).collect();
+        findings.push(PrivacyFinding::AddressReuse {
+            addresses: addresses.into_iter().collect(),
+        });
+        suggestions.push(PrivacySuggestion::AvoidCoins {
+            outpoints: reused.iter().map(|u| u.outpoint.clone()).collect(),
+        });
+    }
+
+    // Addresses outside any known cluster each form their own
+    let clusters: BTreeSet<Result<usize, &str>> = inputs
+        .iter()
+        .map(|u| context.cluster_of(&u.address).ok_or(u.address.as_str()))
+        .collect();
+    if clusters.len() > 1 {
+        findings.push(PrivacyFinding::LinksClusters {
+            clusters: clusters.len(),
+        });
+        suggestions.push(PrivacySuggestion::UseStrategy(
+            SelectionStrategy::LargestFirst,
+        ));
+    }
+
+    if let Some(change) = change {
+        if let Some(payment) = recipients
+            .iter()
+            .find(|r| is_round(r.value) && !is_round(change.value))
+        {
+            findings.push(PrivacyFinding::RoundPayment {
+                amount: payment.value,
+            });
+            suggestions.push(PrivacySuggestion::AdjustAmount);
+        }
+
+        let recipient_types: Vec<ScriptType> = recipients
+            .iter()
+            .filter_map(|r| ScriptType::from_address(&r.address))
+            .fold(Vec::new(), |mut types, t| {
+                if !types.contains(&t) {
+                    types.push(t);
+                }
+                types
+            });
+        if let Some(change_type) = ScriptType::from_address(&change.address) {
+            if !recipient_types.is_empty() && !recipient_types.contains(&change_type) {
+                suggestions.push(PrivacySuggestion::ChangeType(recipient_types[0]));
+                findings.push(PrivacyFinding::ChangeTypeMismatch {
+                    change: change_type,
+                    recipients: recipient_types,
+                });
+            }
+        }
+
+        let unnecessary: Vec<OutPoint> = inputs
+            .iter()
+            .filter(|u| inputs.len() > 1 && u.value < change.value)
+            .map(|u| u.outpoint.clone())
+            .collect();
+        if !unnecessary.is_empty() {
+            findings.push(PrivacyFinding::UnnecessaryInputs {
+                outpoints: unnecessary,
+            });
+            if !suggestions.contains(&PrivacySuggestion::UseStrategy(
+                SelectionStrategy::LargestFirst,
+            )) {
+                suggestions.push(PrivacySuggestion::UseStrategy(
+                    SelectionStrategy::LargestFirst,
+                ));
+            }
+        }
+    }
+
+    let penalty: u32 = findings.iter().map(|f| f.penalty() as u32).sum();
+    PrivacyReport {
+        score: 100u32.saturating_sub(penalty) as u8,
+        findings,
+        suggestions,
+    }
+}
+
+impl SelectionResult {
+    /// Privacy report for a successful selection paying `recipients`, with
+    /// any change going to `change_address`.
+    pub fn privacy_report(
+        &self,
+        recipients: &[TxOut],
+        change_address: &str,
+        context: &PrivacyContext,
+    ) -> Option<PrivacyReport> {
+        let SelectionResult::Success {
+            selected, change, ..
+        } = self
+        else {
+            return None;
+        };
+        let change = (*change > 0).then(|| TxOut {
+            address: change_address.to_string(),
+            value: *change,
+        });
+        Some(privacy_report(
+            selected,
+            recipients,
+            change.as_ref(),
+            context,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utxo(n: u8, address: &str, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(&format!("{:02x}", n).repeat(32), 0),
+            address: address.to_string(),
+            value,
+            confirmations: 6,
+            is_coinbase: false,
+        }
+    }
+
+    fn out(address: &str, value: u64) -> TxOut {
+        TxOut {
+            address: address.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_report_findings_and_score() {
+        let segwit = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let taproot = "bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297";
+        let mut context = PrivacyContext::default();
+        context.receives.insert("bc1qreused".to_string(), 3);
+        context
+            .clusters
+            .push(BTreeSet::from(["bc1qa".to_string(), "bc1qb".to_string()]));
+
+        // One known cluster, exact-ish payment, matching change type
+        let clean = privacy_report(
+            &[utxo(1, "bc1qa", 80_000), utxo(2, "bc1qb", 60_000)],
+            &[out(segwit, 123_456)],
+            Some(&out("bc1qchange", 15_000)),
+            &context,
+        );
+        assert_eq!(clean.score, 100);
+        assert!(clean.suggestions.is_empty());
+
+        let leaky = privacy_report(
+            &[utxo(1, "bc1qa", 80_000), utxo(3, "bc1qreused", 5_000)],
+            &[out(taproot, 50_000)],
+            Some(&out(segwit, 34_321)),
+            &context,
+        );
+        assert_eq!(
+            leaky.findings,
+            vec![
+                PrivacyFinding::AddressReuse {
+                    addresses: vec!["bc1qreused".to_string()]
+                },
+                PrivacyFinding::LinksClusters { clusters: 2 },
+                PrivacyFinding::RoundPayment { amount: 50_000 },
+                PrivacyFinding::ChangeTypeMismatch {
+                    change: ScriptType::P2wpkh,
+                    recipients: vec![ScriptType::P2tr],
+                },
+                PrivacyFinding::UnnecessaryInputs {
+                    outpoints: vec![utxo(3, "", 0).outpoint]
+                },
+            ]
+        );
+        assert_eq!(leaky.score, 100 - 30 - 20 - 10 - 15 - 15);
+        assert!(leaky
+            .suggestions
+            .contains(&PrivacySuggestion::ChangeType(ScriptType::P2tr)));
+
+        let failed = SelectionResult::ConstraintViolation {
+            reason: String::new(),
+        };
+        assert_eq!(failed.privacy_report(&[], segwit, &context), None);
+    }
+}
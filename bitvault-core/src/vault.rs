@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::BlockchainBackend;
+use crate::events::{Event, MessageBus};
+use crate::signer::{SignedTransaction, TransactionSigner};
+use crate::types::{OutPoint, Transaction, TxIn, TxOut, Utxo, SEQUENCE_RBF};
+use crate::utxo_selection::dust::DustPolicy;
+use crate::utxo_selection::estimate_vsize;
+
+/// BIP-68 relative locktimes are limited to 16 bits of blocks.
+pub const MAX_CSV_DELAY: u32 = 0xffff;
+
+// Vault scripts have bigger witnesses than the P2WPKH inputs estimate_vsize assumes
+const VAULT_INPUT_EXTRA_VBYTES: u64 = 40;
+
+/// Keys and delay defining a two-stage vault.
+///
+/// Deposits can only be moved by the unvault key into the unvault output, or by
+/// the cancel key back to cold storage. The unvault output can be spent by the
+/// hot key once `csv_delay` blocks have passed, and by the cancel key at any time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultTemplate {
+    pub unvault_key: String,
+    pub hot_key: String,
+    pub cancel_key: String,
+    /// Address of the cold wallet cancelled funds are returned to.
+    pub cold_address: String,
+    /// Blocks between an unvault confirming and the hot key being able to spend it.
+    pub csv_delay: u32,
+}
+
+impl VaultTemplate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.csv_delay == 0 || self.csv_delay > MAX_CSV_DELAY {
+            return Err(format!(
+                "Unvault delay must be between 1 and {} blocks",
+                MAX_CSV_DELAY
+            ));
+        }
+        let keys = [&self.unvault_key, &self.hot_key, &self.cancel_key];
+        if keys.iter().any(|k| k.is_empty()) {
+            return Err("Vault keys must not be empty".to_string());
+        }
+        if self.cancel_key == self.hot_key || self.cancel_key == self.unvault_key {
+            return Err("The cancel key must differ from the spending keys".to_string());
+        }
+        Ok(())
+    }
+
+    /// Descriptor for deposits into the vault.
+    pub fn deposit_descriptor(&self) -> String {
+        format!(
+            "wsh(or_d(pk({}),pk({})))",
+            self.cancel_key, self.unvault_key
+        )
+    }
+
+    /// Descriptor for the unvault output, spendable by the hot key after the delay.
+    pub fn unvault_descriptor(&self) -> String {
+        format!(
+            "wsh(or_d(pk({}),and_v(v:pk({}),older({}))))",
+            self.cancel_key, self.hot_key, self.csv_delay
+        )
+    }
+}
+
+/// A vault instance: its template and the addresses derived from its descriptors.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vault {
+    pub template: VaultTemplate,
+    pub deposit_address: String,
+    pub unvault_address: String,
+}
+
+fn fee_for(inputs: usize, outputs: usize, fee_rate: u64) -> u64 {
+    (estimate_vsize(inputs, outputs) + VAULT_INPUT_EXTRA_VBYTES * inputs as u64) * fee_rate
+}
+
+fn sweep(
+    coins: &[Utxo],
+    destination: &str,
+    sequence: u32,
+    fee_rate: u64,
+    signer: &dyn TransactionSigner,
+) -> Result<SignedTransaction, String> {
+    if coins.is_empty() {
+        return Err("No vault coins to spend".to_string());
+    }
+    let total: u64 = coins.iter().map(|u| u.value).sum();
+    let fee = fee_for(coins.len(), 1, fee_rate);
+    let value = total
+        .checked_sub(fee)
+        .filter(|v| !DustPolicy::default().is_dust(*v, fee_rate))
+        .ok_or_else(|| format!("Coins ({} sats) don't cover the fee ({} sats)", total, fee))?;
+
+    let transaction = Transaction {
+        lock_time: 0,
+        inputs: coins
+            .iter()
+            .map(|u| TxIn {
+                previous_output: u.outpoint.clone(),
+                sequence,
+            })
+            .collect(),
+        outputs: vec![TxOut {
+            address: destination.to_string(),
+            value,
+        }],
+    };
+    let signed = signer.sign(&transaction, coins)?;
+    if !signed.is_complete() {
+        return Err("Signer did not sign every input".to_string());
+    }
+    Ok(signed)
+}
+
+impl Vault {
+    pub fn new(
+        template: VaultTemplate,
+        deposit_address: &str,
+        unvault_address: &str,
+    ) -> Result<Self, String> {
+        template.validate()?;
+        Ok(Vault {
+            template,
+            deposit_address: deposit_address.to_string(),
+            unvault_address: unvault_address.to_string(),
+        })
+    }
+
+    /// Moves deposits into the unvault output, starting the delay. Sign with the
+    /// unvault key.
+    pub fn build_unvault(
+        &self,
+        deposits: &[Utxo],
+        fee_rate: u64,
+        signer: &dyn TransactionSigner,
+    ) -> Result<SignedTransaction, String> {
+        if let Some(coin) = deposits.iter().find(|u| u.address != self.deposit_address) {
+            return Err(format!("{} is not a vault deposit", coin.outpoint));
+        }
+        sweep(
+            deposits,
+            &self.unvault_address,
+            SEQUENCE_RBF,
+            fee_rate,
+            signer,
+        )
+    }
+
+    /// Claws vault or unvault coins back to cold storage. Sign with the cancel key.
+    pub fn build_cancel(
+        &self,
+        coins: &[Utxo],
+        fee_rate: u64,
+        signer: &dyn TransactionSigner,
+    ) -> Result<SignedTransaction, String> {
+        if let Some(coin) = coins
+            .iter()
+            .find(|u| u.address != self.deposit_address && u.address != self.unvault_address)
+        {
+            return Err(format!("{} is not a vault coin", coin.outpoint));
+        }
+        sweep(
+            coins,
+            &self.template.cold_address,
+            SEQUENCE_RBF,
+            fee_rate,
+            signer,
+        )
+    }
+
+    /// Spends matured unvault coins to `destination`. Sign with the hot key.
+    pub fn build_spend(
+        &self,
+        unvaulted: &[Utxo],
+        destination: &str,
+        fee_rate: u64,
+        signer: &dyn TransactionSigner,
+    ) -> Result<SignedTransaction, String> {
+        for coin in unvaulted {
+            if coin.address != self.unvault_address {
+                return Err(format!("{} is not an unvault output", coin.outpoint));
+            }
+            let remaining = self.blocks_until_spendable(coin);
+            if remaining > 0 {
+                return Err(format!(
+                    "{} is spendable in {} more blocks",
+                    coin.outpoint, remaining
+                ));
+            }
+        }
+        // BIP-68: a sequence below 2^16 with no flags is a relative height lock
+        sweep(
+            unvaulted,
+            destination,
+            self.template.csv_delay,
+            fee_rate,
+            signer,
+        )
+    }
+
+    /// Blocks until the hot key can spend an unvault output. Unconfirmed
+    /// outputs haven't started the delay yet.
+    pub fn blocks_until_spendable(&self, coin: &Utxo) -> u32 {
+        self.template.csv_delay.saturating_sub(coin.confirmations)
+    }
+}
+
+/// Watches a vault's unvault address and raises `UnvaultDetected` for every
+/// new unvault, flagging the ones this wallet didn't start.
+#[derive(Clone, Debug)]
+pub struct VaultWatcher {
+    vault: Vault,
+    expected: HashSet<String>,
+    seen: HashMap<OutPoint, Utxo>,
+}
+
+impl VaultWatcher {
+    pub fn new(vault: Vault) -> Self {
+        VaultWatcher {
+            vault,
+            expected: HashSet::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records an unvault broadcast by this wallet so it isn't treated as theft.
+    pub fn expect_unvault(&mut self, txid: &str) {
+        self.expected.insert(txid.to_string());
+    }
+
+    /// Unvault outputs currently waiting out or past their delay.
+    pub fn pending(&self) -> Vec<&Utxo> {
+        self.seen.values().collect()
+    }
+
+    /// Fetches the unvault address's coins and publishes an event for each new one.
+    /// Returns the newly detected outputs.
+    pub fn sync(
+        &mut self,
+        backend: &dyn BlockchainBackend,
+        bus: Option<&MessageBus>,
+    ) -> Result<Vec<Utxo>, String> {
+        let utxos = backend.address_utxos(&self.vault.unvault_address)?;
+        let current: HashSet<&OutPoint> = utxos.iter().map(|u| &u.outpoint).collect();
+        self.seen.retain(|outpoint, _| current.contains(outpoint));
+
+        let mut detected = Vec::new();
+        for utxo in utxos {
+            if self
+                .seen
+                .insert(utxo.outpoint.clone(), utxo.clone())
+                .is_some()
+            {
+                continue;
+            }
+            let expected = self.expected.contains(&utxo.outpoint.txid);
+            if !expected {
+                log::warn!(
+                    "Unexpected unvault {} of {} sats",
+                    utxo.outpoint,
+                    utxo.value
+                );
+            }
+            if let Some(bus) = bus {
+                bus.publish(Event::UnvaultDetected {
+                    outpoint: utxo.outpoint.to_string(),
+                    value: utxo.value,
+                    blocks_until_spendable: self.vault.blocks_until_spendable(&utxo),
+                    expected,
+                });
+            }
+            detected.push(utxo);
+        }
+        Ok(detected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    struct DummySigner;
+
+    impl TransactionSigner for DummySigner {
+        fn id(&self) -> &str {
+            "dummy"
+        }
+
+        fn sign(&self, tx: &Transaction, _prevouts: &[Utxo]) -> Result<SignedTransaction, String> {
+            Ok(SignedTransaction {
+                transaction: tx.clone(),
+                witnesses: vec![vec!["00".to_string()]; tx.inputs.len()],
+            })
+        }
+    }
+
+    fn vault() -> Vault {
+        let template = VaultTemplate {
+            unvault_key: "unvault".to_string(),
+            hot_key: "hot".to_string(),
+            cancel_key: "cancel".to_string(),
+            cold_address: "bcrt1qcold".to_string(),
+            csv_delay: 144,
+        };
+        Vault::new(template, "bcrt1qdeposit", "bcrt1qunvault").unwrap()
+    }
+
+    #[test]
+    fn test_unvault_spend_and_cancel() {
+        let vault = vault();
+        assert!(vault.template.unvault_descriptor().contains("older(144)"));
+
+        let unvaulted = Utxo {
+            outpoint: OutPoint::new("aa", 0),
+            address: vault.unvault_address.clone(),
+            value: 1_000_000,
+            confirmations: 100,
+            is_coinbase: false,
+        };
+        assert!(vault
+            .build_spend(
+                std::slice::from_ref(&unvaulted),
+                "bcrt1qpay",
+                2,
+                &DummySigner
+            )
+            .is_err());
+
+        let cancel = vault
+            .build_cancel(std::slice::from_ref(&unvaulted), 2, &DummySigner)
+            .unwrap();
+        assert_eq!(cancel.transaction.outputs[0].address, "bcrt1qcold");
+
+        let matured = Utxo {
+            confirmations: 144,
+            ..unvaulted
+        };
+        let spend = vault
+            .build_spend(&[matured], "bcrt1qpay", 2, &DummySigner)
+            .unwrap();
+        assert_eq!(spend.transaction.inputs[0].sequence, 144);
+    }
+
+    #[test]
+    fn test_watcher_flags_unexpected_unvaults() {
+        let backend = MockBackend::new();
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let mut watcher = VaultWatcher::new(vault());
+
+        let ours = backend.fund_address("bcrt1qunvault", 500_000);
+        watcher.expect_unvault(&ours.txid);
+        backend.mine_blocks(1);
+        backend.fund_address("bcrt1qunvault", 700_000);
+
+        assert_eq!(watcher.sync(&backend, Some(&bus)).unwrap().len(), 2);
+        assert!(watcher.sync(&backend, Some(&bus)).unwrap().is_empty());
+
+        let mut flagged: Vec<(u64, bool)> = events
+            .try_iter()
+            .filter_map(|e| match e {
+                Event::UnvaultDetected {
+                    value, expected, ..
+                } => Some((value, expected)),
+                _ => None,
+            })
+            .collect();
+        flagged.sort();
+        assert_eq!(flagged, vec![(500_000, true), (700_000, false)]);
+    }
+}
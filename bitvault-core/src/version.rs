@@ -0,0 +1,260 @@
+//! What this binary was built from, and checking that against a signed
+//! release manifest.
+//!
+//! Release builds publish a manifest with the commit, lockfile hash, profile
+//! and features they were built with, signed by a release key. Anyone
+//! reproducing the build can check their binary reports the same, and the
+//! running wallet can check it is the build it claims to be.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Hex encoded Ed25519 keys that sign official release manifests.
+///
+/// Empty until a release key is published. While it is empty,
+/// [`SignedReleaseManifest::verify_build`] refuses every manifest rather than
+/// reporting a build as verified.
+pub const EMBEDDED_RELEASE_KEYS: &[&str] = &[];
+
+/// Every optional feature of this crate and whether this build has it.
+const FEATURES: &[(&str, bool)] = &[
+    ("cloud-backup", cfg!(feature = "cloud-backup")),
+    ("faucet", cfg!(feature = "faucet")),
+    ("fiat-rates", cfg!(feature = "fiat-rates")),
+    ("native", cfg!(feature = "native")),
+    ("plugins", cfg!(feature = "plugins")),
+    ("test-support", cfg!(feature = "test-support")),
+    ("trace", cfg!(feature = "trace")),
+    ("usb", cfg!(feature = "usb")),
+    ("webhooks", cfg!(feature = "webhooks")),
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// `unknown` when built outside a git checkout without `BITVAULT_GIT_COMMIT`.
+    pub git_commit: String,
+    /// `debug` or `release`.
+    pub profile: String,
+    pub target: String,
+    /// Enabled features, sorted.
+    pub features: Vec<String>,
+    /// SHA-256 of the workspace `Cargo.lock`, pinning every dependency.
+    pub lockfile_sha256: String,
+}
+
+impl BuildInfo {
+    /// This binary's build.
+    pub fn current() -> Self {
+        BuildInfo {
+            version: VERSION.to_string(),
+            git_commit: env!("BITVAULT_GIT_COMMIT").to_string(),
+            profile: env!("BITVAULT_BUILD_PROFILE").to_string(),
+            target: env!("BITVAULT_TARGET").to_string(),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            lockfile_sha256: env!("BITVAULT_LOCKFILE_SHA256").to_string(),
+        }
+    }
+
+    /// Where this build differs from `manifest`. Targets the manifest
+    /// doesn't list are not compared.
+    pub fn mismatches(&self, manifest: &ReleaseManifest) -> Vec<Mismatch> {
+        let mut features = manifest.features.clone();
+        features.sort();
+        let mut fields = vec![
+            ("version", &manifest.version, &self.version),
+            ("git_commit", &manifest.git_commit, &self.git_commit),
+            ("profile", &manifest.profile, &self.profile),
+            (
+                "lockfile_sha256",
+                &manifest.lockfile_sha256,
+                &self.lockfile_sha256,
+            ),
+        ];
+        if !manifest.targets.is_empty() && !manifest.targets.contains(&self.target) {
+            fields.push(("target", &manifest.targets[0], &self.target));
+        }
+        let mut mismatches: Vec<Mismatch> = fields
+            .into_iter()
+            .filter(|(_, expected, actual)| expected != actual)
+            .map(|(field, expected, actual)| Mismatch {
+                field: field.to_string(),
+                expected: expected.clone(),
+                actual: actual.clone(),
+            })
+            .collect();
+        if features != self.features {
+            mismatches.push(Mismatch {
+                field: "features".to_string(),
+                expected: features.join(","),
+                actual: self.features.join(","),
+            });
+        }
+        mismatches
+    }
+}
+
+/// A field where the running build differs from the release manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} but the release has {}",
+            self.field, self.actual, self.expected
+        )
+    }
+}
+
+/// What a release was built from, as published alongside its binaries.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub git_commit: String,
+    pub profile: String,
+    /// Targets binaries were published for.
+    pub targets: Vec<String>,
+    pub features: Vec<String>,
+    pub lockfile_sha256: String,
+}
+
+impl ReleaseManifest {
+    /// A manifest describing `build`, for release tooling.
+    pub fn from_build(build: &BuildInfo) -> Self {
+        ReleaseManifest {
+            version: build.version.clone(),
+            git_commit: build.git_commit.clone(),
+            profile: build.profile.clone(),
+            targets: vec![build.target.clone()],
+            features: build.features.clone(),
+            lockfile_sha256: build.lockfile_sha256.clone(),
+        }
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(self).map_err(|e| format!("Failed to serialize manifest: {}", e))
+    }
+
+    /// Signs the manifest; used by release tooling, never by the wallet itself.
+    pub fn sign(self, key: &SigningKey) -> Result<SignedReleaseManifest, String> {
+        let signature = key.sign(&self.signing_bytes()?);
+        Ok(SignedReleaseManifest {
+            manifest: self,
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+/// A release manifest and its hex Ed25519 signature.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReleaseManifest {
+    pub manifest: ReleaseManifest,
+    pub signature: String,
+}
+
+impl SignedReleaseManifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid release manifest: {}", e))
+    }
+
+    /// Checks the manifest is signed by one of `trusted_keys` (hex Ed25519
+    /// keys, e.g. [`EMBEDDED_RELEASE_KEYS`]) and that `build` matches it.
+    /// Fails if `trusted_keys` is empty.
+    pub fn verify_build(&self, trusted_keys: &[&str], build: &BuildInfo) -> Result<(), String> {
+        if trusted_keys.is_empty() {
+            return Err("No trusted release keys to verify the manifest against".to_string());
+        }
+        let bytes: [u8; 64] = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| "Malformed manifest signature".to_string())?;
+        let signature = Signature::from_bytes(&bytes);
+        let message = self.manifest.signing_bytes()?;
+        let keys = trusted_keys
+            .iter()
+            .map(|key| parse_key(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !keys
+            .iter()
+            .any(|key| key.verify(&message, &signature).is_ok())
+        {
+            return Err("Release manifest is not signed by a trusted key".to_string());
+        }
+
+        let mismatches = build.mismatches(&self.manifest);
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join("; "))
+        }
+    }
+}
+
+fn parse_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(hex_key)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "Release keys must be 32 hex encoded bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid release key: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_build_against_signed_manifest() {
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let trusted = hex::encode(key.verifying_key().to_bytes());
+        let build = BuildInfo::current();
+        assert_eq!(build.version, VERSION);
+        assert!(build.features.windows(2).all(|w| w[0] < w[1]));
+
+        let signed = ReleaseManifest::from_build(&build).sign(&key).unwrap();
+        assert_eq!(signed.verify_build(&[&trusted], &build), Ok(()));
+
+        let other = hex::encode(
+            SigningKey::from_bytes(&[1u8; 32])
+                .verifying_key()
+                .to_bytes(),
+        );
+        assert!(signed.verify_build(&[&other], &build).is_err());
+        assert!(signed.verify_build(&[], &build).is_err());
+
+        let mut tampered = signed.clone();
+        tampered.manifest.features.push("plugins-extra".to_string());
+        assert!(tampered.verify_build(&[&trusted], &build).is_err());
+
+        let mut manifest = ReleaseManifest::from_build(&build);
+        manifest.lockfile_sha256 = "00".repeat(32);
+        manifest.features.push("extra".to_string());
+        let err = manifest
+            .sign(&key)
+            .unwrap()
+            .verify_build(&[&trusted], &build)
+            .unwrap_err();
+        assert!(err.contains("lockfile_sha256"));
+        assert!(err.contains("features"));
+    }
+}
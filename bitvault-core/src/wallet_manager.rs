@@ -0,0 +1,710 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+
+use crate::fee_estimation::{FeeEstimates, FeePriority};
+use crate::metadata::{labels, WalletMetadata};
+use crate::payment_uri;
+use crate::tx_builder::TxBuilder;
+use crate::types::{Network, OutPoint, Transaction, TxOut, Utxo};
+use crate::utxo_selection::dust::DustPolicy;
+use crate::utxo_selection::{
+    is_spendable, select_utxos, SelectionParams, SelectionResult, SelectionStrategy,
+};
+
+/// Type-level marker for a Bitcoin network.
+///
+/// Addresses, coins and wallets carry their network as a type parameter, so
+/// handing a testnet address to a mainnet wallet is a compile error rather
+/// than a runtime check someone might forget.
+pub trait NetworkKind: Sized {
+    const NETWORK: Network;
+
+    #[doc(hidden)]
+    fn wallets(manager: &WalletManager) -> &BTreeMap<String, NetworkWallet<Self>>;
+
+    #[doc(hidden)]
+    fn wallets_mut(manager: &mut WalletManager) -> &mut BTreeMap<String, NetworkWallet<Self>>;
+}
+
+macro_rules! network_kind {
+    ($name:ident, $network:expr, $field:ident) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct $name;
+
+        impl NetworkKind for $name {
+            const NETWORK: Network = $network;
+
+            fn wallets(manager: &WalletManager) -> &BTreeMap<String, NetworkWallet<Self>> {
+                &manager.$field
+            }
+
+            fn wallets_mut(
+                manager: &mut WalletManager,
+            ) -> &mut BTreeMap<String, NetworkWallet<Self>> {
+                &mut manager.$field
+            }
+        }
+    };
+}
+
+network_kind!(Mainnet, Network::Bitcoin, mainnet);
+network_kind!(Testnet, Network::Testnet, testnet);
+network_kind!(Signet, Network::Signet, signet);
+network_kind!(Regtest, Network::Regtest, regtest);
+
+/// An address checked to belong to network `N`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkAddress<N: NetworkKind> {
+    address: String,
+    network: PhantomData<N>,
+}
+
+impl<N: NetworkKind> NetworkAddress<N> {
+    pub fn parse(address: &str) -> Result<Self, String> {
+        payment_uri::check_address(address, N::NETWORK)?;
+        Ok(NetworkAddress {
+            address: address.to_string(),
+            network: PhantomData,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.address
+    }
+}
+
+/// A coin known to live on network `N`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkUtxo<N: NetworkKind> {
+    utxo: Utxo,
+    network: PhantomData<N>,
+}
+
+impl<N: NetworkKind> NetworkUtxo<N> {
+    /// Tags `utxo` with network `N` after checking its address.
+    pub fn new(utxo: Utxo) -> Result<Self, String> {
+        payment_uri::check_address(&utxo.address, N::NETWORK)?;
+        Ok(NetworkUtxo {
+            utxo,
+            network: PhantomData,
+        })
+    }
+
+    pub fn utxo(&self) -> &Utxo {
+        &self.utxo
+    }
+}
+
+/// Confirmed and pending totals in satoshis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.confirmed + self.unconfirmed
+    }
+
+    fn add(&mut self, other: Balance) {
+        self.confirmed += other.confirmed;
+        self.unconfirmed += other.unconfirmed;
+    }
+}
+
+/// A wallet open on network `N`. It only accepts coins and addresses of `N`.
+#[derive(Clone, Debug)]
+pub struct NetworkWallet<N: NetworkKind> {
+    name: String,
+    metadata: WalletMetadata,
+    utxos: Vec<NetworkUtxo<N>>,
+}
+
+impl<N: NetworkKind> NetworkWallet<N> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn network(&self) -> Network {
+        N::NETWORK
+    }
+
+    pub fn metadata(&self) -> &WalletMetadata {
+        &self.metadata
+    }
+
+    /// Metadata to edit; callers persist it with the wallet's other data.
+    pub fn metadata_mut(&mut self) -> &mut WalletMetadata {
+        &mut self.metadata
+    }
+
+    /// Replaces the wallet's coins after a sync.
+    pub fn set_utxos(&mut self, utxos: Vec<NetworkUtxo<N>>) {
+        self.utxos = utxos;
+    }
+
+    /// Replaces the wallet's coins after a sync, labeling new change from
+    /// `transactions` (the ones just seen) per the wallet's
+    /// [`LabelInheritance`](labels::LabelInheritance) rule. Coins that
+    /// already have a label keep it. Returns the coins labeled.
+    pub fn sync_utxos(
+        &mut self,
+        utxos: Vec<NetworkUtxo<N>>,
+        transactions: &[Transaction],
+    ) -> Vec<OutPoint> {
+        let previous: HashMap<OutPoint, Utxo> = self
+            .utxos()
+            .map(|utxo| (utxo.outpoint.clone(), utxo.clone()))
+            .collect();
+        let by_txid: HashMap<String, &Transaction> =
+            transactions.iter().map(|tx| (tx.txid(), tx)).collect();
+
+        let mut labeled = Vec::new();
+        for utxo in utxos.iter().map(NetworkUtxo::utxo) {
+            if previous.contains_key(&utxo.outpoint) || self.metadata.coin_label(utxo).is_some() {
+                continue;
+            }
+            let Some(tx) = by_txid.get(&utxo.outpoint.txid) else {
+                continue;
+            };
+            // Only spends of our own coins make change
+            let spent: Vec<&str> = tx
+                .inputs
+                .iter()
+                .filter_map(|input| previous.get(&input.previous_output))
+                .filter_map(|coin| self.metadata.coin_label(coin))
+                .collect();
+            if let Some(label) = self.metadata.label_inheritance.inherit(&spent) {
+                // Already normalized, as it came from existing labels
+                self.metadata
+                    .labels
+                    .insert(utxo.outpoint.to_string(), label);
+                self.metadata.backup_changes.labels += 1;
+                labeled.push(utxo.outpoint.clone());
+            }
+        }
+        self.utxos = utxos;
+        labeled
+    }
+
+    pub fn utxos(&self) -> impl Iterator<Item = &Utxo> {
+        self.utxos.iter().map(NetworkUtxo::utxo)
+    }
+
+    /// Validates a user-entered destination against this wallet's network.
+    pub fn parse_address(&self, address: &str) -> Result<NetworkAddress<N>, String> {
+        NetworkAddress::parse(address)
+    }
+
+    pub fn balance(&self) -> Balance {
+        self.utxos().fold(Balance::default(), |mut balance, utxo| {
+            if utxo.confirmations > 0 {
+                balance.confirmed += utxo.value;
+            } else {
+                balance.unconfirmed += utxo.value;
+            }
+            balance
+        })
+    }
+
+    /// Coins that can be spent now: not frozen, and with the confirmations
+    /// the wallet's policy asks of their sender.
+    pub fn spendable_balance(&self) -> u64 {
+        let params = self.selection_params(0, 0);
+        self.utxos()
+            .filter(|utxo| is_spendable(utxo, &params))
+            .map(|utxo| utxo.value)
+            .sum()
+    }
+
+    /// Selection parameters honoring the wallet's frozen coins and
+    /// confirmation policy.
+    pub fn selection_params(&self, target: u64, fee_rate: u64) -> SelectionParams {
+        let mut params = SelectionParams::new(target, fee_rate);
+        params.dust = self
+            .metadata
+            .dust_policy
+            .unwrap_or_else(|| DustPolicy::for_network(N::NETWORK));
+        params.frozen = self.metadata.frozen.iter().cloned().collect();
+        params.coin_confirmations = self
+            .utxos()
+            .map(|utxo| {
+                (
+                    utxo.outpoint.clone(),
+                    self.metadata.required_confirmations(utxo),
+                )
+            })
+            .filter(|(_, confirmations)| *confirmations > 0)
+            .collect();
+        params
+    }
+
+    /// Pays `recipients` using only coins filed under `label` (or its
+    /// subcategories), e.g. a savings jar, at the fee rate for `priority`.
+    ///
+    /// Any change goes to `change_address`, which is labeled `label` so the
+    /// change stays in the jar.
+    pub fn send_from_label(
+        &mut self,
+        label: &str,
+        recipients: &[(NetworkAddress<N>, u64)],
+        priority: FeePriority,
+        fees: &FeeEstimates,
+        change_address: &NetworkAddress<N>,
+    ) -> Result<TxBuilder, String> {
+        let label = labels::normalize_label(label)?;
+        if recipients.is_empty() {
+            return Err("No recipients to pay".to_string());
+        }
+        let coins: Vec<Utxo> = self
+            .utxos()
+            .filter(|utxo| {
+                self.metadata
+                    .coin_label(utxo)
+                    .is_some_and(|l| labels::is_within(l, &label))
+            })
+            .cloned()
+            .collect();
+        if coins.is_empty() {
+            return Err(format!("No coins are labeled {}", label));
+        }
+
+        let rate = fees
+            .rate_for_target(priority.target_blocks())
+            .ok_or("No fee estimates available")?;
+        let mut params = self.selection_params(
+            recipients.iter().map(|(_, amount)| amount).sum(),
+            (rate.ceil() as u64).max(1),
+        );
+        params.recipient_outputs = recipients.len();
+        let selection = select_utxos(&coins, &params, SelectionStrategy::LargestFirst);
+        match &selection {
+            SelectionResult::Success { change, .. } => {
+                if *change > 0 {
+                    self.metadata.set_label(change_address.as_str(), &label)?;
+                }
+            }
+            SelectionResult::InsufficientFunds {
+                available,
+                required,
+                ..
+            } => {
+                return Err(format!(
+                    "Not enough in {}: {} sat spendable, {} sat needed including fees",
+                    label, available, required
+                ))
+            }
+            SelectionResult::ConstraintViolation { reason } => return Err(reason.clone()),
+        }
+
+        let outputs = recipients
+            .iter()
+            .map(|(address, value)| TxOut {
+                address: address.as_str().to_string(),
+                value: *value,
+            })
+            .collect();
+        Ok(
+            TxBuilder::from_selection(&selection, outputs, change_address.as_str())?
+                .with_dust(&params.dust, params.fee_rate),
+        )
+    }
+}
+
+/// One wallet's line in the aggregated listing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletSummary {
+    pub name: String,
+    /// Display name, or `name` when none was set.
+    pub display_name: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub created_at: Option<u64>,
+    pub descriptor_checksum: Option<String>,
+    pub backup_verified: bool,
+    pub balance: Balance,
+}
+
+/// All wallets of one network. Totals are never summed across networks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkGroup {
+    pub wallets: Vec<WalletSummary>,
+    pub total: Balance,
+}
+
+/// Holds wallets for several networks at once, each in its own typed map.
+#[derive(Debug, Default)]
+pub struct WalletManager {
+    mainnet: BTreeMap<String, NetworkWallet<Mainnet>>,
+    testnet: BTreeMap<String, NetworkWallet<Testnet>>,
+    signet: BTreeMap<String, NetworkWallet<Signet>>,
+    regtest: BTreeMap<String, NetworkWallet<Regtest>>,
+}
+
+impl WalletManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens an empty wallet named `name` on network `N`.
+    pub fn open<N: NetworkKind>(&mut self, name: &str) -> Result<&mut NetworkWallet<N>, String> {
+        self.open_with_metadata(name, WalletMetadata::default())
+    }
+
+    /// Opens a wallet with the metadata stored alongside it.
+    pub fn open_with_metadata<N: NetworkKind>(
+        &mut self,
+        name: &str,
+        metadata: WalletMetadata,
+    ) -> Result<&mut NetworkWallet<N>, String> {
+        let wallets = N::wallets_mut(self);
+        if wallets.contains_key(name) {
+            return Err(format!(
+                "A {:?} wallet named {} is already open",
+                N::NETWORK,
+                name
+            ));
+        }
+        Ok(wallets
+            .entry(name.to_string())
+            .or_insert_with(|| NetworkWallet {
+                name: name.to_string(),
+                metadata,
+                utxos: Vec::new(),
+            }))
+    }
+
+    pub fn close<N: NetworkKind>(&mut self, name: &str) -> bool {
+        N::wallets_mut(self).remove(name).is_some()
+    }
+
+    pub fn wallet<N: NetworkKind>(&self, name: &str) -> Option<&NetworkWallet<N>> {
+        N::wallets(self).get(name)
+    }
+
+    pub fn wallet_mut<N: NetworkKind>(&mut self, name: &str) -> Option<&mut NetworkWallet<N>> {
+        N::wallets_mut(self).get_mut(name)
+    }
+
+    /// Wallets and balances grouped by network, for side-by-side display.
+    /// Networks without open wallets are omitted.
+    pub fn listing(&self) -> BTreeMap<Network, NetworkGroup> {
+        let mut listing = BTreeMap::new();
+        group(&mut listing, &self.mainnet);
+        group(&mut listing, &self.testnet);
+        group(&mut listing, &self.signet);
+        group(&mut listing, &self.regtest);
+        listing
+    }
+}
+
+fn group<N: NetworkKind>(
+    listing: &mut BTreeMap<Network, NetworkGroup>,
+    wallets: &BTreeMap<String, NetworkWallet<N>>,
+) {
+    if wallets.is_empty() {
+        return;
+    }
+    let mut group = NetworkGroup::default();
+    for wallet in wallets.values() {
+        let balance = wallet.balance();
+        group.total.add(balance);
+        let metadata = &wallet.metadata;
+        group.wallets.push(WalletSummary {
+            name: wallet.name.clone(),
+            display_name: metadata
+                .display_name
+                .clone()
+                .unwrap_or_else(|| wallet.name.clone()),
+            color: metadata.color.clone(),
+            icon: metadata.icon.clone(),
+            created_at: metadata.created_at,
+            descriptor_checksum: metadata.descriptor_checksum.clone(),
+            backup_verified: metadata.is_backup_verified(),
+            balance,
+        });
+    }
+    listing.insert(N::NETWORK, group);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::labels::LabelInheritance;
+    use crate::types::TxIn;
+
+    fn utxo(address: &str, value: u64, confirmations: u32) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new("aa", 0),
+            address: address.to_string(),
+            value,
+            confirmations,
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_send_from_label() {
+        let jar = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let other = "bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297";
+        let coin = |n: u8, address: &str, value: u64| {
+            NetworkUtxo::new(Utxo {
+                outpoint: OutPoint::new(&format!("{:02x}", n).repeat(32), 0),
+                address: address.to_string(),
+                value,
+                confirmations: 6,
+                is_coinbase: false,
+            })
+            .unwrap()
+        };
+        let mut manager = WalletManager::new();
+        let wallet = manager.open::<Mainnet>("main").unwrap();
+        wallet.set_utxos(vec![
+            coin(1, jar, 60_000),
+            coin(2, jar, 30_000),
+            coin(3, other, 20_000),
+            coin(4, jar, 500_000),
+            coin(5, other, 1_000_000),
+        ]);
+        let metadata = wallet.metadata_mut();
+        metadata.set_label(jar, "Savings").unwrap();
+        metadata
+            .set_label(&format!("{}:0", "03".repeat(32)), "Savings/Jar")
+            .unwrap();
+        // A coin's own label beats its address's
+        metadata
+            .set_label(&format!("{}:0", "04".repeat(32)), "Spending")
+            .unwrap();
+
+        let fees = FeeEstimates {
+            rates: [(1, 20.0), (6, 5.0)].into_iter().collect(),
+            fetched_at: 0,
+            source: "test".to_string(),
+            possibly_stale: false,
+        };
+        let payee = wallet
+            .parse_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2")
+            .unwrap();
+        let change = wallet
+            .parse_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")
+            .unwrap();
+
+        let builder = wallet
+            .send_from_label(
+                "Savings",
+                &[(payee.clone(), 95_000)],
+                FeePriority::Medium,
+                &fees,
+                &change,
+            )
+            .unwrap();
+        let tip = crate::backend::BlockHeader {
+            height: 800_000,
+            hash: String::new(),
+            prev_hash: String::new(),
+            time: 0,
+        };
+        let tx = builder.build(&tip, 0).unwrap();
+        let spent: Vec<String> = tx
+            .inputs
+            .iter()
+            .map(|i| i.previous_output.txid[..2].to_string())
+            .collect();
+        assert_eq!(spent.len(), 3);
+        assert!(spent
+            .iter()
+            .all(|n| ["01", "02", "03"].contains(&n.as_str())));
+        assert_eq!(wallet.metadata().label(change.as_str()), Some("Savings"));
+
+        let err = wallet
+            .send_from_label(
+                "Savings",
+                &[(payee.clone(), 200_000)],
+                FeePriority::Medium,
+                &fees,
+                &change,
+            )
+            .unwrap_err();
+        assert!(
+            err.starts_with("Not enough in Savings: 110000 sat spendable"),
+            "{}",
+            err
+        );
+        assert!(wallet
+            .send_from_label(
+                "Travel",
+                &[(payee, 1_000)],
+                FeePriority::High,
+                &fees,
+                &change
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_change_inherits_labels() {
+        let address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let coin = |outpoint: OutPoint, value: u64| {
+            NetworkUtxo::<Mainnet>::new(Utxo {
+                outpoint,
+                address: address.to_string(),
+                value,
+                confirmations: 1,
+                is_coinbase: false,
+            })
+            .unwrap()
+        };
+        let tainted = OutPoint::new(&"01".repeat(32), 0);
+        let clean = OutPoint::new(&"02".repeat(32), 0);
+        let mut manager = WalletManager::new();
+        let wallet = manager.open::<Mainnet>("main").unwrap();
+        wallet.set_utxos(vec![
+            coin(tainted.clone(), 50_000),
+            coin(clean.clone(), 40_000),
+        ]);
+        wallet
+            .metadata_mut()
+            .set_label(&tainted.to_string(), "Tainted/Mixer")
+            .unwrap();
+        wallet
+            .metadata_mut()
+            .set_label(&clean.to_string(), "Income")
+            .unwrap();
+        wallet.metadata_mut().label_inheritance =
+            LabelInheritance::Categories(vec!["Tainted".to_string()]);
+
+        let spend = |from: &OutPoint| Transaction {
+            lock_time: 0,
+            inputs: vec![TxIn {
+                previous_output: from.clone(),
+                sequence: 0xffff_fffd,
+            }],
+            outputs: vec![
+                TxOut {
+                    address: "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string(),
+                    value: 10_000,
+                },
+                TxOut {
+                    address: address.to_string(),
+                    value: 20_000,
+                },
+            ],
+        };
+        let (taint_spend, clean_spend) = (spend(&tainted), spend(&clean));
+        let taint_change = OutPoint::new(&taint_spend.txid(), 1);
+        let clean_change = OutPoint::new(&clean_spend.txid(), 1);
+        let labeled = wallet.sync_utxos(
+            vec![
+                coin(taint_change.clone(), 20_000),
+                coin(clean_change.clone(), 20_000),
+            ],
+            &[taint_spend, clean_spend],
+        );
+        assert_eq!(labeled, vec![taint_change.clone()]);
+        let metadata = wallet.metadata();
+        assert_eq!(
+            metadata.label(&taint_change.to_string()),
+            Some("Tainted/Mixer")
+        );
+        assert_eq!(metadata.label(&clean_change.to_string()), None);
+        assert_eq!(wallet.utxos().count(), 2);
+    }
+
+    #[test]
+    fn test_spendable_follows_confirmation_policy() {
+        use crate::metadata::address_book::{ConfirmationPolicy, Contact};
+        let exchange = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let stranger = "bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297";
+        let coin = |n: u8, address: &str, confirmations: u32| {
+            NetworkUtxo::<Mainnet>::new(Utxo {
+                outpoint: OutPoint::new(&format!("{:02x}", n).repeat(32), 0),
+                address: address.to_string(),
+                value: 10_000,
+                confirmations,
+                is_coinbase: false,
+            })
+            .unwrap()
+        };
+        let mut manager = WalletManager::new();
+        let wallet = manager.open::<Mainnet>("main").unwrap();
+        wallet.set_utxos(vec![
+            coin(1, exchange, 1),
+            coin(2, stranger, 1),
+            coin(3, stranger, 3),
+        ]);
+        assert_eq!(wallet.spendable_balance(), 30_000);
+
+        let metadata = wallet.metadata_mut();
+        metadata.confirmation_policy = ConfirmationPolicy::recommended();
+        metadata.contacts.push(Contact {
+            name: "Exchange".to_string(),
+            category: "exchange".to_string(),
+            deposit_addresses: [exchange.to_string()].into_iter().collect(),
+        });
+        assert_eq!(wallet.spendable_balance(), 20_000);
+        let result = select_utxos(
+            &wallet.utxos().cloned().collect::<Vec<_>>(),
+            &wallet.selection_params(25_000, 1),
+            SelectionStrategy::LargestFirst,
+        );
+        assert!(matches!(
+            result,
+            SelectionResult::InsufficientFunds {
+                available: 20_000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_networks_stay_separate() {
+        let main_address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let test_address = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+        assert!(NetworkUtxo::<Mainnet>::new(utxo(test_address, 1, 1)).is_err());
+
+        let mut manager = WalletManager::new();
+        manager.open::<Mainnet>("savings").unwrap().set_utxos(vec![
+            NetworkUtxo::new(utxo(main_address, 50_000, 3)).unwrap(),
+            NetworkUtxo::new(utxo(main_address, 7_000, 0)).unwrap(),
+        ]);
+        manager
+            .open::<Testnet>("savings")
+            .unwrap()
+            .set_utxos(vec![
+                NetworkUtxo::new(utxo(test_address, 1_000_000, 1)).unwrap()
+            ]);
+        assert!(manager.open::<Mainnet>("savings").is_err());
+
+        let wallet = manager.wallet::<Testnet>("savings").unwrap();
+        assert!(wallet.parse_address(main_address).is_err());
+
+        let listing = manager.listing();
+        assert_eq!(listing.len(), 2);
+        assert_eq!(
+            listing[&Network::Bitcoin].total,
+            Balance {
+                confirmed: 50_000,
+                unconfirmed: 7_000
+            }
+        );
+        assert_eq!(listing[&Network::Testnet].total.total(), 1_000_000);
+        assert_eq!(
+            listing[&Network::Bitcoin].wallets[0].display_name,
+            "savings"
+        );
+
+        let metadata = WalletMetadata {
+            display_name: Some("Cold storage".to_string()),
+            backup_verified_at: Some(1_700_000_000),
+            ..Default::default()
+        };
+        manager
+            .open_with_metadata::<Signet>("cold.wallet", metadata)
+            .unwrap();
+        let summary = &manager.listing()[&Network::Signet].wallets[0];
+        assert_eq!(summary.display_name, "Cold storage");
+        assert!(summary.backup_verified);
+    }
+}
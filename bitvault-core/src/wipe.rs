@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::clock::Clock;
+use crate::events::{Event, MessageBus};
+use crate::platform::PlatformProvider;
+
+/// Why a wipe was started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WipeTrigger {
+    /// The user explicitly asked to destroy the wallet.
+    UserRequested,
+    /// A duress PIN or similar covert signal was entered.
+    Duress,
+}
+
+/// Everything an emergency wipe destroys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WipePlan {
+    /// Key files, wallet files and anything else derived from secrets.
+    pub files: Vec<PathBuf>,
+    /// Entries in the OS credential store.
+    pub secret_keys: Vec<String>,
+}
+
+/// What a wipe did, or in dry-run mode, would do.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WipeReport {
+    pub dry_run: bool,
+    pub erased_files: Vec<PathBuf>,
+    /// Listed files that did not exist.
+    pub missing_files: Vec<PathBuf>,
+    pub cleared_secrets: Vec<String>,
+    /// Failures; the wipe continues past them so one locked file doesn't
+    /// leave everything else intact.
+    pub errors: Vec<String>,
+}
+
+/// Securely erases every file in `plan` and clears its credential store
+/// entries, then publishes a `WalletWiped` audit event.
+///
+/// With `dry_run` nothing is touched and no event is published; the report lists
+/// what would be destroyed.
+pub fn wipe(
+    plan: &WipePlan,
+    trigger: WipeTrigger,
+    platform: &dyn PlatformProvider,
+    bus: Option<&MessageBus>,
+    clock: &dyn Clock,
+    dry_run: bool,
+) -> WipeReport {
+    let mut report = WipeReport {
+        dry_run,
+        ..Default::default()
+    };
+
+    for path in &plan.files {
+        if !path.exists() {
+            report.missing_files.push(path.clone());
+            continue;
+        }
+        if dry_run {
+            report.erased_files.push(path.clone());
+            continue;
+        }
+        match platform.secure_erase(path) {
+            Ok(()) => report.erased_files.push(path.clone()),
+            Err(e) => report.errors.push(e),
+        }
+    }
+
+    for key in &plan.secret_keys {
+        if dry_run {
+            report.cleared_secrets.push(key.clone());
+            continue;
+        }
+        match platform.delete_secret(key) {
+            Ok(true) => report.cleared_secrets.push(key.clone()),
+            Ok(false) => {}
+            Err(e) => report.errors.push(e),
+        }
+    }
+
+    if !dry_run {
+        log::warn!(
+            "Wallet wiped ({:?}): {} files erased, {} errors",
+            trigger,
+            report.erased_files.len(),
+            report.errors.len()
+        );
+        if let Some(bus) = bus {
+            bus.publish(Event::WalletWiped {
+                trigger: format!("{:?}", trigger),
+                files_erased: report.erased_files.len(),
+                at: clock.now(),
+            });
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::platform::HeadlessPlatform;
+    use std::fs;
+
+    #[test]
+    fn test_dry_run_then_wipe() {
+        let dir = std::env::temp_dir().join(format!("bitvault_wipe_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let wallet = dir.join("wallet.dat");
+        fs::write(&wallet, b"encrypted wallet").unwrap();
+        let plan = WipePlan {
+            files: vec![wallet.clone(), dir.join("missing.dat")],
+            secret_keys: Vec::new(),
+        };
+        let bus = MessageBus::new();
+        let events = bus.subscribe();
+        let clock = MockClock::new(42);
+
+        let preview = wipe(
+            &plan,
+            WipeTrigger::UserRequested,
+            &HeadlessPlatform,
+            Some(&bus),
+            &clock,
+            true,
+        );
+        assert_eq!(preview.erased_files, vec![wallet.clone()]);
+        assert_eq!(preview.missing_files.len(), 1);
+        assert!(wallet.exists());
+        assert!(events.try_recv().is_err());
+
+        let report = wipe(
+            &plan,
+            WipeTrigger::Duress,
+            &HeadlessPlatform,
+            Some(&bus),
+            &clock,
+            false,
+        );
+        assert!(report.errors.is_empty());
+        assert!(!wallet.exists());
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::WalletWiped {
+                files_erased: 1,
+                at: 42,
+                ..
+            })
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,161 @@
+//! Swift and Kotlin bindings for the mobile shells, generated with uniffi.
+//!
+//! Covers wallet creation, unlock and balance. Receive address derivation and
+//! PSBT signing will be exported once the core implements them; until then the
+//! shells can't use this crate for spending.
+
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+use bitvault_core::crypto;
+use bitvault_core::key_management::{self, EntropySize};
+use bitvault_core::types::{OutPoint, Utxo};
+
+uniffi::setup_scaffolding!();
+
+/// Errors surfaced to Swift and Kotlin as exceptions.
+#[derive(Debug, uniffi::Error)]
+pub enum FfiError {
+    InvalidInput { message: String },
+    WrongPin,
+    Internal { message: String },
+}
+
+impl std::fmt::Display for FfiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FfiError::InvalidInput { message } => write!(f, "Invalid input: {}", message),
+            FfiError::WrongPin => write!(f, "Wrong PIN"),
+            FfiError::Internal { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FfiError {}
+
+impl From<String> for FfiError {
+    fn from(message: String) -> Self {
+        FfiError::Internal { message }
+    }
+}
+
+/// A new wallet: the phrase to show the user once, and the seed as stored on disk.
+#[derive(uniffi::Record)]
+pub struct CreatedWallet {
+    pub mnemonic: String,
+    pub encrypted_seed: String,
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub value: u64,
+    pub confirmations: u32,
+    pub is_coinbase: bool,
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiBalance {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+}
+
+/// Generates a wallet with `word_count` words and encrypts its seed with `pin`.
+#[uniffi::export]
+pub fn create_wallet(word_count: u32, pin: String) -> Result<CreatedWallet, FfiError> {
+    let pin = Zeroizing::new(pin);
+    let size = EntropySize::from_word_count(word_count as usize).ok_or(FfiError::InvalidInput {
+        message: format!("Unsupported word count {}", word_count),
+    })?;
+    let mnemonic = Zeroizing::new(key_management::generate_mnemonic(size, None)?.to_string());
+    let encrypted_seed = crypto::encrypt_seed(&mnemonic, &pin)?;
+    Ok(CreatedWallet {
+        mnemonic: mnemonic.to_string(),
+        encrypted_seed,
+    })
+}
+
+/// An unlocked wallet. The seed is zeroized when the foreign object is released.
+#[derive(uniffi::Object)]
+pub struct WalletHandle {
+    #[allow(dead_code)]
+    mnemonic: Zeroizing<String>,
+    utxos: Mutex<Vec<Utxo>>,
+}
+
+/// Decrypts `encrypted_seed` with `pin`.
+#[uniffi::export]
+pub fn unlock(encrypted_seed: String, pin: String) -> Result<Arc<WalletHandle>, FfiError> {
+    let pin = Zeroizing::new(pin);
+    if !crypto::verify_pin(&encrypted_seed, &pin)? {
+        return Err(FfiError::WrongPin);
+    }
+    let mnemonic = Zeroizing::new(crypto::decrypt_seed(&encrypted_seed, &pin)?);
+    Ok(Arc::new(WalletHandle {
+        mnemonic,
+        utxos: Mutex::new(Vec::new()),
+    }))
+}
+
+#[uniffi::export]
+impl WalletHandle {
+    /// Replaces the wallet's coins with the result of the shell's latest sync.
+    pub fn set_utxos(&self, utxos: Vec<FfiUtxo>) {
+        let utxos = utxos
+            .into_iter()
+            .map(|u| Utxo {
+                outpoint: OutPoint::new(&u.txid, u.vout),
+                address: u.address,
+                value: u.value,
+                confirmations: u.confirmations,
+                is_coinbase: u.is_coinbase,
+            })
+            .collect();
+        *self.utxos.lock().unwrap() = utxos;
+    }
+
+    pub fn balance(&self) -> FfiBalance {
+        let mut balance = FfiBalance {
+            confirmed: 0,
+            unconfirmed: 0,
+        };
+        for utxo in self.utxos.lock().unwrap().iter() {
+            if utxo.confirmations > 0 {
+                balance.confirmed += utxo.value;
+            } else {
+                balance.unconfirmed += utxo.value;
+            }
+        }
+        balance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_unlock_balance() {
+        let created = create_wallet(12, "123456".to_string()).unwrap();
+        assert_eq!(created.mnemonic.split_whitespace().count(), 12);
+        assert!(matches!(
+            unlock(created.encrypted_seed.clone(), "000000".to_string()),
+            Err(FfiError::WrongPin)
+        ));
+
+        let wallet = unlock(created.encrypted_seed, "123456".to_string()).unwrap();
+        let utxo = |vout, confirmations| FfiUtxo {
+            txid: "aa".to_string(),
+            vout,
+            address: "bcrt1qtest".to_string(),
+            value: 10_000,
+            confirmations,
+            is_coinbase: false,
+        };
+        wallet.set_utxos(vec![utxo(0, 3), utxo(1, 0)]);
+        let balance = wallet.balance();
+        assert_eq!((balance.confirmed, balance.unconfirmed), (10_000, 10_000));
+    }
+}
@@ -9,9 +9,12 @@ use eframe::{
     egui::{self, Context},
     CreationContext,
 };
-use serde::{Deserialize, Serialize};
 
 use crate::config::Settings;
+use bitvault_core::auth_throttle::{AttemptThrottle, ThrottlePolicy};
+use bitvault_core::clock::{Clock, SystemClock};
+use bitvault_core::events::{Event, MessageBus, Subscription};
+use bitvault_core::payment_uri;
 
 pub mod assets;
 pub mod screens;
@@ -19,18 +22,28 @@ pub mod state;
 
 use self::state::{AppState, SharedAppState, View, WalletState};
 
-#[derive(Serialize, Deserialize)]
-struct GreetArgs<'a> {
-    name: &'a str,
-}
-
 pub struct BitVaultApp {
     state: SharedAppState,
     settings: Settings,
+    /// Carries security alerts and other core events.
+    bus: Arc<MessageBus>,
+    /// The app's own subscription to `bus`, drained once per frame.
+    alerts: Subscription,
+}
+
+/// Test-only behaviour, injected by the caller rather than detected inside the app.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TestConfig {
+    /// Skip the lock screen and go straight to onboarding.
+    pub bypass_lock_screen: bool,
 }
 
 impl BitVaultApp {
-    pub fn new(cc: &CreationContext<'_>) -> Self {
+    pub fn new(
+        cc: &CreationContext<'_>,
+        test_config: TestConfig,
+        launch_uri: Option<String>,
+    ) -> Self {
         // Attempt to configure a font with good Unicode support
         let mut fonts = egui::FontDefinitions::default();
 
@@ -57,8 +70,7 @@ impl BitVaultApp {
             log::warn!("Could not load Noto Sans font - using default fonts");
         }
 
-        // Check for testing mode environment variable
-        let testing_mode = std::env::var("TESTING").unwrap_or_default() == "1";
+        let testing_mode = test_config.bypass_lock_screen;
         if testing_mode {
             log::info!("Running in TESTING mode - lock screen will be bypassed");
         }
@@ -66,15 +78,39 @@ impl BitVaultApp {
         // Load settings or use defaults
         let settings = Settings::load();
 
+        // Restore failed unlock attempt counters so restarting doesn't reset the backoff
+        let pin_throttle = match Self::get_attempts_file_path() {
+            Some(path) => AttemptThrottle::load(
+                &path,
+                ThrottlePolicy::default(),
+                SystemClock.now(),
+                Self::get_wallet_file_path().is_some_and(|wallet| wallet.exists()),
+            ),
+            None => AttemptThrottle::new(ThrottlePolicy::default()),
+        };
+
+        // A bitcoin: URI the OS launched us with becomes a draft, shown after unlock
+        let pending_payment = launch_uri.and_then(|uri| {
+            payment_uri::parse_payment_uri(&uri, settings.network)
+                .map_err(|e| log::warn!("Ignoring payment URI: {}", e))
+                .ok()
+        });
+
         // Create the app with default state
+        let bus = Arc::new(MessageBus::new());
+        let alerts = bus.subscribe();
         let app = Self {
             state: Arc::new(RwLock::new(AppState {
                 current_view: View::SplashScreen,
                 splash_timer: Some(1.0), // 1 second splash screen
                 testing_mode,
+                pin_throttle,
+                pending_payment,
                 ..Default::default()
             })),
             settings,
+            bus,
+            alerts,
         };
 
         // Check if a wallet file exists and load it
@@ -116,6 +152,11 @@ impl BitVaultApp {
         None
     }
 
+    // Helper function to get the unlock attempts file path
+    fn get_attempts_file_path() -> Option<PathBuf> {
+        Self::get_wallet_file_path().map(|path| path.with_file_name("unlock_attempts.json"))
+    }
+
     // Save wallet data to disk
     pub fn save_wallet_to_disk(&self, encrypted_data: &str) -> Result<(), String> {
         if let Some(file_path) = Self::get_wallet_file_path() {
@@ -141,8 +182,24 @@ impl BitVaultApp {
     }
 }
 
+impl BitVaultApp {
+    // Log security alerts published on the bus and keep the latest for display
+    fn drain_security_alerts(&self) {
+        while let Ok(event) = self.alerts.try_recv() {
+            if let Event::SecurityAlert { message } = event {
+                log::warn!("Security alert: {}", message);
+                if let Ok(mut state) = self.state.write() {
+                    state.security_alert = Some(message);
+                }
+            }
+        }
+    }
+}
+
 impl eframe::App for BitVaultApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.drain_security_alerts();
+
         // Check for window resize events and save the new size
         let screen_rect = ctx.input(|i| i.screen_rect);
         let size = screen_rect.size();
@@ -1,5 +1,6 @@
 use crate::app::state::{View, WalletState};
 use crate::app::BitVaultApp;
+use bitvault_core::clock::{Clock, SystemClock};
 use eframe::egui::{self, Color32, Ui};
 
 pub fn render(app: &BitVaultApp, ui: &mut Ui) {
@@ -65,6 +66,10 @@ pub fn render(app: &BitVaultApp, ui: &mut Ui) {
                 ui.add_space(10.0);
                 ui.colored_label(Color32::RED, error);
             }
+            if let Some(alert) = &state.security_alert {
+                ui.add_space(10.0);
+                ui.colored_label(Color32::from_rgb(230, 140, 0), alert);
+            }
         }
 
         ui.add_space(20.0);
@@ -72,12 +77,31 @@ pub fn render(app: &BitVaultApp, ui: &mut Ui) {
         let unlock_button = ui.add_enabled(wallet_loaded, egui::Button::new("Unlock"));
         if unlock_button.clicked() || (enter_pressed && wallet_loaded) {
             if let Ok(mut state) = app.state.write() {
-                // Try to load and decrypt the wallet
-                if let Some(encrypted_data) = &state.encrypted_wallet_data {
+                let now = SystemClock.now();
+
+                // Refuse the attempt while backing off from previous failures
+                if let Err(e) = state.pin_throttle.check(now) {
+                    log::warn!("Unlock attempt throttled: {}", e);
+                    state.lock_error = Some(e.to_string());
+                } else if let Some(encrypted_data) = state.encrypted_wallet_data.clone() {
+                    // Check the PIN against the stored key check before decrypting
                     log::info!("Attempting to decrypt wallet");
-                    match bitvault_core::crypto::decrypt_seed(encrypted_data, &state.pin_input) {
+                    let unlocked = match bitvault_core::crypto::verify_pin(
+                        &encrypted_data,
+                        &state.pin_input,
+                    ) {
+                        Ok(true) => {
+                            bitvault_core::crypto::decrypt_seed(&encrypted_data, &state.pin_input)
+                        }
+                        Ok(false) => Err("Incorrect PIN".to_string()),
+                        Err(e) => Err(e),
+                    };
+                    match unlocked {
                         Ok(seed_phrase) => {
                             // Successfully decrypted
+                            if let Err(e) = state.pin_throttle.record_success() {
+                                log::error!("Failed to reset unlock attempts: {}", e);
+                            }
                             state.seed_phrase = Some(seed_phrase);
                             state.wallet_state = WalletState::Unlocked;
                             state.current_view = View::Wallet;
@@ -88,6 +112,9 @@ pub fn render(app: &BitVaultApp, ui: &mut Ui) {
                         Err(e) => {
                             // Failed to decrypt
                             log::error!("Failed to decrypt wallet: {}", e);
+                            if let Err(e) = state.pin_throttle.record_failure(now, Some(&app.bus)) {
+                                log::error!("Failed to record unlock attempt: {}", e);
+                            }
                             state.lock_error = Some("Incorrect PIN. Please try again.".to_string());
                         }
                     }
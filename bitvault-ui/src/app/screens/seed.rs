@@ -1,6 +1,7 @@
 use crate::app::state::{View, WalletState};
 use crate::app::BitVaultApp;
 use crate::wallet;
+use bitvault_core::key_management;
 use eframe::egui::{self, Color32, RichText, Ui};
 
 // PIN choice screen
@@ -117,7 +118,10 @@ pub fn render_seed(app: &BitVaultApp, ui: &mut Ui) {
         // Handle seed phrase generation for new wallet
         if is_creating && !has_seed {
             // Generate seed phrase outside of any locks to avoid deadlocks
-            match wallet::new_12_word_seed() {
+            match wallet::new_seed(
+                app.settings.mnemonic_word_count,
+                app.settings.mnemonic_language,
+            ) {
                 Ok(new_seed) => {
                     log::info!("Successfully generated new seed phrase");
                     // Store the seed phrase in the state
@@ -147,13 +151,15 @@ pub fn render_seed(app: &BitVaultApp, ui: &mut Ui) {
         if is_creating {
             ui.heading("Your Recovery Phrase");
             ui.add_space(10.0);
-            ui.label("Write down these 12 words in order and keep them safe:");
-
             if let Some(seed_phrase) = &seed_phrase {
-                ui.add_space(20.0);
-
                 let words: Vec<&str> = seed_phrase.split_whitespace().collect();
 
+                ui.label(format!(
+                    "Write down these {} words in order and keep them safe:",
+                    words.len()
+                ));
+                ui.add_space(20.0);
+
                 if !words.is_empty() {
                     egui::Grid::new("seed_grid")
                         .num_columns(4)
@@ -204,7 +210,7 @@ pub fn render_seed(app: &BitVaultApp, ui: &mut Ui) {
             // Restoring flow
             ui.heading("Restore from Recovery Phrase");
             ui.add_space(10.0);
-            ui.label("Enter your 12-word recovery phrase:");
+            ui.label("Enter your 12 to 24-word recovery phrase:");
 
             if let Ok(mut state) = app.state.write() {
                 ui.add_space(20.0);
@@ -212,7 +218,7 @@ pub fn render_seed(app: &BitVaultApp, ui: &mut Ui) {
                 // Text input for seed phrase
                 let response = ui.add(
                     egui::TextEdit::multiline(&mut state.verification_input)
-                        .hint_text("Enter your 12 words in order, separated by spaces")
+                        .hint_text("Enter your words in order, separated by spaces")
                         .desired_width(400.0)
                         .desired_rows(3),
                 );
@@ -223,13 +229,28 @@ pub fn render_seed(app: &BitVaultApp, ui: &mut Ui) {
 
                 ui.add_space(20.0);
 
+                // Display validation error if there is one
+                if let Some(error) = &state.restore_error {
+                    ui.colored_label(Color32::RED, error);
+                    ui.add_space(10.0);
+                }
+
                 if (ui.button("Restore Wallet").clicked() || enter_pressed)
                     && !state.verification_input.trim().is_empty()
                 {
-                    // Set the seed phrase from the input
-                    state.seed_phrase = Some(state.verification_input.clone());
-                    state.current_view = View::Wallet;
-                    state.wallet_state = WalletState::Unlocked;
+                    // Check word list membership and checksum before accepting the phrase
+                    match key_management::validate_mnemonic(&state.verification_input) {
+                        Ok(mnemonic) => {
+                            state.seed_phrase = Some(mnemonic.to_string());
+                            state.restore_error = None;
+                            state.current_view = View::Wallet;
+                            state.wallet_state = WalletState::Unlocked;
+                        }
+                        Err(e) => {
+                            log::warn!("Recovery phrase rejected");
+                            state.restore_error = Some(e);
+                        }
+                    }
                 }
             }
         }
@@ -266,7 +287,7 @@ pub fn render_seed_verify(app: &BitVaultApp, ui: &mut Ui) {
         // Text input for verification
         let response = ui.add(
             egui::TextEdit::multiline(&mut verification_input)
-                .hint_text("Enter your 12 words in order, separated by spaces")
+                .hint_text("Enter your words in order, separated by spaces")
                 .desired_width(400.0)
                 .desired_rows(3),
         );
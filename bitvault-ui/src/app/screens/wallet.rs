@@ -24,6 +24,29 @@ pub fn render(app: &BitVaultApp, ui: &mut Ui) {
             // Show send interface
         }
 
+        // A payment request the app was launched with
+        let pending_payment = app
+            .state
+            .read()
+            .ok()
+            .and_then(|state| state.pending_payment.clone());
+        if let Some(draft) = pending_payment {
+            ui.add_space(10.0);
+            ui.label(RichText::new("Payment request").strong());
+            ui.label(format!("To: {}", draft.address));
+            if let Some(amount) = draft.amount {
+                ui.label(format!("Amount: {} sats", amount));
+            }
+            if let Some(label) = draft.label.as_ref().or(draft.message.as_ref()) {
+                ui.label(label);
+            }
+            if ui.button("Dismiss").clicked() {
+                if let Ok(mut state) = app.state.write() {
+                    state.pending_payment = None;
+                }
+            }
+        }
+
         if ui.button("Transactions").clicked() {
             // Show transaction history
         }
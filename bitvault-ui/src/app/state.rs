@@ -1,11 +1,7 @@
-use serde::{Deserialize, Serialize};
+use bitvault_core::auth_throttle::AttemptThrottle;
+use bitvault_core::payment_uri::PaymentDraft;
 use std::sync::{Arc, RwLock};
 
-#[derive(Serialize, Deserialize)]
-pub struct GreetArgs<'a> {
-    pub name: &'a str,
-}
-
 #[derive(Clone, Debug, PartialEq, Default)]
 pub enum WalletState {
     #[default]
@@ -43,12 +39,16 @@ pub struct AppState {
     pub seed_phrase: Option<String>,
     pub seed_words: Vec<String>,
     pub verification_input: String,
+    pub restore_error: Option<String>,
     pub copied_feedback: Option<f32>, // Timer for showing copy feedback (in seconds)
     pub encrypted_wallet_data: Option<String>, // Encrypted wallet data stored on disk
     pub lock_error: Option<String>,   // Error message when unlocking fails
+    pub security_alert: Option<String>, // Latest security alert from the core
     pub splash_timer: Option<f32>,    // Timer for splash screen (in seconds)
     pub testing_mode: bool,           // Flag for testing mode to bypass lock screen
     pub onboarding_completed: bool,   // Flag to track if onboarding has been completed
+    pub pin_throttle: AttemptThrottle,
+    pub pending_payment: Option<PaymentDraft>,
 }
 
 // Create a type alias for a thread-safe, shared reference to the state
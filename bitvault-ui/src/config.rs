@@ -2,11 +2,28 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use bitvault_core::key_management::wordlist::WordlistLanguage;
+use bitvault_core::logging::LogConfig;
+use bitvault_core::types::Network;
+
 // Settings struct to persist application settings
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Settings {
     pub window_width: f32,
     pub window_height: f32,
+    #[serde(default = "default_mnemonic_word_count")]
+    pub mnemonic_word_count: usize,
+    #[serde(default)]
+    pub mnemonic_language: WordlistLanguage,
+    #[serde(default)]
+    pub network: Network,
+    // Last, since TOML needs tables after plain values
+    #[serde(default)]
+    pub logging: LogConfig,
+}
+
+fn default_mnemonic_word_count() -> usize {
+    12
 }
 
 impl Default for Settings {
@@ -14,6 +31,10 @@ impl Default for Settings {
         Settings {
             window_width: 1440.0,
             window_height: 900.0,
+            mnemonic_word_count: default_mnemonic_word_count(),
+            mnemonic_language: WordlistLanguage::default(),
+            network: Network::default(),
+            logging: LogConfig::default(),
         }
     }
 }
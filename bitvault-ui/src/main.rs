@@ -3,18 +3,46 @@ mod config;
 mod icons;
 mod wallet;
 
+use bitvault_core::formats::{Artifact, FormatRegistry};
+use bitvault_core::logging::{self, LogConfig};
+use bitvault_core::platform::instance_lock::{InstanceLock, InstanceLockError};
+use bitvault_core::startup::Startup;
 use eframe::egui;
-use simple_logger::SimpleLogger;
 
 fn main() {
-    // Initialize logger with WARN level to reduce logging output
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Debug)
-        .init()
-        .unwrap();
+    let startup = Startup::new();
+
+    // Default levels until the settings with the user's levels are loaded
+    logging::init(LogConfig::default()).unwrap();
+
+    // `--register-uri-handler` makes BitVault the OS handler for bitcoin: links
+    if std::env::args().any(|arg| arg == "--register-uri-handler") {
+        register_uri_handler();
+        return;
+    }
+
+    // Held until the window closes so a second instance can't touch the same wallet files
+    let _instance_lock = match startup.stage("instance lock", acquire_instance_lock) {
+        Ok(lock) => lock,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+
+    // Before anything reads the wallet files, so none sees a format it doesn't know
+    if let Err(e) = startup.stage("formats", upgrade_formats) {
+        log::error!("{}", e);
+        return;
+    }
 
     // Load settings for the initial window size
-    let settings = config::Settings::load();
+    let settings = startup
+        .stage("settings", || Ok(config::Settings::load()))
+        .unwrap_or_default();
+    if let Err(e) = logging::configure(settings.logging.clone()) {
+        log::warn!("{}", e);
+    }
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -24,10 +52,83 @@ fn main() {
         ..Default::default()
     };
 
+    let test_config = test_config_from_args();
+    let launch_uri = std::env::args()
+        .skip(1)
+        .find(|arg| arg.to_ascii_lowercase().starts_with("bitcoin:"));
+
     eframe::run_native(
         "BitVault",
         native_options,
-        Box::new(|cc| Box::new(app::BitVaultApp::new(cc))),
+        Box::new(move |cc| {
+            let app = app::BitVaultApp::new(cc, test_config, launch_uri);
+            startup.finish();
+            log::info!("{}", startup.report());
+            Box::new(app)
+        }),
     )
     .expect("Failed to start application");
 }
+
+// `--force-unlock` takes over a lock left behind by a crashed instance
+fn acquire_instance_lock() -> Result<Option<InstanceLock>, String> {
+    let Some(dir) = dirs::config_dir().map(|dir| dir.join("bitvault")) else {
+        log::warn!("Could not determine data directory; running without an instance lock");
+        return Ok(None);
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+    let platform = bitvault_core::platform::current();
+    match InstanceLock::acquire(&dir, platform.as_ref()) {
+        Ok(lock) => Ok(Some(lock)),
+        Err(e @ InstanceLockError::Stale { .. }) => {
+            if std::env::args().any(|arg| arg == "--force-unlock") {
+                InstanceLock::take_over(&dir, platform.as_ref())
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            } else {
+                Err(format!("{}. Start with --force-unlock to take over", e))
+            }
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Every file the desktop app keeps in its data directory, with its format version
+fn upgrade_formats() -> Result<(), String> {
+    let Some(dir) = dirs::config_dir().map(|dir| dir.join("bitvault")) else {
+        return Ok(());
+    };
+    FormatRegistry::new()
+        .with_artifact(Artifact::new("key-file", "wallet.dat", 1))?
+        .with_artifact(Artifact::new("settings", "settings.toml", 1))?
+        .with_artifact(Artifact::new("unlock-attempts", "unlock_attempts.json", 1))?
+        .upgrade(&dir)
+        .map(|_| ())
+}
+
+fn register_uri_handler() {
+    let result = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate executable: {}", e))
+        .and_then(|exe| {
+            bitvault_core::platform::current()
+                .register_uri_handler(bitvault_core::payment_uri::BITCOIN_SCHEME, &exe)
+        });
+    match result {
+        Ok(()) => log::info!("Registered as bitcoin: URI handler"),
+        Err(e) => log::error!("Failed to register URI handler: {}", e),
+    }
+}
+
+// Test options are only accepted in builds with the `testing` feature
+#[cfg(feature = "testing")]
+fn test_config_from_args() -> app::TestConfig {
+    app::TestConfig {
+        bypass_lock_screen: std::env::args().any(|arg| arg == "--testing"),
+    }
+}
+
+#[cfg(not(feature = "testing"))]
+fn test_config_from_args() -> app::TestConfig {
+    app::TestConfig::default()
+}
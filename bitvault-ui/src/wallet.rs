@@ -1,20 +1,11 @@
-use anyhow::Result;
-use bip39::{Language, Mnemonic};
-use rand::RngCore;
-use zeroize::Zeroize;
+use anyhow::{anyhow, Result};
+use bitvault_core::key_management::wordlist::WordlistLanguage;
+use bitvault_core::key_management::{self, EntropySize};
 
-pub fn new_12_word_seed() -> Result<String> {
-    let mut entropy = [0u8; 16];
-    rand::rng().fill_bytes(&mut entropy);
-    let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy)?;
-    entropy.zeroize();
+pub fn new_seed(word_count: usize, language: WordlistLanguage) -> Result<String> {
+    let size = EntropySize::from_word_count(word_count)
+        .ok_or_else(|| anyhow!("Unsupported mnemonic length: {} words", word_count))?;
+    let mnemonic =
+        key_management::generate_mnemonic_in(language, size, None).map_err(|e| anyhow!(e))?;
     Ok(mnemonic.to_string())
 }
-
-// pub fn new_24_word_seed() -> Result<String> {
-//     let mut entropy = [0u8; 32];
-//     rand::thread_rng().fill_bytes(&mut entropy);
-//     let mnemonic = Mnemonic::from_entropy(&entropy)?;
-//     entropy.zeroize();
-//     Ok(mnemonic.to_string())
-// }